@@ -5,4 +5,4 @@ CREATE TABLE users (
     phone_number VARCHAR(20),
     verified_at DATETIME,
     admin TINYINT(1) UNSIGNED NOT NULL DEFAULT 0
-);
\ No newline at end of file
+);
@@ -0,0 +1,2 @@
+-- Argon2 PHC strings grow with the parameters and the pepper keyid
+ALTER TABLE users MODIFY password VARCHAR(255) NOT NULL;
@@ -0,0 +1,14 @@
+-- Accounts are unique on the canonical form (see Email::canonical), compared
+-- byte for byte: the default collation would also merge accents ("josé" = "jose").
+-- Lowering the stored address gives the canonical form of the ASCII addresses
+-- accepted until now; rows differing only by case must be merged before running this.
+ALTER TABLE users
+    ADD COLUMN email_canonical VARCHAR(255) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NULL AFTER email;
+
+UPDATE users SET email_canonical = LOWER(TRIM(email));
+
+ALTER TABLE users
+    MODIFY email_canonical VARCHAR(255) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NOT NULL,
+    ADD UNIQUE INDEX users_email_canonical (email_canonical),
+    DROP INDEX email,
+    MODIFY email VARCHAR(255) NOT NULL;
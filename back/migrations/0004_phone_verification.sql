@@ -0,0 +1,3 @@
+ALTER TABLE users
+    ADD COLUMN phone_verified_at DATETIME NULL AFTER phone_number,
+    ADD COLUMN sms_two_factor TINYINT(1) UNSIGNED NOT NULL DEFAULT 0 AFTER phone_verified_at;
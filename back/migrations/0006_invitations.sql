@@ -0,0 +1,20 @@
+-- Invitation codes, required to register when REGISTRATION_MODE=invite_only
+CREATE TABLE invitations (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    code CHAR(16) NOT NULL UNIQUE,
+    inviter_id INT NULL,
+    -- restricts the invitation to one address when set
+    email VARCHAR(255) NULL,
+    email_canonical VARCHAR(255) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NULL,
+    max_uses INT NOT NULL DEFAULT 1,
+    uses INT NOT NULL DEFAULT 0,
+    expires_at DATETIME NULL,
+    revoked_at DATETIME NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX invitations_inviter (inviter_id),
+    FOREIGN KEY (inviter_id) REFERENCES users(id) ON DELETE SET NULL
+);
+
+ALTER TABLE users
+    ADD COLUMN invited_by INT NULL,
+    ADD CONSTRAINT users_invited_by FOREIGN KEY (invited_by) REFERENCES users(id) ON DELETE SET NULL;
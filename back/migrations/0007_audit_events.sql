@@ -0,0 +1,15 @@
+-- Append only: rows are never updated nor deleted by the application
+CREATE TABLE audit_events (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    action VARCHAR(64) NOT NULL,
+    -- who did it, the impersonating admin when impersonating
+    actor_id INT NULL,
+    -- whose account it concerns
+    subject_id INT NULL,
+    ip VARCHAR(45) NULL,
+    -- JSON object
+    metadata TEXT NULL,
+    created_at DATETIME(3) NOT NULL DEFAULT CURRENT_TIMESTAMP(3),
+    INDEX audit_events_actor (actor_id, created_at),
+    INDEX audit_events_subject (subject_id, created_at)
+);
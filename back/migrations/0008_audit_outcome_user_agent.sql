@@ -0,0 +1,11 @@
+ALTER TABLE audit_events
+    ADD COLUMN outcome VARCHAR(16) NOT NULL DEFAULT 'success' AFTER action,
+    ADD COLUMN user_agent VARCHAR(255) NULL AFTER ip,
+    ADD INDEX audit_events_action (action, created_at);
+
+-- Append only, enforced for every client of the database
+CREATE TRIGGER audit_events_no_update BEFORE UPDATE ON audit_events
+    FOR EACH ROW SIGNAL SQLSTATE '45000' SET MESSAGE_TEXT = 'audit_events is append only';
+
+CREATE TRIGGER audit_events_no_delete BEFORE DELETE ON audit_events
+    FOR EACH ROW SIGNAL SQLSTATE '45000' SET MESSAGE_TEXT = 'audit_events is append only';
@@ -0,0 +1,36 @@
+-- Receivers of the account lifecycle events, registered by admins
+CREATE TABLE webhook_endpoints (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    url VARCHAR(2048) NOT NULL,
+    -- HMAC-SHA256 key of the signatures, shown once at creation
+    secret VARCHAR(80) NOT NULL,
+    -- space separated event types, e.g. "user.registered user.deleted"
+    event_types TEXT NOT NULL,
+    description VARCHAR(255) NULL,
+    created_by INT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+-- One row per event and endpoint, also the delivery queue and its log
+CREATE TABLE webhook_deliveries (
+    id BIGINT AUTO_INCREMENT PRIMARY KEY,
+    endpoint_id INT NOT NULL,
+    -- shared by every delivery of the event, replays included: receivers dedupe on it
+    event_id CHAR(36) NOT NULL,
+    event_type VARCHAR(64) NOT NULL,
+    -- JSON body, signed as sent
+    payload TEXT NOT NULL,
+    -- pending, succeeded or failed once out of attempts
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_attempt_at DATETIME NULL,
+    last_status_code SMALLINT NULL,
+    last_error VARCHAR(1024) NULL,
+    replay_of BIGINT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX webhook_deliveries_queue (status, next_attempt_at),
+    INDEX webhook_deliveries_endpoint (endpoint_id, id),
+    FOREIGN KEY (endpoint_id) REFERENCES webhook_endpoints(id) ON DELETE CASCADE
+);
@@ -0,0 +1,5 @@
+-- Set while the account waits for its hard deletion, cleared by a restoration
+ALTER TABLE users
+    ADD COLUMN deletion_requested_at DATETIME NULL,
+    ADD COLUMN deletion_scheduled_at DATETIME NULL,
+    ADD INDEX users_deletion_scheduled (deletion_scheduled_at);
@@ -0,0 +1,20 @@
+-- Personal data exports: requested by the owner, built in the background and downloaded once
+CREATE TABLE data_exports (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    -- pending, ready, downloaded, expired or failed
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    -- ZIP archive, dropped once downloaded or expired
+    archive LONGBLOB NULL,
+    -- SHA-256 of the key of the mailed download link
+    download_key_hash CHAR(64) NULL,
+    -- while pending, leased by the worker building it until then
+    build_after DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    requested_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ready_at DATETIME NULL,
+    expires_at DATETIME NULL,
+    downloaded_at DATETIME NULL,
+    INDEX data_exports_queue (status, build_after),
+    INDEX data_exports_user (user_id, id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,18 @@
+-- What users show of themselves, kept apart from the credentials of `users`.
+-- Created on first access, existing users get theirs now.
+CREATE TABLE user_profiles (
+    user_id INT PRIMARY KEY,
+    display_name VARCHAR(64) NULL,
+    -- square PNG, re-encoded on upload
+    avatar MEDIUMBLOB NULL,
+    avatar_updated_at DATETIME NULL,
+    -- BCP 47 tag, e.g. "fr-FR"
+    locale VARCHAR(16) NULL,
+    -- IANA name, e.g. "Europe/Paris"
+    timezone VARCHAR(64) NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+INSERT INTO user_profiles (user_id) SELECT id FROM users;
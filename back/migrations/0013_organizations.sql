@@ -0,0 +1,42 @@
+-- Tenants: users belong to organizations with a role in each of them
+CREATE TABLE organizations (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(100) NOT NULL,
+    created_by INT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE TABLE organization_members (
+    org_id INT NOT NULL,
+    user_id INT NOT NULL,
+    -- owner, admin, member or viewer
+    role VARCHAR(16) NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (org_id, user_id),
+    INDEX organization_members_user (user_id),
+    FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Invitations by email, the mailed key is only stored hashed
+CREATE TABLE organization_invitations (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    org_id INT NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    email_canonical VARCHAR(255) CHARACTER SET utf8mb4 COLLATE utf8mb4_bin NOT NULL,
+    role VARCHAR(16) NOT NULL,
+    key_hash CHAR(64) NOT NULL UNIQUE,
+    invited_by INT NULL,
+    expires_at DATETIME NOT NULL,
+    accepted_at DATETIME NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX organization_invitations_org (org_id),
+    FOREIGN KEY (org_id) REFERENCES organizations(id) ON DELETE CASCADE,
+    FOREIGN KEY (invited_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+-- Organization carried by the claims of the user's next tokens
+ALTER TABLE users
+    ADD COLUMN active_org_id INT NULL,
+    ADD CONSTRAINT users_active_org FOREIGN KEY (active_org_id) REFERENCES organizations(id) ON DELETE SET NULL;
@@ -0,0 +1,22 @@
+-- Tree of folders and files of each user. Names are unique per folder regardless of case,
+-- root nodes have no parent.
+-- `parent_id` has no foreign key: MySQL stops cascading after 15 levels, subtrees are
+-- deleted by the application.
+CREATE TABLE nodes (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    parent_id INT NULL,
+    -- 0 for the root, so that root names are unique as well
+    parent_key INT AS (IFNULL(parent_id, 0)) STORED,
+    -- folder or file
+    kind VARCHAR(8) NOT NULL,
+    name VARCHAR(255) CHARACTER SET utf8mb4 COLLATE utf8mb4_0900_as_ci NOT NULL,
+    -- content of file nodes, a file appears at most once in the tree
+    file_id INT NULL UNIQUE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+    UNIQUE INDEX nodes_name (user_id, parent_key, name),
+    INDEX nodes_parent (parent_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+);
@@ -0,0 +1,34 @@
+-- Contents stored once per SHA-256 and shared by every file with the same bytes.
+-- `ref_count` is the number of files pointing at the blob, the collector removes the
+-- blobs left at 0.
+CREATE TABLE blobs (
+    sha256 CHAR(64) PRIMARY KEY,
+    size BIGINT NOT NULL,
+    storage_key VARCHAR(255) NOT NULL UNIQUE,
+    ref_count INT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- last integrity check, NULL until the first one
+    verified_at DATETIME NULL,
+    -- set when the content was found missing or not matching its hash
+    corrupted_at DATETIME NULL,
+    INDEX blobs_unreferenced (ref_count),
+    INDEX blobs_verified (verified_at)
+);
+
+-- Objects waiting to be deleted from the store, so that a failed deletion is retried
+CREATE TABLE blob_garbage (
+    storage_key VARCHAR(255) PRIMARY KEY,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO blobs (sha256, size, storage_key, ref_count)
+SELECT sha256, MIN(size), MIN(storage_key), COUNT(*) FROM files GROUP BY sha256;
+
+-- copies of the same content uploaded before deduplication
+INSERT INTO blob_garbage (storage_key)
+SELECT f.storage_key FROM files f JOIN blobs b ON b.sha256 = f.sha256
+WHERE f.storage_key <> b.storage_key;
+
+ALTER TABLE files
+    DROP COLUMN storage_key,
+    ADD CONSTRAINT files_blob FOREIGN KEY (sha256) REFERENCES blobs(sha256);
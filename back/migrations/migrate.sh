@@ -1 +1,3 @@
-mysql -h 127.0.0.1 -P 3306 -u root -p mydb < create_users.sql
\ No newline at end of file
+# Applies every migration in lexical order, files are named NNNN_description.sql
+cd "$(dirname "$0")"
+cat $(ls *.sql | sort) | mysql -h 127.0.0.1 -P 3306 -u root -p mydb
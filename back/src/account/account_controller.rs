@@ -0,0 +1,244 @@
+use actix_session::Session;
+use actix_web::web::{Bytes, Json, Path, Query};
+use actix_web::{
+    HttpResponse, delete, get,
+    http::{
+        StatusCode,
+        header::{CacheControl, CacheDirective, ContentDisposition},
+    },
+    patch, post, put,
+};
+use serde::Deserialize;
+
+use crate::{
+    account::{
+        account_models::phone_number::RawPhoneNumber,
+        account_service::{
+            CreatedToken, DeletionRequest, DeletionStatus, EmailChangeRequest, ExportDownloadQuery,
+            NewInvitation, NewToken, PhoneStatus, ProfileUpdate, VerifyPhoneResult,
+            create_invitation_service, create_token_service, download_export_service,
+            export_status_service, list_invitations_service, list_tokens_service,
+            phone_status_service, profile_service, remove_avatar_service, remove_phone_service,
+            request_deletion_service, request_email_change_service, request_export_service,
+            resend_phone_code_service, revoke_invitation_service, revoke_token_service,
+            set_avatar_service, set_phone_service, set_sms_two_factor_service,
+            update_profile_service, verify_phone_service,
+        },
+    },
+    auth::auth_models::{claims::Claims, refresh_token::REFRESH_TOKEN_KEY, scope::Scope},
+    constants::codes::{ErrorCode, MessageCode},
+    errors::{AppError, AppResult},
+    models::{
+        audit_event_model::ClientInfo, data_export_model::DataExport, invitation_model::Invitation,
+        personal_access_token_model::PersonalAccessToken, profile_model::Profile,
+    },
+    shared::{ApiResponse, JsonResponse},
+};
+
+#[get("/profile")]
+pub async fn get_profile(claims: Claims) -> ApiResponse<Profile> {
+    claims.require_scope(Scope::AccountRead)?;
+    JsonResponse::ok().object(profile_service(claims.user_id).await?)
+}
+
+#[patch("/profile")]
+pub async fn update_profile(
+    claims: Claims,
+    Json(update): Json<ProfileUpdate>,
+) -> ApiResponse<Profile> {
+    claims.require_scope(Scope::AccountWrite)?;
+    JsonResponse::ok().object(update_profile_service(claims.user_id, update).await?)
+}
+
+/// Raw PNG, JPEG, WebP or GIF body, up to `AVATAR_MAX_BYTES`
+#[put("/profile/avatar")]
+pub async fn set_avatar(claims: Claims, body: Bytes) -> ApiResponse<Profile> {
+    claims.require_scope(Scope::AccountWrite)?;
+    JsonResponse::ok().object(set_avatar_service(claims.user_id, &body).await?)
+}
+
+#[delete("/profile/avatar")]
+pub async fn remove_avatar(claims: Claims) -> ApiResponse<Profile> {
+    claims.require_scope(Scope::AccountWrite)?;
+    JsonResponse::ok().object(remove_avatar_service(claims.user_id).await?)
+}
+
+#[get("/phone")]
+pub async fn get_phone(claims: Claims) -> ApiResponse<PhoneStatus> {
+    claims.require_scope(Scope::AccountRead)?;
+    JsonResponse::ok().object(phone_status_service(claims.user_id).await?)
+}
+
+#[put("/phone")]
+pub async fn set_phone(claims: Claims, Json(raw_phone): Json<RawPhoneNumber>) -> ApiResponse {
+    claims.require_scope(Scope::AccountWrite)?;
+    claims.require_not_impersonated()?;
+    set_phone_service(claims.user_id, raw_phone).await?;
+    JsonResponse::ok().message(MessageCode::PhoneCodeSent)
+}
+
+#[delete("/phone")]
+pub async fn remove_phone(claims: Claims) -> ApiResponse {
+    claims.require_scope(Scope::AccountWrite)?;
+    claims.require_not_impersonated()?;
+    remove_phone_service(claims.user_id).await?;
+    JsonResponse::ok().empty()
+}
+
+#[post("/phone/resend")]
+pub async fn resend_phone_code(claims: Claims) -> ApiResponse {
+    claims.require_scope(Scope::AccountWrite)?;
+    claims.require_not_impersonated()?;
+    resend_phone_code_service(claims.user_id).await?;
+    JsonResponse::ok().message(MessageCode::PhoneCodeSent)
+}
+
+#[derive(Deserialize)]
+struct PhoneCode {
+    code: String,
+}
+
+#[post("/phone/verify")]
+pub async fn verify_phone(claims: Claims, Json(req): Json<PhoneCode>) -> ApiResponse {
+    claims.require_scope(Scope::AccountWrite)?;
+    claims.require_not_impersonated()?;
+    match verify_phone_service(claims.user_id, &req.code).await? {
+        VerifyPhoneResult::Verified => JsonResponse::ok().message(MessageCode::PhoneVerified),
+        VerifyPhoneResult::CodeInvalid => {
+            Err(AppError::invalid_field("code", ErrorCode::OtpInvalid))
+        }
+        VerifyPhoneResult::CodeExpired => Err(AppError::Validation(ErrorCode::OtpExpired)),
+    }
+}
+
+#[derive(Deserialize)]
+struct TwoFactorRequest {
+    enabled: bool,
+}
+
+#[put("/two-factor")]
+pub async fn set_two_factor(
+    claims: Claims,
+    Json(req): Json<TwoFactorRequest>,
+) -> ApiResponse<PhoneStatus> {
+    // a leaked token must not be enough to weaken the login
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    JsonResponse::ok().object(set_sms_two_factor_service(claims.user_id, req.enabled).await?)
+}
+
+/// Tokens manage nothing, least of all other tokens
+#[post("/tokens")]
+pub async fn create_token(claims: Claims, Json(req): Json<NewToken>) -> ApiResponse<CreatedToken> {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    let created = create_token_service(claims.user_id, req).await?;
+    JsonResponse::created()
+        .location(format!("/account/tokens/{}", created.details.id))
+        .no_store()
+        .object(created)
+}
+
+#[get("/tokens")]
+pub async fn list_tokens(claims: Claims) -> ApiResponse<Vec<PersonalAccessToken>> {
+    claims.require_interactive()?;
+    JsonResponse::ok().object(list_tokens_service(claims.user_id).await?)
+}
+
+#[delete("/tokens/{token_id}")]
+pub async fn revoke_token(claims: Claims, token_id: Path<i32>) -> ApiResponse<PersonalAccessToken> {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    JsonResponse::ok().object(revoke_token_service(claims.user_id, token_id.into_inner()).await?)
+}
+
+#[post("/invitations")]
+pub async fn create_invitation(
+    claims: Claims,
+    Json(req): Json<NewInvitation>,
+) -> ApiResponse<Invitation> {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    let invitation = create_invitation_service(&claims, req).await?;
+    JsonResponse::created()
+        .location(format!("/account/invitations/{}", invitation.id))
+        .object(invitation)
+}
+
+#[get("/invitations")]
+pub async fn list_invitations(claims: Claims) -> ApiResponse<Vec<Invitation>> {
+    claims.require_interactive()?;
+    JsonResponse::ok().object(list_invitations_service(claims.user_id).await?)
+}
+
+#[delete("/invitations/{invitation_id}")]
+pub async fn revoke_invitation(
+    claims: Claims,
+    invitation_id: Path<i32>,
+) -> ApiResponse<Invitation> {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    JsonResponse::ok()
+        .object(revoke_invitation_service(claims.user_id, invitation_id.into_inner()).await?)
+}
+
+/// The address only changes once the link mailed to the new one is followed
+#[post("/email")]
+pub async fn request_email_change(
+    claims: Claims,
+    Json(req): Json<EmailChangeRequest>,
+) -> ApiResponse {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    request_email_change_service(claims.user_id, req).await?;
+    JsonResponse::status(StatusCode::ACCEPTED).empty()
+}
+
+/// The account is only hard deleted once the grace period is over
+#[post("/deletion")]
+pub async fn request_deletion(
+    claims: Claims,
+    session: Session,
+    client: ClientInfo,
+    Json(req): Json<DeletionRequest>,
+) -> ApiResponse<DeletionStatus> {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    let status = request_deletion_service(claims.user_id, req, &client).await?;
+    session.remove(REFRESH_TOKEN_KEY);
+    JsonResponse::status(StatusCode::ACCEPTED).object(status)
+}
+
+/// Personal data export, built in the background then mailed as a one-time link
+#[post("/export")]
+pub async fn request_export(claims: Claims, client: ClientInfo) -> ApiResponse<DataExport> {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    let export = request_export_service(claims.user_id, &client).await?;
+    JsonResponse::status(StatusCode::ACCEPTED).object(export)
+}
+
+#[get("/export")]
+pub async fn get_export(claims: Claims) -> ApiResponse<DataExport> {
+    claims.require_interactive()?;
+    JsonResponse::ok().object(export_status_service(claims.user_id).await?)
+}
+
+#[get("/export/download")]
+pub async fn download_export(
+    claims: Claims,
+    client: ClientInfo,
+    Query(query): Query<ExportDownloadQuery>,
+) -> AppResult<HttpResponse> {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    let archive = download_export_service(claims.user_id, &query.key, &client).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .insert_header(ContentDisposition::attachment(format!(
+            "personal-data-{}.zip",
+            claims.user_id
+        )))
+        .body(archive))
+}
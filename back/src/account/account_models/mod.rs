@@ -0,0 +1,2 @@
+pub mod otp;
+pub mod phone_number;
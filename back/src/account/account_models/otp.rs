@@ -0,0 +1,81 @@
+use password_hash::rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    account::account_models::phone_number::PhoneNumber,
+    errors::AppResult,
+    shared::get_now_unix,
+    utils::{
+        redis_utils::{redis_del, redis_get, redis_set_ex},
+        sms_utils::send_sms,
+    },
+};
+
+pub const OTP_VALIDITY_SECS: u64 = 10 * 60;
+/// Wrong guesses allowed before the code is discarded
+const OTP_MAX_ATTEMPTS: u8 = 5;
+
+/// One time code texted to `phone`, stored in the cache under a caller chosen key
+#[derive(Serialize, Deserialize)]
+pub struct SmsOtp {
+    user_id: i32,
+    phone: PhoneNumber,
+    code: String,
+    attempts: u8,
+    exp: u64,
+}
+
+pub enum OtpCheck {
+    Valid(SmsOtp),
+    Invalid,
+    /// Unknown key, expired code or too many attempts: a new code must be sent
+    Expired,
+}
+
+impl SmsOtp {
+    pub fn new(user_id: i32, phone: PhoneNumber) -> Self {
+        Self {
+            user_id,
+            phone,
+            code: format!("{:06}", OsRng.next_u32() % 1_000_000),
+            attempts: 0,
+            exp: get_now_unix() + OTP_VALIDITY_SECS,
+        }
+    }
+    pub fn get_user_id(&self) -> i32 {
+        self.user_id
+    }
+    pub fn phone(&self) -> &PhoneNumber {
+        &self.phone
+    }
+
+    /// Store the code under `key` then text `template`, where `__CODE__` is replaced by it
+    pub async fn send(&self, key: &str, template: &str) -> AppResult<()> {
+        redis_set_ex(&key, self, OTP_VALIDITY_SECS).await?;
+        send_sms(&self.phone, &template.replace("__CODE__", &self.code)).await
+    }
+
+    /// A valid code is consumed, a wrong one counts as an attempt
+    pub async fn check(key: &str, code: &str) -> AppResult<OtpCheck> {
+        let Some(mut otp) = redis_get::<_, SmsOtp>(&key).await? else {
+            return Ok(OtpCheck::Expired);
+        };
+        let now = get_now_unix();
+        if otp.exp <= now {
+            redis_del(&key).await?;
+            return Ok(OtpCheck::Expired);
+        }
+        if otp.code == code.trim() {
+            redis_del(&key).await?;
+            return Ok(OtpCheck::Valid(otp));
+        }
+
+        otp.attempts += 1;
+        if otp.attempts >= OTP_MAX_ATTEMPTS {
+            redis_del(&key).await?;
+            return Ok(OtpCheck::Expired);
+        }
+        redis_set_ex(&key, &otp, otp.exp - now).await?;
+        Ok(OtpCheck::Invalid)
+    }
+}
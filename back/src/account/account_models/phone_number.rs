@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+};
+
+/// Shortest and longest E.164 numbers, country code included
+const MIN_DIGITS: usize = 7;
+const MAX_DIGITS: usize = 15;
+
+#[derive(Deserialize)]
+pub struct RawPhoneNumber {
+    phone_number: String,
+}
+impl RawPhoneNumber {
+    pub fn verify(self) -> AppResult<PhoneNumber> {
+        PhoneNumber::new(self.phone_number)
+    }
+}
+
+/// International number in E.164 form, e.g. `+33612345678`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PhoneNumber {
+    number: String,
+}
+impl PhoneNumber {
+    pub fn new(number: impl Into<String>) -> AppResult<Self> {
+        Self::parse(&number.into())
+            .ok_or_else(|| AppError::invalid_field("phone_number", ErrorCode::PhoneInvalid))
+    }
+
+    /// Spaces, dots, dashes and parentheses are ignored and the `00` international
+    /// prefix is accepted, national numbers without a country code are not
+    pub fn parse(raw: &str) -> Option<Self> {
+        let compact: String = raw
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '.' | '-' | '(' | ')'))
+            .collect();
+        let digits = compact
+            .strip_prefix('+')
+            .or_else(|| compact.strip_prefix("00"))?;
+        let valid = (MIN_DIGITS..=MAX_DIGITS).contains(&digits.len())
+            && digits.chars().all(|c| c.is_ascii_digit())
+            && !digits.starts_with('0');
+        valid.then(|| Self {
+            number: format!("+{digits}"),
+        })
+    }
+
+    /// Only the last two digits, e.g. `+•••••••••78`, for responses and logs
+    pub fn masked(&self) -> String {
+        let visible = self.number.len().saturating_sub(2);
+        format!(
+            "+{}{}",
+            "•".repeat(visible.saturating_sub(1)),
+            &self.number[visible..]
+        )
+    }
+}
+impl AsRef<str> for PhoneNumber {
+    fn as_ref(&self) -> &str {
+        &self.number
+    }
+}
+///this trait is should only be use for database deserialisation
+impl From<String> for PhoneNumber {
+    fn from(number: String) -> Self {
+        PhoneNumber { number }
+    }
+}
@@ -0,0 +1,529 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    APP_URL,
+    account::account_models::{
+        otp::{OtpCheck, SmsOtp},
+        phone_number::RawPhoneNumber,
+    },
+    auth::{
+        auth_models::{
+            cache_key::CacheKey,
+            claims::Claims,
+            email::{Email, RawEmail},
+            email_change_link::EmailChangeLink,
+            restore_link::RestoreLink,
+            scope::Scope,
+            session_revocation::revoke_sessions,
+        },
+        registration_policy::REGISTRATION_POLICY,
+    },
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult, FieldError},
+    models::{
+        audit_event_model::{AuditAction, AuditEvent, ClientInfo},
+        data_export_model::DataExport,
+        invitation_model::Invitation,
+        organization_model::Organization,
+        personal_access_token_model::PersonalAccessToken,
+        profile_model::{Profile, ProfileChanges},
+        user_model::User,
+    },
+    services::openapi_service::ApiSchema,
+    shared::patch_field,
+    utils::{
+        email_utils::{escape_html, mail_date, send_mail},
+        env_utils::env_or,
+        image_utils::resize_avatar,
+        password_utils::{PasswordCheck, verify_password},
+        redis_utils::redis_del,
+    },
+};
+
+const PHONE_CODE_SMS: &str = "Votre code de vérification : __CODE__";
+
+const TOKEN_NAME_MAX_LENGTH: usize = 100;
+const DISPLAY_NAME_MAX_LENGTH: usize = 64;
+const MAX_VALIDITY_DAYS: i64 = 366;
+
+fn phone_otp_key(user_id: i32) -> String {
+    format!("otp:phone:{user_id}")
+}
+
+/// What the account owner sees of its phone settings
+#[derive(Serialize)]
+pub struct PhoneStatus {
+    pub phone_number: Option<String>,
+    pub verified: bool,
+    pub sms_two_factor: bool,
+}
+impl From<&User> for PhoneStatus {
+    fn from(user: &User) -> Self {
+        Self {
+            phone_number: user.phone_number.clone(),
+            verified: user.is_phone_verified(),
+            sms_two_factor: user.has_sms_two_factor(),
+        }
+    }
+}
+impl ApiSchema for PhoneStatus {
+    fn schema_name() -> String {
+        "PhoneStatus".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["verified", "sms_two_factor"],
+            "properties": {
+                "phone_number": { "type": "string", "nullable": true, "example": "+33612345678" },
+                "verified": { "type": "boolean" },
+                "sms_two_factor": { "type": "boolean" }
+            }
+        })
+    }
+}
+
+pub async fn phone_status_service(user_id: i32) -> AppResult<PhoneStatus> {
+    Ok(PhoneStatus::from(&User::get(user_id).await?))
+}
+
+/// Replace the number and text it a code, the number stays unverified until the code comes back
+pub async fn set_phone_service(user_id: i32, raw_phone: RawPhoneNumber) -> AppResult<()> {
+    let phone = raw_phone.verify()?;
+    User::set_phone_number(user_id, &phone).await?;
+    SmsOtp::new(user_id, phone)
+        .send(&phone_otp_key(user_id), PHONE_CODE_SMS)
+        .await
+}
+
+pub async fn resend_phone_code_service(user_id: i32) -> AppResult<()> {
+    let user = User::get(user_id).await?;
+    let Some(phone) = user.phone() else {
+        return Err(AppError::Validation(ErrorCode::PhoneNotSet));
+    };
+    SmsOtp::new(user_id, phone)
+        .send(&phone_otp_key(user_id), PHONE_CODE_SMS)
+        .await
+}
+
+pub enum VerifyPhoneResult {
+    Verified,
+    CodeInvalid,
+    CodeExpired,
+}
+
+pub async fn verify_phone_service(user_id: i32, code: &str) -> AppResult<VerifyPhoneResult> {
+    let otp = match SmsOtp::check(&phone_otp_key(user_id), code).await? {
+        OtpCheck::Valid(otp) => otp,
+        OtpCheck::Invalid => return Ok(VerifyPhoneResult::CodeInvalid),
+        OtpCheck::Expired => return Ok(VerifyPhoneResult::CodeExpired),
+    };
+    // the number may have been replaced or removed since the code was sent
+    if User::get(user_id).await?.phone().as_ref() != Some(otp.phone()) {
+        return Ok(VerifyPhoneResult::CodeExpired);
+    }
+    User::verify_phone(user_id).await?;
+    Ok(VerifyPhoneResult::Verified)
+}
+
+pub async fn remove_phone_service(user_id: i32) -> AppResult<()> {
+    redis_del(&phone_otp_key(user_id)).await?;
+    User::remove_phone_number(user_id).await
+}
+
+pub async fn set_sms_two_factor_service(user_id: i32, enabled: bool) -> AppResult<PhoneStatus> {
+    let user = User::get(user_id).await?;
+    if enabled && !user.is_phone_verified() {
+        return Err(AppError::Validation(ErrorCode::PhoneNotVerified));
+    }
+    User::set_sms_two_factor(user_id, enabled).await?;
+    phone_status_service(user_id).await
+}
+
+/// Absent fields are kept, `null` clears them
+#[derive(Deserialize)]
+pub struct ProfileUpdate {
+    #[serde(default, deserialize_with = "patch_field")]
+    display_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "patch_field")]
+    locale: Option<Option<String>>,
+    #[serde(default, deserialize_with = "patch_field")]
+    timezone: Option<Option<String>>,
+}
+
+pub async fn profile_service(user_id: i32) -> AppResult<Profile> {
+    Profile::get(user_id).await
+}
+
+pub async fn update_profile_service(user_id: i32, update: ProfileUpdate) -> AppResult<Profile> {
+    let mut errors = Vec::new();
+    // blank clears the name
+    let display_name = update.display_name.map(|name| {
+        let name = name.map(|name| name.trim().to_string());
+        name.filter(|name| !name.is_empty())
+    });
+    if let Some(Some(name)) = &display_name
+        && (name.chars().count() > DISPLAY_NAME_MAX_LENGTH || name.chars().any(char::is_control))
+    {
+        errors.push(FieldError::new("display_name", ErrorCode::ValidationFailed));
+    }
+    if let Some(Some(locale)) = &update.locale
+        && !is_locale(locale)
+    {
+        errors.push(FieldError::new("locale", ErrorCode::ValidationFailed));
+    }
+    if let Some(Some(timezone)) = &update.timezone
+        && timezone.parse::<chrono_tz::Tz>().is_err()
+    {
+        errors.push(FieldError::new("timezone", ErrorCode::ValidationFailed));
+    }
+    if !errors.is_empty() {
+        return Err(AppError::InvalidFields(errors));
+    }
+
+    let changes = ProfileChanges {
+        display_name,
+        locale: update.locale,
+        timezone: update.timezone,
+    };
+    Profile::update(user_id, &changes).await
+}
+
+/// `language[-REGION]` BCP 47 tags, e.g. `fr` or `fr-FR`
+fn is_locale(tag: &str) -> bool {
+    let mut parts = tag.split('-');
+    let language = parts.next().unwrap_or_default();
+    let region = parts.next();
+    parts.next().is_none()
+        && (2..=3).contains(&language.len())
+        && language.bytes().all(|c| c.is_ascii_lowercase())
+        && region.is_none_or(|region| {
+            (region.len() == 2 && region.bytes().all(|c| c.is_ascii_uppercase()))
+                || (region.len() == 3 && region.bytes().all(|c| c.is_ascii_digit()))
+        })
+}
+
+pub async fn set_avatar_service(user_id: i32, upload: &[u8]) -> AppResult<Profile> {
+    let png = resize_avatar(upload)?;
+    Profile::set_avatar(user_id, Some(&png)).await
+}
+
+pub async fn remove_avatar_service(user_id: i32) -> AppResult<Profile> {
+    Profile::set_avatar(user_id, None).await
+}
+
+#[derive(Deserialize)]
+pub struct NewToken {
+    name: String,
+    scopes: Vec<String>,
+    /// Never expires when absent
+    expires_in_days: Option<i64>,
+}
+
+/// Returned once, at creation: only a hash of `token` is kept
+#[derive(Serialize)]
+pub struct CreatedToken {
+    pub token: String,
+    #[serde(flatten)]
+    pub details: PersonalAccessToken,
+}
+impl ApiSchema for CreatedToken {
+    fn schema_name() -> String {
+        "CreatedToken".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "allOf": [
+                PersonalAccessToken::schema_ref(),
+                {
+                    "type": "object",
+                    "required": ["token"],
+                    "properties": {
+                        "token": {
+                            "type": "string",
+                            "description": "Send as `Authorization: Bearer <token>`, it cannot be retrieved later",
+                            "example": "pat_3f9a1c2b..."
+                        }
+                    }
+                }
+            ]
+        })
+    }
+    fn register(schemas: &mut serde_json::Map<String, Value>) {
+        PersonalAccessToken::register(schemas);
+        schemas.insert(Self::schema_name(), Self::schema());
+    }
+}
+
+/// `expires_in_days` of tokens and invitations, never expiring when absent
+fn expiry_from_days(days: Option<i64>) -> AppResult<Option<OffsetDateTime>> {
+    match days {
+        None => Ok(None),
+        Some(days @ 1..=MAX_VALIDITY_DAYS) => {
+            Ok(Some(OffsetDateTime::now_utc() + Duration::days(days)))
+        }
+        Some(_) => Err(AppError::invalid_field(
+            "expires_in_days",
+            ErrorCode::ValidationFailed,
+        )),
+    }
+}
+
+pub async fn create_token_service(user_id: i32, new_token: NewToken) -> AppResult<CreatedToken> {
+    let name = new_token.name.trim();
+    if name.is_empty() || name.chars().count() > TOKEN_NAME_MAX_LENGTH {
+        return Err(AppError::invalid_field("name", ErrorCode::ValidationFailed));
+    }
+    let scopes = new_token
+        .scopes
+        .iter()
+        .map(|raw| Scope::parse(raw))
+        .collect::<Option<Vec<_>>>()
+        .filter(|scopes| !scopes.is_empty())
+        .ok_or_else(|| AppError::invalid_field("scopes", ErrorCode::ValidationFailed))?;
+    let expires_at = expiry_from_days(new_token.expires_in_days)?;
+
+    let (details, token) = PersonalAccessToken::create(user_id, name, &scopes, expires_at).await?;
+    Ok(CreatedToken { token, details })
+}
+
+pub async fn list_tokens_service(user_id: i32) -> AppResult<Vec<PersonalAccessToken>> {
+    PersonalAccessToken::list(user_id).await
+}
+
+pub async fn revoke_token_service(user_id: i32, token_id: i32) -> AppResult<PersonalAccessToken> {
+    PersonalAccessToken::revoke(user_id, token_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))
+}
+
+#[derive(Deserialize)]
+pub struct NewInvitation {
+    /// Only this address may use the invitation, which is mailed to it
+    email: Option<RawEmail>,
+    /// Defaults to a single use
+    max_uses: Option<i32>,
+    expires_in_days: Option<i64>,
+}
+
+pub async fn create_invitation_service(
+    claims: &Claims,
+    new_invitation: NewInvitation,
+) -> AppResult<Invitation> {
+    let max_uses_cap = REGISTRATION_POLICY.invitation_max_uses(claims)?;
+    let max_uses = new_invitation.max_uses.unwrap_or(1);
+    if max_uses < 1 || max_uses_cap.is_some_and(|cap| max_uses > cap) {
+        return Err(AppError::invalid_field(
+            "max_uses",
+            ErrorCode::ValidationFailed,
+        ));
+    }
+    let email = new_invitation.email.map(RawEmail::verify).transpose()?;
+    let expires_at = expiry_from_days(new_invitation.expires_in_days)?;
+
+    let invitation =
+        Invitation::create(claims.user_id, email.as_ref(), max_uses, expires_at).await?;
+    if let Some(email) = &email {
+        let inviter = User::get(claims.user_id).await?;
+        send_invitation_email(email, inviter.email.as_ref(), &invitation.code)?;
+    }
+    Ok(invitation)
+}
+
+fn send_invitation_email(email: &Email, inviter: &str, code: &str) -> AppResult<()> {
+    let register_url = format!("{APP_URL}/auth/register?invitation={code}");
+
+    let html_template = include_str!("../templates/invitation_email.html");
+    let html = html_template
+        .replace("__REGISTER_URL__", &register_url)
+        .replace("__INVITER__", &escape_html(inviter))
+        .replace("__CODE__", code);
+
+    send_mail(email, "Vous êtes invité à nous rejoindre", html)
+}
+
+pub async fn list_invitations_service(user_id: i32) -> AppResult<Vec<Invitation>> {
+    Invitation::list(user_id).await
+}
+
+pub async fn revoke_invitation_service(user_id: i32, invitation_id: i32) -> AppResult<Invitation> {
+    Invitation::revoke(user_id, invitation_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))
+}
+
+#[derive(Deserialize)]
+pub struct EmailChangeRequest {
+    email: RawEmail,
+    /// Asked again, a stolen access token is not enough
+    password: String,
+}
+
+/// Mails a confirmation link to the new address, the account keeps the current one
+/// until it is followed
+pub async fn request_email_change_service(
+    user_id: i32,
+    request: EmailChangeRequest,
+) -> AppResult<()> {
+    let email = request.email.verify()?;
+    let user = User::get(user_id).await?;
+    if let PasswordCheck::Invalid = verify_password(&request.password, &user.password)? {
+        return Err(AppError::Forbiden(ErrorCode::CredentialsIncorrect));
+    }
+    if User::get_user_id_from_email(&email).await?.is_some() {
+        return Err(AppError::Conflict(ErrorCode::EmailAlreadyExists));
+    }
+    let key = EmailChangeLink::create(user_id, email.clone()).await?;
+    send_email_change_email(&email, &key)
+}
+
+fn send_email_change_email(email: &Email, key: &CacheKey) -> AppResult<()> {
+    let confirm_url = format!("{APP_URL}/auth/email/confirm?token={}", key.as_ref());
+
+    let html_template = include_str!("../templates/email_change_email.html");
+    let html = html_template.replace("__CONFIRM_URL__", &confirm_url);
+
+    send_mail(email, "Confirmez votre nouvelle adresse", html)
+}
+
+#[derive(Deserialize)]
+pub struct DeletionRequest {
+    /// Asked again, a stolen access token is not enough
+    password: String,
+}
+
+#[derive(Serialize)]
+pub struct DeletionStatus {
+    /// UNIX timestamps
+    pub requested_at: i64,
+    pub scheduled_at: i64,
+}
+impl ApiSchema for DeletionStatus {
+    fn schema_name() -> String {
+        "DeletionStatus".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["requested_at", "scheduled_at"],
+            "properties": {
+                "requested_at": { "type": "integer", "format": "int64" },
+                "scheduled_at": {
+                    "type": "integer",
+                    "format": "int64",
+                    "description": "Hard deletion, unless restored before"
+                }
+            }
+        })
+    }
+}
+
+fn deletion_grace_days() -> i64 {
+    env_or("ACCOUNT_DELETION_GRACE_DAYS", 30i64).max(0)
+}
+
+/// Signs the user out everywhere and mails a restore link valid until the hard
+/// deletion done by `jobs::account_purge`
+pub async fn request_deletion_service(
+    user_id: i32,
+    request: DeletionRequest,
+    client: &ClientInfo,
+) -> AppResult<DeletionStatus> {
+    let user = User::get(user_id).await?;
+    if user.is_pending_deletion() {
+        return Err(AppError::Conflict(ErrorCode::AccountPendingDeletion));
+    }
+    if let PasswordCheck::Invalid = verify_password(&request.password, &user.password)? {
+        return Err(AppError::Forbiden(ErrorCode::CredentialsIncorrect));
+    }
+    // the other members would be left without owner
+    if !Organization::owned_alone(user_id).await?.is_empty() {
+        return Err(AppError::Conflict(ErrorCode::OrganizationLastOwner));
+    }
+
+    let grace_days = deletion_grace_days();
+    let requested_at = OffsetDateTime::now_utc();
+    let scheduled_at = requested_at + Duration::days(grace_days);
+    User::schedule_deletion(user_id, requested_at, scheduled_at).await?;
+    revoke_sessions(user_id).await?;
+    PersonalAccessToken::revoke_all(user_id).await?;
+
+    let key = RestoreLink::create(user_id, (grace_days as u64 * 24 * 60 * 60).max(1)).await?;
+    send_deletion_email(&user.email, &key, scheduled_at)?;
+
+    AuditEvent::new(AuditAction::AccountDeletionRequested)
+        .user(user_id)
+        .client(client)
+        .metadata(json!({ "scheduled_at": scheduled_at.unix_timestamp() }))
+        .record()
+        .await?;
+    Ok(DeletionStatus {
+        requested_at: requested_at.unix_timestamp(),
+        scheduled_at: scheduled_at.unix_timestamp(),
+    })
+}
+
+fn send_deletion_email(
+    email: &Email,
+    key: &CacheKey,
+    scheduled_at: OffsetDateTime,
+) -> AppResult<()> {
+    let restore_url = format!("{APP_URL}/auth/restore?token={}", key.as_ref());
+    let deletion_date = mail_date(scheduled_at);
+
+    let html_template = include_str!("../templates/account_deletion_email.html");
+    let html = html_template
+        .replace("__RESTORE_URL__", &restore_url)
+        .replace("__DELETION_DATE__", &deletion_date);
+
+    send_mail(email, "Suppression de votre compte", html)
+}
+
+/// A pending export is returned as is rather than queued twice,
+/// `jobs::data_export` mails the download link once it is built
+pub async fn request_export_service(user_id: i32, client: &ClientInfo) -> AppResult<DataExport> {
+    if let Some(export) = DataExport::latest(user_id).await?
+        && export.is_pending()
+    {
+        return Ok(export);
+    }
+    let export = DataExport::create(user_id).await?;
+    AuditEvent::new(AuditAction::DataExportRequested)
+        .user(user_id)
+        .client(client)
+        .metadata(json!({ "export_id": export.id }))
+        .record()
+        .await?;
+    Ok(export)
+}
+
+pub async fn export_status_service(user_id: i32) -> AppResult<DataExport> {
+    DataExport::latest(user_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))
+}
+
+#[derive(Deserialize)]
+pub struct ExportDownloadQuery {
+    /// From the mailed link
+    pub key: String,
+}
+
+/// The archive is dropped as soon as it is handed out
+pub async fn download_export_service(
+    user_id: i32,
+    key: &str,
+    client: &ClientInfo,
+) -> AppResult<Vec<u8>> {
+    let Some(archive) = DataExport::take_archive(user_id, key).await? else {
+        return Err(AppError::NotFound(ErrorCode::TokenInvalid));
+    };
+    AuditEvent::new(AuditAction::DataExportDownloaded)
+        .user(user_id)
+        .client(client)
+        .record()
+        .await?;
+    Ok(archive)
+}
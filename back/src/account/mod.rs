@@ -0,0 +1,3 @@
+pub mod account_controller;
+pub mod account_models;
+pub mod account_service;
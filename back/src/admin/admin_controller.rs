@@ -0,0 +1,79 @@
+use actix_web::web::{Json, Path, Query};
+use actix_web::{
+    HttpResponse, delete, get,
+    http::header::{CacheControl, CacheDirective, ContentDisposition},
+    post,
+};
+
+use crate::{
+    admin::admin_service::{
+        ExportQuery, ImpersonationRequest, ImpersonationSession, ImpersonationStatus,
+        corrupted_blobs_service, export_audit_events_service, impersonate_service,
+        impersonation_status, list_audit_events_service, stop_impersonation_service,
+    },
+    auth::auth_models::claims::Claims,
+    errors::AppResult,
+    models::{
+        audit_event_model::{AuditFilter, AuditRecord, ClientInfo},
+        blob_model::CorruptedBlob,
+    },
+    shared::{ApiResponse, JsonResponse, PageQuery},
+};
+
+/// Mounted in the `/admin` scope, guarded by `RequireAuth::role(Role::Admin)`
+#[post("/users/{user_id}/impersonate")]
+pub async fn impersonate(
+    client: ClientInfo,
+    claims: Claims,
+    user_id: Path<i32>,
+    Json(request): Json<ImpersonationRequest>,
+) -> ApiResponse<ImpersonationSession> {
+    let session = impersonate_service(&claims, user_id.into_inner(), request, &client).await?;
+    JsonResponse::created().no_store().object(session)
+}
+
+/// Mounted in the `/impersonation` scope, called with the impersonation token
+#[get("")]
+pub async fn current_impersonation(claims: Claims) -> ApiResponse<ImpersonationStatus> {
+    JsonResponse::ok().object(impersonation_status(&claims)?)
+}
+
+#[delete("")]
+pub async fn stop_impersonation(client: ClientInfo, claims: Claims) -> ApiResponse {
+    stop_impersonation_service(&claims, &client).await?;
+    JsonResponse::ok().empty()
+}
+
+/// `?action=&outcome=&actor_id=&subject_id=&since=&until=&page=&per_page=`, newest first
+#[get("/audit-events")]
+pub async fn list_audit_events(
+    Query(filter): Query<AuditFilter>,
+    Query(page): Query<PageQuery>,
+) -> ApiResponse<Vec<AuditRecord>> {
+    let (events, meta) = list_audit_events_service(&filter, page).await?;
+    JsonResponse::ok().no_store().page(events, meta)
+}
+
+/// Same filters as the listing, `?format=csv` (default) or `?format=jsonl`
+#[get("/audit-events/export")]
+pub async fn export_audit_events(
+    client: ClientInfo,
+    claims: Claims,
+    Query(filter): Query<AuditFilter>,
+    Query(export): Query<ExportQuery>,
+) -> AppResult<HttpResponse> {
+    let export = export_audit_events_service(&claims, &filter, export.format, &client).await?;
+    Ok(HttpResponse::Ok()
+        .content_type(export.content_type)
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .insert_header(ContentDisposition::attachment(export.filename))
+        .body(export.body))
+}
+
+/// Contents the integrity scrub found missing or altered, most recent first
+#[get("/blobs/corrupted")]
+pub async fn list_corrupted_blobs() -> ApiResponse<Vec<CorruptedBlob>> {
+    JsonResponse::ok()
+        .no_store()
+        .object(corrupted_blobs_service().await?)
+}
@@ -0,0 +1,285 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    auth::auth_models::{claims::Claims, impersonation::Actor, token::TokenAble},
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    models::{
+        audit_event_model::{AuditAction, AuditEvent, AuditFilter, AuditRecord, ClientInfo},
+        blob_model::{Blob, CorruptedBlob},
+        user_model::User,
+    },
+    services::openapi_service::ApiSchema,
+    shared::{PageMeta, PageQuery, get_now_unix},
+    utils::env_utils::env_or,
+};
+
+const REASON_MAX_LENGTH: usize = 255;
+const AUDIT_PER_PAGE: u32 = 50;
+const AUDIT_MAX_PER_PAGE: u32 = 200;
+/// Larger extracts are split with `since` / `until`
+const AUDIT_EXPORT_MAX_ROWS: u64 = 50_000;
+
+fn impersonation_ttl_secs() -> u64 {
+    env_or("IMPERSONATION_MINUTES", 30u64) * 60
+}
+
+#[derive(Deserialize)]
+pub struct ImpersonationRequest {
+    /// Kept in the audit log, e.g. the support ticket
+    reason: String,
+}
+
+/// The token acts as `user_id` until `expires_at` or until the session is stopped
+#[derive(Serialize)]
+pub struct ImpersonationSession {
+    pub token: String,
+    pub user_id: i32,
+    pub impersonator_id: i32,
+    pub expires_at: u64,
+}
+impl ApiSchema for ImpersonationSession {
+    fn schema_name() -> String {
+        "ImpersonationSession".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["token", "user_id", "impersonator_id", "expires_at"],
+            "properties": {
+                "token": { "type": "string", "description": "JWT whose Claims carry `act`" },
+                "user_id": { "type": "integer", "format": "int32" },
+                "impersonator_id": { "type": "integer", "format": "int32" },
+                "expires_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
+
+/// What the frontend shows while impersonating, `token` excepted
+#[derive(Serialize)]
+pub struct ImpersonationStatus {
+    pub user_id: i32,
+    pub impersonator_id: i32,
+    pub expires_at: u64,
+}
+impl ApiSchema for ImpersonationStatus {
+    fn schema_name() -> String {
+        "ImpersonationStatus".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["user_id", "impersonator_id", "expires_at"],
+            "properties": {
+                "user_id": { "type": "integer", "format": "int32" },
+                "impersonator_id": { "type": "integer", "format": "int32" },
+                "expires_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
+
+/// Admins cannot be impersonated, and an impersonation cannot be nested
+pub async fn impersonate_service(
+    admin: &Claims,
+    user_id: i32,
+    request: ImpersonationRequest,
+    client: &ClientInfo,
+) -> AppResult<ImpersonationSession> {
+    admin.require_not_impersonated()?;
+    let reason = request.reason.trim();
+    if reason.is_empty() || reason.chars().count() > REASON_MAX_LENGTH {
+        return Err(AppError::invalid_field(
+            "reason",
+            ErrorCode::ValidationFailed,
+        ));
+    }
+    let Some(user) = User::try_get(user_id).await? else {
+        return Err(AppError::NotFound(ErrorCode::UserNotFound));
+    };
+    if user.is_admin() || user.id == admin.user_id {
+        return Err(AppError::Forbiden(ErrorCode::ImpersonationForbidden));
+    }
+    if !user.is_verified() {
+        return Err(AppError::Validation(ErrorCode::UserNotVerified));
+    }
+
+    let ttl_secs = impersonation_ttl_secs();
+    let actor = Actor::start(admin.user_id, ttl_secs).await?;
+    let session_id = actor.session_id.clone();
+    let claims = Claims::new_impersonation_claim(user_id, actor, ttl_secs);
+    AuditEvent::new(AuditAction::ImpersonationStarted)
+        .actor(admin.user_id)
+        .subject(user_id)
+        .client(client)
+        .metadata(json!({ "reason": reason, "session_id": session_id, "expires_at": claims.exp }))
+        .record()
+        .await?;
+
+    Ok(ImpersonationSession {
+        token: claims.encode()?.as_ref().to_string(),
+        user_id,
+        impersonator_id: admin.user_id,
+        expires_at: claims.exp,
+    })
+}
+
+pub fn impersonation_status(claims: &Claims) -> AppResult<ImpersonationStatus> {
+    let Some(actor) = &claims.act else {
+        return Err(AppError::NotFound(ErrorCode::NotFound));
+    };
+    Ok(ImpersonationStatus {
+        user_id: claims.user_id,
+        impersonator_id: actor.user_id,
+        expires_at: claims.exp,
+    })
+}
+
+/// The token is refused from now on, the admin is back to its own session
+pub async fn stop_impersonation_service(claims: &Claims, client: &ClientInfo) -> AppResult<()> {
+    let Some(actor) = &claims.act else {
+        return Err(AppError::NotFound(ErrorCode::NotFound));
+    };
+    actor.end().await?;
+    AuditEvent::new(AuditAction::ImpersonationStopped)
+        .actor(actor.user_id)
+        .subject(claims.user_id)
+        .client(client)
+        .metadata(json!({ "session_id": actor.session_id }))
+        .record()
+        .await
+}
+
+pub async fn list_audit_events_service(
+    filter: &AuditFilter,
+    page: PageQuery,
+) -> AppResult<(Vec<AuditRecord>, PageMeta)> {
+    let (page, per_page) = page.resolve(AUDIT_PER_PAGE, AUDIT_MAX_PER_PAGE);
+    let total = AuditRecord::count(filter).await?;
+    let offset = (page as u64 - 1) * per_page as u64;
+    let events = AuditRecord::search(filter, per_page as u64, offset).await?;
+    Ok((events, PageMeta::new(page, per_page, total)))
+}
+
+/// Reported by the integrity scrub, until found intact again or re-uploaded
+pub async fn corrupted_blobs_service() -> AppResult<Vec<CorruptedBlob>> {
+    Blob::corrupted().await
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+pub struct AuditExport {
+    pub content_type: &'static str,
+    pub filename: String,
+    pub body: String,
+}
+
+const CSV_COLUMNS: [&str; 9] = [
+    "id",
+    "created_at",
+    "action",
+    "outcome",
+    "actor_id",
+    "subject_id",
+    "ip",
+    "user_agent",
+    "metadata",
+];
+
+/// Newest first, the export itself is recorded
+pub async fn export_audit_events_service(
+    admin: &Claims,
+    filter: &AuditFilter,
+    format: ExportFormat,
+    client: &ClientInfo,
+) -> AppResult<AuditExport> {
+    let events = AuditRecord::search(filter, AUDIT_EXPORT_MAX_ROWS, 0).await?;
+    AuditEvent::new(AuditAction::AuditExported)
+        .actor(admin.user_id)
+        .client(client)
+        .metadata(json!({
+            "rows": events.len(),
+            "action": filter.action,
+            "actor_id": filter.actor_id,
+            "subject_id": filter.subject_id,
+            "since": filter.since,
+            "until": filter.until,
+        }))
+        .record()
+        .await?;
+
+    let stamp = get_now_unix();
+    let export = match format {
+        ExportFormat::Jsonl => {
+            let mut body = String::new();
+            for event in &events {
+                body.push_str(&serde_json::to_string(event)?);
+                body.push('\n');
+            }
+            AuditExport {
+                content_type: "application/x-ndjson",
+                filename: format!("audit-events-{stamp}.jsonl"),
+                body,
+            }
+        }
+        ExportFormat::Csv => {
+            let mut body = CSV_COLUMNS.join(",");
+            body.push_str("\r\n");
+            for event in &events {
+                let metadata = event.metadata.as_ref().map(Value::to_string);
+                let fields = [
+                    event.id.to_string(),
+                    event.created_at.to_string(),
+                    event.action.clone(),
+                    event.outcome.clone(),
+                    event.actor_id.map(|id| id.to_string()).unwrap_or_default(),
+                    event
+                        .subject_id
+                        .map(|id| id.to_string())
+                        .unwrap_or_default(),
+                    event.ip.clone().unwrap_or_default(),
+                    event.user_agent.clone().unwrap_or_default(),
+                    metadata.unwrap_or_default(),
+                ];
+                let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                body.push_str(&row.join(","));
+                body.push_str("\r\n");
+            }
+            AuditExport {
+                content_type: "text/csv; charset=utf-8",
+                filename: format!("audit-events-{stamp}.csv"),
+                body,
+            }
+        }
+    };
+    Ok(export)
+}
+
+/// RFC 4180 quoting; a leading `=`, `+`, `-` or `@` is defused so that a user agent
+/// cannot run as a formula once the file is opened in a spreadsheet
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
@@ -1,51 +1,111 @@
 use crate::auth::auth_models::auth_state::try_extract_auth_state;
 use crate::auth::auth_models::cache_key::CacheKey;
 use crate::auth::auth_service::{
-    ChangePasswordResult, LoginResult, RegisterResult, ValidateResult, VerifyResult,
-    change_password_service, create_verification_token_and_send_mail, forgot_service,
-    login_service, register_service, resend_verification_mail, validate_service, verify_service,
+    ChangePasswordResult, EmailChangeResult, LoginResult, MagicLinkResult, RegisterResult,
+    RestoreResult, SmsLoginResult, ValidateResult, VerifyResult, change_password_service,
+    confirm_email_change_service, create_verification_token_and_send_mail, forgot_service,
+    login_service, login_sms_service, logout_service, magic_link_login_service, register_service,
+    request_magic_link_service, resend_verification_mail, restore_account_service,
+    validate_service, verify_service,
 };
-use crate::constants::messages::{TOKEN_EXPIRED, TOKEN_INVALID};
+use crate::auth::password_policy::{PASSWORD_POLICY, PasswordPolicy, violations_to_error};
+use crate::auth::registration_policy::{REGISTRATION_POLICY, RegistrationPolicy};
 use crate::{
     auth::auth_models::{
         auth_state::AuthState,
-        credential::{RawEmail, RawLoginCredential},
+        credential::{RawLoginCredential, RawRegistration},
+        csrf_token::CsrfToken,
+        email::RawEmail,
         refresh_token::{REFRESH_TOKEN_KEY, RefreshClaim},
+        sms_challenge::{SmsChallenge, SmsChallengeAnswer},
         token::TokenAble,
     },
-    constants::messages::{
-        CREDENTIALS_INCORECT, EMAIL_ALREADY_EXIST, USER_LOGGED_OUT, USER_NOT_LOGIN,
-        USER_NOT_VERIFIED, USER_VERIFIED,
-    },
+    constants::codes::{ErrorCode, MessageCode},
     errors::{AppError, AppResult},
-    shared::{APIResponse, JsonResponse},
+    models::audit_event_model::ClientInfo,
+    shared::{ApiResponse, JsonResponse},
 };
 use actix_session::{Session, SessionExt, SessionInsertError};
 use actix_web::web::Json;
-use actix_web::{HttpRequest, http::StatusCode, post};
+use actix_web::{HttpRequest, get, post};
 use serde::Deserialize;
 
 #[post("/login")]
-pub async fn login(req: HttpRequest, raw_credentials: Json<RawLoginCredential>) -> APIResponse {
+pub async fn login(
+    req: HttpRequest,
+    client: ClientInfo,
+    raw_credentials: Json<RawLoginCredential>,
+) -> ApiResponse<SmsChallenge> {
     // --- Case 1 : User already connected ---
     let auth_state = try_extract_auth_state(&req).await?;
-    let login_result = login_service(auth_state, raw_credentials.into_inner()).await?;
+    let login_result = login_service(auth_state, raw_credentials.into_inner(), &client).await?;
 
     match login_result {
         LoginResult::CredentialsIncorect => {
-            JsonResponse::unauthorized().message(CREDENTIALS_INCORECT)
+            Err(AppError::Unauthorized(ErrorCode::CredentialsIncorrect))
         }
+        LoginResult::PendingDeletion => Err(AppError::Forbiden(ErrorCode::AccountPendingDeletion)),
         LoginResult::Connected(token_claims, maybe_refresh_claim) => {
             if let Some(refresh_claim) = maybe_refresh_claim {
                 try_insert_refresh_token_in_session(&req.get_session(), &refresh_claim)?;
             }
-            JsonResponse::token(token_claims)
+            JsonResponse::ok().token(token_claims)
         }
+        // no session until the code comes back
+        LoginResult::SecondFactorRequired(challenge) => JsonResponse::ok().object(challenge),
         LoginResult::NotVerified(maybe_refresh_claim) => {
             if let Some(refresh_claim) = maybe_refresh_claim {
                 try_insert_refresh_token_in_session(&req.get_session(), &refresh_claim)?;
             }
-            JsonResponse::ok().message(USER_NOT_VERIFIED)
+            JsonResponse::ok().message(MessageCode::UserNotVerified)
+        }
+    }
+}
+#[post("/login/sms")]
+pub async fn login_sms(
+    req: HttpRequest,
+    client: ClientInfo,
+    Json(answer): Json<SmsChallengeAnswer>,
+) -> ApiResponse {
+    match login_sms_service(answer, &client).await? {
+        SmsLoginResult::Connected(token, refresh_claim) => {
+            try_insert_refresh_token_in_session(&req.get_session(), &refresh_claim)?;
+            JsonResponse::token(token)
+        }
+        SmsLoginResult::CodeInvalid => Err(AppError::Unauthorized(ErrorCode::OtpInvalid)),
+        SmsLoginResult::CodeExpired => Err(AppError::Unauthorized(ErrorCode::OtpExpired)),
+    }
+}
+#[post("/magic-link")]
+pub async fn request_magic_link(
+    auth_state: AuthState,
+    session: Session,
+    Json(raw_email): Json<RawEmail>,
+) -> ApiResponse {
+    if let AuthState::Connected(claims) = auth_state {
+        claims.require_interactive()?;
+        return JsonResponse::ok().token(claims.encode()?);
+    }
+    request_magic_link_service(raw_email, &session).await?;
+    JsonResponse::ok().empty()
+}
+#[post("/magic-link/verify")]
+pub async fn magic_link_login(
+    session: Session,
+    client: ClientInfo,
+    Json(key): Json<CacheKey>,
+) -> ApiResponse<SmsChallenge> {
+    match magic_link_login_service(key, &session, &client).await? {
+        MagicLinkResult::Connected(token, refresh_claim) => {
+            try_insert_refresh_token_in_session(&session, &refresh_claim)?;
+            JsonResponse::ok().token(token)
+        }
+        MagicLinkResult::SecondFactorRequired(challenge) => JsonResponse::ok().object(challenge),
+        MagicLinkResult::Invalid => Err(AppError::NotFound(ErrorCode::TokenInvalid)),
+        MagicLinkResult::Expired => Err(AppError::Unauthorized(ErrorCode::TokenExpired)),
+        MagicLinkResult::OtherDevice => Err(AppError::Forbiden(ErrorCode::MagicLinkOtherDevice)),
+        MagicLinkResult::PendingDeletion => {
+            Err(AppError::Forbiden(ErrorCode::AccountPendingDeletion))
         }
     }
 }
@@ -64,68 +124,92 @@ fn map_session_insert_error(err: SessionInsertError) -> AppError {
 }
 
 #[post("/register")]
-pub async fn register(req: HttpRequest, raw_credentials: Json<RawLoginCredential>) -> APIResponse {
+pub async fn register(
+    req: HttpRequest,
+    client: ClientInfo,
+    Json(registration): Json<RawRegistration>,
+) -> ApiResponse {
     // --- Case 1 : User already connected ---
     let auth_state = try_extract_auth_state(&req).await?;
 
-    match register_service(raw_credentials.into_inner(), auth_state).await? {
+    match register_service(registration, auth_state, &client).await? {
         RegisterResult::EmailAlreadyExist => {
-            JsonResponse::status(StatusCode::CONFLICT).message(EMAIL_ALREADY_EXIST)
+            Err(AppError::Conflict(ErrorCode::EmailAlreadyExists))
         }
-        RegisterResult::NotVerified => JsonResponse::ok().message(USER_NOT_VERIFIED),
+        RegisterResult::NotVerified => JsonResponse::ok().message(MessageCode::UserNotVerified),
         RegisterResult::Token(token) => JsonResponse::ok().token(token),
         RegisterResult::NewUser(refresh_claim, email) => {
             try_insert_refresh_token_in_session(&req.get_session(), &refresh_claim)?;
             create_verification_token_and_send_mail(refresh_claim.get_user_id(), &email).await?;
-            JsonResponse::ok().message(USER_NOT_VERIFIED)
+            JsonResponse::ok().message(MessageCode::UserNotVerified)
         }
     }
 }
 
 type VerificationKey = CacheKey;
 #[post("/verify")]
-pub async fn verify(auth_state: AuthState, Json(verify_key): Json<VerificationKey>) -> APIResponse {
-    match verify_service(auth_state, verify_key).await? {
-        VerifyResult::Invalid => JsonResponse::not_found().message(TOKEN_INVALID),
+pub async fn verify(
+    auth_state: AuthState,
+    client: ClientInfo,
+    Json(verify_key): Json<VerificationKey>,
+) -> ApiResponse {
+    match verify_service(auth_state, verify_key, &client).await? {
+        VerifyResult::Invalid => Err(AppError::NotFound(ErrorCode::TokenInvalid)),
         VerifyResult::Token(token) => JsonResponse::token(token),
-        VerifyResult::Expired => JsonResponse::unauthorized().message(TOKEN_EXPIRED),
+        VerifyResult::Expired => Err(AppError::Unauthorized(ErrorCode::TokenExpired)),
         //in case where an already connected user verified another account
-        VerifyResult::Verified => JsonResponse::ok().message(USER_VERIFIED),
+        VerifyResult::Verified => JsonResponse::ok().message(MessageCode::UserVerified),
     }
 }
 
 #[post("/verify/resend")]
-pub async fn send_verification_email(auth_state: AuthState) -> APIResponse {
+pub async fn send_verification_email(auth_state: AuthState) -> ApiResponse {
     match auth_state {
-        AuthState::Connected(_) => JsonResponse::status(StatusCode::BAD_REQUEST).empty(),
+        AuthState::Connected(_) => Err(AppError::Validation(ErrorCode::UserAlreadyVerified)),
         AuthState::NotVerified(user_id) => {
             resend_verification_mail(user_id).await?;
             JsonResponse::ok().empty()
         }
-        AuthState::Guess => JsonResponse::unauthorized().message(USER_NOT_LOGIN),
+        AuthState::Guess => Err(AppError::Unauthorized(ErrorCode::UserNotLoggedIn)),
     }
 }
 
 #[post("/refresh_token")]
-pub async fn refresh_token(auth_state: AuthState) -> APIResponse {
+pub async fn refresh_token(auth_state: AuthState) -> ApiResponse {
     match auth_state {
-        AuthState::Connected(claims) => JsonResponse::ok().token(claims.encode()?),
-        AuthState::NotVerified(_) => JsonResponse::ok().message(USER_NOT_VERIFIED),
-        AuthState::Guess => JsonResponse::unauthorized().message(USER_NOT_LOGIN),
+        AuthState::Connected(claims) => {
+            claims.require_interactive()?;
+            JsonResponse::ok().token(claims.encode()?)
+        }
+        AuthState::NotVerified(_) => JsonResponse::ok().message(MessageCode::UserNotVerified),
+        AuthState::Guess => Err(AppError::Unauthorized(ErrorCode::UserNotLoggedIn)),
     }
 }
 
+/// Token to send back in the `X-CSRF-Token` header of requests authenticated by the session
+#[get("/csrf")]
+pub async fn csrf_token(session: Session) -> ApiResponse<CsrfToken> {
+    JsonResponse::ok()
+        .no_store()
+        .object(CsrfToken::get_or_create(&session)?)
+}
+
 #[post("/logout")]
-pub async fn logout(session: Session) -> APIResponse {
-    let _ = session.remove(REFRESH_TOKEN_KEY);
-    JsonResponse::ok().message(USER_LOGGED_OUT)
+pub async fn logout(session: Session, client: ClientInfo) -> ApiResponse {
+    logout_service(&session, &client).await?;
+    JsonResponse::ok().message(MessageCode::UserLoggedOut)
 }
 
 #[post("/forgot")]
-pub async fn forgot(auth_state: AuthState, Json(raw_email): Json<RawEmail>) -> APIResponse {
+pub async fn forgot(auth_state: AuthState, Json(raw_email): Json<RawEmail>) -> ApiResponse {
     match auth_state {
-        AuthState::Connected(claims) => return JsonResponse::ok().token(claims.encode()?),
-        AuthState::NotVerified(_) => return JsonResponse::ok().message(USER_NOT_VERIFIED),
+        AuthState::Connected(claims) => {
+            claims.require_interactive()?;
+            return JsonResponse::ok().token(claims.encode()?);
+        }
+        AuthState::NotVerified(_) => {
+            return JsonResponse::ok().message(MessageCode::UserNotVerified);
+        }
         AuthState::Guess => {}
     };
     forgot_service(raw_email).await?;
@@ -133,11 +217,11 @@ pub async fn forgot(auth_state: AuthState, Json(raw_email): Json<RawEmail>) -> A
 }
 
 #[post("/reset/validate")]
-pub async fn validate(key: Json<CacheKey>) -> APIResponse {
+pub async fn validate(key: Json<CacheKey>) -> ApiResponse {
     match validate_service(key.into_inner()).await? {
         ValidateResult::Validate => JsonResponse::ok().empty(),
-        ValidateResult::Expired => JsonResponse::unauthorized().empty(),
-        ValidateResult::Invalid => JsonResponse::not_found().empty(),
+        ValidateResult::Expired => Err(AppError::Unauthorized(ErrorCode::TokenExpired)),
+        ValidateResult::Invalid => Err(AppError::NotFound(ErrorCode::TokenInvalid)),
     }
 }
 
@@ -148,14 +232,45 @@ struct ChangePasswordRequest {
 }
 
 #[post("/reset/update")]
-pub async fn change_password(Json(req): Json<ChangePasswordRequest>) -> APIResponse {
-    match change_password_service(&req.key, &req.raw_password).await? {
+pub async fn change_password(
+    client: ClientInfo,
+    Json(req): Json<ChangePasswordRequest>,
+) -> ApiResponse {
+    match change_password_service(&req.key, &req.raw_password, &client).await? {
         ChangePasswordResult::PasswordChanged => JsonResponse::ok().empty(),
-        ChangePasswordResult::KeyInvalid => JsonResponse::unauthorized().message(TOKEN_INVALID),
-        ChangePasswordResult::PasswordInvalid => {
-            JsonResponse::unauthorized().message(CREDENTIALS_INCORECT)
+        ChangePasswordResult::KeyInvalid => Err(AppError::Unauthorized(ErrorCode::TokenInvalid)),
+        ChangePasswordResult::PasswordInvalid(violations) => {
+            Err(violations_to_error("raw_password", &violations))
         }
 
-        ChangePasswordResult::KeyExpired => JsonResponse::unauthorized().message(TOKEN_EXPIRED),
+        ChangePasswordResult::KeyExpired => Err(AppError::Unauthorized(ErrorCode::TokenExpired)),
     }
 }
+
+#[post("/restore")]
+pub async fn restore_account(client: ClientInfo, key: Json<CacheKey>) -> ApiResponse {
+    match restore_account_service(&key, &client).await? {
+        RestoreResult::Restored => JsonResponse::ok().empty(),
+        RestoreResult::Expired => Err(AppError::Unauthorized(ErrorCode::TokenExpired)),
+        RestoreResult::Invalid => Err(AppError::NotFound(ErrorCode::TokenInvalid)),
+    }
+}
+
+#[post("/email/confirm")]
+pub async fn confirm_email_change(client: ClientInfo, key: Json<CacheKey>) -> ApiResponse {
+    match confirm_email_change_service(&key, &client).await? {
+        EmailChangeResult::Changed => JsonResponse::ok().empty(),
+        EmailChangeResult::Expired => Err(AppError::Unauthorized(ErrorCode::TokenExpired)),
+        EmailChangeResult::Invalid => Err(AppError::NotFound(ErrorCode::TokenInvalid)),
+    }
+}
+
+#[get("/password-policy")]
+pub async fn get_password_policy() -> ApiResponse<PasswordPolicy> {
+    JsonResponse::ok().object(PASSWORD_POLICY.clone())
+}
+
+#[get("/registration-policy")]
+pub async fn get_registration_policy() -> ApiResponse<RegistrationPolicy> {
+    JsonResponse::ok().object(REGISTRATION_POLICY.clone())
+}
@@ -6,6 +6,7 @@ use crate::{
     auth::auth_models::{
         claims::{Claims, try_extract_claims},
         refresh_token::{REFRESH_TOKEN_KEY, RefreshClaim, handle_session_error},
+        session_revocation::is_session_revoked,
         token::TokenAble,
     },
     errors::{AppError, AppResult},
@@ -34,7 +35,7 @@ impl FromRequest for AuthState {
 }
 use actix_session::SessionExt;
 pub async fn try_extract_auth_state(req: &HttpRequest) -> AppResult<AuthState> {
-    if let Ok(claims) = try_extract_claims(req) {
+    if let Ok(claims) = try_extract_claims(req).await {
         return Ok(AuthState::Connected(claims));
     }
     let maybe_unchecked_token_str: Option<String> = req
@@ -47,6 +48,9 @@ pub async fn try_extract_auth_state(req: &HttpRequest) -> AppResult<AuthState> {
     let Ok(refresh_token) = RefreshClaim::decode(&unchecked_token_str) else {
         return Ok(AuthState::Guess);
     };
+    if is_session_revoked(refresh_token.get_user_id(), refresh_token.get_issued_at()).await? {
+        return Ok(AuthState::Guess);
+    }
     //User maybe already verified but not having token
     if let Some(claims) = User::get_claim(refresh_token.get_user_id()).await? {
         return Ok(AuthState::Connected(claims));
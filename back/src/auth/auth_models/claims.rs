@@ -1,19 +1,39 @@
-use actix_web::{FromRequest, HttpRequest, dev::Payload};
-use futures_util::future::{Ready, ready};
+use std::pin::Pin;
+
+use actix_web::{FromRequest, HttpMessage, HttpRequest, dev::Payload};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    auth::auth_models::token::TokenAble,
-    constants::messages::TOKEN_ABSENT,
+    auth::auth_models::{
+        impersonation::Actor, scope::Scope, session_revocation::is_session_revoked,
+        token::TokenAble,
+    },
+    constants::codes::ErrorCode,
     errors::{AppError, AppResult},
+    models::{
+        organization_model::ActiveOrg,
+        personal_access_token_model::{PAT_PREFIX, PersonalAccessToken},
+    },
     shared::get_now_unix,
 };
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Claims {
     pub user_id: i32,
     pub is_user_admin: bool,
     pub exp: u64,
+    /// Checked against `session_revocation`
+    #[serde(default)]
+    pub iat: u64,
+    /// Set when authenticated by a personal access token, `None` is an interactive session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<Scope>>,
+    /// Set when an admin impersonates `user_id`, the frontend must show it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    /// Organization the user works in, with its role there when the token was issued
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org: Option<ActiveOrg>,
 }
 
 impl TokenAble for Claims {}
@@ -21,11 +41,16 @@ impl TokenAble for Claims {}
 impl Claims {
     fn new(user_id: i32, admin: bool) -> Self {
         const HOUR: u64 = 60 * 60;
-        let exp = get_now_unix() + 1 * HOUR;
+        let iat = get_now_unix();
+        let exp = iat + 1 * HOUR;
         Claims {
             user_id,
             exp,
+            iat,
             is_user_admin: admin,
+            scopes: None,
+            act: None,
+            org: None,
         }
     }
     pub fn new_user_claim(user_id: i32) -> Self {
@@ -34,23 +59,86 @@ impl Claims {
     pub fn new_admin_claim(user_id: i32) -> Self {
         Self::new(user_id, true)
     }
+    /// Never admin, whatever the owner of the token is
+    pub fn new_scoped_claim(user_id: i32, scopes: Vec<Scope>) -> Self {
+        Self {
+            scopes: Some(scopes),
+            ..Self::new(user_id, false)
+        }
+    }
+
+    /// Never admin nor refreshed: it ends at `exp` or when the session is stopped
+    pub fn new_impersonation_claim(user_id: i32, actor: Actor, ttl_secs: u64) -> Self {
+        Self {
+            exp: get_now_unix() + ttl_secs,
+            act: Some(actor),
+            ..Self::new(user_id, false)
+        }
+    }
+
+    /// Only sessions carry an organization, tokens and impersonations never do
+    pub fn with_org(mut self, org: Option<ActiveOrg>) -> Self {
+        self.org = org;
+        self
+    }
+
+    pub fn require_scope(&self, scope: Scope) -> AppResult<()> {
+        match &self.scopes {
+            Some(scopes) if !scopes.contains(&scope) => {
+                Err(AppError::Forbiden(ErrorCode::InsufficientScope))
+            }
+            _ => Ok(()),
+        }
+    }
+    /// For actions a token must never do, like creating other tokens
+    pub fn require_interactive(&self) -> AppResult<()> {
+        match self.scopes {
+            Some(_) => Err(AppError::Forbiden(ErrorCode::InsufficientScope)),
+            None => Ok(()),
+        }
+    }
+    /// For actions only the account owner may do: credentials, second factor, deletion
+    pub fn require_not_impersonated(&self) -> AppResult<()> {
+        match self.act {
+            Some(_) => Err(AppError::Forbiden(ErrorCode::ImpersonationForbidden)),
+            None => Ok(()),
+        }
+    }
 }
 
 impl FromRequest for Claims {
     type Error = AppError;
-    type Future = Ready<Result<Self, Self::Error>>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
 
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
         Self::extract(req)
     }
     fn extract(req: &HttpRequest) -> Self::Future {
-        ready(try_extract_claims(req))
+        let req = req.clone();
+        Box::pin(async move { try_extract_claims(&req).await })
     }
 }
-pub fn try_extract_claims(req: &HttpRequest) -> AppResult<Claims> {
+/// Bearer tokens starting with `pat_` are personal access tokens, the others JWTs.
+/// Claims already resolved by `RequireAuth` are reused.
+pub async fn try_extract_claims(req: &HttpRequest) -> AppResult<Claims> {
+    if let Some(claims) = req.extensions().get::<Claims>() {
+        return Ok(claims.clone());
+    }
     let auth_header =
-        try_extract_bearer_header(&req).ok_or(AppError::Unauthorized(TOKEN_ABSENT.to_owned()))?;
-    Ok(Claims::decode(&auth_header)?)
+        try_extract_bearer_header(&req).ok_or(AppError::Unauthorized(ErrorCode::TokenAbsent))?;
+    if auth_header.starts_with(PAT_PREFIX) {
+        return PersonalAccessToken::authenticate(&auth_header).await;
+    }
+    let claims = Claims::decode(&auth_header)?;
+    if is_session_revoked(claims.user_id, claims.iat).await? {
+        return Err(AppError::Unauthorized(ErrorCode::SessionInvalid));
+    }
+    if let Some(actor) = &claims.act
+        && !actor.is_active().await?
+    {
+        return Err(AppError::Unauthorized(ErrorCode::SessionInvalid));
+    }
+    Ok(claims)
 }
 
 fn try_extract_bearer_header(req: &HttpRequest) -> Option<String> {
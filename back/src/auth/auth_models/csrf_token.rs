@@ -0,0 +1,77 @@
+use actix_session::Session;
+use password_hash::rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::{
+    auth::auth_models::refresh_token::handle_session_error,
+    errors::{AppError, AppResult},
+    services::openapi_service::ApiSchema,
+};
+
+pub const CSRF_SESSION_KEY: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// Synchronizer token: kept in the session, echoed by the client in [`CSRF_HEADER`]
+#[derive(Serialize)]
+pub struct CsrfToken {
+    pub csrf_token: String,
+    pub header: &'static str,
+}
+
+impl CsrfToken {
+    /// The token lives as long as the session, so every tab shares it
+    pub fn get_or_create(session: &Session) -> AppResult<Self> {
+        let existing = session
+            .get::<String>(CSRF_SESSION_KEY)
+            .map_err(handle_session_error)?;
+        let csrf_token = match existing {
+            Some(token) => token,
+            None => {
+                let mut bytes = [0u8; 32];
+                OsRng.fill_bytes(&mut bytes);
+                let token = hex::encode(bytes);
+                session.insert(CSRF_SESSION_KEY, &token).map_err(|err| {
+                    AppError::Internal(format!("CSRF token not stored in session: {err}"))
+                })?;
+                token
+            }
+        };
+        Ok(Self {
+            csrf_token,
+            header: CSRF_HEADER,
+        })
+    }
+
+    pub fn matches(session: &Session, presented: &str) -> AppResult<bool> {
+        let Some(expected) = session
+            .get::<String>(CSRF_SESSION_KEY)
+            .map_err(handle_session_error)?
+        else {
+            return Ok(false);
+        };
+        // constant time, the comparison must not leak the matching prefix length
+        Ok(expected.len() == presented.len()
+            && expected
+                .bytes()
+                .zip(presented.bytes())
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0)
+    }
+}
+
+impl ApiSchema for CsrfToken {
+    fn schema_name() -> String {
+        "CsrfToken".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["csrf_token", "header"],
+            "properties": {
+                "csrf_token": { "type": "string" },
+                "header": { "type": "string", "example": CSRF_HEADER }
+            }
+        })
+    }
+}
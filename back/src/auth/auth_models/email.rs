@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Decode, Encode, MySql, Type};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+};
+
+/// RFC 5321 limits, in octets
+const MAX_LENGTH: usize = 254;
+const MAX_LOCAL_LENGTH: usize = 64;
+
+#[derive(Deserialize)]
+#[serde(transparent)]
+pub struct RawEmail {
+    raw_mail: String,
+}
+impl RawEmail {
+    pub fn verify(self) -> AppResult<Email> {
+        Email::new(self.raw_mail)
+    }
+}
+
+/// Mailbox parsed per RFC 5321, with the UTF-8 extensions of RFC 6531.
+/// The address is kept as typed except for the domain, lower cased and IDNA
+/// normalized, while `canonical` is the form accounts are unique on.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "String", from = "String")]
+pub struct Email {
+    email: String,
+    canonical: String,
+}
+impl Email {
+    pub fn new(email: impl Into<String>) -> AppResult<Self> {
+        Self::parse(&email.into())
+            .ok_or_else(|| AppError::invalid_field("email", ErrorCode::EmailInvalid))
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        // the local part may contain a quoted '@', the domain never does
+        let (local, domain) = raw.trim().rsplit_once('@')?;
+        let local = parse_local_part(local)?;
+        let (ascii_domain, unicode_domain) = parse_domain(domain)?;
+        if local.len() + 1 + ascii_domain.len() > MAX_LENGTH {
+            return None;
+        }
+        Some(Self {
+            canonical: format!("{}@{ascii_domain}", local.to_lowercase()),
+            email: format!("{local}@{unicode_domain}"),
+        })
+    }
+
+    /// Lower cased local part and ASCII (punycode) domain, `Bob@Exämple.com`
+    /// and `bob@xn--exmple-cua.com` are the same account
+    pub fn canonical(&self) -> &str {
+        &self.canonical
+    }
+
+    /// Address with an ASCII domain, for SMTP servers without SMTPUTF8
+    pub fn to_ascii(&self) -> String {
+        let (local, _) = self.email.rsplit_once('@').unwrap_or_default();
+        let (_, domain) = self.canonical.rsplit_once('@').unwrap_or_default();
+        format!("{local}@{domain}")
+    }
+}
+impl AsRef<str> for Email {
+    fn as_ref(&self) -> &str {
+        &self.email
+    }
+}
+impl From<Email> for String {
+    fn from(email: Email) -> Self {
+        email.email
+    }
+}
+///this trait is should only be use for database deserialisation
+impl From<String> for Email {
+    fn from(email: String) -> Self {
+        Self::parse(&email).unwrap_or_else(|| Email {
+            canonical: email.trim().to_lowercase(),
+            email,
+        })
+    }
+}
+impl Type<MySql> for Email {
+    fn type_info() -> <MySql as sqlx::Database>::TypeInfo {
+        <String as Type<MySql>>::type_info()
+    }
+}
+
+impl<'q> Encode<'q, MySql> for Email {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <MySql as sqlx::Database>::ArgumentBuffer<'q>,
+    ) -> Result<sqlx::encode::IsNull, Box<dyn std::error::Error + Send + Sync>> {
+        <String as Encode<MySql>>::encode_by_ref(&self.email, buf)
+    }
+}
+
+impl<'r> Decode<'r, MySql> for Email {
+    fn decode(
+        value: <MySql as sqlx::Database>::ValueRef<'r>,
+    ) -> Result<Self, sqlx::error::BoxDynError> {
+        let inner = <String as Decode<MySql>>::decode(value)?;
+        Ok(Email::from(inner))
+    }
+}
+
+/// Dot-atom or quoted string, NFC normalized. A quoted string that does not
+/// need the quotes (`"bob"`) is stored unquoted, as it is the same mailbox.
+fn parse_local_part(local: &str) -> Option<String> {
+    let local: String = local.nfc().collect();
+    if local.is_empty() || local.len() > MAX_LOCAL_LENGTH {
+        return None;
+    }
+    let Some(quoted) = local.strip_prefix('"').and_then(|l| l.strip_suffix('"')) else {
+        return is_dot_atom(&local).then_some(local);
+    };
+    let content = unquote(quoted)?;
+    if is_dot_atom(&content) {
+        return Some(content);
+    }
+    let escaped: String = content
+        .chars()
+        .flat_map(|c| match c {
+            '"' | '\\' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect();
+    Some(format!("\"{escaped}\""))
+}
+
+/// atext of RFC 5322, extended with any non ASCII character by RFC 6531
+fn is_atext(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || "!#$%&'*+-/=?^_`{|}~".contains(c)
+        || (!c.is_ascii() && !c.is_control())
+}
+
+fn is_dot_atom(local: &str) -> bool {
+    local
+        .split('.')
+        .all(|atom| !atom.is_empty() && atom.chars().all(is_atext))
+}
+
+/// Content of a quoted string, `None` if a quote or control character is not escaped
+fn unquote(quoted: &str) -> Option<String> {
+    let is_printable = |c: &char| (' '..='~').contains(c) || (!c.is_ascii() && !c.is_control());
+    let mut content = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => content.push(chars.next().filter(|c| (' '..='~').contains(c))?),
+            '"' => return None,
+            c if is_printable(&c) => content.push(c),
+            _ => return None,
+        }
+    }
+    Some(content)
+}
+
+/// ASCII and Unicode forms of a host name. Address literals (`[192.0.2.1]`)
+/// are valid for SMTP but not accepted for an account.
+fn parse_domain(domain: &str) -> Option<(String, String)> {
+    let ascii = idna::domain_to_ascii_strict(domain).ok()?;
+    let labels: Vec<&str> = ascii.split('.').collect();
+    let tld = labels.last()?;
+    if labels.len() < 2
+        || labels.iter().any(|label| label.is_empty())
+        || tld.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let (unicode, result) = idna::domain_to_unicode(&ascii);
+    result.ok()?;
+    Some((ascii, unicode))
+}
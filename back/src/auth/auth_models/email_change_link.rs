@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::auth_models::{cache_key::CacheKey, email::Email},
+    errors::AppResult,
+    shared::get_now_unix,
+    utils::redis_utils::{redis_del, redis_get, redis_set_ex},
+};
+
+/// Seconds the confirmation link of a new address stays valid
+pub const EMAIL_CHANGE_LINK_SECS: u64 = 24 * 60 * 60;
+
+/// Single use link mailed to the new address of an account, following it proves
+/// the address and switches the account over
+#[derive(Serialize, Deserialize)]
+pub struct EmailChangeLink {
+    user_id: i32,
+    email: Email,
+    exp: u64,
+}
+
+pub enum EmailChangeLinkCheck {
+    Valid(i32, Email),
+    Invalid,
+    Expired(i32),
+}
+
+fn email_change_link_key(key: &CacheKey) -> String {
+    format!("email-change:{}", key.as_ref())
+}
+
+impl EmailChangeLink {
+    /// Store a link moving `user_id` to `email` and return its key
+    pub async fn create(user_id: i32, email: Email) -> AppResult<CacheKey> {
+        let link = Self {
+            user_id,
+            email,
+            exp: get_now_unix() + EMAIL_CHANGE_LINK_SECS,
+        };
+        let key = CacheKey::new();
+        redis_set_ex(&email_change_link_key(&key), &link, EMAIL_CHANGE_LINK_SECS).await?;
+        Ok(key)
+    }
+
+    /// The link is consumed, valid or not
+    pub async fn check(key: &CacheKey) -> AppResult<EmailChangeLinkCheck> {
+        let cache_key = email_change_link_key(key);
+        let Some(link) = redis_get::<_, EmailChangeLink>(&cache_key).await? else {
+            return Ok(EmailChangeLinkCheck::Invalid);
+        };
+        redis_del(&cache_key).await?;
+        if link.exp <= get_now_unix() {
+            return Ok(EmailChangeLinkCheck::Expired(link.user_id));
+        }
+        Ok(EmailChangeLinkCheck::Valid(link.user_id, link.email))
+    }
+}
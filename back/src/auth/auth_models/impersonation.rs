@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::AppResult,
+    utils::redis_utils::{redis_del, redis_get, redis_set_ex},
+};
+
+fn impersonation_key(session_id: &str) -> String {
+    format!("impersonation:{session_id}")
+}
+
+/// `act` claim (RFC 8693) of a token issued to an admin acting as another user.
+/// The session lives in the cache so that stopping it invalidates the token at once.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Actor {
+    /// The impersonating admin
+    pub user_id: i32,
+    pub session_id: String,
+}
+
+impl Actor {
+    pub async fn start(impersonator_id: i32, ttl_secs: u64) -> AppResult<Self> {
+        let actor = Self {
+            user_id: impersonator_id,
+            session_id: uuid::Uuid::new_v4().to_string(),
+        };
+        redis_set_ex(
+            &impersonation_key(&actor.session_id),
+            &actor.user_id,
+            ttl_secs,
+        )
+        .await?;
+        Ok(actor)
+    }
+    pub async fn is_active(&self) -> AppResult<bool> {
+        let stored = redis_get::<_, i32>(&impersonation_key(&self.session_id)).await?;
+        Ok(stored == Some(self.user_id))
+    }
+    pub async fn end(&self) -> AppResult<()> {
+        redis_del(&impersonation_key(&self.session_id)).await
+    }
+}
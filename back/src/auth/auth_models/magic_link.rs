@@ -0,0 +1,91 @@
+use actix_session::Session;
+use password_hash::rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    auth::auth_models::{cache_key::CacheKey, refresh_token::handle_session_error},
+    errors::{AppError, AppResult},
+    shared::get_now_unix,
+    utils::redis_utils::{redis_del, redis_get, redis_set_ex},
+};
+
+pub const MAGIC_LINK_VALIDITY_SECS: u64 = 15 * 60;
+/// Random id of the browser which asked for a link, kept in its session
+const MAGIC_LINK_DEVICE_KEY: &str = "magic_link_device";
+
+/// Single use sign-in link, only accepted from the session that requested it:
+/// a forwarded or intercepted link is useless on another device
+#[derive(Serialize, Deserialize)]
+pub struct MagicLink {
+    user_id: i32,
+    device_hash: String,
+    exp: u64,
+}
+
+pub enum MagicLinkCheck {
+    Valid(i32),
+    Invalid,
+    Expired,
+    /// Kept in the cache, the requesting browser may still use it
+    OtherDevice,
+}
+
+fn magic_link_key(key: &CacheKey) -> String {
+    format!("magic:{}", key.as_ref())
+}
+
+fn hash_device(device: &str) -> String {
+    hex::encode(Sha256::digest(device.as_bytes()))
+}
+
+fn get_or_create_device(session: &Session) -> AppResult<String> {
+    let existing = session
+        .get::<String>(MAGIC_LINK_DEVICE_KEY)
+        .map_err(handle_session_error)?;
+    if let Some(device) = existing {
+        return Ok(device);
+    }
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let device = hex::encode(bytes);
+    session
+        .insert(MAGIC_LINK_DEVICE_KEY, &device)
+        .map_err(|err| AppError::Internal(format!("Magic link device not stored: {err}")))?;
+    Ok(device)
+}
+
+impl MagicLink {
+    /// Store a link for `user_id`, bound to `session`, and return its key
+    pub async fn create(user_id: i32, session: &Session) -> AppResult<CacheKey> {
+        let link = Self {
+            user_id,
+            device_hash: hash_device(&get_or_create_device(session)?),
+            exp: get_now_unix() + MAGIC_LINK_VALIDITY_SECS,
+        };
+        let key = CacheKey::new();
+        redis_set_ex(&magic_link_key(&key), &link, MAGIC_LINK_VALIDITY_SECS).await?;
+        Ok(key)
+    }
+
+    /// A valid link is consumed
+    pub async fn check(key: &CacheKey, session: &Session) -> AppResult<MagicLinkCheck> {
+        let cache_key = magic_link_key(key);
+        let Some(link) = redis_get::<_, MagicLink>(&cache_key).await? else {
+            return Ok(MagicLinkCheck::Invalid);
+        };
+        if link.exp <= get_now_unix() {
+            redis_del(&cache_key).await?;
+            return Ok(MagicLinkCheck::Expired);
+        }
+        let device = session
+            .get::<String>(MAGIC_LINK_DEVICE_KEY)
+            .map_err(handle_session_error)?;
+        if device.is_none_or(|device| hash_device(&device) != link.device_hash) {
+            return Ok(MagicLinkCheck::OtherDevice);
+        }
+        redis_del(&cache_key).await?;
+        session.remove(MAGIC_LINK_DEVICE_KEY);
+        Ok(MagicLinkCheck::Valid(link.user_id))
+    }
+}
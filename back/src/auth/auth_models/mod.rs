@@ -2,6 +2,15 @@ pub mod auth_state;
 pub mod cache_key;
 pub mod claims;
 pub mod credential;
+pub mod csrf_token;
+pub mod email;
+pub mod email_change_link;
+pub mod impersonation;
 pub mod internal_user_claim;
+pub mod magic_link;
 pub mod refresh_token;
+pub mod restore_link;
+pub mod scope;
+pub mod session_revocation;
+pub mod sms_challenge;
 pub mod token;
@@ -13,16 +13,22 @@ pub const REFRESH_TOKEN_KEY: &str = "refresh_token";
 pub struct RefreshClaim {
     user_id: i32,
     exp: u64,
+    #[serde(default)]
+    iat: u64,
 }
 impl RefreshClaim {
     pub fn new(user_id: i32) -> Self {
         const DAY_SECS: u64 = 60 * 60 * 24;
-        let exp = get_now_unix() + 7 * DAY_SECS;
-        Self { user_id, exp }
+        let iat = get_now_unix();
+        let exp = iat + 7 * DAY_SECS;
+        Self { user_id, exp, iat }
     }
     pub fn get_user_id(&self) -> i32 {
         self.user_id
     }
+    pub fn get_issued_at(&self) -> u64 {
+        self.iat
+    }
 }
 impl TokenAble for RefreshClaim {}
 
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::auth_models::cache_key::CacheKey,
+    errors::AppResult,
+    shared::get_now_unix,
+    utils::redis_utils::{redis_del, redis_get, redis_set_ex},
+};
+
+/// Single use link cancelling a pending deletion, valid for the whole grace period.
+/// Kept apart from the reset and verification keys: neither is accepted for the other
+#[derive(Serialize, Deserialize)]
+pub struct RestoreLink {
+    user_id: i32,
+    exp: u64,
+}
+
+pub enum RestoreLinkCheck {
+    Valid(i32),
+    Invalid,
+    Expired(i32),
+}
+
+fn restore_link_key(key: &CacheKey) -> String {
+    format!("restore:{}", key.as_ref())
+}
+
+impl RestoreLink {
+    /// Store a link for `user_id` and return its key
+    pub async fn create(user_id: i32, validity_secs: u64) -> AppResult<CacheKey> {
+        let link = Self {
+            user_id,
+            exp: get_now_unix() + validity_secs,
+        };
+        let key = CacheKey::new();
+        redis_set_ex(&restore_link_key(&key), &link, validity_secs).await?;
+        Ok(key)
+    }
+
+    /// The link is consumed, valid or not
+    pub async fn check(key: &CacheKey) -> AppResult<RestoreLinkCheck> {
+        let cache_key = restore_link_key(key);
+        let Some(link) = redis_get::<_, RestoreLink>(&cache_key).await? else {
+            return Ok(RestoreLinkCheck::Invalid);
+        };
+        redis_del(&cache_key).await?;
+        if link.exp <= get_now_unix() {
+            return Ok(RestoreLinkCheck::Expired(link.user_id));
+        }
+        Ok(RestoreLinkCheck::Valid(link.user_id))
+    }
+}
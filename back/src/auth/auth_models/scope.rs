@@ -0,0 +1,55 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// What a personal access token may do, interactive sessions are not restricted.
+/// The string form is stored in the database and sent by clients: never rename one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "account:read")]
+    AccountRead,
+    #[serde(rename = "account:write")]
+    AccountWrite,
+    #[serde(rename = "files:read")]
+    FilesRead,
+    #[serde(rename = "files:write")]
+    FilesWrite,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [
+        Scope::AccountRead,
+        Scope::AccountWrite,
+        Scope::FilesRead,
+        Scope::FilesWrite,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::AccountRead => "account:read",
+            Scope::AccountWrite => "account:write",
+            Scope::FilesRead => "files:read",
+            Scope::FilesWrite => "files:write",
+        }
+    }
+    pub fn parse(raw: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == raw)
+    }
+    /// Space separated, the storage format
+    pub fn join(scopes: &[Scope]) -> String {
+        scopes
+            .iter()
+            .map(Scope::as_str)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+    /// Unknown scopes, e.g. removed ones, are dropped
+    pub fn split(raw: &str) -> Vec<Scope> {
+        raw.split_whitespace().filter_map(Scope::parse).collect()
+    }
+}
+impl Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
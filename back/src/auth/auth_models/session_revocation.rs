@@ -0,0 +1,30 @@
+use crate::{
+    errors::AppResult,
+    shared::get_now_unix,
+    utils::redis_utils::{redis_get, redis_set_ex},
+};
+
+/// Outlives every token: refresh tokens last 7 days
+const REVOCATION_TTL_SECS: u64 = 8 * 24 * 60 * 60;
+
+fn revocation_key(user_id: i32) -> String {
+    format!("sessions_revoked:{user_id}")
+}
+
+/// Access and refresh tokens of `user_id` issued until now are refused from now on,
+/// the ones issued by a login from the next second on are not
+pub async fn revoke_sessions(user_id: i32) -> AppResult<()> {
+    redis_set_ex(
+        &revocation_key(user_id),
+        &get_now_unix(),
+        REVOCATION_TTL_SECS,
+    )
+    .await
+}
+
+/// `issued_at` is the `iat` of the token, 0 for tokens issued before it existed.
+/// Both are whole seconds: a token of the second of the revocation may predate it
+pub async fn is_session_revoked(user_id: i32, issued_at: u64) -> AppResult<bool> {
+    let revoked_at = redis_get::<_, u64>(&revocation_key(user_id)).await?;
+    Ok(revoked_at.is_some_and(|revoked_at| issued_at <= revoked_at))
+}
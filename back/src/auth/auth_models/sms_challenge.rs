@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::services::openapi_service::ApiSchema;
+
+/// Answered by `/auth/login` instead of a token when the SMS second factor is on
+#[derive(Serialize)]
+pub struct SmsChallenge {
+    pub challenge_id: String,
+    /// Masked, only the last digits are shown
+    pub phone_number: String,
+    pub expires_in: u64,
+}
+
+#[derive(Deserialize)]
+pub struct SmsChallengeAnswer {
+    pub challenge_id: String,
+    pub code: String,
+}
+
+impl ApiSchema for SmsChallenge {
+    fn schema_name() -> String {
+        "SmsChallenge".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["challenge_id", "phone_number", "expires_in"],
+            "properties": {
+                "challenge_id": { "type": "string", "format": "uuid" },
+                "phone_number": { "type": "string", "example": "+•••••••••78" },
+                "expires_in": { "type": "integer", "description": "Seconds before the code expires" }
+            }
+        })
+    }
+}
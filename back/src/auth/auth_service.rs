@@ -1,24 +1,38 @@
 use crate::APP_URL;
 use crate::auth::auth_models::cache_key::ResetResult;
-use crate::auth::auth_models::credential::RawEmail;
-use crate::shared::is_valid_password;
+use crate::auth::auth_models::email::RawEmail;
+use crate::auth::password_policy::{PASSWORD_POLICY, PolicyViolation, violations_to_error};
+use crate::auth::registration_policy::{REGISTRATION_POLICY, RegistrationMode};
 use crate::{
+    account::account_models::otp::{OTP_VALIDITY_SECS, OtpCheck, SmsOtp},
     auth::auth_models::{
         auth_state::AuthState,
         cache_key::CacheKey,
-        credential::{Email, RawLoginCredential},
+        credential::{RawLoginCredential, RawRegistration},
+        email::Email,
+        email_change_link::{EmailChangeLink, EmailChangeLinkCheck},
         internal_user_claim::InternalUserClaim,
-        refresh_token::RefreshClaim,
+        magic_link::{MagicLink, MagicLinkCheck},
+        refresh_token::{REFRESH_TOKEN_KEY, RefreshClaim, try_extract_refresh_token_from_session},
+        restore_link::{RestoreLink, RestoreLinkCheck},
+        sms_challenge::{SmsChallenge, SmsChallengeAnswer},
         token::{ExpiredAbleTokenError, ExpiredTokenAble, Token, TokenAble, TokenError},
     },
-    constants::messages::EMAIL_ALREADY_EXIST,
+    constants::codes::ErrorCode,
     errors::{AppError, AppResult},
-    models::user_model::User,
+    models::{
+        audit_event_model::{AuditAction, AuditEvent, ClientInfo},
+        invitation_model::{ClaimedInvitation, Invitation},
+        user_model::User,
+    },
     utils::{
         email_utils::send_mail,
         redis_utils::{redis_del, redis_get},
     },
+    webhooks::{webhook_event::WebhookEventType, webhook_service::emit_webhook},
 };
+use actix_session::Session;
+use serde_json::json;
 use std::time::Duration;
 const EMAIL_LINK_VALDITY_DURATION: Duration = Duration::from_secs(30 * 60);
 
@@ -30,31 +44,97 @@ pub enum RegisterResult {
 }
 
 pub async fn register_service(
-    raw_credentials: RawLoginCredential,
+    registration: RawRegistration,
     auth_state: AuthState,
+    client: &ClientInfo,
 ) -> AppResult<RegisterResult> {
     match auth_state {
         AuthState::Connected(claims) => {
+            claims.require_interactive()?;
             return Ok(RegisterResult::Token(claims.encode()?));
         }
         AuthState::NotVerified(_) => return Ok(RegisterResult::NotVerified),
         AuthState::Guess => {}
     }
-    let credentials = raw_credentials.verify()?;
-
-    let user_id = match User::create(&credentials).await {
-        Err(AppError::Conflict(msg)) if msg == EMAIL_ALREADY_EXIST => {
-            return Ok(RegisterResult::EmailAlreadyExist);
+    if REGISTRATION_POLICY.mode == RegistrationMode::Closed {
+        return Err(AppError::Forbiden(ErrorCode::RegistrationClosed));
+    }
+    let credentials = registration.credentials.verify()?;
+    let violations = PASSWORD_POLICY
+        .check(credentials.get_password(), credentials.get_email())
+        .await?;
+    if !violations.is_empty() {
+        return Err(violations_to_error("password", &violations));
+    }
+    let invitation =
+        claim_invitation(registration.invitation_code, credentials.get_email()).await?;
+
+    let inviter_id = invitation.as_ref().and_then(|claimed| claimed.inviter_id);
+    let user_id = match User::create(&credentials, inviter_id).await {
+        Ok(user_id) => user_id,
+        Err(err) => {
+            if let Some(claimed) = &invitation {
+                Invitation::release(claimed).await?;
+            }
+            if let AppError::Conflict(ErrorCode::EmailAlreadyExists) = err {
+                AuditEvent::new(AuditAction::Register)
+                    .failed()
+                    .client(client)
+                    .metadata(json!({ "email": credentials.get_email().as_ref() }))
+                    .record()
+                    .await?;
+                return Ok(RegisterResult::EmailAlreadyExist);
+            }
+            return Err(err);
         }
-        others => others?,
     };
 
+    AuditEvent::new(AuditAction::Register)
+        .user(user_id)
+        .client(client)
+        .metadata(json!({ "invited_by": inviter_id }))
+        .record()
+        .await?;
+    emit_webhook(
+        WebhookEventType::UserRegistered,
+        json!({
+            "user_id": user_id,
+            "email": credentials.get_email().as_ref(),
+            "invited_by": inviter_id,
+        }),
+    )
+    .await;
+
     let rfresh_token = RefreshClaim::new(user_id);
     return Ok(RegisterResult::NewUser(
         rfresh_token,
         credentials.into_email(),
     ));
 }
+/// A code is optional in open mode but, when given, must be valid
+async fn claim_invitation(
+    code: Option<String>,
+    email: &Email,
+) -> AppResult<Option<ClaimedInvitation>> {
+    let code = code.filter(|code| !code.trim().is_empty());
+    let Some(code) = code else {
+        return match REGISTRATION_POLICY.mode {
+            RegistrationMode::InviteOnly => Err(AppError::invalid_field(
+                "invitation_code",
+                ErrorCode::InvitationRequired,
+            )),
+            _ => Ok(None),
+        };
+    };
+    match Invitation::claim(&code, email).await? {
+        Some(claimed) => Ok(Some(claimed)),
+        None => Err(AppError::invalid_field(
+            "invitation_code",
+            ErrorCode::InvitationInvalid,
+        )),
+    }
+}
+
 pub async fn resend_verification_mail(user_id: i32) -> AppResult<()> {
     let email = User::get(user_id).await?.email;
     create_verification_token_and_send_mail(user_id, &email).await
@@ -88,19 +168,27 @@ pub enum VerifyResult {
 pub async fn verify_service(
     auth_state: AuthState,
     verify_key: CacheKey,
+    client: &ClientInfo,
 ) -> AppResult<VerifyResult> {
     // --- Case 1 : User already connected ---
 
+    let event = AuditEvent::new(AuditAction::EmailVerification).client(client);
     let verified_user_id = match verify_key.get_from_cache().await? {
-        ResetResult::Invalide => return Ok(VerifyResult::Invalid),
+        ResetResult::Invalide => {
+            event.failed().record().await?;
+            return Ok(VerifyResult::Invalid);
+        }
         ResetResult::Expired(user_id) => {
             verify_key.invalidate().await?;
             handle_expired_verify_link(user_id).await?;
+            event.failed().subject(user_id).record().await?;
             return Ok(VerifyResult::Expired);
         }
         ResetResult::Ok(user_id) => {
             verify_key.invalidate().await?;
             User::verify_user(user_id).await?;
+            event.user(user_id).record().await?;
+            emit_user_verified(&User::get(user_id).await?).await;
             user_id
         }
     };
@@ -119,6 +207,13 @@ pub async fn verify_service(
         )))?;
     Ok(VerifyResult::Token(token))
 }
+async fn emit_user_verified(user: &User) {
+    emit_webhook(
+        WebhookEventType::UserVerified,
+        json!({ "user_id": user.id, "email": user.email.as_ref() }),
+    )
+    .await
+}
 async fn handle_expired_verify_link(user_id: i32) -> AppResult<()> {
     let Some(user) = User::try_get(user_id).await? else {
         let err_message = format!("Verification token exists for non-existing user_id={user_id}");
@@ -146,17 +241,21 @@ async fn decode_internal(raw_token: &str) -> AppResult<DecodeResult> {
 pub enum LoginResult {
     Connected(Token, Option<RefreshClaim>),
     NotVerified(Option<RefreshClaim>),
+    SecondFactorRequired(SmsChallenge),
     CredentialsIncorect,
+    PendingDeletion,
 }
 
 pub async fn login_service(
     auth_state: AuthState,
     raw_credentials: RawLoginCredential,
+    client: &ClientInfo,
 ) -> AppResult<LoginResult> {
     // --- Case 1 : User already connected ---
 
     match auth_state {
         AuthState::Connected(claims) => {
+            claims.require_interactive()?;
             return Ok(LoginResult::Connected(claims.encode()?, None));
         }
         AuthState::NotVerified(_) => return Ok(LoginResult::NotVerified(None)),
@@ -167,9 +266,39 @@ pub async fn login_service(
 
     let credentials = raw_credentials.verify()?;
     let Some(user) = User::get_from_credential(&credentials).await? else {
+        let email = credentials.get_email();
+        let mut event = AuditEvent::new(AuditAction::Login)
+            .failed()
+            .client(client)
+            .metadata(json!({ "method": "password", "email": email.as_ref() }));
+        // the account under attack, when there is one
+        if let Some(user_id) = User::get_user_id_from_email(email).await? {
+            event = event.subject(user_id);
+        }
+        event.record().await?;
         return Ok(LoginResult::CredentialsIncorect);
     };
 
+    // the restore link is the way back in
+    if user.is_pending_deletion() {
+        return record_failure(
+            login_event("password", client).user(user.id),
+            LoginResult::PendingDeletion,
+        )
+        .await;
+    }
+
+    // recorded once the code is checked by `login_sms_service`
+    if user.has_sms_two_factor() {
+        return Ok(LoginResult::SecondFactorRequired(
+            start_sms_challenge(&user).await?,
+        ));
+    }
+
+    login_event("password", client)
+        .user(user.id)
+        .record()
+        .await?;
     let refresh_claims = RefreshClaim::new(user.id);
 
     // --- Step 3 : Distinguish verified / unverified users ---
@@ -182,6 +311,140 @@ pub async fn login_service(
         Ok(LoginResult::NotVerified(Some(refresh_claims)))
     }
 }
+fn login_event(method: &str, client: &ClientInfo) -> AuditEvent {
+    AuditEvent::new(AuditAction::Login)
+        .client(client)
+        .metadata(json!({ "method": method }))
+}
+
+async fn record_failure<T>(event: AuditEvent, result: T) -> AppResult<T> {
+    event.failed().record().await?;
+    Ok(result)
+}
+
+const LOGIN_CODE_SMS: &str = "Votre code de connexion : __CODE__";
+
+fn login_otp_key(challenge_id: &str) -> String {
+    format!("otp:login:{challenge_id}")
+}
+
+async fn start_sms_challenge(user: &User) -> AppResult<SmsChallenge> {
+    let Some(phone) = user.phone() else {
+        return Err(AppError::Internal(format!(
+            "SMS second factor on for user {} without phone number",
+            user.id
+        )));
+    };
+    let challenge = SmsChallenge {
+        challenge_id: uuid::Uuid::new_v4().to_string(),
+        phone_number: phone.masked(),
+        expires_in: OTP_VALIDITY_SECS,
+    };
+    SmsOtp::new(user.id, phone)
+        .send(&login_otp_key(&challenge.challenge_id), LOGIN_CODE_SMS)
+        .await?;
+    Ok(challenge)
+}
+
+pub enum SmsLoginResult {
+    Connected(Token, RefreshClaim),
+    CodeInvalid,
+    CodeExpired,
+}
+
+/// Second step of the login, the password was checked when the challenge was created
+pub async fn login_sms_service(
+    answer: SmsChallengeAnswer,
+    client: &ClientInfo,
+) -> AppResult<SmsLoginResult> {
+    let event = login_event("sms", client);
+    let otp = match SmsOtp::check(&login_otp_key(&answer.challenge_id), &answer.code).await? {
+        OtpCheck::Valid(otp) => otp,
+        OtpCheck::Invalid => return record_failure(event, SmsLoginResult::CodeInvalid).await,
+        OtpCheck::Expired => return record_failure(event, SmsLoginResult::CodeExpired).await,
+    };
+    let user_id = otp.get_user_id();
+    event.user(user_id).record().await?;
+    let claims = User::get_claim(user_id)
+        .await?
+        .ok_or(AppError::Internal(format!(
+            "SMS challenge answered for unverified user {user_id}"
+        )))?;
+    Ok(SmsLoginResult::Connected(
+        claims.encode()?,
+        RefreshClaim::new(user_id),
+    ))
+}
+
+/// Nothing tells the caller whether the address has an account
+pub async fn request_magic_link_service(raw_email: RawEmail, session: &Session) -> AppResult<()> {
+    let email = raw_email.verify()?;
+    if let Some(user_id) = User::get_user_id_from_email(&email).await? {
+        let key = MagicLink::create(user_id, session).await?;
+        send_magic_link_email(&email, &key)?;
+    }
+    Ok(())
+}
+
+fn send_magic_link_email(user_email: &Email, key: &CacheKey) -> AppResult<()> {
+    let magic_link_url = format!("{APP_URL}/auth/magic-link?token={}", key.as_ref());
+
+    let html_template = include_str!("../templates/magic_link_email.html");
+    let html = html_template.replace("__MAGIC_LINK_URL__", &magic_link_url);
+
+    send_mail(user_email, "Votre lien de connexion", html)
+}
+
+pub enum MagicLinkResult {
+    Connected(Token, RefreshClaim),
+    SecondFactorRequired(SmsChallenge),
+    Invalid,
+    Expired,
+    OtherDevice,
+    PendingDeletion,
+}
+
+/// Same outcome as `login_service`, the link standing for the password
+pub async fn magic_link_login_service(
+    key: CacheKey,
+    session: &Session,
+    client: &ClientInfo,
+) -> AppResult<MagicLinkResult> {
+    let event = login_event("magic_link", client);
+    let user_id = match MagicLink::check(&key, session).await? {
+        MagicLinkCheck::Valid(user_id) => user_id,
+        MagicLinkCheck::Invalid => return record_failure(event, MagicLinkResult::Invalid).await,
+        MagicLinkCheck::Expired => return record_failure(event, MagicLinkResult::Expired).await,
+        MagicLinkCheck::OtherDevice => {
+            return record_failure(event, MagicLinkResult::OtherDevice).await;
+        }
+    };
+    let user = User::get(user_id).await?;
+    if user.is_pending_deletion() {
+        return record_failure(event.user(user_id), MagicLinkResult::PendingDeletion).await;
+    }
+    // following the link proves the address, as the verification mail would
+    if !user.is_verified() {
+        User::verify_user(user_id).await?;
+        emit_user_verified(&user).await;
+    }
+    if user.has_sms_two_factor() {
+        return Ok(MagicLinkResult::SecondFactorRequired(
+            start_sms_challenge(&user).await?,
+        ));
+    }
+    event.user(user_id).record().await?;
+    let claims = User::get_claim(user_id)
+        .await?
+        .ok_or(AppError::Internal(format!(
+            "User {user_id} still unverified after following a magic link"
+        )))?;
+    Ok(MagicLinkResult::Connected(
+        claims.encode()?,
+        RefreshClaim::new(user_id),
+    ))
+}
+
 pub async fn forgot_service(raw_email: RawEmail) -> AppResult<()> {
     let email = raw_email.verify()?;
     if let Some(user_id) = User::get_user_id_from_email(&email).await? {
@@ -254,31 +517,121 @@ pub enum ChangePasswordResult {
     KeyInvalid,
     PasswordChanged,
     KeyExpired,
-    PasswordInvalid,
+    PasswordInvalid(Vec<PolicyViolation>),
 }
 
 pub async fn change_password_service(
     key: &CacheKey,
     raw_password: &str,
+    client: &ClientInfo,
 ) -> AppResult<ChangePasswordResult> {
-    let password = if !is_valid_password(raw_password) {
-        return Ok(ChangePasswordResult::PasswordInvalid);
-    } else {
-        raw_password
-    };
-
+    let event = AuditEvent::new(AuditAction::PasswordReset).client(client);
     let response = match key.get_from_cache().await? {
-        ResetResult::Invalide => ChangePasswordResult::KeyInvalid,
+        ResetResult::Invalide => {
+            event.failed().record().await?;
+            ChangePasswordResult::KeyInvalid
+        }
         ResetResult::Expired(user_id) => {
             regenerate_reset_token(user_id).await?;
             key.invalidate().await?;
+            event.failed().subject(user_id).record().await?;
             ChangePasswordResult::KeyExpired
         }
         ResetResult::Ok(user_id) => {
+            // the key stays valid so the user can retry with a stronger password
+            let email = User::get(user_id).await?.email;
+            let violations = PASSWORD_POLICY.check(raw_password, &email).await?;
+            if !violations.is_empty() {
+                return Ok(ChangePasswordResult::PasswordInvalid(violations));
+            }
             key.invalidate().await?;
-            User::change_password(user_id, password).await?;
+            User::change_password(user_id, raw_password).await?;
+            event.user(user_id).record().await?;
             ChangePasswordResult::PasswordChanged
         }
     };
     Ok(response)
 }
+
+/// Drops the refresh token, the access token lives until it expires
+pub async fn logout_service(session: &Session, client: &ClientInfo) -> AppResult<()> {
+    let refresh_claim = try_extract_refresh_token_from_session(session).ok();
+    session.remove(REFRESH_TOKEN_KEY);
+    let Some(refresh_claim) = refresh_claim else {
+        return Ok(());
+    };
+    AuditEvent::new(AuditAction::Logout)
+        .user(refresh_claim.get_user_id())
+        .client(client)
+        .record()
+        .await
+}
+
+pub enum RestoreResult {
+    Restored,
+    Invalid,
+    Expired,
+}
+
+/// Cancels a pending deletion from the link mailed when it was requested
+pub async fn restore_account_service(
+    key: &CacheKey,
+    client: &ClientInfo,
+) -> AppResult<RestoreResult> {
+    let event = AuditEvent::new(AuditAction::AccountRestored).client(client);
+    let response = match RestoreLink::check(key).await? {
+        RestoreLinkCheck::Invalid => record_failure(event, RestoreResult::Invalid).await?,
+        RestoreLinkCheck::Expired(user_id) => {
+            record_failure(event.subject(user_id), RestoreResult::Expired).await?
+        }
+        RestoreLinkCheck::Valid(user_id) => {
+            let Some(user) = User::try_get(user_id).await? else {
+                return Ok(RestoreResult::Invalid);
+            };
+            if user.is_pending_deletion() {
+                User::cancel_deletion(user_id).await?;
+                event.user(user_id).record().await?;
+            }
+            RestoreResult::Restored
+        }
+    };
+    Ok(response)
+}
+
+pub enum EmailChangeResult {
+    Changed,
+    Invalid,
+    Expired,
+}
+
+/// Moves the account to the address the confirmation link was mailed to
+pub async fn confirm_email_change_service(
+    key: &CacheKey,
+    client: &ClientInfo,
+) -> AppResult<EmailChangeResult> {
+    let event = AuditEvent::new(AuditAction::EmailChanged).client(client);
+    let response = match EmailChangeLink::check(key).await? {
+        EmailChangeLinkCheck::Invalid => record_failure(event, EmailChangeResult::Invalid).await?,
+        EmailChangeLinkCheck::Expired(user_id) => {
+            record_failure(event.subject(user_id), EmailChangeResult::Expired).await?
+        }
+        EmailChangeLinkCheck::Valid(user_id, email) => {
+            let Some(user) = User::try_get(user_id).await? else {
+                return Ok(EmailChangeResult::Invalid);
+            };
+            User::change_email(user_id, &email).await?;
+            event.user(user_id).record().await?;
+            emit_webhook(
+                WebhookEventType::UserEmailChanged,
+                json!({
+                    "user_id": user_id,
+                    "email": email.as_ref(),
+                    "previous_email": user.email.as_ref(),
+                }),
+            )
+            .await;
+            EmailChangeResult::Changed
+        }
+    };
+    Ok(response)
+}
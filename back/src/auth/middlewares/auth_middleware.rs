@@ -1,33 +1,131 @@
-use actix_session::SessionExt;
+use std::{
+    future::{Ready, ready},
+    rc::Rc,
+};
+
 use actix_web::{
-    Error, HttpResponse,
-    dev::{Service, ServiceRequest, ServiceResponse},
+    Error, HttpMessage,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use futures_util::future::LocalBoxFuture;
+
+use crate::{
+    auth::{
+        auth_models::{
+            auth_state::{AuthState, try_extract_auth_state},
+            claims::Claims,
+            scope::Scope,
+        },
+        middlewares::csrf_middleware::verify_csrf_token,
+    },
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
 };
 
-pub async fn auth_middleware<S>(req: ServiceRequest, next: S) -> Result<ServiceResponse, Error>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Admin,
+}
+impl Role {
+    fn granted_to(&self, claims: &Claims) -> bool {
+        match self {
+            Role::Admin => claims.is_user_admin,
+        }
+    }
+}
+
+/// Rejects the requests of a whole scope unless the user is connected, that is
+/// logged in and verified, and meets the extra requirements:
+///
+/// `web::scope("/admin").wrap(RequireAuth::role(Role::Admin))`
+///
+/// The resolved [`Claims`] are put in the request extensions, where the `Claims`
+/// and `AuthState` extractors of the handlers find them. Requests authenticated by
+/// the session cookie alone must carry the CSRF token, see [`verify_csrf_token`].
+#[derive(Debug, Clone, Default)]
+pub struct RequireAuth {
+    role: Option<Role>,
+    scope: Option<Scope>,
+}
+
+impl RequireAuth {
+    pub fn connected() -> Self {
+        Self::default()
+    }
+    pub fn role(role: Role) -> Self {
+        Self {
+            role: Some(role),
+            ..Self::default()
+        }
+    }
+    /// Personal access tokens must carry `scope`, sessions always do
+    pub fn with_scope(mut self, scope: Scope) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    fn check(&self, auth_state: AuthState) -> AppResult<Claims> {
+        let claims = match auth_state {
+            AuthState::Connected(claims) => claims,
+            AuthState::NotVerified(_) => {
+                return Err(AppError::Forbiden(ErrorCode::UserNotVerified));
+            }
+            AuthState::Guess => return Err(AppError::Unauthorized(ErrorCode::UserNotLoggedIn)),
+        };
+        if self.role.is_some_and(|role| !role.granted_to(&claims)) {
+            return Err(AppError::Forbiden(ErrorCode::Forbidden));
+        }
+        if let Some(scope) = self.scope {
+            claims.require_scope(scope)?;
+        }
+        Ok(claims)
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequireAuth
 where
-    S: Service<ServiceRequest, Response = ServiceResponse, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
 {
-    let session = req.get_session();
-
-    let maybe_user_id = session.get::<i32>("user_id").unwrap();
-    let maybe_verified = session.get::<bool>("verified").unwrap();
-
-    if let (Some(_id), Some(true)) = (maybe_user_id, maybe_verified) {
-        // utilisateur connecté et vérifié
-        next.call(req).await
-    } else {
-        // pas connecté ou non vérifié
-        let (req, _pl) = req.into_parts();
-        let msg = if maybe_user_id.is_none() {
-            "Not connected"
-        } else {
-            "Account not verified"
-        };
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequireAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequireAuthMiddleware {
+            service: Rc::new(service),
+            requirement: self.clone(),
+        }))
+    }
+}
+
+pub struct RequireAuthMiddleware<S> {
+    service: Rc<S>,
+    requirement: RequireAuth,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireAuthMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
 
-        Ok(ServiceResponse::new(
-            req,
-            HttpResponse::Unauthorized().json(msg),
-        ))
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let requirement = self.requirement.clone();
+        Box::pin(async move {
+            verify_csrf_token(&req).await?;
+            let auth_state = try_extract_auth_state(req.request()).await?;
+            let claims = requirement.check(auth_state)?;
+            req.extensions_mut().insert(claims);
+            service.call(req).await
+        })
     }
 }
@@ -0,0 +1,57 @@
+use actix_session::SessionExt;
+use actix_web::{
+    Error,
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+};
+
+use crate::{
+    auth::auth_models::{
+        claims::try_extract_claims,
+        csrf_token::{CSRF_HEADER, CsrfToken},
+        refresh_token::{REFRESH_TOKEN_KEY, handle_session_error},
+    },
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+};
+
+/// Wrap with `middleware::from_fn` on the scopes whose handlers read the session
+/// without [`RequireAuth`](super::auth_middleware::RequireAuth), which runs the same
+/// check itself.
+pub async fn csrf_middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    verify_csrf_token(&req).await?;
+    next.call(req).await
+}
+
+/// A request is authenticated by its cookie alone when it carries no valid bearer
+/// token but a session holding a refresh token: such unsafe requests must echo the
+/// session CSRF token in the `X-CSRF-Token` header.
+pub async fn verify_csrf_token(req: &ServiceRequest) -> AppResult<()> {
+    if requires_csrf_token(req).await? {
+        let presented = req
+            .headers()
+            .get(CSRF_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if presented.is_empty() || !CsrfToken::matches(&req.get_session(), presented)? {
+            return Err(AppError::Forbiden(ErrorCode::CsrfTokenInvalid));
+        }
+    }
+    Ok(())
+}
+
+async fn requires_csrf_token(req: &ServiceRequest) -> AppResult<bool> {
+    if req.method().is_safe() || try_extract_claims(req.request()).await.is_ok() {
+        return Ok(false);
+    }
+    let has_refresh_token = req
+        .get_session()
+        .get::<String>(REFRESH_TOKEN_KEY)
+        .map_err(handle_session_error)?
+        .is_some();
+    Ok(has_refresh_token)
+}
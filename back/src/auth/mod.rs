@@ -3,3 +3,5 @@ pub mod auth_extractor;
 pub mod auth_models;
 pub mod auth_service;
 pub mod middlewares;
+pub mod password_policy;
+pub mod registration_policy;
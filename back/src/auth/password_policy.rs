@@ -0,0 +1,241 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufRead, BufReader, ErrorKind},
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use actix_web::web;
+use serde::Serialize;
+use serde_json::{Value, json};
+use sha1::{Digest, Sha1};
+
+use crate::{
+    auth::auth_models::email::Email,
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult, FieldError},
+    services::openapi_service::ApiSchema,
+    utils::env_utils::{env_flag, env_opt, env_or},
+};
+
+/// Only applied when a password is set (register, reset), never at login so
+/// tightening the policy does not lock existing users out.
+pub static PASSWORD_POLICY: LazyLock<PasswordPolicy> = LazyLock::new(PasswordPolicy::from_env);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyViolation {
+    TooShort,
+    TooLong,
+    MissingLowercase,
+    MissingUppercase,
+    MissingDigit,
+    MissingSymbol,
+    TooPredictable,
+    ContainsEmail,
+    Breached,
+}
+impl PolicyViolation {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            PolicyViolation::TooShort => ErrorCode::PasswordTooShort,
+            PolicyViolation::TooLong => ErrorCode::PasswordTooLong,
+            PolicyViolation::MissingLowercase => ErrorCode::PasswordMissingLowercase,
+            PolicyViolation::MissingUppercase => ErrorCode::PasswordMissingUppercase,
+            PolicyViolation::MissingDigit => ErrorCode::PasswordMissingDigit,
+            PolicyViolation::MissingSymbol => ErrorCode::PasswordMissingSymbol,
+            PolicyViolation::TooPredictable => ErrorCode::PasswordTooPredictable,
+            PolicyViolation::ContainsEmail => ErrorCode::PasswordContainsEmail,
+            PolicyViolation::Breached => ErrorCode::PasswordBreached,
+        }
+    }
+}
+
+/// One field error per violation, all on `field`
+pub fn violations_to_error(field: &str, violations: &[PolicyViolation]) -> AppError {
+    AppError::InvalidFields(
+        violations
+            .iter()
+            .map(|violation| FieldError::new(field, violation.code()))
+            .collect(),
+    )
+}
+
+/// Served as is to the frontend so it can display the rules before submitting
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    pub min_entropy_bits: f64,
+    pub reject_email: bool,
+    pub breached_check: bool,
+    /// Directory of k-anonymity range files: one file per 5 hex chars SHA-1 prefix,
+    /// each line being `SUFFIX:COUNT` (the format of the HIBP range API)
+    #[serde(skip)]
+    pub breached_dataset: Option<PathBuf>,
+}
+
+impl PasswordPolicy {
+    pub fn from_env() -> Self {
+        let breached_dataset = env_opt("PASSWORD_BREACHED_DATASET_DIR").map(PathBuf::from);
+        Self {
+            min_length: env_or("PASSWORD_MIN_LENGTH", 8),
+            max_length: env_or("PASSWORD_MAX_LENGTH", 128),
+            require_lowercase: env_flag("PASSWORD_REQUIRE_LOWERCASE", true),
+            require_uppercase: env_flag("PASSWORD_REQUIRE_UPPERCASE", true),
+            require_digit: env_flag("PASSWORD_REQUIRE_DIGIT", true),
+            require_symbol: env_flag("PASSWORD_REQUIRE_SYMBOL", true),
+            min_entropy_bits: env_or("PASSWORD_MIN_ENTROPY_BITS", 35.0),
+            reject_email: env_flag("PASSWORD_REJECT_EMAIL", true),
+            breached_check: breached_dataset.is_some(),
+            breached_dataset,
+        }
+    }
+
+    /// Rules that need no IO
+    pub fn check_rules(&self, password: &str, email: &Email) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+        if length < self.min_length {
+            violations.push(PolicyViolation::TooShort);
+        }
+        if length > self.max_length {
+            violations.push(PolicyViolation::TooLong);
+        }
+        if self.require_lowercase && !password.chars().any(|c| c.is_lowercase()) {
+            violations.push(PolicyViolation::MissingLowercase);
+        }
+        if self.require_uppercase && !password.chars().any(|c| c.is_uppercase()) {
+            violations.push(PolicyViolation::MissingUppercase);
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            violations.push(PolicyViolation::MissingDigit);
+        }
+        if self.require_symbol && !password.chars().any(|c| !c.is_alphanumeric()) {
+            violations.push(PolicyViolation::MissingSymbol);
+        }
+        if estimate_entropy_bits(password) < self.min_entropy_bits {
+            violations.push(PolicyViolation::TooPredictable);
+        }
+        if self.reject_email && contains_email(password, email) {
+            violations.push(PolicyViolation::ContainsEmail);
+        }
+        violations
+    }
+
+    /// Every violation, the breached dataset is only consulted once the rules pass
+    pub async fn check(&self, password: &str, email: &Email) -> AppResult<Vec<PolicyViolation>> {
+        let mut violations = self.check_rules(password, email);
+        if violations.is_empty() && self.is_breached(password).await? {
+            violations.push(PolicyViolation::Breached);
+        }
+        Ok(violations)
+    }
+
+    async fn is_breached(&self, password: &str) -> AppResult<bool> {
+        let Some(dataset) = self.breached_dataset.clone() else {
+            return Ok(false);
+        };
+        let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        web::block(move || lookup_breached_hash(&dataset, &hash))
+            .await
+            .map_err(|err| AppError::Internal(format!("breached lookup aborted: {err}")))?
+            .map_err(|err| AppError::Internal(format!("breached dataset unreadable: {err}")))
+    }
+}
+
+impl ApiSchema for PasswordPolicy {
+    fn schema_name() -> String {
+        "PasswordPolicy".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "min_length": { "type": "integer" },
+                "max_length": { "type": "integer" },
+                "require_lowercase": { "type": "boolean" },
+                "require_uppercase": { "type": "boolean" },
+                "require_digit": { "type": "boolean" },
+                "require_symbol": { "type": "boolean" },
+                "min_entropy_bits": { "type": "number" },
+                "reject_email": { "type": "boolean" },
+                "breached_check": { "type": "boolean" }
+            }
+        })
+    }
+}
+
+fn lookup_breached_hash(dataset: &Path, hash: &str) -> std::io::Result<bool> {
+    let (prefix, suffix) = hash.split_at(5);
+    let file = match File::open(dataset.join(prefix)) {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let candidate = line.split(':').next().unwrap_or_default().trim();
+        if candidate.eq_ignore_ascii_case(suffix) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn contains_email(password: &str, email: &Email) -> bool {
+    let password = password.to_lowercase();
+    let email = email.as_ref().to_lowercase();
+    let local_part = email.split('@').next().unwrap_or_default();
+    password.contains(&email) || (local_part.chars().count() >= 3 && password.contains(local_part))
+}
+
+/// Rough brute force cost: size of the character pool times the number of
+/// characters that are not a repetition or the continuation of a sequence (`abc`, `321`)
+pub fn estimate_entropy_bits(password: &str) -> f64 {
+    let chars: Vec<char> = password.chars().collect();
+    let mut pool = 0u32;
+    if chars.iter().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if chars.iter().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if chars
+        .iter()
+        .any(|c| c.is_ascii() && !c.is_ascii_alphanumeric())
+    {
+        pool += 33;
+    }
+    if chars.iter().any(|c| !c.is_ascii()) {
+        pool += 100;
+    }
+    if pool == 0 {
+        return 0.0;
+    }
+
+    let mut seen = HashSet::new();
+    let mut effective = 0.0;
+    for (i, c) in chars.iter().enumerate() {
+        let step = i
+            .checked_sub(1)
+            .map(|prev| *c as i64 - chars[prev] as i64)
+            .unwrap_or(i64::MAX);
+        effective += if step.abs() <= 1 {
+            // repeated char or sequence: almost free to guess
+            0.25
+        } else if !seen.insert(*c) {
+            0.5
+        } else {
+            1.0
+        };
+    }
+    effective * (pool as f64).log2()
+}
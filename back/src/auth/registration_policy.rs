@@ -0,0 +1,85 @@
+use std::{str::FromStr, sync::LazyLock};
+
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use crate::{
+    auth::auth_models::claims::Claims,
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    services::openapi_service::ApiSchema,
+    utils::env_utils::{env_flag, env_or},
+};
+
+/// Read once at startup, like the password policy
+pub static REGISTRATION_POLICY: LazyLock<RegistrationPolicy> =
+    LazyLock::new(RegistrationPolicy::from_env);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationMode {
+    /// Anyone may register, an invitation code is optional
+    Open,
+    InviteOnly,
+    /// Only imported or already registered users
+    Closed,
+}
+impl FromStr for RegistrationMode {
+    type Err = ();
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.to_ascii_lowercase().replace('-', "_").as_str() {
+            "open" => Ok(Self::Open),
+            "invite_only" => Ok(Self::InviteOnly),
+            "closed" => Ok(Self::Closed),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Served to the frontend so it knows whether to ask for an invitation code
+#[derive(Debug, Clone, Serialize)]
+pub struct RegistrationPolicy {
+    pub mode: RegistrationMode,
+    /// Whether non admin users may invite
+    pub users_can_invite: bool,
+    /// Uses allowed on an invitation issued by a non admin user
+    pub user_invitation_max_uses: i32,
+}
+
+impl RegistrationPolicy {
+    pub fn from_env() -> Self {
+        Self {
+            mode: env_or("REGISTRATION_MODE", RegistrationMode::Open),
+            users_can_invite: env_flag("INVITATIONS_BY_USERS", true),
+            user_invitation_max_uses: env_or("USER_INVITATION_MAX_USES", 5),
+        }
+    }
+
+    /// Highest `max_uses` the issuer may set, admins are not limited
+    pub fn invitation_max_uses(&self, issuer: &Claims) -> AppResult<Option<i32>> {
+        if issuer.is_user_admin {
+            return Ok(None);
+        }
+        if !self.users_can_invite {
+            return Err(AppError::Forbiden(ErrorCode::Forbidden));
+        }
+        Ok(Some(self.user_invitation_max_uses))
+    }
+}
+
+impl ApiSchema for RegistrationPolicy {
+    fn schema_name() -> String {
+        "RegistrationPolicy".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["mode", "users_can_invite", "user_invitation_max_uses"],
+            "properties": {
+                "mode": { "type": "string", "enum": ["open", "invite_only", "closed"] },
+                "users_can_invite": { "type": "boolean" },
+                "user_invitation_max_uses": { "type": "integer", "format": "int32" }
+            }
+        })
+    }
+}
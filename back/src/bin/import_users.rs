@@ -0,0 +1,57 @@
+//! Import users from the legacy system.
+//!
+//! Usage: `import_users <file.csv>`, each line being `email,bcrypt_hash[,verified]`.
+//! The bcrypt hashes are stored as is and upgraded to Argon2 on the next login.
+
+use std::{env, fs::read_to_string, process::ExitCode};
+
+use back::{auth::auth_models::email::Email, models::user_model::User};
+
+#[actix_web::main]
+async fn main() -> ExitCode {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("Usage: import_users <file.csv>");
+        return ExitCode::FAILURE;
+    };
+    let file = match read_to_string(&path) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Cannot read {path}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let (mut imported, mut skipped) = (0, 0);
+    for (number, line) in file.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("email,") {
+            continue;
+        }
+        let mut fields = line.split(',').map(str::trim);
+        let (Some(email), Some(hash)) = (fields.next(), fields.next()) else {
+            eprintln!("line {}: expected email,bcrypt_hash", number + 1);
+            skipped += 1;
+            continue;
+        };
+        let verified = matches!(fields.next(), Some("1" | "true" | "yes"));
+
+        let result = match Email::new(email) {
+            Ok(email) => User::import_legacy(&email, hash, verified).await,
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(_) => imported += 1,
+            Err(err) => {
+                eprintln!("line {}: {email} skipped: {err}", number + 1);
+                skipped += 1;
+            }
+        }
+    }
+
+    println!("{imported} users imported, {skipped} skipped");
+    if skipped == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,257 @@
+use std::fmt::Display;
+
+use serde::{Serialize, Serializer};
+
+use crate::{APP_URL, constants::messages::*};
+
+/// Stable, machine-readable identifiers sent to clients in the `code` field.
+/// The string form of a variant is part of the public API: never rename one,
+/// add a new variant instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    CredentialsIncorrect,
+    EmailInvalid,
+    PasswordInvalid,
+    PasswordTooShort,
+    PasswordTooLong,
+    PasswordMissingLowercase,
+    PasswordMissingUppercase,
+    PasswordMissingDigit,
+    PasswordMissingSymbol,
+    PasswordTooPredictable,
+    PasswordContainsEmail,
+    PasswordBreached,
+    EmailAlreadyExists,
+    RegistrationClosed,
+    InvitationRequired,
+    InvitationInvalid,
+    PhoneInvalid,
+    PhoneNotSet,
+    PhoneNotVerified,
+    OtpInvalid,
+    OtpExpired,
+    TokenAbsent,
+    TokenInvalid,
+    TokenExpired,
+    SessionInvalid,
+    CsrfTokenInvalid,
+    InsufficientScope,
+    ImpersonationForbidden,
+    AccountPendingDeletion,
+    MagicLinkOtherDevice,
+    ImageInvalid,
+    FileTooLarge,
+    RangeNotSatisfiable,
+    TusVersionUnsupported,
+    UploadLengthInvalid,
+    UploadOffsetMismatch,
+    UploadContentTypeInvalid,
+    ChecksumAlgorithmUnsupported,
+    UploadChecksumMismatch,
+    NodeNameTaken,
+    NodeParentInvalid,
+    NodeFileAlreadyPlaced,
+    NodeCopyTooLarge,
+    OrganizationRoleInsufficient,
+    OrganizationLastOwner,
+    UserNotFound,
+    UserNotLoggedIn,
+    UserNotVerified,
+    UserAlreadyVerified,
+    MalformedRequest,
+    ValidationFailed,
+    Forbidden,
+    NotFound,
+    Conflict,
+    DatabaseError,
+    CacheUnavailable,
+    MailFailed,
+    SmsFailed,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::CredentialsIncorrect => "CREDENTIALS_INCORRECT",
+            ErrorCode::EmailInvalid => "EMAIL_INVALID",
+            ErrorCode::PasswordInvalid => "PASSWORD_INVALID",
+            ErrorCode::PasswordTooShort => "PASSWORD_TOO_SHORT",
+            ErrorCode::PasswordTooLong => "PASSWORD_TOO_LONG",
+            ErrorCode::PasswordMissingLowercase => "PASSWORD_MISSING_LOWERCASE",
+            ErrorCode::PasswordMissingUppercase => "PASSWORD_MISSING_UPPERCASE",
+            ErrorCode::PasswordMissingDigit => "PASSWORD_MISSING_DIGIT",
+            ErrorCode::PasswordMissingSymbol => "PASSWORD_MISSING_SYMBOL",
+            ErrorCode::PasswordTooPredictable => "PASSWORD_TOO_PREDICTABLE",
+            ErrorCode::PasswordContainsEmail => "PASSWORD_CONTAINS_EMAIL",
+            ErrorCode::PasswordBreached => "PASSWORD_BREACHED",
+            ErrorCode::EmailAlreadyExists => "EMAIL_ALREADY_EXISTS",
+            ErrorCode::RegistrationClosed => "REGISTRATION_CLOSED",
+            ErrorCode::InvitationRequired => "INVITATION_REQUIRED",
+            ErrorCode::InvitationInvalid => "INVITATION_INVALID",
+            ErrorCode::PhoneInvalid => "PHONE_INVALID",
+            ErrorCode::PhoneNotSet => "PHONE_NOT_SET",
+            ErrorCode::PhoneNotVerified => "PHONE_NOT_VERIFIED",
+            ErrorCode::OtpInvalid => "OTP_INVALID",
+            ErrorCode::OtpExpired => "OTP_EXPIRED",
+            ErrorCode::TokenAbsent => "TOKEN_ABSENT",
+            ErrorCode::TokenInvalid => "TOKEN_INVALID",
+            ErrorCode::TokenExpired => "TOKEN_EXPIRED",
+            ErrorCode::SessionInvalid => "SESSION_INVALID",
+            ErrorCode::CsrfTokenInvalid => "CSRF_TOKEN_INVALID",
+            ErrorCode::InsufficientScope => "INSUFFICIENT_SCOPE",
+            ErrorCode::ImpersonationForbidden => "IMPERSONATION_FORBIDDEN",
+            ErrorCode::AccountPendingDeletion => "ACCOUNT_PENDING_DELETION",
+            ErrorCode::MagicLinkOtherDevice => "MAGIC_LINK_OTHER_DEVICE",
+            ErrorCode::ImageInvalid => "IMAGE_INVALID",
+            ErrorCode::FileTooLarge => "FILE_TOO_LARGE",
+            ErrorCode::RangeNotSatisfiable => "RANGE_NOT_SATISFIABLE",
+            ErrorCode::TusVersionUnsupported => "TUS_VERSION_UNSUPPORTED",
+            ErrorCode::UploadLengthInvalid => "UPLOAD_LENGTH_INVALID",
+            ErrorCode::UploadOffsetMismatch => "UPLOAD_OFFSET_MISMATCH",
+            ErrorCode::UploadContentTypeInvalid => "UPLOAD_CONTENT_TYPE_INVALID",
+            ErrorCode::ChecksumAlgorithmUnsupported => "CHECKSUM_ALGORITHM_UNSUPPORTED",
+            ErrorCode::UploadChecksumMismatch => "UPLOAD_CHECKSUM_MISMATCH",
+            ErrorCode::NodeNameTaken => "NODE_NAME_TAKEN",
+            ErrorCode::NodeParentInvalid => "NODE_PARENT_INVALID",
+            ErrorCode::NodeFileAlreadyPlaced => "NODE_FILE_ALREADY_PLACED",
+            ErrorCode::NodeCopyTooLarge => "NODE_COPY_TOO_LARGE",
+            ErrorCode::OrganizationRoleInsufficient => "ORGANIZATION_ROLE_INSUFFICIENT",
+            ErrorCode::OrganizationLastOwner => "ORGANIZATION_LAST_OWNER",
+            ErrorCode::UserNotFound => "USER_NOT_FOUND",
+            ErrorCode::UserNotLoggedIn => "USER_NOT_LOGGED_IN",
+            ErrorCode::UserNotVerified => "USER_NOT_VERIFIED",
+            ErrorCode::UserAlreadyVerified => "USER_ALREADY_VERIFIED",
+            ErrorCode::MalformedRequest => "MALFORMED_REQUEST",
+            ErrorCode::ValidationFailed => "VALIDATION_FAILED",
+            ErrorCode::Forbidden => "FORBIDDEN",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::Conflict => "CONFLICT",
+            ErrorCode::DatabaseError => "DATABASE_ERROR",
+            ErrorCode::CacheUnavailable => "CACHE_UNAVAILABLE",
+            ErrorCode::MailFailed => "MAIL_FAILED",
+            ErrorCode::SmsFailed => "SMS_FAILED",
+            ErrorCode::InternalError => "INTERNAL_ERROR",
+        }
+    }
+    /// Human readable summary, identical for every occurrence of the code
+    pub fn title(&self) -> &'static str {
+        match self {
+            ErrorCode::CredentialsIncorrect => CREDENTIALS_INCORECT,
+            ErrorCode::EmailInvalid => EMAIL_INVALID,
+            ErrorCode::PasswordInvalid => PASSWORD_INVALID,
+            ErrorCode::PasswordTooShort => PASSWORD_TOO_SHORT,
+            ErrorCode::PasswordTooLong => PASSWORD_TOO_LONG,
+            ErrorCode::PasswordMissingLowercase => PASSWORD_MISSING_LOWERCASE,
+            ErrorCode::PasswordMissingUppercase => PASSWORD_MISSING_UPPERCASE,
+            ErrorCode::PasswordMissingDigit => PASSWORD_MISSING_DIGIT,
+            ErrorCode::PasswordMissingSymbol => PASSWORD_MISSING_SYMBOL,
+            ErrorCode::PasswordTooPredictable => PASSWORD_TOO_PREDICTABLE,
+            ErrorCode::PasswordContainsEmail => PASSWORD_CONTAINS_EMAIL,
+            ErrorCode::PasswordBreached => PASSWORD_BREACHED,
+            ErrorCode::EmailAlreadyExists => EMAIL_ALREADY_EXIST,
+            ErrorCode::RegistrationClosed => REGISTRATION_CLOSED,
+            ErrorCode::InvitationRequired => INVITATION_REQUIRED,
+            ErrorCode::InvitationInvalid => INVITATION_INVALID,
+            ErrorCode::PhoneInvalid => PHONE_INVALID,
+            ErrorCode::PhoneNotSet => PHONE_NOT_SET,
+            ErrorCode::PhoneNotVerified => PHONE_NOT_VERIFIED,
+            ErrorCode::OtpInvalid => OTP_INVALID,
+            ErrorCode::OtpExpired => OTP_EXPIRED,
+            ErrorCode::TokenAbsent => TOKEN_ABSENT,
+            ErrorCode::TokenInvalid => TOKEN_INVALID,
+            ErrorCode::TokenExpired => TOKEN_EXPIRED,
+            ErrorCode::SessionInvalid => SESSION_INVALID,
+            ErrorCode::CsrfTokenInvalid => CSRF_TOKEN_INVALID,
+            ErrorCode::InsufficientScope => INSUFFICIENT_SCOPE,
+            ErrorCode::ImpersonationForbidden => IMPERSONATION_FORBIDDEN,
+            ErrorCode::AccountPendingDeletion => ACCOUNT_PENDING_DELETION,
+            ErrorCode::MagicLinkOtherDevice => MAGIC_LINK_OTHER_DEVICE,
+            ErrorCode::ImageInvalid => IMAGE_INVALID,
+            ErrorCode::FileTooLarge => FILE_TOO_LARGE,
+            ErrorCode::RangeNotSatisfiable => RANGE_NOT_SATISFIABLE,
+            ErrorCode::TusVersionUnsupported => TUS_VERSION_UNSUPPORTED,
+            ErrorCode::UploadLengthInvalid => UPLOAD_LENGTH_INVALID,
+            ErrorCode::UploadOffsetMismatch => UPLOAD_OFFSET_MISMATCH,
+            ErrorCode::UploadContentTypeInvalid => UPLOAD_CONTENT_TYPE_INVALID,
+            ErrorCode::ChecksumAlgorithmUnsupported => CHECKSUM_ALGORITHM_UNSUPPORTED,
+            ErrorCode::UploadChecksumMismatch => UPLOAD_CHECKSUM_MISMATCH,
+            ErrorCode::NodeNameTaken => NODE_NAME_TAKEN,
+            ErrorCode::NodeParentInvalid => NODE_PARENT_INVALID,
+            ErrorCode::NodeFileAlreadyPlaced => NODE_FILE_ALREADY_PLACED,
+            ErrorCode::NodeCopyTooLarge => NODE_COPY_TOO_LARGE,
+            ErrorCode::OrganizationRoleInsufficient => ORGANIZATION_ROLE_INSUFFICIENT,
+            ErrorCode::OrganizationLastOwner => ORGANIZATION_LAST_OWNER,
+            ErrorCode::UserNotFound => USER_NOT_FOUND,
+            ErrorCode::UserNotLoggedIn => USER_NOT_LOGIN,
+            ErrorCode::UserNotVerified => USER_NOT_VERIFIED,
+            ErrorCode::UserAlreadyVerified => USER_ALREADY_VERIFIED,
+            ErrorCode::MalformedRequest => MALFORMED_REQUEST,
+            ErrorCode::ValidationFailed => VALIDATION_FAILED,
+            ErrorCode::Forbidden => FORBIDDEN,
+            ErrorCode::NotFound => NOT_FOUND,
+            ErrorCode::Conflict => CONFLICT,
+            ErrorCode::DatabaseError => DATABASE_ERROR,
+            ErrorCode::CacheUnavailable => CACHE_UNAVAILABLE,
+            ErrorCode::MailFailed => MAIL_FAILED,
+            ErrorCode::SmsFailed => SMS_FAILED,
+            ErrorCode::InternalError => INTERNAL_ERROR,
+        }
+    }
+    /// URI used as the RFC 7807 `type` member, e.g. `https://localhost/problems/token-expired`
+    pub fn type_uri(&self) -> String {
+        format!(
+            "{APP_URL}/problems/{}",
+            self.as_str().to_ascii_lowercase().replace('_', "-")
+        )
+    }
+}
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// Codes attached to informational (non error) messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageCode {
+    UserCreated,
+    UserNotVerified,
+    UserVerified,
+    UserLoggedOut,
+    PhoneCodeSent,
+    PhoneVerified,
+}
+
+impl MessageCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageCode::UserCreated => "USER_CREATED",
+            MessageCode::UserNotVerified => "USER_NOT_VERIFIED",
+            MessageCode::UserVerified => "USER_VERIFIED",
+            MessageCode::UserLoggedOut => "USER_LOGGED_OUT",
+            MessageCode::PhoneCodeSent => "PHONE_CODE_SENT",
+            MessageCode::PhoneVerified => "PHONE_VERIFIED",
+        }
+    }
+    pub fn message(&self) -> &'static str {
+        match self {
+            MessageCode::UserCreated => USER_CREATED,
+            MessageCode::UserNotVerified => USER_NOT_VERIFIED,
+            MessageCode::UserVerified => USER_VERIFIED,
+            MessageCode::UserLoggedOut => USER_LOGGED_OUT,
+            MessageCode::PhoneCodeSent => PHONE_CODE_SENT,
+            MessageCode::PhoneVerified => PHONE_VERIFIED,
+        }
+    }
+}
+impl Serialize for MessageCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
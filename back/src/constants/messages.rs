@@ -2,13 +2,68 @@ pub const TOKEN_INVALID: &str = "Token invalide";
 pub const TOKEN_EXPIRED: &str = "Token expired";
 pub const TOKEN_ABSENT: &str = "Token absent";
 pub const SESSION_INVALID: &str = "Session invalid";
+pub const CSRF_TOKEN_INVALID: &str = "CSRF token missing or invalid";
+pub const REGISTRATION_CLOSED: &str = "Registration is closed";
+pub const INVITATION_REQUIRED: &str = "An invitation code is required to register";
+pub const INVITATION_INVALID: &str = "Invitation code unknown, expired or already used";
+pub const MAGIC_LINK_OTHER_DEVICE: &str = "Sign-in link requested from another browser";
+pub const IMAGE_INVALID: &str = "Image unreadable or in an unsupported format";
+pub const FILE_TOO_LARGE: &str = "File larger than the upload limit";
+pub const RANGE_NOT_SATISFIABLE: &str = "Requested range starts past the end of the file";
+pub const TUS_VERSION_UNSUPPORTED: &str = "Only version 1.0.0 of the tus protocol is supported";
+pub const UPLOAD_LENGTH_INVALID: &str = "Upload-Length missing or not a number";
+pub const UPLOAD_OFFSET_MISMATCH: &str = "Upload-Offset does not match the bytes received so far";
+pub const UPLOAD_CONTENT_TYPE_INVALID: &str =
+    "Chunks must be sent as application/offset+octet-stream";
+pub const CHECKSUM_ALGORITHM_UNSUPPORTED: &str =
+    "Checksum algorithm not supported, use sha1 or sha256";
+pub const UPLOAD_CHECKSUM_MISMATCH: &str = "Chunk does not match its Upload-Checksum";
+pub const NODE_NAME_TAKEN: &str = "An item with this name already exists in the folder";
+pub const NODE_PARENT_INVALID: &str =
+    "Parent must be one of your folders, outside of the moved item";
+pub const NODE_FILE_ALREADY_PLACED: &str = "File already placed in the folder tree";
+pub const NODE_COPY_TOO_LARGE: &str = "Too many items to copy at once";
+pub const ORGANIZATION_ROLE_INSUFFICIENT: &str = "Role in the organization too low for this action";
+pub const ORGANIZATION_LAST_OWNER: &str = "An organization cannot be left without owner";
+pub const IMPERSONATION_FORBIDDEN: &str = "Not allowed while impersonating a user";
+pub const ACCOUNT_PENDING_DELETION: &str =
+    "Account scheduled for deletion, use the link sent by email to restore it";
+pub const INSUFFICIENT_SCOPE: &str = "Token not allowed to perform this action";
 pub const USER_CREATED: &str = "User created successfully";
 pub const USER_NOT_FOUND: &str = "User not found";
 pub const USER_NOT_VERIFIED: &str = "User not verified";
+pub const USER_ALREADY_VERIFIED: &str = "User already verified";
 pub const USER_VERIFIED: &str = "User verified";
 pub const USER_NOT_LOGIN: &str = "User not logged in";
 pub const USER_LOGGED_OUT: &str = "User logged out";
 pub const EMAIL_ALREADY_EXIST: &str = "Email already exist";
 pub const EMAIL_INVALID: &str = "Email invalid";
+pub const PHONE_INVALID: &str = "Phone number must be in international format, e.g. +33612345678";
+pub const PHONE_NOT_SET: &str = "No phone number on this account";
+pub const PHONE_NOT_VERIFIED: &str = "Phone number not verified";
+pub const PHONE_CODE_SENT: &str = "A verification code was sent by SMS";
+pub const PHONE_VERIFIED: &str = "Phone number verified";
+pub const OTP_INVALID: &str = "Code incorrect";
+pub const OTP_EXPIRED: &str = "Code expired, request a new one";
+pub const PASSWORD_INVALID: &str = "Password invalid";
+pub const PASSWORD_TOO_SHORT: &str = "Password too short";
+pub const PASSWORD_TOO_LONG: &str = "Password too long";
+pub const PASSWORD_MISSING_LOWERCASE: &str = "Password needs a lowercase letter";
+pub const PASSWORD_MISSING_UPPERCASE: &str = "Password needs an uppercase letter";
+pub const PASSWORD_MISSING_DIGIT: &str = "Password needs a digit";
+pub const PASSWORD_MISSING_SYMBOL: &str = "Password needs a symbol";
+pub const PASSWORD_TOO_PREDICTABLE: &str = "Password too predictable";
+pub const PASSWORD_CONTAINS_EMAIL: &str = "Password must not contain the email address";
+pub const PASSWORD_BREACHED: &str = "Password appears in a known data breach";
 pub const SERIALIZATION_FAILED: &str = "Serialization failed";
 pub const CREDENTIALS_INCORECT: &str = "Credentials incorect";
+pub const MALFORMED_REQUEST: &str = "Malformed request";
+pub const VALIDATION_FAILED: &str = "One or more fields are invalid";
+pub const FORBIDDEN: &str = "Forbidden";
+pub const NOT_FOUND: &str = "Resource not found";
+pub const CONFLICT: &str = "Conflict with the current state of the resource";
+pub const INTERNAL_ERROR: &str = "Internal server error";
+pub const DATABASE_ERROR: &str = "Database unavailable";
+pub const CACHE_UNAVAILABLE: &str = "Cache unavailable";
+pub const MAIL_FAILED: &str = "Mail could not be sent";
+pub const SMS_FAILED: &str = "SMS could not be sent";
@@ -1,4 +1,4 @@
-use actix_web::{HttpResponse, ResponseError};
+use actix_web::{HttpRequest, HttpResponse, ResponseError, error::JsonPayloadError};
 use bb8::RunError;
 
 use redis::RedisError;
@@ -7,17 +7,23 @@ use thiserror::Error;
 
 use crate::{
     auth::auth_models::token::TokenError,
-    constants::messages::{SERIALIZATION_FAILED, TOKEN_EXPIRED, TOKEN_INVALID},
+    constants::{codes::ErrorCode, messages::SERIALIZATION_FAILED},
 };
 
 pub type AppResult<T> = Result<T, AppError>;
 
+/// Variants carrying an [`ErrorCode`] are client facing, the `String` ones hold
+/// internal details that are logged but never sent in the response.
 #[derive(Debug, Error)]
 pub enum AppError {
     #[error("Unauthorized: {0}")]
-    Unauthorized(String),
+    Unauthorized(ErrorCode),
     #[error("Validation error: {0}")]
-    Validation(String),
+    Validation(ErrorCode),
+    #[error("Invalid fields: {0:?}")]
+    InvalidFields(Vec<FieldError>),
+    #[error("Not found: {0}")]
+    NotFound(ErrorCode),
     #[error("Database error: {0}")]
     Database(String),
     #[error("Cache error: {0}")]
@@ -26,11 +32,60 @@ pub enum AppError {
     Internal(String),
     #[error("Mail sending error: {0}")]
     Mail(String),
+    #[error("SMS sending error: {0}")]
+    Sms(String),
     #[error("Conflict: {0}")]
-    Conflict(String),
+    Conflict(ErrorCode),
     #[error("Forbiden: {0}")]
-    Forbiden(String),
+    Forbiden(ErrorCode),
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(ErrorCode),
+    #[error("Range not satisfiable: {0}")]
+    RangeNotSatisfiable(ErrorCode),
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(ErrorCode),
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(ErrorCode),
+    /// 460, the status tus gives to a chunk not matching its `Upload-Checksum`
+    #[error("Checksum mismatch: {0}")]
+    ChecksumMismatch(ErrorCode),
 }
+impl AppError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            AppError::Unauthorized(code)
+            | AppError::Validation(code)
+            | AppError::NotFound(code)
+            | AppError::Conflict(code)
+            | AppError::Forbiden(code)
+            | AppError::PayloadTooLarge(code)
+            | AppError::RangeNotSatisfiable(code)
+            | AppError::PreconditionFailed(code)
+            | AppError::UnsupportedMediaType(code)
+            | AppError::ChecksumMismatch(code) => *code,
+            AppError::InvalidFields(_) => ErrorCode::ValidationFailed,
+            AppError::Database(_) => ErrorCode::DatabaseError,
+            AppError::Cache(_) => ErrorCode::CacheUnavailable,
+            AppError::Internal(_) => ErrorCode::InternalError,
+            AppError::Mail(_) => ErrorCode::MailFailed,
+            AppError::Sms(_) => ErrorCode::SmsFailed,
+        }
+    }
+    pub fn invalid_field(field: impl Into<String>, code: ErrorCode) -> Self {
+        AppError::InvalidFields(vec![FieldError::new(field, code)])
+    }
+    fn is_internal(&self) -> bool {
+        matches!(
+            self,
+            AppError::Database(_)
+                | AppError::Cache(_)
+                | AppError::Internal(_)
+                | AppError::Mail(_)
+                | AppError::Sms(_)
+        )
+    }
+}
+
 impl From<sqlx::error::Error> for AppError {
     fn from(value: sqlx::error::Error) -> Self {
         eprintln!("Database error:{value}");
@@ -68,39 +123,95 @@ impl From<lettre::error::Error> for AppError {
 }
 impl From<serde_json::Error> for AppError {
     fn from(value: serde_json::Error) -> Self {
-        eprintln!("error:{}", value);
-        Self::Internal("".to_string())
+        Self::Internal(format!("serde_json: {value}"))
+    }
+}
+impl From<zip::result::ZipError> for AppError {
+    fn from(value: zip::result::ZipError) -> Self {
+        Self::Internal(format!("zip: {value}"))
+    }
+}
+impl From<std::io::Error> for AppError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Internal(format!("io: {value}"))
     }
 }
 impl From<jsonwebtoken::errors::Error> for AppError {
     fn from(value: jsonwebtoken::errors::Error) -> Self {
-        eprintln!("Intenal error: {value}");
-        Self::Internal(SERIALIZATION_FAILED.to_string())
+        Self::Internal(format!("{SERIALIZATION_FAILED}: {value}"))
     }
 }
 impl From<TokenError> for AppError {
     fn from(value: TokenError) -> Self {
         match value {
-            TokenError::Expired => AppError::Unauthorized(TOKEN_EXPIRED.to_string()),
-            TokenError::Invalid => AppError::Validation(TOKEN_INVALID.to_string()),
+            TokenError::Expired => AppError::Unauthorized(ErrorCode::TokenExpired),
+            TokenError::Invalid => AppError::Validation(ErrorCode::TokenInvalid),
             TokenError::EncodeError(msg) => AppError::Internal(msg),
         }
     }
 }
 
+#[derive(Debug, Serialize, Clone)]
+pub struct FieldError {
+    pub field: String,
+    pub code: ErrorCode,
+    pub detail: &'static str,
+}
+impl FieldError {
+    pub fn new(field: impl Into<String>, code: ErrorCode) -> Self {
+        Self {
+            field: field.into(),
+            code,
+            detail: code.title(),
+        }
+    }
+}
+
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// RFC 7807 body, `code` and `errors` are extension members
 #[derive(Serialize)]
-struct ErrorResponse {
-    error: String,
-    code: u16,
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: &'static str,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    instance: String,
+    code: ErrorCode,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    errors: Vec<FieldError>,
 }
 
 impl ResponseError for AppError {
     fn error_response(&self) -> HttpResponse {
         let status = self.status_code();
-        HttpResponse::build(status).json(ErrorResponse {
-            error: self.to_string(),
-            code: status.as_u16(),
-        })
+        let code = self.code();
+        let instance = format!("urn:uuid:{}", uuid::Uuid::new_v4());
+
+        let detail = if self.is_internal() {
+            eprintln!("[{instance}] {self}");
+            Some(format!("Reference {instance} when reporting this error"))
+        } else {
+            None
+        };
+        let errors = match self {
+            AppError::InvalidFields(errors) => errors.clone(),
+            _ => Vec::new(),
+        };
+
+        HttpResponse::build(status)
+            .content_type(PROBLEM_CONTENT_TYPE)
+            .json(ProblemDetails {
+                problem_type: code.type_uri(),
+                title: code.title(),
+                status: status.as_u16(),
+                detail,
+                instance,
+                code,
+                errors,
+            })
     }
 
     fn status_code(&self) -> actix_web::http::StatusCode {
@@ -108,12 +219,28 @@ impl ResponseError for AppError {
         match self {
             AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
             AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidFields(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
             AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Cache(_) => StatusCode::SERVICE_UNAVAILABLE,
             AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Mail(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Sms(_) => StatusCode::BAD_GATEWAY,
             AppError::Conflict(_) => StatusCode::CONFLICT,
             AppError::Forbiden(_) => StatusCode::FORBIDDEN,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::RangeNotSatisfiable(_) => StatusCode::RANGE_NOT_SATISFIABLE,
+            AppError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::ChecksumMismatch(_) => {
+                StatusCode::from_u16(460).expect("460 is a valid status code")
+            }
         }
     }
 }
+
+/// Registered on `web::JsonConfig` so malformed bodies also answer with a problem document
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    eprintln!("Rejected json payload: {err}");
+    AppError::Validation(ErrorCode::MalformedRequest).into()
+}
@@ -0,0 +1,160 @@
+use actix_multipart::Multipart;
+use actix_web::web::{Path, Payload, Query};
+use actix_web::{
+    HttpRequest, HttpResponse, delete, get,
+    http::header::{
+        self, CacheControl, CacheDirective, Charset, ContentDisposition, ContentRange,
+        ContentRangeSpec, DispositionParam, DispositionType, EntityTag, ExtendedValue, Header,
+    },
+    post,
+};
+
+use crate::{
+    auth::auth_models::{claims::Claims, scope::Scope},
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    files::files_service::{
+        RawUploadQuery, delete_file_service, download_file_service, get_file_service,
+        list_files_service, upload_multipart_service, upload_raw_service,
+    },
+    models::file_model::StoredFile,
+    shared::{ApiResponse, JsonResponse, PageQuery},
+    storage::ByteRange,
+};
+
+/// Plain ASCII `filename` for old clients, the exact name in `filename*`
+fn attachment(name: &str) -> ContentDisposition {
+    let fallback: String = name
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '_' })
+        .collect();
+    let mut parameters = vec![DispositionParam::Filename(fallback)];
+    if !name.is_ascii() {
+        parameters.push(DispositionParam::FilenameExt(ExtendedValue {
+            charset: Charset::Ext("UTF-8".to_string()),
+            language_tag: None,
+            value: name.as_bytes().to_vec(),
+        }));
+    }
+    ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters,
+    }
+}
+
+/// Mounted in the `/files` scope, for any connected user.
+/// `multipart/form-data` body, the file is the `file` part
+#[post("")]
+pub async fn upload_file(claims: Claims, multipart: Multipart) -> ApiResponse<StoredFile> {
+    claims.require_scope(Scope::FilesWrite)?;
+    let file = upload_multipart_service(claims.user_id, multipart).await?;
+    JsonResponse::created()
+        .location(format!("/files/{}", file.id))
+        .object(file)
+}
+
+/// The body is the file, streamed to the store as it arrives
+#[post("/raw")]
+pub async fn upload_raw_file(
+    claims: Claims,
+    req: HttpRequest,
+    Query(query): Query<RawUploadQuery>,
+    body: Payload,
+) -> ApiResponse<StoredFile> {
+    claims.require_scope(Scope::FilesWrite)?;
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    let file = upload_raw_service(claims.user_id, query, content_type, body).await?;
+    JsonResponse::created()
+        .location(format!("/files/{}", file.id))
+        .object(file)
+}
+
+/// Newest first, `?page=&per_page=`
+#[get("")]
+pub async fn list_files(
+    claims: Claims,
+    Query(page): Query<PageQuery>,
+) -> ApiResponse<Vec<StoredFile>> {
+    claims.require_scope(Scope::FilesRead)?;
+    let (files, meta) = list_files_service(claims.user_id, page).await?;
+    JsonResponse::ok().page(files, meta)
+}
+
+#[get("/{file_id}")]
+pub async fn get_file(claims: Claims, file_id: Path<i32>) -> ApiResponse<StoredFile> {
+    claims.require_scope(Scope::FilesRead)?;
+    JsonResponse::ok().object(get_file_service(claims.user_id, file_id.into_inner()).await?)
+}
+
+/// The single byte range asked by `Range`, if any. Several ranges, or an `If-Range`
+/// naming another version, get the whole file as RFC 9110 allows.
+fn requested_range(req: &HttpRequest, file: &StoredFile) -> AppResult<Option<ByteRange>> {
+    let Ok(header::Range::Bytes(specs)) = header::Range::parse(req) else {
+        return Ok(None);
+    };
+    let [spec] = specs.as_slice() else {
+        return Ok(None);
+    };
+    if let Some(if_range) = req.headers().get(header::IF_RANGE) {
+        let current = EntityTag::new_strong(file.sha256.clone()).to_string();
+        if if_range.to_str().ok() != Some(current.as_str()) {
+            return Ok(None);
+        }
+    }
+    let (first, last) =
+        spec.to_satisfiable_range(file.size as u64)
+            .ok_or(AppError::RangeNotSatisfiable(
+                ErrorCode::RangeNotSatisfiable,
+            ))?;
+    Ok(Some(ByteRange {
+        first,
+        last: Some(last),
+    }))
+}
+
+/// Always an attachment: the scope sandboxes whatever the browser renders anyway.
+/// A single `Range` is answered with 206 Partial Content
+#[get("/{file_id}/content")]
+pub async fn download_file(
+    claims: Claims,
+    req: HttpRequest,
+    file_id: Path<i32>,
+) -> AppResult<HttpResponse> {
+    claims.require_scope(Scope::FilesRead)?;
+    let file_id = file_id.into_inner();
+    let file = get_file_service(claims.user_id, file_id).await?;
+    let range = requested_range(&req, &file)?;
+    let (file, content) = download_file_service(claims.user_id, file_id, range).await?;
+
+    let mut response = match range {
+        Some(range) => {
+            let mut response = HttpResponse::PartialContent();
+            response.insert_header(ContentRange(ContentRangeSpec::Bytes {
+                range: Some((range.first, range.first + content.size - 1)),
+                instance_length: Some(content.total_size),
+            }));
+            response
+        }
+        None => HttpResponse::Ok(),
+    };
+    Ok(response
+        .content_type(file.content_type.as_str())
+        .insert_header(CacheControl(vec![
+            CacheDirective::Private,
+            CacheDirective::NoCache,
+        ]))
+        .insert_header(header::ETag(EntityTag::new_strong(file.sha256.clone())))
+        .insert_header((header::ACCEPT_RANGES, "bytes"))
+        .insert_header(attachment(&file.name))
+        .no_chunking(content.size)
+        .streaming(content.body))
+}
+
+#[delete("/{file_id}")]
+pub async fn delete_file(claims: Claims, file_id: Path<i32>) -> ApiResponse<StoredFile> {
+    claims.require_scope(Scope::FilesWrite)?;
+    JsonResponse::ok().object(delete_file_service(claims.user_id, file_id.into_inner()).await?)
+}
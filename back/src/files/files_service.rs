@@ -0,0 +1,160 @@
+use std::fmt::Display;
+
+use actix_multipart::Multipart;
+use actix_web::{mime::Mime, web::Bytes};
+use futures_util::{Stream, StreamExt};
+use serde::Deserialize;
+
+use crate::{
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    models::file_model::StoredFile,
+    shared::{PageMeta, PageQuery},
+    storage::{ByteRange, StoredContent, max_upload_bytes, storage, store},
+};
+
+const FILE_NAME_MAX_LENGTH: usize = 255;
+const CONTENT_TYPE_MAX_LENGTH: usize = 127;
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+const FILES_PER_PAGE: u32 = 50;
+const FILES_MAX_PER_PAGE: u32 = 200;
+/// Form field holding the file of a multipart upload
+const MULTIPART_FILE_FIELD: &str = "file";
+
+/// `?name=` of a raw upload
+#[derive(Deserialize)]
+pub struct RawUploadQuery {
+    pub name: String,
+}
+
+/// Keeps the last path segment of what the client sent, without control characters
+pub fn sanitize_file_name(raw: &str) -> AppResult<String> {
+    let base = raw.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = base.chars().filter(|c| !c.is_control()).collect();
+    let name = name.trim();
+    if name.is_empty() || name == "." || name == ".." || name.chars().count() > FILE_NAME_MAX_LENGTH
+    {
+        return Err(AppError::invalid_field("name", ErrorCode::ValidationFailed));
+    }
+    Ok(name.to_string())
+}
+
+/// Unparsable or missing types are stored as `application/octet-stream`
+pub fn sanitize_content_type(raw: Option<&str>) -> String {
+    raw.map(str::trim)
+        .filter(|raw| raw.len() <= CONTENT_TYPE_MAX_LENGTH)
+        .and_then(|raw| raw.parse::<Mime>().ok())
+        .map(|mime| mime.essence_str().to_string())
+        .unwrap_or_else(|| DEFAULT_CONTENT_TYPE.to_string())
+}
+
+/// Streams `body` to the store, then records it. The content is removed if the record
+/// fails, or when the same bytes were already stored: the file then shares their blob
+pub async fn store_file<S, E>(
+    user_id: i32,
+    name: &str,
+    content_type: &str,
+    body: S,
+) -> AppResult<StoredFile>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin + 'static,
+    E: Display,
+{
+    let object = store(body, max_upload_bytes()).await?;
+    let (result, duplicate) = match StoredFile::create(user_id, name, content_type, &object).await {
+        Ok((file, key)) => (Ok(file), key != object.key),
+        Err(err) => (Err(err), true),
+    };
+    if duplicate && let Err(cleanup) = storage().delete(&object.key).await {
+        eprintln!("Orphan object {} left in the store: {cleanup}", object.key);
+    }
+    result
+}
+
+/// Body sent as is, the file name comes from the query and the type from `Content-Type`
+pub async fn upload_raw_service<S, E>(
+    user_id: i32,
+    query: RawUploadQuery,
+    content_type: Option<&str>,
+    body: S,
+) -> AppResult<StoredFile>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin + 'static,
+    E: Display,
+{
+    let name = sanitize_file_name(&query.name)?;
+    store_file(user_id, &name, &sanitize_content_type(content_type), body).await
+}
+
+/// Stores the `file` part of a `multipart/form-data` body, the other parts are skipped
+pub async fn upload_multipart_service(
+    user_id: i32,
+    mut multipart: Multipart,
+) -> AppResult<StoredFile> {
+    while let Some(field) = multipart.next().await {
+        let mut field = field.map_err(|err| {
+            eprintln!("Rejected multipart body: {err}");
+            AppError::Validation(ErrorCode::MalformedRequest)
+        })?;
+        if field.name() != Some(MULTIPART_FILE_FIELD) {
+            while field.next().await.is_some() {}
+            continue;
+        }
+        let raw_name = field
+            .content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .unwrap_or_default()
+            .to_string();
+        let name = sanitize_file_name(&raw_name)?;
+        let content_type =
+            sanitize_content_type(field.content_type().map(|mime| mime.essence_str()));
+        return store_file(user_id, &name, &content_type, field).await;
+    }
+    Err(AppError::invalid_field(
+        MULTIPART_FILE_FIELD,
+        ErrorCode::ValidationFailed,
+    ))
+}
+
+pub async fn list_files_service(
+    user_id: i32,
+    page: PageQuery,
+) -> AppResult<(Vec<StoredFile>, PageMeta)> {
+    let (page, per_page) = page.resolve(FILES_PER_PAGE, FILES_MAX_PER_PAGE);
+    let total = StoredFile::count(user_id).await?;
+    let offset = (page as u64 - 1) * per_page as u64;
+    let files = StoredFile::list(user_id, per_page as u64, offset).await?;
+    Ok((files, PageMeta::new(page, per_page, total)))
+}
+
+pub async fn get_file_service(user_id: i32, file_id: i32) -> AppResult<StoredFile> {
+    StoredFile::get(user_id, file_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))
+}
+
+/// The metadata with the content, or only `range` of it
+pub async fn download_file_service(
+    user_id: i32,
+    file_id: i32,
+    range: Option<ByteRange>,
+) -> AppResult<(StoredFile, StoredContent)> {
+    let (file, key) = StoredFile::get_with_key(user_id, file_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))?;
+    let content = match range {
+        Some(range) => storage().get_range(&key, range).await?,
+        None => storage().get(&key).await?,
+    };
+    let content = content.ok_or(AppError::Internal(format!(
+        "Content of file {file_id} missing from the store"
+    )))?;
+    Ok((file, content))
+}
+
+/// The content is removed from the store later on, once no file shares it anymore
+pub async fn delete_file_service(user_id: i32, file_id: i32) -> AppResult<StoredFile> {
+    StoredFile::delete(user_id, file_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))
+}
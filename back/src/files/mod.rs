@@ -0,0 +1,2 @@
+pub mod files_controller;
+pub mod files_service;
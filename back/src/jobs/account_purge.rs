@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use serde_json::json;
+use time::OffsetDateTime;
+
+use crate::{
+    errors::AppResult,
+    models::{
+        audit_event_model::{AuditAction, AuditEvent},
+        user_model::User,
+    },
+    utils::env_utils::env_or,
+    webhooks::{webhook_event::WebhookEventType, webhook_service::emit_webhook},
+};
+
+const BATCH_SIZE: u64 = 100;
+
+pub async fn run() {
+    let interval = Duration::from_secs(env_or("ACCOUNT_PURGE_INTERVAL_SECS", 3600));
+    loop {
+        if let Err(err) = purge_deleted_accounts().await {
+            eprintln!("Account purge failed: {err}");
+        }
+        actix_rt::time::sleep(interval).await;
+    }
+}
+
+/// Hard deletes the accounts whose grace period is over and returns how many
+pub async fn purge_deleted_accounts() -> AppResult<usize> {
+    let mut purged = 0;
+    loop {
+        let now = OffsetDateTime::now_utc();
+        let users = User::due_for_deletion(now, BATCH_SIZE).await?;
+        let count = users.len();
+        for user in users {
+            // restored in the meantime. Contents no one else shares are collected later on
+            if !User::hard_delete(user.id, now).await? {
+                continue;
+            }
+            purged += 1;
+            AuditEvent::new(AuditAction::AccountDeleted)
+                .subject(user.id)
+                .record()
+                .await?;
+            emit_webhook(
+                WebhookEventType::UserDeleted,
+                json!({ "user_id": user.id, "email": user.email }),
+            )
+            .await;
+        }
+        if (count as u64) < BATCH_SIZE {
+            return Ok(purged);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use crate::{
+    errors::AppResult, models::blob_model::Blob, storage::storage, utils::env_utils::env_or,
+};
+
+const BATCH_SIZE: u64 = 100;
+
+pub async fn run() {
+    let interval = Duration::from_secs(env_or("BLOB_GC_INTERVAL_SECS", 3600));
+    loop {
+        if let Err(err) = collect_garbage().await {
+            eprintln!("Blob collection failed: {err}");
+        }
+        actix_rt::time::sleep(interval).await;
+    }
+}
+
+/// Forgets the blobs no file points at anymore, then deletes their content from the
+/// store and returns how many objects were deleted. An object the store failed to
+/// delete is logged and stays queued for the next run, the others are still deleted.
+pub async fn collect_garbage() -> AppResult<usize> {
+    while Blob::forget_unreferenced(BATCH_SIZE).await? == BATCH_SIZE {}
+    let mut deleted = 0;
+    loop {
+        let keys = Blob::garbage(BATCH_SIZE).await?;
+        let count = keys.len();
+        let mut batch_deleted = 0;
+        for key in keys {
+            if let Err(err) = storage().delete(&key).await {
+                eprintln!("Garbage object {key} kept in the store: {err}");
+                continue;
+            }
+            Blob::forget_garbage(key.as_str()).await?;
+            batch_deleted += 1;
+        }
+        deleted += batch_deleted;
+        // the kept objects come first in the next batch, a batch of them only ends the run
+        if (count as u64) < BATCH_SIZE || batch_deleted == 0 {
+            return Ok(deleted);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    errors::AppResult, models::blob_model::Blob, storage::storage, utils::env_utils::env_or,
+};
+
+pub async fn run() {
+    let interval = Duration::from_secs(env_or("BLOB_SCRUB_INTERVAL_SECS", 3600));
+    let batch = env_or("BLOB_SCRUB_BATCH", 100u64);
+    loop {
+        match scrub_blobs(batch).await {
+            Ok(corrupted) => {
+                for sha256 in corrupted {
+                    eprintln!("Blob {sha256} is corrupted: missing or altered in the store");
+                }
+            }
+            Err(err) => eprintln!("Blob scrub failed: {err}"),
+        }
+        actix_rt::time::sleep(interval).await;
+    }
+}
+
+/// Whether the content still has the size and hash it was stored with.
+/// `None` when the store could not be read, which says nothing about the content
+async fn is_intact(blob: &Blob) -> Option<bool> {
+    let content = match storage().get(&blob.storage_key).await {
+        Ok(Some(content)) => content,
+        Ok(None) => return Some(false),
+        Err(err) => {
+            eprintln!("Blob {} unreadable: {err}", blob.sha256);
+            return None;
+        }
+    };
+    let mut body = content.body;
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    while let Some(chunk) = body.next().await {
+        match chunk {
+            Ok(chunk) => {
+                size += chunk.len() as u64;
+                hasher.update(&chunk);
+            }
+            Err(err) => {
+                eprintln!("Blob {} unreadable: {err}", blob.sha256);
+                return None;
+            }
+        }
+    }
+    Some(size == blob.size as u64 && hex::encode(hasher.finalize()) == blob.sha256)
+}
+
+/// Re-hashes up to `limit` blobs, the ones checked the longest ago first, and returns
+/// the hashes of those found corrupted. They are flagged until a check finds them
+/// intact again, or an upload of the same bytes replaces them.
+pub async fn scrub_blobs(limit: u64) -> AppResult<Vec<String>> {
+    let mut corrupted = Vec::new();
+    for blob in Blob::due_for_scrub(limit).await? {
+        let Some(intact) = is_intact(&blob).await else {
+            continue;
+        };
+        blob.record_scrub(intact).await?;
+        if !intact {
+            corrupted.push(blob.sha256);
+        }
+    }
+    Ok(corrupted)
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Write},
+};
+
+use serde_json::{Value, json};
+use time::{Duration, OffsetDateTime};
+use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+use crate::{
+    APP_URL,
+    auth::auth_models::email::Email,
+    errors::AppResult,
+    models::{
+        audit_event_model::{AuditAction, AuditRecord},
+        data_export_model::{DataExport, PendingExport},
+        file_model::StoredFile,
+        invitation_model::Invitation,
+        node_model::{Node, NodeKind},
+        organization_model::Organization,
+        personal_access_token_model::PersonalAccessToken,
+        profile_model::Profile,
+        user_model::User,
+    },
+    utils::{
+        email_utils::{mail_date, send_mail},
+        env_utils::env_or,
+    },
+};
+
+const BATCH_SIZE: u64 = 5;
+/// Longer than any build, an export is never built twice at once
+const LEASE_SECS: i64 = 10 * 60;
+
+const README: &str = "\
+Copie de vos données personnelles
+
+profile.json        votre compte et votre profil, sans le mot de passe
+avatar.png          votre photo de profil, si vous en avez une
+sessions.json       vos jetons d'accès personnels et l'historique de vos connexions ;
+                    les sessions du navigateur ne sont conservées que dans leur cookie
+audit_events.json   le journal des actions faites par vous ou sur votre compte
+invitations.json    les invitations que vous avez émises
+organizations.json  les organisations dont vous êtes membre, avec votre rôle
+files.json          vos fichiers et leur chemin dans vos dossiers ; leur contenu se
+                    télécharge depuis l'application
+folders.json        vos dossiers
+
+Les dates sont des timestamps UNIX, en secondes.
+";
+
+fn link_validity_hours() -> i64 {
+    env_or("DATA_EXPORT_LINK_HOURS", 48i64).max(1)
+}
+
+pub async fn run() {
+    let interval = std::time::Duration::from_secs(env_or("DATA_EXPORT_POLL_SECS", 30));
+    loop {
+        if let Err(err) = build_pending_exports().await {
+            eprintln!("Data export failed: {err}");
+        }
+        if let Err(err) = DataExport::expire(OffsetDateTime::now_utc()).await {
+            eprintln!("Data export expiration failed: {err}");
+        }
+        actix_rt::time::sleep(interval).await;
+    }
+}
+
+/// Builds one batch of requested exports and returns how many were handled
+pub async fn build_pending_exports() -> AppResult<usize> {
+    let exports = DataExport::claim_pending(BATCH_SIZE, LEASE_SECS).await?;
+    let count = exports.len();
+    for export in exports {
+        if let Err(err) = build_export(&export).await {
+            eprintln!("Data export {} failed: {err}", export.id);
+            DataExport::mark_failed(export.id).await?;
+        }
+    }
+    Ok(count)
+}
+
+async fn build_export(export: &PendingExport) -> AppResult<()> {
+    let user = User::get(export.user_id).await?;
+    let archive = build_archive(&user).await?;
+    let key = uuid::Uuid::new_v4().simple().to_string();
+    let expires_at = OffsetDateTime::now_utc() + Duration::hours(link_validity_hours());
+    DataExport::mark_ready(export.id, &archive, &key, expires_at).await?;
+    send_export_email(&user.email, &key, expires_at)
+}
+
+/// One JSON document per source of personal data, described by a README
+async fn build_archive(user: &User) -> AppResult<Vec<u8>> {
+    let events = AuditRecord::for_user(user.id).await?;
+    let session_actions = [AuditAction::Login.as_str(), AuditAction::Logout.as_str()];
+    let logins: Vec<&AuditRecord> = events
+        .iter()
+        .filter(|event| event.subject_id == Some(user.id))
+        .filter(|event| session_actions.contains(&event.action.as_str()))
+        .collect();
+    let sessions = json!({
+        "personal_access_tokens": PersonalAccessToken::list(user.id).await?,
+        "logins": logins,
+    });
+    let (files, folders) = files_and_folders(user.id).await?;
+    let documents = [
+        ("profile.json", profile(user, &Profile::get(user.id).await?)),
+        ("sessions.json", sessions),
+        ("audit_events.json", json!(events)),
+        ("invitations.json", json!(Invitation::list(user.id).await?)),
+        (
+            "organizations.json",
+            json!(Organization::list(user.id).await?),
+        ),
+        ("files.json", files),
+        ("folders.json", folders),
+    ];
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file("README.txt", options)?;
+    zip.write_all(README.as_bytes())?;
+    for (name, document) in documents {
+        zip.start_file(name, options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&document)?)?;
+    }
+    if let Some(avatar) = Profile::avatar(user.id).await? {
+        zip.start_file("avatar.png", options)?;
+        zip.write_all(&avatar)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// File metadata with the path each file is placed at, if any, and the folder tree
+async fn files_and_folders(user_id: i32) -> AppResult<(Value, Value)> {
+    let nodes = Node::paths(user_id).await?;
+    let paths: HashMap<i32, &str> = nodes
+        .iter()
+        .filter_map(|node| Some((node.file_id?, node.path.as_str())))
+        .collect();
+    let files: Vec<Value> = StoredFile::list(user_id, u64::MAX, 0)
+        .await?
+        .into_iter()
+        .map(|file| {
+            json!({
+                "name": file.name,
+                "content_type": file.content_type,
+                "size": file.size,
+                "sha256": file.sha256,
+                "created_at": file.created_at,
+                "path": paths.get(&file.id),
+            })
+        })
+        .collect();
+    let folders: Vec<Value> = nodes
+        .iter()
+        .filter(|node| node.kind == NodeKind::Folder)
+        .map(|node| json!({ "path": node.path, "created_at": node.created_at }))
+        .collect();
+    Ok((json!(files), json!(folders)))
+}
+
+fn profile(user: &User, profile: &Profile) -> Value {
+    let unix = |date: time::PrimitiveDateTime| date.assume_utc().unix_timestamp();
+    json!({
+        "id": user.id,
+        "email": user.email,
+        "phone_number": user.phone_number,
+        "phone_verified_at": user.phone_verified_at.map(unix),
+        "sms_two_factor": user.sms_two_factor != 0,
+        "verified_at": user.verified_at.map(unix),
+        "admin": user.admin != 0,
+        "invited_by": user.invited_by,
+        "deletion_scheduled_at": user.deletion_scheduled_at.map(unix),
+        "display_name": profile.display_name,
+        "locale": profile.locale,
+        "timezone": profile.timezone,
+        "profile_created_at": profile.created_at,
+        "profile_updated_at": profile.updated_at,
+    })
+}
+
+fn send_export_email(email: &Email, key: &str, expires_at: OffsetDateTime) -> AppResult<()> {
+    let download_url = format!("{APP_URL}/account/export/download?token={key}");
+    let expiration_date = format!(
+        "{} à {:02}:{:02} UTC",
+        mail_date(expires_at),
+        expires_at.hour(),
+        expires_at.minute()
+    );
+
+    let html_template = include_str!("../templates/data_export_email.html");
+    let html = html_template
+        .replace("__DOWNLOAD_URL__", &download_url)
+        .replace("__EXPIRATION_DATE__", &expiration_date);
+
+    send_mail(email, "Votre export de données", html)
+}
@@ -0,0 +1,7 @@
+//! Background work spawned by `main` next to the HTTP server
+pub mod account_purge;
+pub mod blob_gc;
+pub mod blob_scrub;
+pub mod data_export;
+pub mod upload_cleanup;
+pub mod webhook_worker;
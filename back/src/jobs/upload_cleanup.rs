@@ -0,0 +1,43 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::{
+    errors::AppResult,
+    storage::{StorageKey, storage},
+    uploads::tus_upload::{SEGMENTS_PREFIX, TusUpload},
+    utils::env_utils::env_or,
+};
+
+pub async fn run() {
+    let interval = Duration::from_secs(env_or("UPLOAD_CLEANUP_INTERVAL_SECS", 3600));
+    loop {
+        if let Err(err) = remove_expired_segments().await {
+            eprintln!("Upload cleanup failed: {err}");
+        }
+        actix_rt::time::sleep(interval).await;
+    }
+}
+
+/// Deletes the chunks of the tus uploads which expired before completion and returns
+/// how many. Their state leaves the cache by itself, the chunks stay in the store.
+pub async fn remove_expired_segments() -> AppResult<usize> {
+    let mut by_upload: HashMap<String, Vec<StorageKey>> = HashMap::new();
+    for object in storage().list(&format!("{SEGMENTS_PREFIX}/")).await? {
+        if let Some(id) = TusUpload::id_of_segment(&object.key) {
+            by_upload
+                .entry(id.to_string())
+                .or_default()
+                .push(object.key);
+        }
+    }
+    let mut removed = 0;
+    for (id, keys) in by_upload {
+        if TusUpload::get(&id).await?.is_some() {
+            continue;
+        }
+        for key in keys {
+            storage().delete(&key).await?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,120 @@
+use std::{sync::LazyLock, time::Duration};
+
+use reqwest::{Client, header, redirect};
+use time::OffsetDateTime;
+
+use crate::{
+    errors::AppResult,
+    models::webhook_delivery_model::{
+        AttemptOutcome, DeliveryStatus, OutgoingDelivery, WebhookDelivery,
+    },
+    shared::get_now_unix,
+    utils::env_utils::env_or,
+    webhooks::webhook_signature::{
+        DELIVERY_ID_HEADER, EVENT_ID_HEADER, EVENT_TYPE_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER,
+        sign,
+    },
+};
+
+const BATCH_SIZE: u64 = 20;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longer than a request, a delivery is never sent twice at once
+const LEASE_SECS: i64 = 60;
+const FIRST_RETRY_SECS: i64 = 30;
+const MAX_RETRY_SECS: i64 = 6 * 60 * 60;
+
+/// Redirects are not followed: the registered URL is the only one ever called
+static HTTP_CLIENT: LazyLock<Client> = LazyLock::new(|| {
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(redirect::Policy::none())
+        .user_agent("cloud-app-webhooks/1")
+        .build()
+        .expect("can't build the webhook HTTP client")
+});
+
+fn max_attempts() -> i32 {
+    env_or("WEBHOOK_MAX_ATTEMPTS", 8)
+}
+
+/// 30s, 1min, 2min... up to 6h between two attempts
+fn retry_delay_secs(attempts: i32) -> i64 {
+    let exponent = (attempts - 1).clamp(0, 20) as u32;
+    FIRST_RETRY_SECS
+        .saturating_mul(2i64.pow(exponent))
+        .min(MAX_RETRY_SECS)
+}
+
+/// Polls the queue every `WEBHOOK_POLL_SECS` (5 by default), forever
+pub async fn run() {
+    let interval = Duration::from_secs(env_or("WEBHOOK_POLL_SECS", 5));
+    loop {
+        match deliver_due_webhooks().await {
+            // a full batch: more may be waiting
+            Ok(sent) if sent as u64 == BATCH_SIZE => continue,
+            Ok(_) => {}
+            Err(err) => eprintln!("Webhook delivery failed: {err}"),
+        }
+        actix_rt::time::sleep(interval).await;
+    }
+}
+
+/// Sends one batch of due deliveries and returns how many were attempted
+pub async fn deliver_due_webhooks() -> AppResult<usize> {
+    let deliveries = WebhookDelivery::claim_due(BATCH_SIZE, LEASE_SECS).await?;
+    let count = deliveries.len();
+    for delivery in deliveries {
+        let id = delivery.id;
+        let outcome = attempt(delivery).await;
+        WebhookDelivery::record_attempt(id, outcome).await?;
+    }
+    Ok(count)
+}
+
+async fn attempt(delivery: OutgoingDelivery) -> AttemptOutcome {
+    let timestamp = get_now_unix();
+    let response = HTTP_CLIENT
+        .post(&delivery.url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(EVENT_ID_HEADER, &delivery.event_id)
+        .header(DELIVERY_ID_HEADER, delivery.id.to_string())
+        .header(EVENT_TYPE_HEADER, &delivery.event_type)
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(
+            SIGNATURE_HEADER,
+            sign(&delivery.secret, timestamp, &delivery.payload),
+        )
+        .body(delivery.payload)
+        .send()
+        .await;
+
+    let (status_code, error) = match response {
+        Ok(response) if response.status().is_success() => {
+            return AttemptOutcome {
+                status: DeliveryStatus::Succeeded,
+                status_code: Some(response.status().as_u16()),
+                error: None,
+                next_attempt_at: OffsetDateTime::now_utc(),
+            };
+        }
+        Ok(response) => (
+            Some(response.status().as_u16()),
+            Some(format!("Endpoint answered {}", response.status())),
+        ),
+        Err(err) => (None, Some(format!("Endpoint unreachable: {err}"))),
+    };
+
+    let attempts = delivery.attempts + 1;
+    let status = if attempts >= max_attempts() {
+        DeliveryStatus::Failed
+    } else {
+        DeliveryStatus::Pending
+    };
+    AttemptOutcome {
+        status,
+        status_code,
+        error,
+        next_attempt_at: OffsetDateTime::now_utc()
+            + time::Duration::seconds(retry_delay_secs(attempts)),
+    }
+}
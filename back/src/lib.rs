@@ -0,0 +1,259 @@
+use actix_session::{SessionMiddleware, storage::CookieSessionStore};
+use actix_web::{
+    HttpResponse, Responder,
+    cookie::Key,
+    middleware::{DefaultHeaders, from_fn},
+    web::{self, get},
+};
+use serde::Serialize;
+use sqlx::{MySql, Pool, mysql::MySqlPoolOptions};
+use std::sync::LazyLock;
+
+use crate::{
+    account::account_controller::{
+        create_invitation, create_token, download_export, get_export, get_phone, get_profile,
+        list_invitations, list_tokens, remove_avatar, remove_phone, request_deletion,
+        request_email_change, request_export, resend_phone_code, revoke_invitation, revoke_token,
+        set_avatar, set_phone, set_two_factor, update_profile, verify_phone,
+    },
+    admin::admin_controller::{
+        current_impersonation, export_audit_events, impersonate, list_audit_events,
+        list_corrupted_blobs, stop_impersonation,
+    },
+    auth::{
+        auth_controller::{
+            change_password, confirm_email_change, csrf_token, forgot, get_password_policy,
+            get_registration_policy, login, login_sms, logout, magic_link_login, refresh_token,
+            register, request_magic_link, restore_account, send_verification_email, validate,
+            verify,
+        },
+        middlewares::{
+            auth_middleware::{RequireAuth, Role},
+            csrf_middleware::csrf_middleware,
+        },
+    },
+    errors::json_error_handler,
+    files::files_controller::{
+        delete_file, download_file, get_file, list_files, upload_file, upload_raw_file,
+    },
+    middlewares::security_headers::SecurityHeaders,
+    nodes::nodes_controller::{
+        copy_node, create_folder, delete_node, get_node, list_children, place_file, resolve_path,
+        update_node,
+    },
+    organizations::organizations_controller::{
+        accept_organization_invitation, change_member_role, create_organization, invite_member,
+        list_members, list_organizations, remove_member, switch_organization,
+    },
+    services::openapi_service::{openapi_json, openapi_yaml},
+    uploads::uploads_controller::{
+        TUS_RESUMABLE, TUS_VERSION, append_upload, create_upload, terminate_upload, tus_options,
+        upload_status,
+    },
+    users::users_controller::{get_avatar, get_user},
+    utils::image_utils::AVATAR_MAX_BYTES,
+    webhooks::webhook_controller::{
+        create_webhook, delete_webhook, get_webhook_delivery, list_webhook_deliveries,
+        list_webhooks, replay_webhook_delivery,
+    },
+};
+
+pub mod account;
+pub mod admin;
+pub mod auth;
+pub mod constants;
+pub mod errors;
+pub mod files;
+pub mod jobs;
+pub mod middlewares;
+pub mod models;
+pub mod nodes;
+pub mod organizations;
+pub mod services;
+pub mod shared;
+pub mod storage;
+pub mod uploads;
+pub mod users;
+pub mod utils;
+pub mod webhooks;
+
+pub const SECRET: &[u8; 44] = b"laOOVyHM6s3IcgDAty1O7AXAdRZR6eaaQi65v3qhVRg=";
+pub const APP_URL: &str = "https://localhost";
+
+#[derive(Serialize)]
+struct PingResponse {
+    message: String,
+}
+
+pub static DB_POOL: LazyLock<Pool<MySql>> = std::sync::LazyLock::new(|| {
+    MySqlPoolOptions::new()
+        .max_connections(6)
+        .connect_lazy(&std::env::var("DATABASE_URL").expect("DATABASE_URL not define !"))
+        .expect("Can't connect to DB")
+});
+
+pub fn session_middleware() -> SessionMiddleware<CookieSessionStore> {
+    SessionMiddleware::builder(
+        CookieSessionStore::default(),
+        Key::from(b"THE VERY VERY VERY SECURE KEY TO ALLOW A VERY VERY VERY SECRET SESSION"),
+    )
+    .cookie_secure(true) // HTTPS seulement
+    .cookie_http_only(true) // pas accessible via JS
+    .cookie_same_site(actix_web::cookie::SameSite::Strict) // protège contre CSRF basique
+    .session_lifecycle(
+        actix_session::config::PersistentSession::default().session_ttl(time::Duration::days(7)), // 7 jours
+    )
+    .build()
+}
+
+/// Every route of the API, shared by `main` and the integration tests.
+/// Each scope sets its own security headers, CORS is applied to the whole `App`
+/// by `middlewares::cors::cors_middleware`.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::JsonConfig::default().error_handler(json_error_handler))
+        .service(
+            web::scope("/auth")
+                .wrap(from_fn(csrf_middleware))
+                .wrap(SecurityHeaders::api().build())
+                .service(login)
+                .service(login_sms)
+                .service(request_magic_link)
+                .service(magic_link_login)
+                .service(register)
+                .service(verify)
+                .service(refresh_token)
+                .service(logout)
+                .service(csrf_token)
+                .service(send_verification_email)
+                .service(forgot)
+                .service(validate)
+                .service(change_password)
+                .service(restore_account)
+                .service(confirm_email_change)
+                .service(get_password_policy)
+                .service(get_registration_policy),
+        )
+        .service(
+            web::scope("/account")
+                .wrap(RequireAuth::connected())
+                .wrap(SecurityHeaders::api().build())
+                // raw bodies are only read by the avatar upload
+                .app_data(web::PayloadConfig::new(AVATAR_MAX_BYTES))
+                .service(get_profile)
+                .service(update_profile)
+                .service(set_avatar)
+                .service(remove_avatar)
+                .service(get_phone)
+                .service(set_phone)
+                .service(remove_phone)
+                .service(resend_phone_code)
+                .service(verify_phone)
+                .service(set_two_factor)
+                .service(create_token)
+                .service(list_tokens)
+                .service(revoke_token)
+                .service(create_invitation)
+                .service(list_invitations)
+                .service(revoke_invitation)
+                .service(request_email_change)
+                .service(request_deletion)
+                .service(request_export)
+                .service(get_export)
+                .service(download_export),
+        )
+        .service(
+            web::scope("/admin")
+                .wrap(RequireAuth::role(Role::Admin))
+                .wrap(SecurityHeaders::api().build())
+                .service(impersonate)
+                .service(export_audit_events)
+                .service(list_audit_events)
+                .service(list_corrupted_blobs)
+                .service(create_webhook)
+                .service(list_webhooks)
+                .service(delete_webhook)
+                .service(list_webhook_deliveries)
+                .service(get_webhook_delivery)
+                .service(replay_webhook_delivery),
+        )
+        .service(
+            web::scope("/impersonation")
+                .wrap(RequireAuth::connected())
+                .wrap(SecurityHeaders::api().build())
+                .service(current_impersonation)
+                .service(stop_impersonation),
+        )
+        .service(
+            web::scope("/files")
+                .wrap(RequireAuth::connected())
+                .wrap(SecurityHeaders::download().build())
+                .service(upload_file)
+                .service(upload_raw_file)
+                .service(list_files)
+                .service(get_file)
+                .service(download_file)
+                .service(delete_file),
+        )
+        .service(
+            web::scope("/uploads")
+                .wrap(DefaultHeaders::new().add((TUS_RESUMABLE, TUS_VERSION)))
+                .wrap(RequireAuth::connected())
+                .wrap(SecurityHeaders::api().build())
+                .service(tus_options)
+                .service(create_upload)
+                .service(upload_status)
+                .service(append_upload)
+                .service(terminate_upload),
+        )
+        .service(
+            web::scope("/nodes")
+                .wrap(RequireAuth::connected())
+                .wrap(SecurityHeaders::api().build())
+                .service(create_folder)
+                .service(place_file)
+                .service(list_children)
+                .service(resolve_path)
+                .service(get_node)
+                .service(update_node)
+                .service(copy_node)
+                .service(delete_node),
+        )
+        .service(
+            web::scope("/orgs")
+                .wrap(RequireAuth::connected())
+                .wrap(SecurityHeaders::api().build())
+                .service(create_organization)
+                .service(list_organizations)
+                .service(accept_organization_invitation)
+                .service(list_members)
+                .service(invite_member)
+                .service(change_member_role)
+                .service(remove_member)
+                .service(switch_organization),
+        )
+        .service(
+            web::scope("/users")
+                .wrap(RequireAuth::connected())
+                .wrap(SecurityHeaders::api().build())
+                .service(get_user),
+        )
+        .service(
+            web::scope("/avatars")
+                .wrap(SecurityHeaders::download().build())
+                .service(get_avatar),
+        )
+        .service(openapi_yaml)
+        .service(openapi_json)
+        .service(
+            web::scope("/public")
+                .wrap(SecurityHeaders::public().build())
+                .route("/ping", get().to(handle_ping)),
+        );
+}
+
+async fn handle_ping() -> impl Responder {
+    let response = PingResponse {
+        message: "pong".to_string(),
+    };
+    HttpResponse::Ok().json(response)
+}
@@ -1,46 +1,15 @@
-use actix_session::{SessionMiddleware, storage::CookieSessionStore};
-use actix_web::{
-    App, HttpResponse, HttpServer, Responder,
-    cookie::Key,
-    web::{self, get, service},
-};
-use serde::Serialize;
-use sqlx::{MySql, Pool, mysql::MySqlPoolOptions};
-use std::sync::LazyLock;
-
-use crate::{
-    auth::{
-        auth_controller::{
-            login, logout, refresh_token, register, send_verification_email, verify,
-        },
-        auth_models::{claims::Claims, token::TokenAble},
-    },
-    services::openapi_service::openapi_yaml,
+use actix_web::{App, HttpServer};
+
+use back::{
+    DB_POOL,
+    auth::auth_models::{claims::Claims, token::TokenAble},
+    configure,
+    jobs::{account_purge, blob_gc, blob_scrub, data_export, upload_cleanup, webhook_worker},
+    middlewares::cors::cors_middleware,
+    session_middleware,
     utils::redis_utils::init_redis_pool,
 };
 
-pub mod auth;
-pub mod constants;
-pub mod errors;
-pub mod models;
-pub mod services;
-pub mod shared;
-pub mod utils;
-
-pub const SECRET: &[u8; 44] = b"laOOVyHM6s3IcgDAty1O7AXAdRZR6eaaQi65v3qhVRg=";
-pub const APP_URL: &str = "https://localhost";
-
-#[derive(Serialize)]
-struct PingResponse {
-    message: String,
-}
-
-static DB_POOL: LazyLock<Pool<MySql>> = std::sync::LazyLock::new(|| {
-    MySqlPoolOptions::new()
-        .max_connections(6)
-        .connect_lazy(&std::env::var("DATABASE_URL").expect("DATABASE_URL not define !"))
-        .expect("Can't connect to DB")
-});
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     let rows = sqlx::query("SHOW TABLES")
@@ -68,44 +37,20 @@ async fn main() -> std::io::Result<()> {
         );
     }
 
+    actix_rt::spawn(webhook_worker::run());
+    actix_rt::spawn(account_purge::run());
+    actix_rt::spawn(data_export::run());
+    actix_rt::spawn(upload_cleanup::run());
+    actix_rt::spawn(blob_gc::run());
+    actix_rt::spawn(blob_scrub::run());
+
     HttpServer::new(move || {
         App::new()
-            .wrap(
-                SessionMiddleware::builder(
-                    CookieSessionStore::default(),
-                    Key::from(
-                        b"THE VERY VERY VERY SECURE KEY TO ALLOW A VERY VERY VERY SECRET SESSION",
-                    ),
-                )
-                .cookie_secure(true) // HTTPS seulement
-                .cookie_http_only(true) // pas accessible via JS
-                .cookie_same_site(actix_web::cookie::SameSite::Strict) // protège contre CSRF basique
-                .session_lifecycle(
-                    actix_session::config::PersistentSession::default()
-                        .session_ttl(time::Duration::days(7)), // 7 jours
-                )
-                .build(),
-            )
-            .service(
-                web::scope("/auth")
-                    .service(login)
-                    .service(register)
-                    .service(verify)
-                    .service(refresh_token)
-                    .service(logout)
-                    .service(send_verification_email),
-            )
-            .service(openapi_yaml)
-            .service(web::scope("/public").route("/ping", get().to(handle_ping)))
+            .wrap(session_middleware())
+            .wrap(cors_middleware())
+            .configure(configure)
     })
     .bind(("0.0.0.0", 8080))?
     .run()
     .await
 }
-
-async fn handle_ping() -> impl Responder {
-    let response = PingResponse {
-        message: "pong".to_string(),
-    };
-    HttpResponse::Ok().json(response)
-}
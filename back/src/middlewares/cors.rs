@@ -0,0 +1,114 @@
+use std::sync::LazyLock;
+
+use actix_cors::Cors;
+use actix_web::http::{Method, header};
+
+use crate::{
+    auth::auth_models::csrf_token::CSRF_HEADER,
+    uploads::uploads_controller::{
+        TUS_RESUMABLE, UPLOAD_CHECKSUM, UPLOAD_EXPIRES, UPLOAD_FILE_ID, UPLOAD_LENGTH,
+        UPLOAD_METADATA, UPLOAD_OFFSET,
+    },
+    utils::env_utils::{env_flag, env_opt, env_or},
+};
+
+static CORS_CONFIG: LazyLock<CorsConfig> = LazyLock::new(CorsConfig::from_env);
+
+/// Read from `CORS_ALLOWED_ORIGINS` (comma separated, `*` for any), `CORS_ALLOW_CREDENTIALS`,
+/// `CORS_ALLOWED_METHODS` (comma separated) and `CORS_MAX_AGE` (seconds).
+/// Without allowed origins only same-origin requests work, as before.
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    pub allow_credentials: bool,
+    pub allowed_methods: Vec<Method>,
+    pub max_age: usize,
+}
+
+impl CorsConfig {
+    pub fn from_env() -> Self {
+        let allowed_methods = env_opt("CORS_ALLOWED_METHODS")
+            .map(|raw| {
+                raw.split(',')
+                    .filter_map(|method| method.trim().to_ascii_uppercase().parse().ok())
+                    .collect()
+            })
+            .unwrap_or_else(|| {
+                vec![
+                    Method::GET,
+                    Method::POST,
+                    Method::PUT,
+                    Method::PATCH,
+                    Method::DELETE,
+                    Method::HEAD,
+                ]
+            });
+        Self {
+            allowed_origins: split_list(env_opt("CORS_ALLOWED_ORIGINS")),
+            allow_credentials: env_flag("CORS_ALLOW_CREDENTIALS", true),
+            allowed_methods,
+            max_age: env_or("CORS_MAX_AGE", 3600),
+        }
+    }
+
+    pub fn middleware(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods(self.allowed_methods.clone())
+            .allowed_headers([
+                header::AUTHORIZATION.as_str(),
+                header::CONTENT_TYPE.as_str(),
+                header::IF_NONE_MATCH.as_str(),
+                header::RANGE.as_str(),
+                header::IF_RANGE.as_str(),
+                CSRF_HEADER,
+                TUS_RESUMABLE,
+                UPLOAD_LENGTH,
+                UPLOAD_OFFSET,
+                UPLOAD_METADATA,
+                UPLOAD_CHECKSUM,
+            ])
+            .expose_headers([
+                header::ETAG.as_str(),
+                header::LOCATION.as_str(),
+                header::CONTENT_RANGE.as_str(),
+                TUS_RESUMABLE,
+                UPLOAD_LENGTH,
+                UPLOAD_OFFSET,
+                UPLOAD_METADATA,
+                UPLOAD_EXPIRES,
+                UPLOAD_FILE_ID,
+            ])
+            .max_age(self.max_age);
+
+        let any_origin = self.allowed_origins.iter().any(|origin| origin == "*");
+        if any_origin {
+            cors = cors.allow_any_origin();
+        } else {
+            for origin in &self.allowed_origins {
+                cors = cors.allowed_origin(origin);
+            }
+        }
+        // the session cookie must never be readable from any site
+        if self.allow_credentials && !any_origin {
+            cors = cors.supports_credentials();
+        } else if self.allow_credentials {
+            eprintln!("CORS_ALLOW_CREDENTIALS ignored, CORS_ALLOWED_ORIGINS is `*`");
+        }
+        cors
+    }
+}
+
+fn split_list(raw: Option<String>) -> Vec<String> {
+    raw.map(|raw| {
+        raw.split(',')
+            .map(|item| item.trim().trim_end_matches('/').to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Must be the outermost middleware so preflight requests are answered first
+pub fn cors_middleware() -> Cors {
+    CORS_CONFIG.middleware()
+}
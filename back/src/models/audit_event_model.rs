@@ -0,0 +1,382 @@
+use actix_web::{FromRequest, HttpRequest, dev::Payload, http::header};
+use futures_util::future::{Ready, ready};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sqlx::{query, query_as, query_scalar};
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+use crate::{
+    DB_POOL,
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    services::openapi_service::ApiSchema,
+};
+
+const USER_AGENT_MAX_LENGTH: usize = 255;
+
+/// The string form is stored: never rename one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum AuditAction {
+    #[serde(rename = "auth.login")]
+    Login,
+    #[serde(rename = "auth.logout")]
+    Logout,
+    #[serde(rename = "auth.register")]
+    Register,
+    #[serde(rename = "auth.email_verification")]
+    EmailVerification,
+    #[serde(rename = "auth.password_reset")]
+    PasswordReset,
+    #[serde(rename = "impersonation.started")]
+    ImpersonationStarted,
+    #[serde(rename = "impersonation.stopped")]
+    ImpersonationStopped,
+    #[serde(rename = "audit.exported")]
+    AuditExported,
+    #[serde(rename = "account.deletion_requested")]
+    AccountDeletionRequested,
+    #[serde(rename = "account.restored")]
+    AccountRestored,
+    #[serde(rename = "account.deleted")]
+    AccountDeleted,
+    #[serde(rename = "account.export_requested")]
+    DataExportRequested,
+    #[serde(rename = "account.export_downloaded")]
+    DataExportDownloaded,
+    #[serde(rename = "account.email_changed")]
+    EmailChanged,
+}
+impl AuditAction {
+    pub const ALL: [AuditAction; 14] = [
+        AuditAction::Login,
+        AuditAction::Logout,
+        AuditAction::Register,
+        AuditAction::EmailVerification,
+        AuditAction::PasswordReset,
+        AuditAction::ImpersonationStarted,
+        AuditAction::ImpersonationStopped,
+        AuditAction::AuditExported,
+        AuditAction::AccountDeletionRequested,
+        AuditAction::AccountRestored,
+        AuditAction::AccountDeleted,
+        AuditAction::DataExportRequested,
+        AuditAction::DataExportDownloaded,
+        AuditAction::EmailChanged,
+    ];
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Login => "auth.login",
+            AuditAction::Logout => "auth.logout",
+            AuditAction::Register => "auth.register",
+            AuditAction::EmailVerification => "auth.email_verification",
+            AuditAction::PasswordReset => "auth.password_reset",
+            AuditAction::ImpersonationStarted => "impersonation.started",
+            AuditAction::ImpersonationStopped => "impersonation.stopped",
+            AuditAction::AuditExported => "audit.exported",
+            AuditAction::AccountDeletionRequested => "account.deletion_requested",
+            AuditAction::AccountRestored => "account.restored",
+            AuditAction::AccountDeleted => "account.deleted",
+            AuditAction::DataExportRequested => "account.export_requested",
+            AuditAction::DataExportDownloaded => "account.export_downloaded",
+            AuditAction::EmailChanged => "account.email_changed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+impl AuditOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOutcome::Success => "success",
+            AuditOutcome::Failure => "failure",
+        }
+    }
+}
+
+/// Caller address, as forwarded by the reverse proxy, and user agent
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+impl ClientInfo {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let user_agent = req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|agent| agent.chars().take(USER_AGENT_MAX_LENGTH).collect());
+        Self {
+            ip: req
+                .connection_info()
+                .realip_remote_addr()
+                .map(str::to_string),
+            user_agent,
+        }
+    }
+}
+impl FromRequest for ClientInfo {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(ClientInfo::from_request(req)))
+    }
+}
+
+/// Who did what to whose account, built then `record`ed:
+///
+/// `AuditEvent::new(action).actor(admin_id).subject(user_id).client(&client).record().await?`
+pub struct AuditEvent {
+    action: AuditAction,
+    outcome: AuditOutcome,
+    actor_id: Option<i32>,
+    subject_id: Option<i32>,
+    client: ClientInfo,
+    metadata: Option<Value>,
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction) -> Self {
+        Self {
+            action,
+            outcome: AuditOutcome::Success,
+            actor_id: None,
+            subject_id: None,
+            client: ClientInfo::default(),
+            metadata: None,
+        }
+    }
+    pub fn failed(mut self) -> Self {
+        self.outcome = AuditOutcome::Failure;
+        self
+    }
+    pub fn actor(mut self, user_id: i32) -> Self {
+        self.actor_id = Some(user_id);
+        self
+    }
+    pub fn subject(mut self, user_id: i32) -> Self {
+        self.subject_id = Some(user_id);
+        self
+    }
+    /// A user acting on its own account
+    pub fn user(self, user_id: i32) -> Self {
+        self.actor(user_id).subject(user_id)
+    }
+    pub fn client(mut self, client: &ClientInfo) -> Self {
+        self.client = client.clone();
+        self
+    }
+    pub fn metadata(mut self, metadata: Value) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    pub async fn record(self) -> AppResult<()> {
+        let metadata = self.metadata.map(|m| m.to_string());
+        query!(
+            r#"
+            INSERT INTO audit_events
+                (action, outcome, actor_id, subject_id, ip, user_agent, metadata)
+            VALUES (?,?,?,?,?,?,?)
+            "#,
+            self.action.as_str(),
+            self.outcome.as_str(),
+            self.actor_id,
+            self.subject_id,
+            self.client.ip,
+            self.client.user_agent,
+            metadata
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Query string of the admin listing and export, every filter is optional
+#[derive(Deserialize, Default)]
+pub struct AuditFilter {
+    pub action: Option<String>,
+    pub outcome: Option<AuditOutcome>,
+    pub actor_id: Option<i32>,
+    pub subject_id: Option<i32>,
+    /// UNIX timestamps, `until` excluded
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+impl AuditFilter {
+    fn bounds(&self) -> AppResult<(Option<OffsetDateTime>, Option<OffsetDateTime>)> {
+        let parse = |field: &str, timestamp: Option<i64>| {
+            timestamp
+                .map(OffsetDateTime::from_unix_timestamp)
+                .transpose()
+                .map_err(|_| AppError::invalid_field(field, ErrorCode::ValidationFailed))
+        };
+        Ok((parse("since", self.since)?, parse("until", self.until)?))
+    }
+}
+
+/// A stored event, as listed and exported
+#[derive(Serialize)]
+pub struct AuditRecord {
+    pub id: i64,
+    pub action: String,
+    pub outcome: String,
+    pub actor_id: Option<i32>,
+    pub subject_id: Option<i32>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub metadata: Option<Value>,
+    /// UNIX timestamp
+    pub created_at: i64,
+}
+
+struct AuditRow {
+    id: i64,
+    action: String,
+    outcome: String,
+    actor_id: Option<i32>,
+    subject_id: Option<i32>,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    metadata: Option<String>,
+    created_at: PrimitiveDateTime,
+}
+fn unix(date: PrimitiveDateTime) -> i64 {
+    date.assume_utc().unix_timestamp()
+}
+
+impl From<AuditRow> for AuditRecord {
+    fn from(row: AuditRow) -> Self {
+        Self {
+            id: row.id,
+            action: row.action,
+            outcome: row.outcome,
+            actor_id: row.actor_id,
+            subject_id: row.subject_id,
+            ip: row.ip,
+            user_agent: row.user_agent,
+            metadata: row.metadata.and_then(|raw| serde_json::from_str(&raw).ok()),
+            created_at: unix(row.created_at),
+        }
+    }
+}
+
+impl AuditRecord {
+    /// Newest first
+    pub async fn search(filter: &AuditFilter, limit: u64, offset: u64) -> AppResult<Vec<Self>> {
+        let (since, until) = filter.bounds()?;
+        let outcome = filter.outcome.as_ref().map(AuditOutcome::as_str);
+        let rows = query_as!(
+            AuditRow,
+            r#"
+            SELECT id, action, outcome, actor_id, subject_id, ip, user_agent, metadata, created_at
+            FROM audit_events
+            WHERE (? IS NULL OR action = ?)
+                AND (? IS NULL OR outcome = ?)
+                AND (? IS NULL OR actor_id = ?)
+                AND (? IS NULL OR subject_id = ?)
+                AND (? IS NULL OR created_at >= ?)
+                AND (? IS NULL OR created_at < ?)
+            ORDER BY id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            filter.action,
+            filter.action,
+            outcome,
+            outcome,
+            filter.actor_id,
+            filter.actor_id,
+            filter.subject_id,
+            filter.subject_id,
+            since,
+            since,
+            until,
+            until,
+            limit,
+            offset
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(rows.into_iter().map(Self::from).collect())
+    }
+
+    pub async fn count(filter: &AuditFilter) -> AppResult<u64> {
+        let (since, until) = filter.bounds()?;
+        let outcome = filter.outcome.as_ref().map(AuditOutcome::as_str);
+        let total = query_scalar!(
+            r#"
+            SELECT COUNT(*) FROM audit_events
+            WHERE (? IS NULL OR action = ?)
+                AND (? IS NULL OR outcome = ?)
+                AND (? IS NULL OR actor_id = ?)
+                AND (? IS NULL OR subject_id = ?)
+                AND (? IS NULL OR created_at >= ?)
+                AND (? IS NULL OR created_at < ?)
+            "#,
+            filter.action,
+            filter.action,
+            outcome,
+            outcome,
+            filter.actor_id,
+            filter.actor_id,
+            filter.subject_id,
+            filter.subject_id,
+            since,
+            since,
+            until,
+            until
+        )
+        .fetch_one(&*DB_POOL)
+        .await?;
+        Ok(total as u64)
+    }
+
+    /// Every event done by or about `user_id`, oldest first
+    pub async fn for_user(user_id: i32) -> AppResult<Vec<Self>> {
+        let rows = query_as!(
+            AuditRow,
+            r#"
+            SELECT id, action, outcome, actor_id, subject_id, ip, user_agent, metadata, created_at
+            FROM audit_events
+            WHERE actor_id = ? OR subject_id = ?
+            ORDER BY id
+            "#,
+            user_id,
+            user_id
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(rows.into_iter().map(Self::from).collect())
+    }
+}
+
+impl ApiSchema for AuditRecord {
+    fn schema_name() -> String {
+        "AuditRecord".to_string()
+    }
+    fn schema() -> Value {
+        let actions: Vec<_> = AuditAction::ALL.iter().map(AuditAction::as_str).collect();
+        json!({
+            "type": "object",
+            "required": ["id", "action", "outcome", "created_at"],
+            "properties": {
+                "id": { "type": "integer", "format": "int64" },
+                "action": { "type": "string", "enum": actions },
+                "outcome": { "type": "string", "enum": ["success", "failure"] },
+                "actor_id": { "type": "integer", "format": "int32", "nullable": true },
+                "subject_id": { "type": "integer", "format": "int32", "nullable": true },
+                "ip": { "type": "string", "nullable": true },
+                "user_agent": { "type": "string", "nullable": true },
+                "metadata": { "type": "object", "nullable": true },
+                "created_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
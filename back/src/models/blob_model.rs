@@ -0,0 +1,285 @@
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::{MySqlConnection, query, query_as};
+use time::PrimitiveDateTime;
+
+use crate::{
+    DB_POOL,
+    errors::{AppError, AppResult},
+    services::openapi_service::ApiSchema,
+    storage::{StorageKey, StoredObject},
+};
+
+/// Content stored once for every file with the same SHA-256
+pub struct Blob {
+    pub sha256: String,
+    pub size: i64,
+    pub storage_key: StorageKey,
+}
+
+struct BlobRow {
+    sha256: String,
+    size: i64,
+    storage_key: String,
+}
+impl TryFrom<BlobRow> for Blob {
+    type Error = AppError;
+    /// Keys that do not parse were not written by us, they are never handed to the store
+    fn try_from(row: BlobRow) -> AppResult<Self> {
+        let storage_key = StorageKey::parse(&row.storage_key).ok_or(AppError::Internal(
+            format!("Blob {} has an invalid storage key", row.sha256),
+        ))?;
+        Ok(Self {
+            sha256: row.sha256,
+            size: row.size,
+            storage_key,
+        })
+    }
+}
+
+/// A blob the scrub found missing or altered, as shown to administrators
+#[derive(Serialize)]
+pub struct CorruptedBlob {
+    pub sha256: String,
+    pub size: i64,
+    /// Files pointing at it
+    pub ref_count: i32,
+    /// UNIX timestamp
+    pub corrupted_at: i64,
+}
+
+struct CorruptedRow {
+    sha256: String,
+    size: i64,
+    ref_count: i32,
+    corrupted_at: Option<PrimitiveDateTime>,
+}
+impl From<CorruptedRow> for CorruptedBlob {
+    fn from(row: CorruptedRow) -> Self {
+        Self {
+            sha256: row.sha256,
+            size: row.size,
+            ref_count: row.ref_count,
+            corrupted_at: row
+                .corrupted_at
+                .map(|date| date.assume_utc().unix_timestamp())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl Blob {
+    /// Points one more file at the content of `object`, stored as a new blob unless the
+    /// same bytes already were. Returns the key the content lives under: another one than
+    /// `object.key` when it was a duplicate, the caller then deletes its own copy.
+    /// A corrupted blob is healed with the fresh copy instead.
+    pub async fn acquire(
+        conn: &mut MySqlConnection,
+        object: &StoredObject,
+    ) -> AppResult<StorageKey> {
+        query!(
+            r#"
+            INSERT INTO blobs (sha256, size, storage_key, ref_count) VALUES (?,?,?,1)
+            ON DUPLICATE KEY UPDATE ref_count = ref_count + 1
+            "#,
+            object.sha256,
+            object.size as i64,
+            object.key.as_str()
+        )
+        .execute(&mut *conn)
+        .await?;
+        let current = query!(
+            "SELECT storage_key, corrupted_at FROM blobs WHERE sha256=? FOR UPDATE",
+            object.sha256
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        if current.storage_key == object.key.as_str() || current.corrupted_at.is_none() {
+            return StorageKey::parse(&current.storage_key).ok_or(AppError::Internal(format!(
+                "Blob {} has an invalid storage key",
+                object.sha256
+            )));
+        }
+
+        query!(
+            "INSERT IGNORE INTO blob_garbage (storage_key) VALUES (?)",
+            current.storage_key
+        )
+        .execute(&mut *conn)
+        .await?;
+        query!(
+            r#"
+            UPDATE blobs SET storage_key=?, corrupted_at=NULL, verified_at=NOW()
+            WHERE sha256=?
+            "#,
+            object.key.as_str(),
+            object.sha256
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(object.key.clone())
+    }
+
+    /// One more file points at an existing blob. Returns whether the blob exists
+    pub async fn share(conn: &mut MySqlConnection, sha256: &str) -> AppResult<bool> {
+        let response = query!(
+            "UPDATE blobs SET ref_count = ref_count + 1 WHERE sha256=?",
+            sha256
+        )
+        .execute(conn)
+        .await?;
+        Ok(response.rows_affected() == 1)
+    }
+
+    /// One file less points at the blob, the collector removes it once at 0
+    pub async fn release(conn: &mut MySqlConnection, sha256: &str) -> AppResult<()> {
+        query!(
+            "UPDATE blobs SET ref_count = ref_count - 1 WHERE sha256=? AND ref_count > 0",
+            sha256
+        )
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Releases the blobs of every file of the user, before its rows go by cascade
+    pub async fn release_files_of(conn: &mut MySqlConnection, user_id: i32) -> AppResult<()> {
+        query!(
+            r#"
+            UPDATE blobs b
+            JOIN (SELECT sha256, COUNT(*) AS files FROM files WHERE user_id=? GROUP BY sha256) f
+                ON f.sha256 = b.sha256
+            SET b.ref_count = GREATEST(b.ref_count - f.files, 0)
+            "#,
+            user_id
+        )
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Forgets up to `limit` blobs no file points at, their objects are queued for
+    /// deletion. Returns how many
+    pub async fn forget_unreferenced(limit: u64) -> AppResult<u64> {
+        let mut transaction = DB_POOL.begin().await?;
+        let rows = query!(
+            "SELECT sha256, storage_key FROM blobs WHERE ref_count=0 LIMIT ? FOR UPDATE",
+            limit
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        for row in &rows {
+            query!(
+                "INSERT IGNORE INTO blob_garbage (storage_key) VALUES (?)",
+                row.storage_key
+            )
+            .execute(&mut *transaction)
+            .await?;
+            query!("DELETE FROM blobs WHERE sha256=?", row.sha256)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(rows.len() as u64)
+    }
+
+    /// Objects waiting to be deleted from the store, oldest first
+    pub async fn garbage(limit: u64) -> AppResult<Vec<StorageKey>> {
+        let rows = query!(
+            "SELECT storage_key FROM blob_garbage ORDER BY created_at, storage_key LIMIT ?",
+            limit
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        let mut keys = Vec::with_capacity(rows.len());
+        for row in rows {
+            match StorageKey::parse(&row.storage_key) {
+                Some(key) => keys.push(key),
+                None => {
+                    eprintln!("Invalid key {} dropped from the garbage", row.storage_key);
+                    Self::forget_garbage(&row.storage_key).await?;
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    /// The object is gone from the store
+    pub async fn forget_garbage(storage_key: &str) -> AppResult<()> {
+        query!("DELETE FROM blob_garbage WHERE storage_key=?", storage_key)
+            .execute(&*DB_POOL)
+            .await?;
+        Ok(())
+    }
+
+    /// Blobs checked the longest ago, never checked ones first
+    pub async fn due_for_scrub(limit: u64) -> AppResult<Vec<Self>> {
+        let rows = query_as!(
+            BlobRow,
+            r#"
+            SELECT sha256, size, storage_key FROM blobs
+            ORDER BY verified_at IS NOT NULL, verified_at, sha256
+            LIMIT ?
+            "#,
+            limit
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        rows.into_iter().map(Self::try_from).collect()
+    }
+
+    /// Records the outcome of a check, a blob found intact again is no longer corrupted.
+    /// Ignored when the blob was healed with another object meanwhile
+    pub async fn record_scrub(&self, intact: bool) -> AppResult<()> {
+        query!(
+            r#"
+            UPDATE blobs
+            SET verified_at=NOW(), corrupted_at=IF(?, NULL, IFNULL(corrupted_at, NOW()))
+            WHERE sha256=? AND storage_key=?
+            "#,
+            intact,
+            self.sha256,
+            self.storage_key.as_str()
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
+    }
+
+    /// Every blob the scrub found corrupted, most recent first
+    pub async fn corrupted() -> AppResult<Vec<CorruptedBlob>> {
+        let rows = query_as!(
+            CorruptedRow,
+            r#"
+            SELECT sha256, size, ref_count, corrupted_at FROM blobs
+            WHERE corrupted_at IS NOT NULL
+            ORDER BY corrupted_at DESC, sha256
+            "#
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(rows.into_iter().map(CorruptedBlob::from).collect())
+    }
+}
+
+impl ApiSchema for CorruptedBlob {
+    fn schema_name() -> String {
+        "CorruptedBlob".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["sha256", "size", "ref_count", "corrupted_at"],
+            "properties": {
+                "sha256": { "type": "string", "description": "Hex SHA-256 the content should have" },
+                "size": { "type": "integer", "format": "int64", "description": "In bytes" },
+                "ref_count": {
+                    "type": "integer",
+                    "format": "int32",
+                    "description": "Files sharing this content"
+                },
+                "corrupted_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
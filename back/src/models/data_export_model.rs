@@ -0,0 +1,261 @@
+use serde::Serialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use sqlx::{query, query_as};
+use time::{Duration, OffsetDateTime, PrimitiveDateTime};
+
+use crate::{
+    DB_POOL,
+    errors::{AppError, AppResult},
+    services::openapi_service::ApiSchema,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportStatus {
+    Pending,
+    Ready,
+    Downloaded,
+    Expired,
+    Failed,
+}
+impl ExportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportStatus::Pending => "pending",
+            ExportStatus::Ready => "ready",
+            ExportStatus::Downloaded => "downloaded",
+            ExportStatus::Expired => "expired",
+            ExportStatus::Failed => "failed",
+        }
+    }
+}
+
+/// What the owner sees of an export, never the archive nor its download key
+#[derive(Serialize)]
+pub struct DataExport {
+    pub id: i32,
+    pub status: String,
+    /// UNIX timestamps
+    pub requested_at: i64,
+    pub ready_at: Option<i64>,
+    pub expires_at: Option<i64>,
+    pub downloaded_at: Option<i64>,
+}
+
+struct DataExportRow {
+    id: i32,
+    status: String,
+    requested_at: PrimitiveDateTime,
+    ready_at: Option<PrimitiveDateTime>,
+    expires_at: Option<PrimitiveDateTime>,
+    downloaded_at: Option<PrimitiveDateTime>,
+}
+fn unix(date: PrimitiveDateTime) -> i64 {
+    date.assume_utc().unix_timestamp()
+}
+impl From<DataExportRow> for DataExport {
+    fn from(row: DataExportRow) -> Self {
+        Self {
+            id: row.id,
+            status: row.status,
+            requested_at: unix(row.requested_at),
+            ready_at: row.ready_at.map(unix),
+            expires_at: row.expires_at.map(unix),
+            downloaded_at: row.downloaded_at.map(unix),
+        }
+    }
+}
+
+/// Export claimed by the worker, to build for `user_id`
+pub struct PendingExport {
+    pub id: i32,
+    pub user_id: i32,
+}
+
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+impl DataExport {
+    pub async fn create(user_id: i32) -> AppResult<Self> {
+        let response = query!("INSERT INTO data_exports (user_id) VALUES (?)", user_id)
+            .execute(&*DB_POOL)
+            .await?;
+        let id = response.last_insert_id() as i32;
+        Self::latest(user_id)
+            .await?
+            .filter(|export| export.id == id)
+            .ok_or(AppError::Internal(format!(
+                "Data export {id} vanished after its creation"
+            )))
+    }
+
+    pub async fn latest(user_id: i32) -> AppResult<Option<Self>> {
+        let maybe_row = query_as!(
+            DataExportRow,
+            r#"
+            SELECT id, status, requested_at, ready_at, expires_at, downloaded_at
+            FROM data_exports WHERE user_id=?
+            ORDER BY id DESC LIMIT 1
+            "#,
+            user_id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_row.map(Self::from))
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.status == ExportStatus::Pending.as_str()
+    }
+
+    /// Leases up to `limit` pending exports, a crashed worker leaves them to the next one
+    pub async fn claim_pending(limit: u64, lease_secs: i64) -> AppResult<Vec<PendingExport>> {
+        let now = OffsetDateTime::now_utc();
+        let leased_until = now + Duration::seconds(lease_secs);
+        let due = query_as!(
+            PendingExport,
+            r#"
+            SELECT id, user_id FROM data_exports
+            WHERE status = 'pending' AND build_after <= ?
+            ORDER BY id
+            LIMIT ?
+            "#,
+            now,
+            limit
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+
+        let mut claimed = Vec::with_capacity(due.len());
+        for export in due {
+            let response = query!(
+                r#"
+                UPDATE data_exports SET build_after = ?
+                WHERE id=? AND status = 'pending' AND build_after <= ?
+                "#,
+                leased_until,
+                export.id,
+                now
+            )
+            .execute(&*DB_POOL)
+            .await?;
+            // taken by another worker meanwhile
+            if response.rows_affected() == 1 {
+                claimed.push(export);
+            }
+        }
+        Ok(claimed)
+    }
+
+    /// Stores the archive, downloadable with `key` until `expires_at`
+    pub async fn mark_ready(
+        id: i32,
+        archive: &[u8],
+        key: &str,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<()> {
+        query!(
+            r#"
+            UPDATE data_exports
+            SET status = 'ready', archive = ?, download_key_hash = ?, ready_at = ?, expires_at = ?
+            WHERE id=? AND status = 'pending'
+            "#,
+            archive,
+            hash_key(key),
+            OffsetDateTime::now_utc(),
+            expires_at,
+            id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn mark_failed(id: i32) -> AppResult<()> {
+        query!(
+            "UPDATE data_exports SET status = 'failed' WHERE id=? AND status = 'pending'",
+            id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
+    }
+
+    /// Hands the archive out once: `None` when the key is unknown, used or expired
+    pub async fn take_archive(user_id: i32, key: &str) -> AppResult<Option<Vec<u8>>> {
+        let now = OffsetDateTime::now_utc();
+        let mut transaction = DB_POOL.begin().await?;
+        let maybe_archive = query!(
+            r#"
+            SELECT id, archive FROM data_exports
+            WHERE user_id=? AND download_key_hash=? AND status = 'ready' AND expires_at > ?
+            LIMIT 1 FOR UPDATE
+            "#,
+            user_id,
+            hash_key(key),
+            now
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+        let Some(row) = maybe_archive else {
+            return Ok(None);
+        };
+        query!(
+            r#"
+            UPDATE data_exports
+            SET status = 'downloaded', archive = NULL, download_key_hash = NULL, downloaded_at = ?
+            WHERE id=?
+            "#,
+            now,
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(row.archive)
+    }
+
+    /// Drops the archives nobody downloaded in time, returns how many
+    pub async fn expire(now: OffsetDateTime) -> AppResult<u64> {
+        let response = query!(
+            r#"
+            UPDATE data_exports
+            SET status = 'expired', archive = NULL, download_key_hash = NULL
+            WHERE status = 'ready' AND expires_at <= ?
+            "#,
+            now
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(response.rows_affected())
+    }
+}
+
+impl ApiSchema for DataExport {
+    fn schema_name() -> String {
+        "DataExport".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "status", "requested_at"],
+            "properties": {
+                "id": { "type": "integer", "format": "int32" },
+                "status": {
+                    "type": "string",
+                    "enum": ["pending", "ready", "downloaded", "expired", "failed"]
+                },
+                "requested_at": { "type": "integer", "format": "int64" },
+                "ready_at": { "type": "integer", "format": "int64", "nullable": true },
+                "expires_at": {
+                    "type": "integer",
+                    "format": "int64",
+                    "nullable": true,
+                    "description": "End of validity of the mailed download link"
+                },
+                "downloaded_at": { "type": "integer", "format": "int64", "nullable": true }
+            }
+        })
+    }
+}
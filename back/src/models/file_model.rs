@@ -0,0 +1,232 @@
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::{MySqlConnection, query, query_as, query_scalar};
+use time::PrimitiveDateTime;
+
+use crate::{
+    DB_POOL,
+    errors::{AppError, AppResult},
+    models::blob_model::Blob,
+    services::openapi_service::ApiSchema,
+    storage::{StorageKey, StoredObject},
+};
+
+/// Metadata of an uploaded file, as its owner sees it
+#[derive(Serialize)]
+pub struct StoredFile {
+    pub id: i32,
+    pub name: String,
+    pub content_type: String,
+    pub size: i64,
+    /// Hex SHA-256 of the content
+    pub sha256: String,
+    /// UNIX timestamp
+    pub created_at: i64,
+}
+
+struct FileRow {
+    id: i32,
+    name: String,
+    content_type: String,
+    size: i64,
+    sha256: String,
+    created_at: PrimitiveDateTime,
+}
+fn unix(date: PrimitiveDateTime) -> i64 {
+    date.assume_utc().unix_timestamp()
+}
+impl From<FileRow> for StoredFile {
+    fn from(row: FileRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            content_type: row.content_type,
+            size: row.size,
+            sha256: row.sha256,
+            created_at: unix(row.created_at),
+        }
+    }
+}
+
+/// Keys that do not parse were not written by us, they are never handed to the store
+fn parse_key(id: i32, raw: &str) -> AppResult<StorageKey> {
+    StorageKey::parse(raw).ok_or(AppError::Internal(format!(
+        "File {id} has an invalid storage key"
+    )))
+}
+
+impl StoredFile {
+    /// Records the file with its content in a blob. Returns the key the content lives
+    /// under, another one than `object.key` when the same bytes were already stored.
+    pub async fn create(
+        user_id: i32,
+        name: &str,
+        content_type: &str,
+        object: &StoredObject,
+    ) -> AppResult<(Self, StorageKey)> {
+        let mut transaction = DB_POOL.begin().await?;
+        let key = Blob::acquire(&mut transaction, object).await?;
+        let response = query!(
+            r#"
+            INSERT INTO files (user_id, name, content_type, size, sha256)
+            VALUES (?,?,?,?,?)
+            "#,
+            user_id,
+            name,
+            content_type,
+            object.size as i64,
+            object.sha256
+        )
+        .execute(&mut *transaction)
+        .await?;
+        // read before the commit: past it, the caller must not delete the content anymore
+        let row = query_as!(
+            FileRow,
+            r#"
+            SELECT id, name, content_type, size, sha256, created_at
+            FROM files WHERE id=? LIMIT 1
+            "#,
+            response.last_insert_id() as i32
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok((Self::from(row), key))
+    }
+
+    /// A new file of the user with the content of `id`, both sharing the same blob.
+    /// Runs on the caller's transaction, returns the id of the copy. `None` when the
+    /// file does not exist or belongs to someone else
+    pub async fn duplicate(
+        conn: &mut MySqlConnection,
+        user_id: i32,
+        id: i32,
+        name: &str,
+    ) -> AppResult<Option<i32>> {
+        // kept until the blob is shared
+        let Some(source) = query!(
+            "SELECT content_type, size, sha256 FROM files WHERE id=? AND user_id=? FOR UPDATE",
+            id,
+            user_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        else {
+            return Ok(None);
+        };
+        if !Blob::share(conn, &source.sha256).await? {
+            return Err(AppError::Internal(format!("Blob of file {id} missing")));
+        }
+        let response = query!(
+            r#"
+            INSERT INTO files (user_id, name, content_type, size, sha256)
+            VALUES (?,?,?,?,?)
+            "#,
+            user_id,
+            name,
+            source.content_type,
+            source.size,
+            source.sha256
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(Some(response.last_insert_id() as i32))
+    }
+
+    /// `None` when the file does not exist or belongs to someone else
+    pub async fn get(user_id: i32, id: i32) -> AppResult<Option<Self>> {
+        let maybe_row = query_as!(
+            FileRow,
+            r#"
+            SELECT id, name, content_type, size, sha256, created_at
+            FROM files WHERE id=? AND user_id=? LIMIT 1
+            "#,
+            id,
+            user_id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_row.map(Self::from))
+    }
+
+    /// The metadata with the key of the content in the store
+    pub async fn get_with_key(user_id: i32, id: i32) -> AppResult<Option<(Self, StorageKey)>> {
+        let Some(file) = Self::get(user_id, id).await? else {
+            return Ok(None);
+        };
+        let raw_key = query_scalar!(
+            r#"
+            SELECT b.storage_key FROM files f JOIN blobs b ON b.sha256 = f.sha256
+            WHERE f.id=? LIMIT 1
+            "#,
+            id
+        )
+        .fetch_one(&*DB_POOL)
+        .await?;
+        Ok(Some((file, parse_key(id, &raw_key)?)))
+    }
+
+    /// Newest first
+    pub async fn list(user_id: i32, limit: u64, offset: u64) -> AppResult<Vec<Self>> {
+        let rows = query_as!(
+            FileRow,
+            r#"
+            SELECT id, name, content_type, size, sha256, created_at
+            FROM files WHERE user_id=?
+            ORDER BY created_at DESC, id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            user_id,
+            limit,
+            offset
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(rows.into_iter().map(Self::from).collect())
+    }
+
+    pub async fn count(user_id: i32) -> AppResult<u64> {
+        let total = query_scalar!("SELECT COUNT(*) FROM files WHERE user_id=?", user_id)
+            .fetch_one(&*DB_POOL)
+            .await?;
+        Ok(total as u64)
+    }
+
+    /// Forgets the file, its blob is collected once no other file points at it
+    pub async fn delete(user_id: i32, id: i32) -> AppResult<Option<Self>> {
+        let Some(file) = Self::get(user_id, id).await? else {
+            return Ok(None);
+        };
+        let mut transaction = DB_POOL.begin().await?;
+        let response = query!("DELETE FROM files WHERE id=? AND user_id=?", id, user_id)
+            .execute(&mut *transaction)
+            .await?;
+        // deleted meanwhile, the other request released the blob
+        if response.rows_affected() == 0 {
+            return Ok(None);
+        }
+        Blob::release(&mut transaction, &file.sha256).await?;
+        transaction.commit().await?;
+        Ok(Some(file))
+    }
+}
+
+impl ApiSchema for StoredFile {
+    fn schema_name() -> String {
+        "File".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "name", "content_type", "size", "sha256", "created_at"],
+            "properties": {
+                "id": { "type": "integer", "format": "int32" },
+                "name": { "type": "string", "maxLength": 255, "example": "report.pdf" },
+                "content_type": { "type": "string", "example": "application/pdf" },
+                "size": { "type": "integer", "format": "int64", "description": "In bytes" },
+                "sha256": { "type": "string", "description": "Hex SHA-256 of the content" },
+                "created_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
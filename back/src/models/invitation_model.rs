@@ -0,0 +1,214 @@
+use password_hash::rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::{query, query_as};
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+use crate::{
+    DB_POOL,
+    auth::auth_models::email::Email,
+    errors::{AppError, AppResult},
+    services::openapi_service::ApiSchema,
+};
+
+/// Unambiguous when read aloud or typed from a mail: no 0/O nor 1/I
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LENGTH: usize = 16;
+
+#[derive(Serialize)]
+pub struct Invitation {
+    pub id: i32,
+    pub code: String,
+    pub email: Option<String>,
+    pub max_uses: i32,
+    pub uses: i32,
+    /// UNIX timestamps
+    pub expires_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+    pub created_at: i64,
+}
+
+struct InvitationRow {
+    id: i32,
+    code: String,
+    email: Option<String>,
+    max_uses: i32,
+    uses: i32,
+    expires_at: Option<PrimitiveDateTime>,
+    revoked_at: Option<PrimitiveDateTime>,
+    created_at: PrimitiveDateTime,
+}
+impl From<InvitationRow> for Invitation {
+    fn from(row: InvitationRow) -> Self {
+        let unix = |date: PrimitiveDateTime| date.assume_utc().unix_timestamp();
+        Self {
+            id: row.id,
+            code: row.code,
+            email: row.email,
+            max_uses: row.max_uses,
+            uses: row.uses,
+            expires_at: row.expires_at.map(unix),
+            revoked_at: row.revoked_at.map(unix),
+            created_at: unix(row.created_at),
+        }
+    }
+}
+
+/// Use taken by a registration, to give back if the account is not created
+pub struct ClaimedInvitation {
+    pub id: i32,
+    pub inviter_id: Option<i32>,
+}
+
+fn generate_code() -> String {
+    let mut bytes = [0u8; CODE_LENGTH];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+        .iter()
+        .map(|b| CODE_ALPHABET[*b as usize % CODE_ALPHABET.len()] as char)
+        .collect()
+}
+
+/// Codes are shown grouped (`ABCD-EFGH-...`) and typed in any case
+fn normalize_code(raw: &str) -> String {
+    raw.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+impl Invitation {
+    pub async fn create(
+        inviter_id: i32,
+        email: Option<&Email>,
+        max_uses: i32,
+        expires_at: Option<OffsetDateTime>,
+    ) -> AppResult<Self> {
+        let response = query!(
+            r#"
+            INSERT INTO invitations (code, inviter_id, email, email_canonical, max_uses, expires_at)
+            VALUES (?,?,?,?,?,?)
+            "#,
+            generate_code(),
+            inviter_id,
+            email.map(|email| email.as_ref()),
+            email.map(Email::canonical),
+            max_uses,
+            expires_at
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        let id = response.last_insert_id() as i32;
+        Self::get(inviter_id, id)
+            .await?
+            .ok_or(AppError::Internal(format!(
+                "Invitation {id} vanished after its creation"
+            )))
+    }
+
+    pub async fn get(inviter_id: i32, id: i32) -> AppResult<Option<Self>> {
+        let maybe_row = query_as!(
+            InvitationRow,
+            r#"
+            SELECT id, code, email, max_uses, uses, expires_at, revoked_at, created_at
+            FROM invitations WHERE id=? AND inviter_id=? LIMIT 1
+            "#,
+            id,
+            inviter_id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_row.map(Self::from))
+    }
+
+    pub async fn list(inviter_id: i32) -> AppResult<Vec<Self>> {
+        let rows = query_as!(
+            InvitationRow,
+            r#"
+            SELECT id, code, email, max_uses, uses, expires_at, revoked_at, created_at
+            FROM invitations WHERE inviter_id=?
+            ORDER BY created_at DESC, id DESC
+            "#,
+            inviter_id
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(rows.into_iter().map(Self::from).collect())
+    }
+
+    /// `None` when the invitation does not exist or was issued by someone else
+    pub async fn revoke(inviter_id: i32, id: i32) -> AppResult<Option<Self>> {
+        if Self::get(inviter_id, id).await?.is_none() {
+            return Ok(None);
+        }
+        query!(
+            "UPDATE invitations SET revoked_at = COALESCE(revoked_at, ?) WHERE id = ?",
+            OffsetDateTime::now_utc(),
+            id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Self::get(inviter_id, id).await
+    }
+
+    /// Atomically takes one use of a usable invitation valid for `email`
+    pub async fn claim(raw_code: &str, email: &Email) -> AppResult<Option<ClaimedInvitation>> {
+        let code = normalize_code(raw_code);
+        let response = query!(
+            r#"
+            UPDATE invitations SET uses = uses + 1
+            WHERE code = ? AND revoked_at IS NULL AND uses < max_uses
+                AND (expires_at IS NULL OR expires_at > ?)
+                AND (email_canonical IS NULL OR email_canonical = ?)
+            "#,
+            code,
+            OffsetDateTime::now_utc(),
+            email.canonical()
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        if response.rows_affected() == 0 {
+            return Ok(None);
+        }
+        let claimed = query_as!(
+            ClaimedInvitation,
+            "SELECT id, inviter_id FROM invitations WHERE code = ? LIMIT 1",
+            code
+        )
+        .fetch_one(&*DB_POOL)
+        .await?;
+        Ok(Some(claimed))
+    }
+
+    pub async fn release(claimed: &ClaimedInvitation) -> AppResult<()> {
+        query!(
+            "UPDATE invitations SET uses = uses - 1 WHERE id = ? AND uses > 0",
+            claimed.id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
+    }
+}
+
+impl ApiSchema for Invitation {
+    fn schema_name() -> String {
+        "Invitation".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "code", "max_uses", "uses", "created_at"],
+            "properties": {
+                "id": { "type": "integer", "format": "int32" },
+                "code": { "type": "string", "example": "K7QMXR2WPA9HTD4E" },
+                "email": { "type": "string", "format": "idn-email", "nullable": true },
+                "max_uses": { "type": "integer", "format": "int32" },
+                "uses": { "type": "integer", "format": "int32" },
+                "expires_at": { "type": "integer", "format": "int64", "nullable": true },
+                "revoked_at": { "type": "integer", "format": "int64", "nullable": true },
+                "created_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
@@ -1 +1,13 @@
+pub mod audit_event_model;
+pub mod blob_model;
+pub mod data_export_model;
+pub mod file_model;
+pub mod invitation_model;
+pub mod node_model;
+pub mod organization_invitation_model;
+pub mod organization_model;
+pub mod personal_access_token_model;
+pub mod profile_model;
 pub mod user_model;
+pub mod webhook_delivery_model;
+pub mod webhook_endpoint_model;
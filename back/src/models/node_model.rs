@@ -0,0 +1,604 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sqlx::{MySqlConnection, query, query_as, query_scalar};
+use time::PrimitiveDateTime;
+
+use crate::{
+    DB_POOL,
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    models::file_model::StoredFile,
+    services::openapi_service::ApiSchema,
+};
+
+/// The string form is stored: never rename one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    Folder,
+    File,
+}
+impl NodeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeKind::Folder => "folder",
+            NodeKind::File => "file",
+        }
+    }
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "folder" => Some(NodeKind::Folder),
+            "file" => Some(NodeKind::File),
+            _ => None,
+        }
+    }
+}
+
+/// Order of the children of a folder, folders always come first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeSort {
+    #[default]
+    Name,
+    CreatedAt,
+    UpdatedAt,
+    Size,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// A folder, or a file of `/files` placed in the tree
+#[derive(Serialize)]
+pub struct Node {
+    pub id: i32,
+    /// `None` at the root
+    pub parent_id: Option<i32>,
+    pub kind: NodeKind,
+    pub name: String,
+    /// Set for files only
+    pub file_id: Option<i32>,
+    pub size: Option<i64>,
+    pub content_type: Option<String>,
+    /// UNIX timestamp
+    pub created_at: i64,
+    /// UNIX timestamp
+    pub updated_at: i64,
+}
+
+struct NodeRow {
+    id: i32,
+    parent_id: Option<i32>,
+    kind: String,
+    name: String,
+    file_id: Option<i32>,
+    size: Option<i64>,
+    content_type: Option<String>,
+    created_at: PrimitiveDateTime,
+    updated_at: PrimitiveDateTime,
+}
+fn unix(date: PrimitiveDateTime) -> i64 {
+    date.assume_utc().unix_timestamp()
+}
+impl NodeRow {
+    /// Rows of an unknown kind are not shown
+    fn into_node(self) -> Option<Node> {
+        Some(Node {
+            id: self.id,
+            parent_id: self.parent_id,
+            kind: NodeKind::parse(&self.kind)?,
+            name: self.name,
+            file_id: self.file_id,
+            size: self.size,
+            content_type: self.content_type,
+            created_at: unix(self.created_at),
+            updated_at: unix(self.updated_at),
+        })
+    }
+}
+
+/// A node and its descendants, parents before their children
+pub struct SubtreeNode {
+    pub id: i32,
+    pub parent_id: Option<i32>,
+    pub kind: NodeKind,
+    pub name: String,
+    pub file_id: Option<i32>,
+}
+
+/// Where a node sits in the tree, as in `/docs/2026/report.pdf`
+#[derive(Serialize)]
+pub struct NodePath {
+    pub kind: NodeKind,
+    pub path: String,
+    /// Set for files only
+    pub file_id: Option<i32>,
+    /// UNIX timestamp
+    pub created_at: i64,
+}
+
+/// Tree changes of a user are serialized on its row: a move never races into a cycle
+/// and no node is created in a folder being deleted
+async fn lock_tree(conn: &mut MySqlConnection, user_id: i32) -> AppResult<()> {
+    query!("SELECT id FROM users WHERE id=? FOR UPDATE", user_id)
+        .fetch_optional(conn)
+        .await?;
+    Ok(())
+}
+
+/// The root, or a folder of the user
+async fn check_parent(
+    conn: &mut MySqlConnection,
+    user_id: i32,
+    parent_id: Option<i32>,
+) -> AppResult<()> {
+    let Some(parent_id) = parent_id else {
+        return Ok(());
+    };
+    let kind = query_scalar!(
+        "SELECT kind FROM nodes WHERE id=? AND user_id=?",
+        parent_id,
+        user_id
+    )
+    .fetch_optional(conn)
+    .await?;
+    match kind.as_deref().and_then(NodeKind::parse) {
+        Some(NodeKind::Folder) => Ok(()),
+        _ => Err(AppError::invalid_field(
+            "parent_id",
+            ErrorCode::NodeParentInvalid,
+        )),
+    }
+}
+
+/// The unique index on names is the only one a client can hit
+fn map_duplicate(err: sqlx::Error) -> AppError {
+    match &err {
+        sqlx::Error::Database(db_err)
+            if db_err
+                .downcast_ref::<sqlx::mysql::MySqlDatabaseError>()
+                .number()
+                == 1062 =>
+        {
+            AppError::Conflict(ErrorCode::NodeNameTaken)
+        }
+        _ => err.into(),
+    }
+}
+
+/// Inserts the node once its parent is checked, returns its id
+async fn insert_row(
+    conn: &mut MySqlConnection,
+    user_id: i32,
+    parent_id: Option<i32>,
+    kind: NodeKind,
+    name: &str,
+    file_id: Option<i32>,
+) -> AppResult<i32> {
+    let response = query!(
+        "INSERT INTO nodes (user_id, parent_id, kind, name, file_id) VALUES (?,?,?,?,?)",
+        user_id,
+        parent_id,
+        kind.as_str(),
+        name,
+        file_id
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(map_duplicate)?;
+    // downloads are named after the node
+    if let Some(file_id) = file_id {
+        query!("UPDATE files SET name=? WHERE id=?", name, file_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(response.last_insert_id() as i32)
+}
+
+impl Node {
+    /// `None` when the node does not exist or belongs to someone else
+    pub async fn get(user_id: i32, id: i32) -> AppResult<Option<Self>> {
+        let maybe_row = query_as!(
+            NodeRow,
+            r#"
+            SELECT n.id, n.parent_id, n.kind, n.name, n.file_id,
+                f.size AS "size?", f.content_type AS "content_type?", n.created_at, n.updated_at
+            FROM nodes n LEFT JOIN files f ON f.id = n.file_id
+            WHERE n.id=? AND n.user_id=? LIMIT 1
+            "#,
+            id,
+            user_id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_row.and_then(NodeRow::into_node))
+    }
+
+    /// The child of `parent_id` called `name`, regardless of case
+    pub async fn child(
+        user_id: i32,
+        parent_id: Option<i32>,
+        name: &str,
+    ) -> AppResult<Option<Self>> {
+        let maybe_row = query_as!(
+            NodeRow,
+            r#"
+            SELECT n.id, n.parent_id, n.kind, n.name, n.file_id,
+                f.size AS "size?", f.content_type AS "content_type?", n.created_at, n.updated_at
+            FROM nodes n LEFT JOIN files f ON f.id = n.file_id
+            WHERE n.user_id=? AND n.parent_key=IFNULL(?, 0) AND n.name=? LIMIT 1
+            "#,
+            user_id,
+            parent_id,
+            name
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_row.and_then(NodeRow::into_node))
+    }
+
+    /// Children of `parent_id`, or of the root, folders first
+    pub async fn list(
+        user_id: i32,
+        parent_id: Option<i32>,
+        sort: NodeSort,
+        order: SortOrder,
+        limit: u64,
+        offset: u64,
+    ) -> AppResult<Vec<Self>> {
+        let by = |key: NodeSort, direction: SortOrder| sort == key && order == direction;
+        let rows = query_as!(
+            NodeRow,
+            r#"
+            SELECT n.id, n.parent_id, n.kind, n.name, n.file_id,
+                f.size AS "size?", f.content_type AS "content_type?", n.created_at, n.updated_at
+            FROM nodes n LEFT JOIN files f ON f.id = n.file_id
+            WHERE n.user_id=? AND n.parent_key=IFNULL(?, 0)
+            ORDER BY n.kind = 'file',
+                CASE WHEN ? THEN n.name END ASC,
+                CASE WHEN ? THEN n.name END DESC,
+                CASE WHEN ? THEN n.created_at END ASC,
+                CASE WHEN ? THEN n.created_at END DESC,
+                CASE WHEN ? THEN n.updated_at END ASC,
+                CASE WHEN ? THEN n.updated_at END DESC,
+                CASE WHEN ? THEN f.size END ASC,
+                CASE WHEN ? THEN f.size END DESC,
+                n.name, n.id
+            LIMIT ? OFFSET ?
+            "#,
+            user_id,
+            parent_id,
+            by(NodeSort::Name, SortOrder::Asc),
+            by(NodeSort::Name, SortOrder::Desc),
+            by(NodeSort::CreatedAt, SortOrder::Asc),
+            by(NodeSort::CreatedAt, SortOrder::Desc),
+            by(NodeSort::UpdatedAt, SortOrder::Asc),
+            by(NodeSort::UpdatedAt, SortOrder::Desc),
+            by(NodeSort::Size, SortOrder::Asc),
+            by(NodeSort::Size, SortOrder::Desc),
+            limit,
+            offset
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(rows.into_iter().filter_map(NodeRow::into_node).collect())
+    }
+
+    pub async fn count(user_id: i32, parent_id: Option<i32>) -> AppResult<u64> {
+        let total = query_scalar!(
+            "SELECT COUNT(*) FROM nodes WHERE user_id=? AND parent_key=IFNULL(?, 0)",
+            user_id,
+            parent_id
+        )
+        .fetch_one(&*DB_POOL)
+        .await?;
+        Ok(total as u64)
+    }
+
+    pub async fn create_folder(
+        user_id: i32,
+        parent_id: Option<i32>,
+        name: &str,
+    ) -> AppResult<Self> {
+        Self::insert(user_id, parent_id, NodeKind::Folder, name, None).await
+    }
+
+    /// `file_id` must belong to the user, it is placed in the tree at most once and
+    /// takes the name of its node
+    pub async fn place_file(
+        user_id: i32,
+        parent_id: Option<i32>,
+        name: &str,
+        file_id: i32,
+    ) -> AppResult<Self> {
+        Self::insert(user_id, parent_id, NodeKind::File, name, Some(file_id)).await
+    }
+
+    async fn insert(
+        user_id: i32,
+        parent_id: Option<i32>,
+        kind: NodeKind,
+        name: &str,
+        file_id: Option<i32>,
+    ) -> AppResult<Self> {
+        let mut transaction = DB_POOL.begin().await?;
+        lock_tree(&mut transaction, user_id).await?;
+        check_parent(&mut transaction, user_id, parent_id).await?;
+        if let Some(file_id) = file_id {
+            let placed = query_scalar!("SELECT id FROM nodes WHERE file_id=?", file_id)
+                .fetch_optional(&mut *transaction)
+                .await?;
+            if placed.is_some() {
+                return Err(AppError::Conflict(ErrorCode::NodeFileAlreadyPlaced));
+            }
+        }
+        let id = insert_row(&mut transaction, user_id, parent_id, kind, name, file_id).await?;
+        transaction.commit().await?;
+        Self::get(user_id, id)
+            .await?
+            .ok_or(AppError::Internal(format!(
+                "Node {id} vanished after its creation"
+            )))
+    }
+
+    /// Renames and/or moves the node, `Some(None)` moves it to the root. A folder cannot
+    /// go inside itself. `None` when the node does not exist
+    pub async fn update(
+        user_id: i32,
+        id: i32,
+        name: Option<&str>,
+        parent_id: Option<Option<i32>>,
+    ) -> AppResult<Option<Self>> {
+        let mut transaction = DB_POOL.begin().await?;
+        lock_tree(&mut transaction, user_id).await?;
+        let Some(current) = query!(
+            "SELECT parent_id, name, file_id FROM nodes WHERE id=? AND user_id=?",
+            id,
+            user_id
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        if let Some(Some(target)) = parent_id {
+            check_parent(&mut transaction, user_id, Some(target)).await?;
+            let inside = query_scalar!(
+                r#"
+                WITH RECURSIVE ancestors (id, parent_id) AS (
+                    SELECT id, parent_id FROM nodes WHERE id=? AND user_id=?
+                    UNION ALL
+                    SELECT n.id, n.parent_id FROM nodes n JOIN ancestors a ON n.id = a.parent_id
+                )
+                SELECT COUNT(*) FROM ancestors WHERE id=?
+                "#,
+                target,
+                user_id,
+                id
+            )
+            .fetch_one(&mut *transaction)
+            .await?;
+            if inside > 0 {
+                return Err(AppError::invalid_field(
+                    "parent_id",
+                    ErrorCode::NodeParentInvalid,
+                ));
+            }
+        }
+
+        let parent_id = parent_id.unwrap_or(current.parent_id);
+        let name = name.unwrap_or(&current.name);
+        query!(
+            "UPDATE nodes SET parent_id=?, name=? WHERE id=?",
+            parent_id,
+            name,
+            id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(map_duplicate)?;
+        // downloads are named after the node
+        if let Some(file_id) = current.file_id {
+            query!("UPDATE files SET name=? WHERE id=?", name, file_id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Self::get(user_id, id).await
+    }
+
+    /// Empty when the node does not exist or belongs to someone else. Run on the
+    /// transaction holding the tree lock, the nodes cannot change meanwhile
+    pub async fn subtree(
+        conn: &mut MySqlConnection,
+        user_id: i32,
+        id: i32,
+    ) -> AppResult<Vec<SubtreeNode>> {
+        let rows = query!(
+            r#"
+            WITH RECURSIVE subtree (id, parent_id, kind, name, file_id, depth) AS (
+                SELECT id, parent_id, kind, name, file_id, 0 FROM nodes WHERE id=? AND user_id=?
+                UNION ALL
+                SELECT n.id, n.parent_id, n.kind, n.name, n.file_id, s.depth + 1
+                FROM nodes n JOIN subtree s ON n.parent_id = s.id
+            )
+            SELECT id AS "id!: i32", parent_id AS "parent_id: i32", kind AS "kind!: String",
+                name AS "name!: String", file_id AS "file_id: i32"
+            FROM subtree ORDER BY depth, id
+            "#,
+            id,
+            user_id
+        )
+        .fetch_all(conn)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                Some(SubtreeNode {
+                    id: row.id,
+                    parent_id: row.parent_id,
+                    kind: NodeKind::parse(&row.kind)?,
+                    name: row.name,
+                    file_id: row.file_id,
+                })
+            })
+            .collect())
+    }
+
+    /// Every node of the user with its full path, by path
+    pub async fn paths(user_id: i32) -> AppResult<Vec<NodePath>> {
+        let rows = query!(
+            r#"
+            WITH RECURSIVE tree (id, kind, path, file_id, created_at) AS (
+                SELECT id, kind, CAST(CONCAT('/', name) AS CHAR(65535)), file_id, created_at
+                FROM nodes WHERE user_id=? AND parent_id IS NULL
+                UNION ALL
+                SELECT n.id, n.kind, CONCAT(t.path, '/', n.name), n.file_id, n.created_at
+                FROM nodes n JOIN tree t ON n.parent_id = t.id
+            )
+            SELECT kind AS "kind!: String", path AS "path!: String", file_id AS "file_id: i32",
+                created_at AS "created_at!: PrimitiveDateTime"
+            FROM tree ORDER BY path
+            "#,
+            user_id
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                Some(NodePath {
+                    kind: NodeKind::parse(&row.kind)?,
+                    path: row.path,
+                    file_id: row.file_id,
+                    created_at: unix(row.created_at),
+                })
+            })
+            .collect())
+    }
+
+    /// Recreates the node and its descendants under `parent_id`, parents first, in one
+    /// transaction: a copy failing midway leaves nothing behind. Files are copied too,
+    /// sharing their content with the originals. `None` when the node does not exist
+    pub async fn copy(
+        user_id: i32,
+        id: i32,
+        parent_id: Option<i32>,
+        name: &str,
+        max_nodes: usize,
+    ) -> AppResult<Option<Self>> {
+        let mut transaction = DB_POOL.begin().await?;
+        lock_tree(&mut transaction, user_id).await?;
+        let nodes = Self::subtree(&mut transaction, user_id, id).await?;
+        if nodes.is_empty() {
+            return Ok(None);
+        }
+        if nodes.len() > max_nodes {
+            return Err(AppError::Validation(ErrorCode::NodeCopyTooLarge));
+        }
+        if parent_id.is_some_and(|parent_id| nodes.iter().any(|node| node.id == parent_id)) {
+            return Err(AppError::invalid_field(
+                "parent_id",
+                ErrorCode::NodeParentInvalid,
+            ));
+        }
+        check_parent(&mut transaction, user_id, parent_id).await?;
+
+        let mut copies: HashMap<i32, i32> = HashMap::new();
+        let mut root_id = None;
+        for node in &nodes {
+            let (parent_id, name) = match root_id {
+                None => (parent_id, name),
+                Some(_) => (
+                    node.parent_id.and_then(|id| copies.get(&id)).copied(),
+                    node.name.as_str(),
+                ),
+            };
+            let (kind, file_id) = match (node.kind, node.file_id) {
+                (NodeKind::File, Some(file_id)) => {
+                    let copy = StoredFile::duplicate(&mut transaction, user_id, file_id, name)
+                        .await?
+                        .ok_or(AppError::NotFound(ErrorCode::NotFound))?;
+                    (NodeKind::File, Some(copy))
+                }
+                _ => (NodeKind::Folder, None),
+            };
+            let copy_id =
+                insert_row(&mut transaction, user_id, parent_id, kind, name, file_id).await?;
+            copies.insert(node.id, copy_id);
+            root_id.get_or_insert(copy_id);
+        }
+        transaction.commit().await?;
+        match root_id {
+            Some(root_id) => Self::get(user_id, root_id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Deletes the node and its descendants, returns the files they held so the caller
+    /// deletes them too. `None` when the node does not exist
+    pub async fn delete(user_id: i32, id: i32) -> AppResult<Option<Vec<i32>>> {
+        let mut transaction = DB_POOL.begin().await?;
+        lock_tree(&mut transaction, user_id).await?;
+        let nodes = Self::subtree(&mut transaction, user_id, id).await?;
+        if nodes.is_empty() {
+            return Ok(None);
+        }
+        // children first, no row ever points to a deleted parent
+        for node in nodes.iter().rev() {
+            query!("DELETE FROM nodes WHERE id=?", node.id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(Some(nodes.iter().filter_map(|node| node.file_id).collect()))
+    }
+}
+
+impl ApiSchema for Node {
+    fn schema_name() -> String {
+        "Node".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": [
+                "id", "parent_id", "kind", "name", "file_id", "size", "content_type",
+                "created_at", "updated_at"
+            ],
+            "properties": {
+                "id": { "type": "integer", "format": "int32" },
+                "parent_id": {
+                    "type": "integer",
+                    "format": "int32",
+                    "nullable": true,
+                    "description": "`null` at the root"
+                },
+                "kind": { "type": "string", "enum": ["folder", "file"] },
+                "name": {
+                    "type": "string",
+                    "maxLength": 255,
+                    "description": "Unique in its folder regardless of case"
+                },
+                "file_id": {
+                    "type": "integer",
+                    "format": "int32",
+                    "nullable": true,
+                    "description": "The file in `/files`, `null` for folders"
+                },
+                "size": { "type": "integer", "format": "int64", "nullable": true },
+                "content_type": { "type": "string", "nullable": true },
+                "created_at": { "type": "integer", "format": "int64" },
+                "updated_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
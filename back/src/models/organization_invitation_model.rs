@@ -0,0 +1,172 @@
+use password_hash::rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use sqlx::{query, query_as};
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+use crate::{
+    DB_POOL,
+    auth::auth_models::email::Email,
+    errors::{AppError, AppResult},
+    models::organization_model::OrgRole,
+    services::openapi_service::ApiSchema,
+};
+
+/// What the organization admins see of an invitation, never its key
+#[derive(Serialize)]
+pub struct OrganizationInvitation {
+    pub id: i32,
+    pub org_id: i32,
+    pub email: String,
+    pub role: OrgRole,
+    /// UNIX timestamps
+    pub expires_at: i64,
+    pub accepted_at: Option<i64>,
+    pub created_at: i64,
+}
+
+struct OrganizationInvitationRow {
+    id: i32,
+    org_id: i32,
+    email: String,
+    role: String,
+    expires_at: PrimitiveDateTime,
+    accepted_at: Option<PrimitiveDateTime>,
+    created_at: PrimitiveDateTime,
+}
+fn unix(date: PrimitiveDateTime) -> i64 {
+    date.assume_utc().unix_timestamp()
+}
+impl OrganizationInvitationRow {
+    fn into_invitation(self) -> Option<OrganizationInvitation> {
+        Some(OrganizationInvitation {
+            id: self.id,
+            org_id: self.org_id,
+            email: self.email,
+            role: OrgRole::parse(&self.role)?,
+            expires_at: unix(self.expires_at),
+            accepted_at: self.accepted_at.map(unix),
+            created_at: unix(self.created_at),
+        })
+    }
+}
+
+/// Membership granted by an accepted invitation
+pub struct AcceptedInvitation {
+    pub org_id: i32,
+    pub role: OrgRole,
+}
+
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+impl OrganizationInvitation {
+    /// Returns the stored invitation with the plain key to mail, which cannot be retrieved afterwards
+    pub async fn create(
+        org_id: i32,
+        email: &Email,
+        role: OrgRole,
+        invited_by: i32,
+        expires_at: OffsetDateTime,
+    ) -> AppResult<(Self, String)> {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let key = hex::encode(bytes);
+
+        let response = query!(
+            r#"
+            INSERT INTO organization_invitations
+                (org_id, email, email_canonical, role, key_hash, invited_by, expires_at)
+            VALUES (?,?,?,?,?,?,?)
+            "#,
+            org_id,
+            email.as_ref(),
+            email.canonical(),
+            role.as_str(),
+            hash_key(&key),
+            invited_by,
+            expires_at
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        let id = response.last_insert_id() as i32;
+        let created = Self::get(org_id, id)
+            .await?
+            .ok_or(AppError::Internal(format!(
+                "Organization invitation {id} vanished after its creation"
+            )))?;
+        Ok((created, key))
+    }
+
+    pub async fn get(org_id: i32, id: i32) -> AppResult<Option<Self>> {
+        let maybe_row = query_as!(
+            OrganizationInvitationRow,
+            r#"
+            SELECT id, org_id, email, role, expires_at, accepted_at, created_at
+            FROM organization_invitations WHERE id=? AND org_id=? LIMIT 1
+            "#,
+            id,
+            org_id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_row.and_then(OrganizationInvitationRow::into_invitation))
+    }
+
+    /// Uses the invitation once: `None` when the key is unknown, used, expired
+    /// or was sent to another address than `email`
+    pub async fn accept(key: &str, email: &Email) -> AppResult<Option<AcceptedInvitation>> {
+        let now = OffsetDateTime::now_utc();
+        let mut transaction = DB_POOL.begin().await?;
+        let maybe_row = query!(
+            r#"
+            SELECT id, org_id, role FROM organization_invitations
+            WHERE key_hash=? AND email_canonical=? AND accepted_at IS NULL AND expires_at > ?
+            LIMIT 1 FOR UPDATE
+            "#,
+            hash_key(key),
+            email.canonical(),
+            now
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+        let Some(row) = maybe_row else {
+            return Ok(None);
+        };
+        query!(
+            "UPDATE organization_invitations SET accepted_at = ? WHERE id=?",
+            now,
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(OrgRole::parse(&row.role).map(|role| AcceptedInvitation {
+            org_id: row.org_id,
+            role,
+        }))
+    }
+}
+
+impl ApiSchema for OrganizationInvitation {
+    fn schema_name() -> String {
+        "OrganizationInvitation".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "org_id", "email", "role", "expires_at", "created_at"],
+            "properties": {
+                "id": { "type": "integer", "format": "int32" },
+                "org_id": { "type": "integer", "format": "int32" },
+                "email": { "type": "string", "format": "email" },
+                "role": { "type": "string", "enum": ["owner", "admin", "member", "viewer"] },
+                "expires_at": { "type": "integer", "format": "int64" },
+                "accepted_at": { "type": "integer", "format": "int64", "nullable": true },
+                "created_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
@@ -0,0 +1,428 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sqlx::{MySqlConnection, query, query_as, query_scalar};
+use time::PrimitiveDateTime;
+
+use crate::{
+    DB_POOL,
+    errors::{AppError, AppResult},
+    services::openapi_service::ApiSchema,
+};
+
+/// Role of a user in one organization, from the most to the least powerful.
+/// The string form is stored: never rename one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrgRole {
+    Owner,
+    Admin,
+    Member,
+    Viewer,
+}
+impl OrgRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrgRole::Owner => "owner",
+            OrgRole::Admin => "admin",
+            OrgRole::Member => "member",
+            OrgRole::Viewer => "viewer",
+        }
+    }
+    /// Unknown stored roles grant nothing
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "owner" => Some(OrgRole::Owner),
+            "admin" => Some(OrgRole::Admin),
+            "member" => Some(OrgRole::Member),
+            "viewer" => Some(OrgRole::Viewer),
+            _ => None,
+        }
+    }
+    fn rank(&self) -> u8 {
+        match self {
+            OrgRole::Owner => 3,
+            OrgRole::Admin => 2,
+            OrgRole::Member => 1,
+            OrgRole::Viewer => 0,
+        }
+    }
+    /// `Admin.at_least(Member)` is true, every role is at least `Viewer`
+    pub fn at_least(&self, min: OrgRole) -> bool {
+        self.rank() >= min.rank()
+    }
+}
+
+/// Organization the user currently works in, carried by its claims
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActiveOrg {
+    pub id: i32,
+    pub role: OrgRole,
+}
+
+/// An organization as seen by one of its members
+#[derive(Serialize)]
+pub struct Organization {
+    pub id: i32,
+    pub name: String,
+    /// Role of the requesting user
+    pub role: OrgRole,
+    /// UNIX timestamp
+    pub created_at: i64,
+}
+
+struct OrganizationRow {
+    id: i32,
+    name: String,
+    role: String,
+    created_at: PrimitiveDateTime,
+}
+fn unix(date: PrimitiveDateTime) -> i64 {
+    date.assume_utc().unix_timestamp()
+}
+impl OrganizationRow {
+    fn into_organization(self) -> Option<Organization> {
+        Some(Organization {
+            id: self.id,
+            name: self.name,
+            role: OrgRole::parse(&self.role)?,
+            created_at: unix(self.created_at),
+        })
+    }
+}
+
+/// Outcome of a role change or a removal
+pub enum MembershipChange {
+    Done,
+    NotMember,
+    /// Every organization keeps at least one owner
+    LastOwner,
+}
+
+impl Organization {
+    /// `owner_id` becomes its first owner
+    pub async fn create(name: &str, owner_id: i32) -> AppResult<Self> {
+        let mut transaction = DB_POOL.begin().await?;
+        let response = query!(
+            "INSERT INTO organizations (name, created_by) VALUES (?,?)",
+            name,
+            owner_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        let id = response.last_insert_id() as i32;
+        query!(
+            "INSERT INTO organization_members (org_id, user_id, role) VALUES (?,?,?)",
+            id,
+            owner_id,
+            OrgRole::Owner.as_str()
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Self::get(id, owner_id)
+            .await?
+            .ok_or(AppError::Internal(format!(
+                "Organization {id} vanished after its creation"
+            )))
+    }
+
+    /// `None` unless `user_id` is a member
+    pub async fn get(id: i32, user_id: i32) -> AppResult<Option<Self>> {
+        let maybe_row = query_as!(
+            OrganizationRow,
+            r#"
+            SELECT o.id, o.name, m.role, o.created_at
+            FROM organizations o
+            JOIN organization_members m ON m.org_id = o.id
+            WHERE o.id=? AND m.user_id=?
+            LIMIT 1
+            "#,
+            id,
+            user_id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_row.and_then(OrganizationRow::into_organization))
+    }
+
+    /// Every organization `user_id` belongs to, by name
+    pub async fn list(user_id: i32) -> AppResult<Vec<Self>> {
+        let rows = query_as!(
+            OrganizationRow,
+            r#"
+            SELECT o.id, o.name, m.role, o.created_at
+            FROM organizations o
+            JOIN organization_members m ON m.org_id = o.id
+            WHERE m.user_id=?
+            ORDER BY o.name, o.id
+            "#,
+            user_id
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(OrganizationRow::into_organization)
+            .collect())
+    }
+
+    /// Organizations `user_id` is the only owner of while others are members: it
+    /// cannot leave them before handing the ownership over
+    pub async fn owned_alone(user_id: i32) -> AppResult<Vec<i32>> {
+        let org_ids = query_scalar!(
+            r#"
+            SELECT m.org_id FROM organization_members m
+            WHERE m.user_id=? AND m.role=?
+                AND NOT EXISTS (
+                    SELECT 1 FROM organization_members o
+                    WHERE o.org_id = m.org_id AND o.user_id <> m.user_id AND o.role = m.role
+                )
+                AND EXISTS (
+                    SELECT 1 FROM organization_members o
+                    WHERE o.org_id = m.org_id AND o.user_id <> m.user_id
+                )
+            "#,
+            user_id,
+            OrgRole::Owner.as_str()
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(org_ids)
+    }
+
+    /// Before `user_id` is deleted, every organization it is the last owner of goes to
+    /// the remaining member of the highest role, the most senior first. One left empty
+    /// is deleted.
+    pub async fn hand_over(conn: &mut MySqlConnection, user_id: i32) -> AppResult<()> {
+        let org_ids = query_scalar!(
+            r#"
+            SELECT m.org_id FROM organization_members m
+            WHERE m.user_id=? AND m.role=?
+                AND NOT EXISTS (
+                    SELECT 1 FROM organization_members o
+                    WHERE o.org_id = m.org_id AND o.user_id <> m.user_id AND o.role = m.role
+                )
+            FOR UPDATE
+            "#,
+            user_id,
+            OrgRole::Owner.as_str()
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        for org_id in org_ids {
+            let successor = query_scalar!(
+                r#"
+                SELECT user_id FROM organization_members
+                WHERE org_id=? AND user_id<>?
+                ORDER BY FIELD(role, ?, ?, ?), created_at, user_id
+                LIMIT 1
+                FOR UPDATE
+                "#,
+                org_id,
+                user_id,
+                OrgRole::Admin.as_str(),
+                OrgRole::Member.as_str(),
+                OrgRole::Viewer.as_str()
+            )
+            .fetch_optional(&mut *conn)
+            .await?;
+            match successor {
+                Some(successor) => {
+                    query!(
+                        "UPDATE organization_members SET role=? WHERE org_id=? AND user_id=?",
+                        OrgRole::Owner.as_str(),
+                        org_id,
+                        successor
+                    )
+                    .execute(&mut *conn)
+                    .await?;
+                }
+                None => {
+                    query!("DELETE FROM organizations WHERE id=?", org_id)
+                        .execute(&mut *conn)
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A member as listed to the other members of the organization
+#[derive(Serialize)]
+pub struct Member {
+    pub user_id: i32,
+    pub display_name: Option<String>,
+    pub role: OrgRole,
+    /// UNIX timestamp
+    pub joined_at: i64,
+}
+
+struct MemberRow {
+    user_id: i32,
+    display_name: Option<String>,
+    role: String,
+    created_at: PrimitiveDateTime,
+}
+
+impl Member {
+    pub async fn list(org_id: i32) -> AppResult<Vec<Self>> {
+        let rows = query_as!(
+            MemberRow,
+            r#"
+            SELECT m.user_id, p.display_name, m.role, m.created_at
+            FROM organization_members m
+            LEFT JOIN user_profiles p ON p.user_id = m.user_id
+            WHERE m.org_id=?
+            ORDER BY m.created_at, m.user_id
+            "#,
+            org_id
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                Some(Self {
+                    user_id: row.user_id,
+                    display_name: row.display_name,
+                    role: OrgRole::parse(&row.role)?,
+                    joined_at: unix(row.created_at),
+                })
+            })
+            .collect())
+    }
+
+    /// `None` unless `user_id` is a member
+    pub async fn role(org_id: i32, user_id: i32) -> AppResult<Option<OrgRole>> {
+        let maybe_role = query_scalar!(
+            "SELECT role FROM organization_members WHERE org_id=? AND user_id=? LIMIT 1",
+            org_id,
+            user_id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_role.as_deref().and_then(OrgRole::parse))
+    }
+
+    /// An existing member keeps its current role, returns whether it was added
+    pub async fn add(org_id: i32, user_id: i32, role: OrgRole) -> AppResult<bool> {
+        let response = query!(
+            "INSERT IGNORE INTO organization_members (org_id, user_id, role) VALUES (?,?,?)",
+            org_id,
+            user_id,
+            role.as_str()
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(response.rows_affected() == 1)
+    }
+
+    /// The members are locked meanwhile so that two demotions cannot leave no owner
+    pub async fn set_role(org_id: i32, user_id: i32, role: OrgRole) -> AppResult<MembershipChange> {
+        let mut transaction = DB_POOL.begin().await?;
+        let roles = query!(
+            "SELECT user_id, role FROM organization_members WHERE org_id=? FOR UPDATE",
+            org_id
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        let Some(current) = roles.iter().find(|row| row.user_id == user_id) else {
+            return Ok(MembershipChange::NotMember);
+        };
+        let owners = roles
+            .iter()
+            .filter(|row| row.role == OrgRole::Owner.as_str())
+            .count();
+        if current.role == OrgRole::Owner.as_str() && role != OrgRole::Owner && owners <= 1 {
+            return Ok(MembershipChange::LastOwner);
+        }
+        query!(
+            "UPDATE organization_members SET role=? WHERE org_id=? AND user_id=?",
+            role.as_str(),
+            org_id,
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(MembershipChange::Done)
+    }
+
+    /// Also leaves the organization if it was the active one of `user_id`
+    pub async fn remove(org_id: i32, user_id: i32) -> AppResult<MembershipChange> {
+        let mut transaction = DB_POOL.begin().await?;
+        let roles = query!(
+            "SELECT user_id, role FROM organization_members WHERE org_id=? FOR UPDATE",
+            org_id
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        let Some(current) = roles.iter().find(|row| row.user_id == user_id) else {
+            return Ok(MembershipChange::NotMember);
+        };
+        let owners = roles
+            .iter()
+            .filter(|row| row.role == OrgRole::Owner.as_str())
+            .count();
+        if current.role == OrgRole::Owner.as_str() && owners <= 1 {
+            return Ok(MembershipChange::LastOwner);
+        }
+        query!(
+            "DELETE FROM organization_members WHERE org_id=? AND user_id=?",
+            org_id,
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        query!(
+            "UPDATE users SET active_org_id = NULL WHERE id=? AND active_org_id=?",
+            user_id,
+            org_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(MembershipChange::Done)
+    }
+}
+
+impl ApiSchema for Organization {
+    fn schema_name() -> String {
+        "Organization".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "name", "role", "created_at"],
+            "properties": {
+                "id": { "type": "integer", "format": "int32" },
+                "name": { "type": "string", "maxLength": 100 },
+                "role": {
+                    "type": "string",
+                    "enum": ["owner", "admin", "member", "viewer"],
+                    "description": "Role of the requesting user"
+                },
+                "created_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
+
+impl ApiSchema for Member {
+    fn schema_name() -> String {
+        "Member".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["user_id", "role", "joined_at"],
+            "properties": {
+                "user_id": { "type": "integer", "format": "int32" },
+                "display_name": { "type": "string", "nullable": true },
+                "role": { "type": "string", "enum": ["owner", "admin", "member", "viewer"] },
+                "joined_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
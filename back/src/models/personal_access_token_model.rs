@@ -0,0 +1,233 @@
+use password_hash::rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use sqlx::{query, query_as};
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+use crate::{
+    DB_POOL,
+    auth::auth_models::{claims::Claims, scope::Scope, token::TokenError},
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    services::openapi_service::ApiSchema,
+};
+
+/// Tells a personal access token apart from a JWT in the `Authorization` header
+pub const PAT_PREFIX: &str = "pat_";
+/// `last_used_at` is not rewritten by every request of a busy script
+const LAST_USED_PRECISION_SECS: i64 = 60;
+
+/// What the owner sees of a token, never the token itself
+#[derive(Serialize)]
+pub struct PersonalAccessToken {
+    pub id: i32,
+    pub name: String,
+    /// First characters of the token, enough to recognise it
+    pub token_prefix: String,
+    pub scopes: Vec<Scope>,
+    /// UNIX timestamps
+    pub expires_at: Option<i64>,
+    pub last_used_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+    pub created_at: i64,
+}
+
+struct TokenRow {
+    id: i32,
+    name: String,
+    token_prefix: String,
+    scopes: String,
+    expires_at: Option<PrimitiveDateTime>,
+    last_used_at: Option<PrimitiveDateTime>,
+    revoked_at: Option<PrimitiveDateTime>,
+    created_at: PrimitiveDateTime,
+}
+impl From<TokenRow> for PersonalAccessToken {
+    fn from(row: TokenRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name,
+            token_prefix: row.token_prefix,
+            scopes: Scope::split(&row.scopes),
+            expires_at: row.expires_at.map(unix),
+            last_used_at: row.last_used_at.map(unix),
+            revoked_at: row.revoked_at.map(unix),
+            created_at: unix(row.created_at),
+        }
+    }
+}
+
+fn unix(date: PrimitiveDateTime) -> i64 {
+    date.assume_utc().unix_timestamp()
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+impl PersonalAccessToken {
+    /// Returns the stored token with the plain one, which cannot be retrieved afterwards
+    pub async fn create(
+        user_id: i32,
+        name: &str,
+        scopes: &[Scope],
+        expires_at: Option<OffsetDateTime>,
+    ) -> AppResult<(Self, String)> {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let token = format!("{PAT_PREFIX}{}", hex::encode(bytes));
+
+        let response = query!(
+            r#"
+            INSERT INTO personal_access_tokens
+                (user_id, name, token_hash, token_prefix, scopes, expires_at)
+            VALUES (?,?,?,?,?,?)
+            "#,
+            user_id,
+            name,
+            hash_token(&token),
+            &token[..12],
+            Scope::join(scopes),
+            expires_at
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        let id = response.last_insert_id() as i32;
+        let created = Self::get(user_id, id)
+            .await?
+            .ok_or(AppError::Internal(format!(
+                "Personal access token {id} vanished after its creation"
+            )))?;
+        Ok((created, token))
+    }
+
+    pub async fn get(user_id: i32, id: i32) -> AppResult<Option<Self>> {
+        let maybe_row = query_as!(
+            TokenRow,
+            r#"
+            SELECT id, name, token_prefix, scopes, expires_at, last_used_at, revoked_at, created_at
+            FROM personal_access_tokens WHERE id=? AND user_id=? LIMIT 1
+            "#,
+            id,
+            user_id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_row.map(Self::from))
+    }
+
+    pub async fn list(user_id: i32) -> AppResult<Vec<Self>> {
+        let rows = query_as!(
+            TokenRow,
+            r#"
+            SELECT id, name, token_prefix, scopes, expires_at, last_used_at, revoked_at, created_at
+            FROM personal_access_tokens WHERE user_id=?
+            ORDER BY created_at DESC, id DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(rows.into_iter().map(Self::from).collect())
+    }
+
+    /// `None` when the token does not exist or belongs to someone else
+    pub async fn revoke(user_id: i32, id: i32) -> AppResult<Option<Self>> {
+        if Self::get(user_id, id).await?.is_none() {
+            return Ok(None);
+        }
+        query!(
+            r#"
+            UPDATE personal_access_tokens
+            SET revoked_at = COALESCE(revoked_at, ?)
+            WHERE id = ? AND user_id = ?
+            "#,
+            OffsetDateTime::now_utc(),
+            id,
+            user_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Self::get(user_id, id).await
+    }
+
+    /// Every live token of the user, e.g. when the account is being deleted
+    pub async fn revoke_all(user_id: i32) -> AppResult<()> {
+        query!(
+            r#"
+            UPDATE personal_access_tokens
+            SET revoked_at = ?
+            WHERE user_id = ? AND revoked_at IS NULL
+            "#,
+            OffsetDateTime::now_utc(),
+            user_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
+    }
+
+    /// Claims restricted to the scopes of a `pat_` bearer token
+    pub async fn authenticate(token: &str) -> AppResult<Claims> {
+        let maybe_row = query!(
+            r#"
+            SELECT t.id, t.user_id, t.scopes, t.expires_at, t.last_used_at, t.revoked_at
+            FROM personal_access_tokens t JOIN users u ON u.id = t.user_id
+            WHERE t.token_hash = ? AND u.verified_at IS NOT NULL
+            LIMIT 1
+            "#,
+            hash_token(token)
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        let Some(row) = maybe_row.filter(|row| row.revoked_at.is_none()) else {
+            return Err(AppError::Unauthorized(ErrorCode::TokenInvalid));
+        };
+        let now = OffsetDateTime::now_utc();
+        if row.expires_at.is_some_and(|exp| exp.assume_utc() <= now) {
+            return Err(TokenError::Expired.into());
+        }
+
+        let recently_used = row.last_used_at.is_some_and(|used| {
+            (now - used.assume_utc()).whole_seconds() < LAST_USED_PRECISION_SECS
+        });
+        if !recently_used {
+            query!(
+                "UPDATE personal_access_tokens SET last_used_at = ? WHERE id = ?",
+                now,
+                row.id
+            )
+            .execute(&*DB_POOL)
+            .await?;
+        }
+
+        Ok(Claims::new_scoped_claim(
+            row.user_id,
+            Scope::split(&row.scopes),
+        ))
+    }
+}
+
+impl ApiSchema for PersonalAccessToken {
+    fn schema_name() -> String {
+        "PersonalAccessToken".to_string()
+    }
+    fn schema() -> Value {
+        let scopes: Vec<_> = Scope::ALL.iter().map(Scope::as_str).collect();
+        json!({
+            "type": "object",
+            "required": ["id", "name", "token_prefix", "scopes", "created_at"],
+            "properties": {
+                "id": { "type": "integer", "format": "int32" },
+                "name": { "type": "string" },
+                "token_prefix": { "type": "string", "example": "pat_3f9a1c2b" },
+                "scopes": { "type": "array", "items": { "type": "string", "enum": scopes } },
+                "expires_at": { "type": "integer", "format": "int64", "nullable": true },
+                "last_used_at": { "type": "integer", "format": "int64", "nullable": true },
+                "revoked_at": { "type": "integer", "format": "int64", "nullable": true },
+                "created_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
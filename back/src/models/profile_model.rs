@@ -0,0 +1,236 @@
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::{query, query_as};
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+use crate::{
+    DB_POOL,
+    errors::{AppError, AppResult},
+    services::openapi_service::ApiSchema,
+};
+
+/// What the owner sees and edits of its profile
+#[derive(Serialize)]
+pub struct Profile {
+    pub user_id: i32,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    /// UNIX timestamps
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+struct ProfileRow {
+    user_id: i32,
+    display_name: Option<String>,
+    avatar_updated_at: Option<PrimitiveDateTime>,
+    locale: Option<String>,
+    timezone: Option<String>,
+    created_at: PrimitiveDateTime,
+    updated_at: PrimitiveDateTime,
+}
+fn unix(date: PrimitiveDateTime) -> i64 {
+    date.assume_utc().unix_timestamp()
+}
+/// Relative to the API root, the version changes with the image so it can be cached
+fn avatar_url(user_id: i32, avatar_updated_at: PrimitiveDateTime) -> String {
+    format!("/avatars/{user_id}?v={}", unix(avatar_updated_at))
+}
+impl From<ProfileRow> for Profile {
+    fn from(row: ProfileRow) -> Self {
+        Self {
+            user_id: row.user_id,
+            display_name: row.display_name,
+            avatar_url: row
+                .avatar_updated_at
+                .map(|date| avatar_url(row.user_id, date)),
+            locale: row.locale,
+            timezone: row.timezone,
+            created_at: unix(row.created_at),
+            updated_at: unix(row.updated_at),
+        }
+    }
+}
+
+/// Validated fields of a PATCH: `None` keeps the value, `Some(None)` clears it
+pub struct ProfileChanges {
+    pub display_name: Option<Option<String>>,
+    pub locale: Option<Option<String>>,
+    pub timezone: Option<Option<String>>,
+}
+
+impl Profile {
+    /// Created on first access
+    pub async fn get(user_id: i32) -> AppResult<Self> {
+        if let Some(profile) = Self::find(user_id).await? {
+            return Ok(profile);
+        }
+        query!(
+            "INSERT IGNORE INTO user_profiles (user_id) VALUES (?)",
+            user_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Self::find(user_id).await?.ok_or(AppError::Internal(format!(
+            "Profile of user {user_id} vanished after its creation"
+        )))
+    }
+
+    async fn find(user_id: i32) -> AppResult<Option<Self>> {
+        let maybe_row = query_as!(
+            ProfileRow,
+            r#"
+            SELECT user_id, display_name, avatar_updated_at, locale, timezone, created_at, updated_at
+            FROM user_profiles WHERE user_id=? LIMIT 1
+            "#,
+            user_id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_row.map(Self::from))
+    }
+
+    pub async fn update(user_id: i32, changes: &ProfileChanges) -> AppResult<Self> {
+        // makes sure the row exists
+        Self::get(user_id).await?;
+        query!(
+            r#"
+            UPDATE user_profiles SET
+                display_name = IF(?, ?, display_name),
+                locale = IF(?, ?, locale),
+                timezone = IF(?, ?, timezone),
+                updated_at = ?
+            WHERE user_id=?
+            "#,
+            changes.display_name.is_some(),
+            changes.display_name.clone().flatten(),
+            changes.locale.is_some(),
+            changes.locale.clone().flatten(),
+            changes.timezone.is_some(),
+            changes.timezone.clone().flatten(),
+            OffsetDateTime::now_utc(),
+            user_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Self::get(user_id).await
+    }
+
+    /// `None` removes the avatar
+    pub async fn set_avatar(user_id: i32, png: Option<&[u8]>) -> AppResult<Self> {
+        Self::get(user_id).await?;
+        let now = OffsetDateTime::now_utc();
+        query!(
+            r#"
+            UPDATE user_profiles SET avatar = ?, avatar_updated_at = ?, updated_at = ?
+            WHERE user_id=?
+            "#,
+            png,
+            png.map(|_| now),
+            now,
+            user_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Self::get(user_id).await
+    }
+
+    /// The PNG, only for users others may see
+    pub async fn avatar(user_id: i32) -> AppResult<Option<Vec<u8>>> {
+        let maybe_avatar = query!(
+            r#"
+            SELECT p.avatar FROM user_profiles p
+            JOIN users u ON u.id = p.user_id
+            WHERE p.user_id=? AND u.verified_at IS NOT NULL AND u.deletion_scheduled_at IS NULL
+            LIMIT 1
+            "#,
+            user_id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_avatar.and_then(|row| row.avatar))
+    }
+}
+
+/// What any connected user may see of another one, never its credentials
+#[derive(Serialize)]
+pub struct UserView {
+    pub id: i32,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+struct UserViewRow {
+    id: i32,
+    display_name: Option<String>,
+    avatar_updated_at: Option<PrimitiveDateTime>,
+}
+
+impl UserView {
+    /// `None` for unknown, unverified or deleted users
+    pub async fn get(user_id: i32) -> AppResult<Option<Self>> {
+        let maybe_row = query_as!(
+            UserViewRow,
+            r#"
+            SELECT u.id, p.display_name, p.avatar_updated_at
+            FROM users u
+            LEFT JOIN user_profiles p ON p.user_id = u.id
+            WHERE u.id=? AND u.verified_at IS NOT NULL AND u.deletion_scheduled_at IS NULL
+            LIMIT 1
+            "#,
+            user_id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_row.map(|row| Self {
+            id: row.id,
+            display_name: row.display_name,
+            avatar_url: row.avatar_updated_at.map(|date| avatar_url(row.id, date)),
+        }))
+    }
+}
+
+impl ApiSchema for Profile {
+    fn schema_name() -> String {
+        "Profile".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["user_id", "created_at", "updated_at"],
+            "properties": {
+                "user_id": { "type": "integer", "format": "int32" },
+                "display_name": { "type": "string", "maxLength": 64, "nullable": true },
+                "avatar_url": {
+                    "type": "string",
+                    "nullable": true,
+                    "example": "/avatars/42?v=1735689600",
+                    "description": "Relative to the API root, changes with the image"
+                },
+                "locale": { "type": "string", "nullable": true, "example": "fr-FR" },
+                "timezone": { "type": "string", "nullable": true, "example": "Europe/Paris" },
+                "created_at": { "type": "integer", "format": "int64" },
+                "updated_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
+
+impl ApiSchema for UserView {
+    fn schema_name() -> String {
+        "UserView".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id"],
+            "properties": {
+                "id": { "type": "integer", "format": "int32" },
+                "display_name": { "type": "string", "nullable": true },
+                "avatar_url": { "type": "string", "nullable": true }
+            }
+        })
+    }
+}
@@ -1,64 +1,58 @@
-use serde::{Deserialize, Serialize};
-
 use sqlx::{query, query_as, query_scalar};
 use time::{OffsetDateTime, PrimitiveDateTime};
 
 use crate::{
     DB_POOL,
+    account::account_models::phone_number::PhoneNumber,
     auth::{
         auth_extractor::TryFromClaim,
         auth_models::{
             claims::Claims,
-            credential::{Email, LoginCredential},
+            credential::LoginCredential,
+            email::Email,
             token::{Token, TokenAble},
         },
     },
-    constants::messages::{EMAIL_ALREADY_EXIST, USER_NOT_FOUND},
+    constants::{codes::ErrorCode, messages::USER_NOT_FOUND},
     errors::{AppError, AppResult},
-    utils::redis_utils::{redis_get, redis_set_ex},
+    models::{
+        blob_model::Blob,
+        organization_model::{ActiveOrg, Member, Organization},
+    },
+    utils::password_utils::{PasswordCheck, hash_password, is_bcrypt_hash, verify_password},
 };
 
-use argon2::{Argon2, PasswordHasher, PasswordVerifier};
-use password_hash::{PasswordHash, SaltString, rand_core};
-
-#[derive(Deserialize, Serialize, Clone)]
+/// Credentials and account state, never serialized: clients get a `UserView` or a `Profile`
+#[derive(Clone)]
 pub struct User {
     pub id: i32,
     pub email: Email,
     pub password: String,
     pub phone_number: Option<String>,
+    pub phone_verified_at: Option<PrimitiveDateTime>,
+    pub sms_two_factor: u8,
     pub verified_at: Option<PrimitiveDateTime>,
     pub admin: u8,
-}
-
-fn hash_password(password: &str) -> String {
-    let salt = SaltString::generate(&mut rand_core::OsRng);
-    let argon2 = Argon2::default();
-    let password_hash = argon2.hash_password(password.as_bytes(), &salt).unwrap();
-    password_hash.to_string() // contient hash + salt + paramètres encodés
-}
-
-fn verify_password(password: &str, hash: &str) -> bool {
-    let parsed_hash = PasswordHash::new(hash).unwrap();
-    let argon2 = Argon2::default();
-    argon2
-        .verify_password(password.as_bytes(), &parsed_hash)
-        .is_ok()
+    pub invited_by: Option<i32>,
+    /// Set while the account waits for its hard deletion
+    pub deletion_scheduled_at: Option<PrimitiveDateTime>,
+    /// Organization put in the claims, kept while the user is a member
+    pub active_org_id: Option<i32>,
 }
 
 impl User {
     pub async fn try_get(id: i32) -> AppResult<Option<Self>> {
-        if let Some(user) = redis_get(&id).await? {
-            return Ok(Some(user));
-        }
-
-        let maybe_user = query_as!(User, r#"SELECT * FROM users WHERE id=? LIMIT 1"#, id)
-            .fetch_optional(&*DB_POOL)
-            .await?;
-        if let Some(user) = &maybe_user {
-            let _ = redis_set_ex(&format!("user:{}", user.id), user, 3600).await;
-        }
-
+        let maybe_user = query_as!(
+            User,
+            r#"
+            SELECT id, email, password, phone_number, phone_verified_at,
+                sms_two_factor, verified_at, admin, invited_by, deletion_scheduled_at, active_org_id
+            FROM users WHERE id=? LIMIT 1
+            "#,
+            id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
         Ok(maybe_user)
     }
     pub async fn get(id: i32) -> AppResult<Self> {
@@ -69,26 +63,21 @@ impl User {
             )))
     }
 
-    pub async fn create(credential: &LoginCredential) -> AppResult<i32> {
-        let hashed_password = hash_password(credential.get_password());
+    /// `invited_by` is the issuer of the invitation used to register, if any
+    pub async fn create(credential: &LoginCredential, invited_by: Option<i32>) -> AppResult<i32> {
+        let hashed_password = hash_password(credential.get_password())?;
 
         let db_response = query!(
-            "INSERT INTO users (email,password) VALUES (?,?);",
-            credential.get_email(),
-            hashed_password
+            "INSERT INTO users (email,email_canonical,password,invited_by) VALUES (?,?,?,?);",
+            credential.get_email().as_ref(),
+            credential.get_email().canonical(),
+            hashed_password,
+            invited_by
         )
         .execute(&*DB_POOL)
         .await;
         let err = match db_response {
-            Ok(response) => {
-                let user_id = response.last_insert_id() as i32;
-                let maybe_user = User::try_get(user_id).await?;
-                if let Some(user) = maybe_user {
-                    let _ = redis_set_ex(&format!("user:{}", user.id), &user, 3600).await;
-                }
-
-                return Ok(user_id);
-            }
+            Ok(response) => return Ok(response.last_insert_id() as i32),
 
             Err(err) => err,
         };
@@ -97,7 +86,7 @@ impl User {
             let err = db_err.downcast_ref::<sqlx::mysql::MySqlDatabaseError>();
             if err.number() == 1062 {
                 // Duplicate entry
-                return Err(AppError::Conflict(EMAIL_ALREADY_EXIST.to_string()));
+                return Err(AppError::Conflict(ErrorCode::EmailAlreadyExists));
             }
         }
         // Pour les autres erreurs
@@ -106,20 +95,65 @@ impl User {
     pub async fn get_from_credential(credential: &LoginCredential) -> AppResult<Option<Self>> {
         let maybe_user = query_as!(
             User,
-            r#"SELECT * FROM users WHERE email=? LIMIT 1"#,
-            credential.get_email(),
+            r#"
+            SELECT id, email, password, phone_number, phone_verified_at,
+                sms_two_factor, verified_at, admin, invited_by, deletion_scheduled_at, active_org_id
+            FROM users WHERE email_canonical=? LIMIT 1
+            "#,
+            credential.get_email().canonical(),
         )
         .fetch_optional(&*DB_POOL)
         .await?;
-        let Some(user) = maybe_user else {
+        let Some(mut user) = maybe_user else {
             return Ok(None);
         };
-        if !verify_password(credential.get_password(), &user.password) {
-            return Ok(None);
+        match verify_password(credential.get_password(), &user.password)? {
+            PasswordCheck::Invalid => return Ok(None),
+            PasswordCheck::Valid => {}
+            PasswordCheck::ValidNeedsRehash => {
+                // the plain password is only known here, upgrade the stored hash
+                match Self::change_password(user.id, credential.get_password()).await {
+                    Ok(password) => user.password = password,
+                    Err(err) => eprintln!("Rehash of user {} failed: {err}", user.id),
+                }
+            }
         }
 
         Ok(Some(user))
     }
+    /// Insert a user coming from the legacy system with its bcrypt hash as is,
+    /// it is replaced by an Argon2 hash on the first successful login
+    pub async fn import_legacy(email: &Email, bcrypt_hash: &str, verified: bool) -> AppResult<i32> {
+        if !is_bcrypt_hash(bcrypt_hash) {
+            return Err(AppError::invalid_field(
+                "password",
+                ErrorCode::PasswordInvalid,
+            ));
+        }
+        let verified_at = verified.then(OffsetDateTime::now_utc);
+
+        let db_response = query!(
+            "INSERT INTO users (email,email_canonical,password,verified_at) VALUES (?,?,?,?);",
+            email.as_ref(),
+            email.canonical(),
+            bcrypt_hash,
+            verified_at
+        )
+        .execute(&*DB_POOL)
+        .await;
+        match db_response {
+            Ok(response) => Ok(response.last_insert_id() as i32),
+            Err(sqlx::Error::Database(db_err))
+                if db_err
+                    .downcast_ref::<sqlx::mysql::MySqlDatabaseError>()
+                    .number()
+                    == 1062 =>
+            {
+                Err(AppError::Conflict(ErrorCode::EmailAlreadyExists))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
     pub async fn verify_user(user_id: i32) -> AppResult<()> {
         // on récupère le timestamp actuel
         let now = OffsetDateTime::now_utc();
@@ -144,16 +178,9 @@ impl User {
     }
     ///Only return a token if the user have been verified
     pub async fn get_token(id: i32) -> AppResult<Option<Token>> {
-        let maybe_user = Self::try_get(id).await?;
-        let Some(user) = maybe_user else {
-            return Err(AppError::Internal(USER_NOT_FOUND.to_string()));
-        };
-
-        if user.is_verified() {
-            let new_claims = Claims::new_user_claim(id);
-            return Ok(Some(new_claims.encode()?));
-        } else {
-            Ok(None)
+        match Self::get_claim(id).await? {
+            Some(claims) => Ok(Some(claims.encode()?)),
+            None => Ok(None),
         }
     }
     pub async fn get_claim(id: i32) -> AppResult<Option<Claims>> {
@@ -161,25 +188,48 @@ impl User {
             return Err(AppError::Internal(USER_NOT_FOUND.to_string()));
         };
 
-        if user.is_verified() {
-            return Ok(Some(Claims::new_user_claim(id)));
-        } else {
-            Ok(None)
+        if !user.is_verified() {
+            return Ok(None);
         }
+        let claims = if user.is_admin() {
+            Claims::new_admin_claim(id)
+        } else {
+            Claims::new_user_claim(id)
+        };
+        Ok(Some(claims.with_org(user.active_org().await?)))
+    }
+    /// The active organization with the current role, `None` once the user left it
+    pub async fn active_org(&self) -> AppResult<Option<ActiveOrg>> {
+        let Some(org_id) = self.active_org_id else {
+            return Ok(None);
+        };
+        let maybe_role = Member::role(org_id, self.id).await?;
+        Ok(maybe_role.map(|role| ActiveOrg { id: org_id, role }))
+    }
+    /// The caller checks that the user is a member of `org_id`
+    pub async fn set_active_org(user_id: i32, org_id: i32) -> AppResult<()> {
+        query!(
+            "UPDATE users SET active_org_id = ? WHERE id = ?",
+            org_id,
+            user_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
     }
     ///This function do not check if the for password so the id should not be use where security is needed
     pub async fn get_user_id_from_email(email: &Email) -> AppResult<Option<i32>> {
         let maybe_user_id = query_scalar!(
-            r#"SELECT id FROM users WHERE email=? LIMIT 1"#,
-            email.as_ref()
+            r#"SELECT id FROM users WHERE email_canonical=? LIMIT 1"#,
+            email.canonical()
         )
         .fetch_optional(&*DB_POOL)
         .await?;
         Ok(maybe_user_id)
     }
-    ///This function assume that caller verified user_id and password validity
-    pub async fn change_password(user_id: i32, password: &str) -> AppResult<()> {
-        let hashed_password = hash_password(password);
+    ///This function assume that caller verified user_id and password validity, returns the new hash
+    pub async fn change_password(user_id: i32, password: &str) -> AppResult<String> {
+        let hashed_password = hash_password(password)?;
 
         query!(
             r#"
@@ -192,15 +242,183 @@ impl User {
         )
         .execute(&*DB_POOL)
         .await?;
+        Ok(hashed_password)
+    }
+    ///This function assume that caller proved the ownership of `email`
+    pub async fn change_email(user_id: i32, email: &Email) -> AppResult<()> {
+        let db_response = query!(
+            "UPDATE users SET email = ?, email_canonical = ? WHERE id = ?",
+            email.as_ref(),
+            email.canonical(),
+            user_id
+        )
+        .execute(&*DB_POOL)
+        .await;
+        let err = match db_response {
+            Ok(_) => return Ok(()),
+            Err(err) => err,
+        };
+        if let sqlx::Error::Database(db_err) = &err {
+            let err = db_err.downcast_ref::<sqlx::mysql::MySqlDatabaseError>();
+            if err.number() == 1062 {
+                // taken by another account since the link was mailed
+                return Err(AppError::Conflict(ErrorCode::EmailAlreadyExists));
+            }
+        }
+        Err(err.into())
+    }
+
+    /// A new number starts unverified, which also turns the SMS second factor off
+    pub async fn set_phone_number(user_id: i32, phone: &PhoneNumber) -> AppResult<()> {
+        query!(
+            r#"
+            UPDATE users
+            SET phone_number = ?, phone_verified_at = NULL, sms_two_factor = 0
+            WHERE id = ?
+            "#,
+            phone.as_ref(),
+            user_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
+    }
+    pub async fn remove_phone_number(user_id: i32) -> AppResult<()> {
+        query!(
+            r#"
+            UPDATE users
+            SET phone_number = NULL, phone_verified_at = NULL, sms_two_factor = 0
+            WHERE id = ?
+            "#,
+            user_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
+    }
+    pub async fn verify_phone(user_id: i32) -> AppResult<()> {
+        let now = OffsetDateTime::now_utc();
+        query!(
+            "UPDATE users SET phone_verified_at = ? WHERE id = ?",
+            now,
+            user_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
+    }
+    ///This function assume that caller checked the phone number is verified
+    pub async fn set_sms_two_factor(user_id: i32, enabled: bool) -> AppResult<()> {
+        query!(
+            "UPDATE users SET sms_two_factor = ? WHERE id = ?",
+            enabled,
+            user_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn schedule_deletion(
+        user_id: i32,
+        requested_at: OffsetDateTime,
+        scheduled_at: OffsetDateTime,
+    ) -> AppResult<()> {
+        query!(
+            r#"
+            UPDATE users
+            SET deletion_requested_at = ?, deletion_scheduled_at = ?
+            WHERE id = ?
+            "#,
+            requested_at,
+            scheduled_at,
+            user_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
+    }
+    pub async fn cancel_deletion(user_id: i32) -> AppResult<()> {
+        query!(
+            r#"
+            UPDATE users
+            SET deletion_requested_at = NULL, deletion_scheduled_at = NULL
+            WHERE id = ?
+            "#,
+            user_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
         Ok(())
     }
+    /// Accounts whose grace period is over, oldest first
+    pub async fn due_for_deletion(now: OffsetDateTime, limit: u64) -> AppResult<Vec<Self>> {
+        let users = query_as!(
+            User,
+            r#"
+            SELECT id, email, password, phone_number, phone_verified_at,
+                sms_two_factor, verified_at, admin, invited_by, deletion_scheduled_at, active_org_id
+            FROM users
+            WHERE deletion_scheduled_at IS NOT NULL AND deletion_scheduled_at <= ?
+            ORDER BY deletion_scheduled_at
+            LIMIT ?
+            "#,
+            now,
+            limit
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(users)
+    }
+    /// Deletes the user with its tokens, invitations and files, unless it was restored meanwhile.
+    /// Its organizations are handed over first. Audit events keep the bare id.
+    /// Returns whether the user was deleted.
+    pub async fn hard_delete(user_id: i32, now: OffsetDateTime) -> AppResult<bool> {
+        let mut transaction = DB_POOL.begin().await?;
+        query!("DELETE FROM invitations WHERE inviter_id = ?", user_id)
+            .execute(&mut *transaction)
+            .await?;
+        // files go by cascade without releasing their blobs
+        Blob::release_files_of(&mut transaction, user_id).await?;
+        // memberships go by cascade, no organization is left without owner
+        Organization::hand_over(&mut transaction, user_id).await?;
+        // personal access tokens follow by cascade
+        let response = query!(
+            r#"
+            DELETE FROM users
+            WHERE id = ? AND deletion_scheduled_at IS NOT NULL AND deletion_scheduled_at <= ?
+            "#,
+            user_id,
+            now
+        )
+        .execute(&mut *transaction)
+        .await?;
+        if response.rows_affected() == 0 {
+            transaction.rollback().await?;
+            return Ok(false);
+        }
+        transaction.commit().await?;
+        Ok(true)
+    }
 
+    pub fn phone(&self) -> Option<PhoneNumber> {
+        self.phone_number.clone().map(PhoneNumber::from)
+    }
+    pub fn is_phone_verified(&self) -> bool {
+        self.phone_number.is_some() && self.phone_verified_at.is_some()
+    }
+    pub fn has_sms_two_factor(&self) -> bool {
+        self.sms_two_factor != 0 && self.is_phone_verified()
+    }
     pub fn is_admin(&self) -> bool {
         self.admin != 0
     }
     pub fn is_verified(&self) -> bool {
         self.verified_at.is_some()
     }
+    pub fn is_pending_deletion(&self) -> bool {
+        self.deletion_scheduled_at.is_some()
+    }
 }
 
 use async_trait::async_trait;
@@ -210,7 +428,7 @@ impl TryFromClaim for User {
     async fn try_from_claim(claims: &Claims) -> Result<Self, actix_web::Error> {
         let user = Self::try_get(claims.user_id)
             .await?
-            .ok_or_else(|| AppError::Internal(USER_NOT_FOUND.to_string()))?;
+            .ok_or(AppError::NotFound(ErrorCode::UserNotFound))?;
         Ok(user)
     }
 }
@@ -0,0 +1,310 @@
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::{query, query_as, query_scalar};
+use time::{Duration, OffsetDateTime, PrimitiveDateTime};
+
+use crate::{
+    DB_POOL,
+    errors::{AppError, AppResult},
+    services::openapi_service::ApiSchema,
+};
+
+const ERROR_MAX_LENGTH: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    Pending,
+    Succeeded,
+    /// Out of attempts, only a replay sends it again
+    Failed,
+}
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Succeeded => "succeeded",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+/// One event sent to one endpoint, with the outcome of its last attempt
+#[derive(Serialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub endpoint_id: i32,
+    pub event_id: String,
+    pub event_type: String,
+    pub payload: Value,
+    pub status: String,
+    pub attempts: i32,
+    pub last_status_code: Option<u16>,
+    pub last_error: Option<String>,
+    /// The delivery this one replays
+    pub replay_of: Option<i64>,
+    /// UNIX timestamps
+    pub next_attempt_at: i64,
+    pub last_attempt_at: Option<i64>,
+    pub created_at: i64,
+}
+
+struct WebhookDeliveryRow {
+    id: i64,
+    endpoint_id: i32,
+    event_id: String,
+    event_type: String,
+    payload: String,
+    status: String,
+    attempts: i32,
+    last_status_code: Option<i16>,
+    last_error: Option<String>,
+    replay_of: Option<i64>,
+    next_attempt_at: PrimitiveDateTime,
+    last_attempt_at: Option<PrimitiveDateTime>,
+    created_at: PrimitiveDateTime,
+}
+fn unix(date: PrimitiveDateTime) -> i64 {
+    date.assume_utc().unix_timestamp()
+}
+impl From<WebhookDeliveryRow> for WebhookDelivery {
+    fn from(row: WebhookDeliveryRow) -> Self {
+        Self {
+            id: row.id,
+            endpoint_id: row.endpoint_id,
+            event_id: row.event_id,
+            event_type: row.event_type,
+            payload: serde_json::from_str(&row.payload).unwrap_or(Value::Null),
+            status: row.status,
+            attempts: row.attempts,
+            last_status_code: row.last_status_code.map(|code| code as u16),
+            last_error: row.last_error,
+            replay_of: row.replay_of,
+            next_attempt_at: unix(row.next_attempt_at),
+            last_attempt_at: row.last_attempt_at.map(unix),
+            created_at: unix(row.created_at),
+        }
+    }
+}
+
+/// What the worker needs to send a claimed delivery
+pub struct OutgoingDelivery {
+    pub id: i64,
+    pub event_id: String,
+    pub event_type: String,
+    pub payload: String,
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+}
+
+/// Result of one attempt, `status_code` is `None` when the endpoint was unreachable
+pub struct AttemptOutcome {
+    pub status: DeliveryStatus,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub next_attempt_at: OffsetDateTime,
+}
+
+impl WebhookDelivery {
+    pub async fn enqueue(
+        endpoint_id: i32,
+        event_id: &str,
+        event_type: &str,
+        payload: &str,
+    ) -> AppResult<()> {
+        query!(
+            r#"
+            INSERT INTO webhook_deliveries
+                (endpoint_id, event_id, event_type, payload, next_attempt_at)
+            VALUES (?,?,?,?,?)
+            "#,
+            endpoint_id,
+            event_id,
+            event_type,
+            payload,
+            OffsetDateTime::now_utc()
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get(endpoint_id: i32, id: i64) -> AppResult<Option<Self>> {
+        let maybe_row = query_as!(
+            WebhookDeliveryRow,
+            r#"
+            SELECT id, endpoint_id, event_id, event_type, payload, status, attempts,
+                last_status_code, last_error, replay_of, next_attempt_at, last_attempt_at, created_at
+            FROM webhook_deliveries WHERE id=? AND endpoint_id=? LIMIT 1
+            "#,
+            id,
+            endpoint_id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_row.map(Self::from))
+    }
+
+    /// Newest first
+    pub async fn list(endpoint_id: i32, limit: u64, offset: u64) -> AppResult<Vec<Self>> {
+        let rows = query_as!(
+            WebhookDeliveryRow,
+            r#"
+            SELECT id, endpoint_id, event_id, event_type, payload, status, attempts,
+                last_status_code, last_error, replay_of, next_attempt_at, last_attempt_at, created_at
+            FROM webhook_deliveries WHERE endpoint_id=?
+            ORDER BY id DESC
+            LIMIT ? OFFSET ?
+            "#,
+            endpoint_id,
+            limit,
+            offset
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(rows.into_iter().map(Self::from).collect())
+    }
+
+    pub async fn count(endpoint_id: i32) -> AppResult<u64> {
+        let total = query_scalar!(
+            "SELECT COUNT(*) FROM webhook_deliveries WHERE endpoint_id=?",
+            endpoint_id
+        )
+        .fetch_one(&*DB_POOL)
+        .await?;
+        Ok(total as u64)
+    }
+
+    /// Queues the same event again, the original delivery is left as logged
+    pub async fn replay(endpoint_id: i32, id: i64) -> AppResult<Option<Self>> {
+        let response = query!(
+            r#"
+            INSERT INTO webhook_deliveries
+                (endpoint_id, event_id, event_type, payload, replay_of, next_attempt_at)
+            SELECT endpoint_id, event_id, event_type, payload, id, ?
+            FROM webhook_deliveries WHERE id=? AND endpoint_id=?
+            "#,
+            OffsetDateTime::now_utc(),
+            id,
+            endpoint_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        if response.rows_affected() == 0 {
+            return Ok(None);
+        }
+        let replay_id = response.last_insert_id() as i64;
+        Self::get(endpoint_id, replay_id)
+            .await?
+            .ok_or(AppError::Internal(format!(
+                "Webhook delivery {replay_id} vanished after its creation"
+            )))
+            .map(Some)
+    }
+
+    /// Due deliveries, oldest first, leased for `lease_secs` so that
+    /// concurrent workers never send the same one twice
+    pub async fn claim_due(limit: u64, lease_secs: i64) -> AppResult<Vec<OutgoingDelivery>> {
+        let now = OffsetDateTime::now_utc();
+        let leased_until = now + Duration::seconds(lease_secs);
+        let due_ids = query_scalar!(
+            r#"
+            SELECT id FROM webhook_deliveries
+            WHERE status = 'pending' AND next_attempt_at <= ?
+            ORDER BY next_attempt_at, id
+            LIMIT ?
+            "#,
+            now,
+            limit
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+
+        let mut claimed = Vec::with_capacity(due_ids.len());
+        for id in due_ids {
+            let response = query!(
+                r#"
+                UPDATE webhook_deliveries
+                SET next_attempt_at = ?
+                WHERE id=? AND status = 'pending' AND next_attempt_at <= ?
+                "#,
+                leased_until,
+                id,
+                now
+            )
+            .execute(&*DB_POOL)
+            .await?;
+            if response.rows_affected() == 0 {
+                // taken by another worker meanwhile
+                continue;
+            }
+            let delivery = query_as!(
+                OutgoingDelivery,
+                r#"
+                SELECT d.id, d.event_id, d.event_type, d.payload, d.attempts, e.url, e.secret
+                FROM webhook_deliveries d
+                JOIN webhook_endpoints e ON e.id = d.endpoint_id
+                WHERE d.id=?
+                "#,
+                id
+            )
+            .fetch_optional(&*DB_POOL)
+            .await?;
+            claimed.extend(delivery);
+        }
+        Ok(claimed)
+    }
+
+    pub async fn record_attempt(id: i64, outcome: AttemptOutcome) -> AppResult<()> {
+        let error = outcome
+            .error
+            .map(|error| error.chars().take(ERROR_MAX_LENGTH).collect::<String>());
+        query!(
+            r#"
+            UPDATE webhook_deliveries
+            SET status=?, attempts = attempts + 1, last_attempt_at=?,
+                last_status_code=?, last_error=?, next_attempt_at=?
+            WHERE id=?
+            "#,
+            outcome.status.as_str(),
+            OffsetDateTime::now_utc(),
+            outcome.status_code,
+            error,
+            outcome.next_attempt_at,
+            id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        Ok(())
+    }
+}
+
+impl ApiSchema for WebhookDelivery {
+    fn schema_name() -> String {
+        "WebhookDelivery".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": [
+                "id", "endpoint_id", "event_id", "event_type", "payload", "status",
+                "attempts", "next_attempt_at", "created_at"
+            ],
+            "properties": {
+                "id": { "type": "integer", "format": "int64" },
+                "endpoint_id": { "type": "integer", "format": "int32" },
+                "event_id": { "type": "string", "format": "uuid" },
+                "event_type": { "type": "string", "example": "user.registered" },
+                "payload": { "type": "object", "description": "The body as sent" },
+                "status": { "type": "string", "enum": ["pending", "succeeded", "failed"] },
+                "attempts": { "type": "integer", "format": "int32" },
+                "last_status_code": { "type": "integer", "nullable": true },
+                "last_error": { "type": "string", "nullable": true },
+                "replay_of": { "type": "integer", "format": "int64", "nullable": true },
+                "next_attempt_at": { "type": "integer", "format": "int64" },
+                "last_attempt_at": { "type": "integer", "format": "int64", "nullable": true },
+                "created_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
@@ -0,0 +1,145 @@
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::{query, query_as};
+use time::PrimitiveDateTime;
+
+use crate::{
+    DB_POOL,
+    errors::{AppError, AppResult},
+    services::openapi_service::ApiSchema,
+    webhooks::{webhook_event::WebhookEventType, webhook_signature::generate_secret},
+};
+
+/// A receiver of the webhooks, the secret is only returned by `create`
+#[derive(Serialize)]
+pub struct WebhookEndpoint {
+    pub id: i32,
+    pub url: String,
+    pub event_types: Vec<WebhookEventType>,
+    pub description: Option<String>,
+    /// UNIX timestamp
+    pub created_at: i64,
+}
+
+struct WebhookEndpointRow {
+    id: i32,
+    url: String,
+    event_types: String,
+    description: Option<String>,
+    created_at: PrimitiveDateTime,
+}
+impl From<WebhookEndpointRow> for WebhookEndpoint {
+    fn from(row: WebhookEndpointRow) -> Self {
+        Self {
+            id: row.id,
+            url: row.url,
+            event_types: WebhookEventType::split(&row.event_types),
+            description: row.description,
+            created_at: row.created_at.assume_utc().unix_timestamp(),
+        }
+    }
+}
+
+impl WebhookEndpoint {
+    /// Returns the endpoint and its signing secret, which is not shown again
+    pub async fn create(
+        admin_id: i32,
+        url: &str,
+        event_types: &[WebhookEventType],
+        description: Option<&str>,
+    ) -> AppResult<(Self, String)> {
+        let secret = generate_secret();
+        let response = query!(
+            r#"
+            INSERT INTO webhook_endpoints (url, secret, event_types, description, created_by)
+            VALUES (?,?,?,?,?)
+            "#,
+            url,
+            secret,
+            WebhookEventType::join(event_types),
+            description,
+            admin_id
+        )
+        .execute(&*DB_POOL)
+        .await?;
+        let id = response.last_insert_id() as i32;
+        let endpoint = Self::get(id).await?.ok_or(AppError::Internal(format!(
+            "Webhook endpoint {id} vanished after its creation"
+        )))?;
+        Ok((endpoint, secret))
+    }
+
+    pub async fn get(id: i32) -> AppResult<Option<Self>> {
+        let maybe_row = query_as!(
+            WebhookEndpointRow,
+            r#"
+            SELECT id, url, event_types, description, created_at
+            FROM webhook_endpoints WHERE id=? LIMIT 1
+            "#,
+            id
+        )
+        .fetch_optional(&*DB_POOL)
+        .await?;
+        Ok(maybe_row.map(Self::from))
+    }
+
+    pub async fn list() -> AppResult<Vec<Self>> {
+        let rows = query_as!(
+            WebhookEndpointRow,
+            r#"
+            SELECT id, url, event_types, description, created_at
+            FROM webhook_endpoints ORDER BY id
+            "#
+        )
+        .fetch_all(&*DB_POOL)
+        .await?;
+        Ok(rows.into_iter().map(Self::from).collect())
+    }
+
+    /// Pending deliveries go with it
+    pub async fn delete(id: i32) -> AppResult<Option<Self>> {
+        let Some(endpoint) = Self::get(id).await? else {
+            return Ok(None);
+        };
+        query!("DELETE FROM webhook_endpoints WHERE id=?", id)
+            .execute(&*DB_POOL)
+            .await?;
+        Ok(Some(endpoint))
+    }
+
+    /// Ids of the endpoints listening to `event_type`
+    pub async fn subscribed(event_type: WebhookEventType) -> AppResult<Vec<i32>> {
+        let endpoints = Self::list().await?;
+        Ok(endpoints
+            .into_iter()
+            .filter(|endpoint| endpoint.event_types.contains(&event_type))
+            .map(|endpoint| endpoint.id)
+            .collect())
+    }
+}
+
+impl ApiSchema for WebhookEndpoint {
+    fn schema_name() -> String {
+        "WebhookEndpoint".to_string()
+    }
+    fn schema() -> Value {
+        let events: Vec<_> = WebhookEventType::ALL
+            .iter()
+            .map(WebhookEventType::as_str)
+            .collect();
+        json!({
+            "type": "object",
+            "required": ["id", "url", "event_types", "created_at"],
+            "properties": {
+                "id": { "type": "integer", "format": "int32" },
+                "url": { "type": "string", "format": "uri" },
+                "event_types": {
+                    "type": "array",
+                    "items": { "type": "string", "enum": events }
+                },
+                "description": { "type": "string", "nullable": true },
+                "created_at": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
@@ -0,0 +1,2 @@
+pub mod nodes_controller;
+pub mod nodes_service;
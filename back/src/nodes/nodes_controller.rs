@@ -0,0 +1,89 @@
+use actix_web::web::{Json, Path, Query};
+use actix_web::{delete, get, patch, post};
+
+use crate::{
+    auth::auth_models::{claims::Claims, scope::Scope},
+    models::node_model::Node,
+    nodes::nodes_service::{
+        ChildrenQuery, NewFileNode, NewFolder, NodeCopy, NodeUpdate, PathQuery, copy_node_service,
+        create_folder_service, delete_node_service, get_node_service, list_children_service,
+        place_file_service, resolve_path_service, update_node_service,
+    },
+    shared::{ApiResponse, JsonResponse, PageQuery},
+};
+
+/// Mounted in the `/nodes` scope, for any connected user
+#[post("/folders")]
+pub async fn create_folder(claims: Claims, Json(req): Json<NewFolder>) -> ApiResponse<Node> {
+    claims.require_scope(Scope::FilesWrite)?;
+    let node = create_folder_service(claims.user_id, req).await?;
+    JsonResponse::created()
+        .location(format!("/nodes/{}", node.id))
+        .object(node)
+}
+
+/// Places a file of `/files` in a folder, or at the root
+#[post("/files")]
+pub async fn place_file(claims: Claims, Json(req): Json<NewFileNode>) -> ApiResponse<Node> {
+    claims.require_scope(Scope::FilesWrite)?;
+    let node = place_file_service(claims.user_id, req).await?;
+    JsonResponse::created()
+        .location(format!("/nodes/{}", node.id))
+        .object(node)
+}
+
+/// Children of `?parent_id=`, of the root without it. Folders first, then by `?sort=`
+#[get("")]
+pub async fn list_children(
+    claims: Claims,
+    Query(query): Query<ChildrenQuery>,
+    Query(page): Query<PageQuery>,
+) -> ApiResponse<Vec<Node>> {
+    claims.require_scope(Scope::FilesRead)?;
+    let (nodes, meta) = list_children_service(claims.user_id, query, page).await?;
+    JsonResponse::ok().page(nodes, meta)
+}
+
+/// Before `/{node_id}` so that `resolve` is not taken for an id
+#[get("/resolve")]
+pub async fn resolve_path(claims: Claims, Query(query): Query<PathQuery>) -> ApiResponse<Node> {
+    claims.require_scope(Scope::FilesRead)?;
+    JsonResponse::ok().object(resolve_path_service(claims.user_id, query).await?)
+}
+
+#[get("/{node_id}")]
+pub async fn get_node(claims: Claims, node_id: Path<i32>) -> ApiResponse<Node> {
+    claims.require_scope(Scope::FilesRead)?;
+    JsonResponse::ok().object(get_node_service(claims.user_id, node_id.into_inner()).await?)
+}
+
+/// Renames and/or moves
+#[patch("/{node_id}")]
+pub async fn update_node(
+    claims: Claims,
+    node_id: Path<i32>,
+    Json(req): Json<NodeUpdate>,
+) -> ApiResponse<Node> {
+    claims.require_scope(Scope::FilesWrite)?;
+    JsonResponse::ok().object(update_node_service(claims.user_id, node_id.into_inner(), req).await?)
+}
+
+#[post("/{node_id}/copy")]
+pub async fn copy_node(
+    claims: Claims,
+    node_id: Path<i32>,
+    Json(req): Json<NodeCopy>,
+) -> ApiResponse<Node> {
+    claims.require_scope(Scope::FilesWrite)?;
+    let node = copy_node_service(claims.user_id, node_id.into_inner(), req).await?;
+    JsonResponse::created()
+        .location(format!("/nodes/{}", node.id))
+        .object(node)
+}
+
+/// Folders are deleted with everything inside, files included
+#[delete("/{node_id}")]
+pub async fn delete_node(claims: Claims, node_id: Path<i32>) -> ApiResponse<Node> {
+    claims.require_scope(Scope::FilesWrite)?;
+    JsonResponse::ok().object(delete_node_service(claims.user_id, node_id.into_inner()).await?)
+}
@@ -0,0 +1,206 @@
+use serde::Deserialize;
+
+use crate::{
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    models::{
+        file_model::StoredFile,
+        node_model::{Node, NodeKind, NodeSort, SortOrder},
+    },
+    shared::{PageMeta, PageQuery, patch_field},
+    utils::env_utils::env_or,
+};
+
+const NODE_NAME_MAX_LENGTH: usize = 255;
+const NODES_PER_PAGE: u32 = 100;
+const NODES_MAX_PER_PAGE: u32 = 500;
+
+/// Most nodes one copy creates, 1000 unless `NODES_COPY_MAX` says otherwise
+fn copy_max_nodes() -> usize {
+    env_or("NODES_COPY_MAX", 1000)
+}
+
+#[derive(Deserialize)]
+pub struct NewFolder {
+    #[serde(default)]
+    parent_id: Option<i32>,
+    name: String,
+}
+
+/// Places a file of `/files` in the tree, under its own name unless `name` is given
+#[derive(Deserialize)]
+pub struct NewFileNode {
+    #[serde(default)]
+    parent_id: Option<i32>,
+    file_id: i32,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// `?parent_id=&sort=&order=`, the root without `parent_id`
+#[derive(Deserialize)]
+pub struct ChildrenQuery {
+    parent_id: Option<i32>,
+    #[serde(default)]
+    sort: NodeSort,
+    #[serde(default)]
+    order: SortOrder,
+}
+
+#[derive(Deserialize)]
+pub struct PathQuery {
+    path: String,
+}
+
+/// Absent fields are kept, a `null` parent moves the node to the root
+#[derive(Deserialize)]
+pub struct NodeUpdate {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default, deserialize_with = "patch_field")]
+    parent_id: Option<Option<i32>>,
+}
+
+/// Copied in the same folder unless `parent_id` is given, `null` being the root
+#[derive(Deserialize)]
+pub struct NodeCopy {
+    #[serde(default, deserialize_with = "patch_field")]
+    parent_id: Option<Option<i32>>,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Names are single path segments: no separator, no control character
+fn validate_node_name(raw: &str) -> AppResult<String> {
+    let name = raw.trim();
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.chars().count() > NODE_NAME_MAX_LENGTH
+        || name
+            .chars()
+            .any(|c| c == '/' || c == '\\' || c.is_control())
+    {
+        return Err(AppError::invalid_field("name", ErrorCode::ValidationFailed));
+    }
+    Ok(name.to_string())
+}
+
+async fn get_node(user_id: i32, node_id: i32) -> AppResult<Node> {
+    Node::get(user_id, node_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))
+}
+
+pub async fn create_folder_service(user_id: i32, req: NewFolder) -> AppResult<Node> {
+    let name = validate_node_name(&req.name)?;
+    Node::create_folder(user_id, req.parent_id, &name).await
+}
+
+pub async fn place_file_service(user_id: i32, req: NewFileNode) -> AppResult<Node> {
+    let file = StoredFile::get(user_id, req.file_id)
+        .await?
+        .ok_or(AppError::invalid_field("file_id", ErrorCode::NotFound))?;
+    let name = validate_node_name(req.name.as_deref().unwrap_or(&file.name))?;
+    Node::place_file(user_id, req.parent_id, &name, file.id).await
+}
+
+pub async fn list_children_service(
+    user_id: i32,
+    query: ChildrenQuery,
+    page: PageQuery,
+) -> AppResult<(Vec<Node>, PageMeta)> {
+    if let Some(parent_id) = query.parent_id {
+        let parent = get_node(user_id, parent_id).await?;
+        if parent.kind != NodeKind::Folder {
+            return Err(AppError::invalid_field(
+                "parent_id",
+                ErrorCode::NodeParentInvalid,
+            ));
+        }
+    }
+    let (page, per_page) = page.resolve(NODES_PER_PAGE, NODES_MAX_PER_PAGE);
+    let total = Node::count(user_id, query.parent_id).await?;
+    let offset = (page as u64 - 1) * per_page as u64;
+    let nodes = Node::list(
+        user_id,
+        query.parent_id,
+        query.sort,
+        query.order,
+        per_page as u64,
+        offset,
+    )
+    .await?;
+    Ok((nodes, PageMeta::new(page, per_page, total)))
+}
+
+pub async fn get_node_service(user_id: i32, node_id: i32) -> AppResult<Node> {
+    get_node(user_id, node_id).await
+}
+
+/// `/docs/2026/report.pdf`, names compared regardless of case. Empty segments are
+/// skipped, the root itself is not a node
+pub async fn resolve_path_service(user_id: i32, query: PathQuery) -> AppResult<Node> {
+    let segments: Vec<&str> = query
+        .path
+        .split('/')
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    if segments.is_empty() {
+        return Err(AppError::invalid_field("path", ErrorCode::ValidationFailed));
+    }
+    let mut current: Option<Node> = None;
+    for segment in segments {
+        if current
+            .as_ref()
+            .is_some_and(|node| node.kind != NodeKind::Folder)
+        {
+            return Err(AppError::NotFound(ErrorCode::NotFound));
+        }
+        let parent_id = current.as_ref().map(|node| node.id);
+        current = Some(
+            Node::child(user_id, parent_id, segment)
+                .await?
+                .ok_or(AppError::NotFound(ErrorCode::NotFound))?,
+        );
+    }
+    current.ok_or(AppError::NotFound(ErrorCode::NotFound))
+}
+
+/// Renames and/or moves the node
+pub async fn update_node_service(user_id: i32, node_id: i32, req: NodeUpdate) -> AppResult<Node> {
+    let name = req.name.as_deref().map(validate_node_name).transpose()?;
+    Node::update(user_id, node_id, name.as_deref(), req.parent_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))
+}
+
+/// Deep copy for folders, in the same folder unless told otherwise
+pub async fn copy_node_service(user_id: i32, node_id: i32, req: NodeCopy) -> AppResult<Node> {
+    let source = get_node(user_id, node_id).await?;
+    let parent_id = req.parent_id.unwrap_or(source.parent_id);
+    let name = validate_node_name(req.name.as_deref().unwrap_or(&source.name))?;
+    Node::copy(user_id, node_id, parent_id, &name, copy_max_nodes())
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))
+}
+
+/// Best effort, a file left behind is still listed in `/files`
+async fn delete_files(user_id: i32, file_ids: &[i32]) {
+    for &file_id in file_ids {
+        if let Err(err) = StoredFile::delete(user_id, file_id).await {
+            eprintln!("File {file_id} kept after its node was deleted: {err}");
+        }
+    }
+}
+
+/// Deletes the node, for a folder everything inside too, files included
+pub async fn delete_node_service(user_id: i32, node_id: i32) -> AppResult<Node> {
+    let node = get_node(user_id, node_id).await?;
+    let file_ids = Node::delete(user_id, node_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))?;
+    delete_files(user_id, &file_ids).await;
+    Ok(node)
+}
@@ -0,0 +1,2 @@
+pub mod organizations_controller;
+pub mod organizations_service;
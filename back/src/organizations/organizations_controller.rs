@@ -0,0 +1,99 @@
+use actix_web::web::{Json, Path};
+use actix_web::{delete, get, post, put};
+
+use crate::{
+    auth::auth_models::claims::Claims,
+    models::{
+        organization_invitation_model::OrganizationInvitation,
+        organization_model::{Member, Organization},
+    },
+    organizations::organizations_service::{
+        InvitationKey, NewOrganization, NewOrganizationInvitation, RoleChange,
+        accept_invitation_service, change_member_role_service, create_organization_service,
+        invite_member_service, list_members_service, list_organizations_service,
+        remove_member_service, switch_organization_service,
+    },
+    shared::{ApiResponse, JsonResponse},
+};
+
+/// Mounted in the `/orgs` scope, for any connected user.
+/// Tokens and impersonations never act on organizations.
+#[post("")]
+pub async fn create_organization(
+    claims: Claims,
+    Json(req): Json<NewOrganization>,
+) -> ApiResponse<Organization> {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    let organization = create_organization_service(claims.user_id, req).await?;
+    JsonResponse::created()
+        .location(format!("/orgs/{}", organization.id))
+        .object(organization)
+}
+
+#[get("")]
+pub async fn list_organizations(claims: Claims) -> ApiResponse<Vec<Organization>> {
+    claims.require_interactive()?;
+    JsonResponse::ok().object(list_organizations_service(claims.user_id).await?)
+}
+
+/// Before `/{org_id}/...` so that `invitations` is not taken for an id
+#[post("/invitations/accept")]
+pub async fn accept_organization_invitation(
+    claims: Claims,
+    Json(key): Json<InvitationKey>,
+) -> ApiResponse<Organization> {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    JsonResponse::ok().object(accept_invitation_service(claims.user_id, key).await?)
+}
+
+#[get("/{org_id}/members")]
+pub async fn list_members(claims: Claims, org_id: Path<i32>) -> ApiResponse<Vec<Member>> {
+    claims.require_interactive()?;
+    JsonResponse::ok().object(list_members_service(claims.user_id, org_id.into_inner()).await?)
+}
+
+#[post("/{org_id}/invitations")]
+pub async fn invite_member(
+    claims: Claims,
+    org_id: Path<i32>,
+    Json(req): Json<NewOrganizationInvitation>,
+) -> ApiResponse<OrganizationInvitation> {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    let invitation = invite_member_service(claims.user_id, org_id.into_inner(), req).await?;
+    JsonResponse::created().object(invitation)
+}
+
+#[put("/{org_id}/members/{user_id}")]
+pub async fn change_member_role(
+    claims: Claims,
+    path: Path<(i32, i32)>,
+    Json(req): Json<RoleChange>,
+) -> ApiResponse<Vec<Member>> {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    let (org_id, member_id) = path.into_inner();
+    JsonResponse::ok()
+        .object(change_member_role_service(claims.user_id, org_id, member_id, req).await?)
+}
+
+/// Removing oneself leaves the organization
+#[delete("/{org_id}/members/{user_id}")]
+pub async fn remove_member(claims: Claims, path: Path<(i32, i32)>) -> ApiResponse {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    let (org_id, member_id) = path.into_inner();
+    remove_member_service(claims.user_id, org_id, member_id).await?;
+    JsonResponse::ok().empty()
+}
+
+/// Returns a token whose claims carry the organization, the refresh token follows it
+#[post("/{org_id}/switch")]
+pub async fn switch_organization(claims: Claims, org_id: Path<i32>) -> ApiResponse {
+    claims.require_interactive()?;
+    claims.require_not_impersonated()?;
+    JsonResponse::ok()
+        .token(switch_organization_service(claims.user_id, org_id.into_inner()).await?)
+}
@@ -0,0 +1,210 @@
+use serde::Deserialize;
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    APP_URL,
+    auth::auth_models::{
+        email::{Email, RawEmail},
+        token::Token,
+    },
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    models::{
+        organization_invitation_model::OrganizationInvitation,
+        organization_model::{Member, MembershipChange, OrgRole, Organization},
+        user_model::User,
+    },
+    utils::{
+        email_utils::{escape_html, mail_date, send_mail},
+        env_utils::env_or,
+    },
+};
+
+const ORGANIZATION_NAME_MAX_LENGTH: usize = 100;
+
+fn invitation_validity() -> Duration {
+    Duration::days(env_or("ORGANIZATION_INVITATION_DAYS", 7i64).max(1))
+}
+
+#[derive(Deserialize)]
+pub struct NewOrganization {
+    name: String,
+}
+
+#[derive(Deserialize)]
+pub struct NewOrganizationInvitation {
+    email: RawEmail,
+    role: OrgRole,
+}
+
+#[derive(Deserialize)]
+pub struct RoleChange {
+    role: OrgRole,
+}
+
+#[derive(Deserialize)]
+pub struct InvitationKey {
+    key: String,
+}
+
+/// Role of `user_id` read from the database, not from its possibly older claims.
+/// Non members are told the organization does not exist.
+async fn require_role(org_id: i32, user_id: i32, min: OrgRole) -> AppResult<OrgRole> {
+    let role = Member::role(org_id, user_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))?;
+    if !role.at_least(min) {
+        return Err(AppError::Forbiden(ErrorCode::OrganizationRoleInsufficient));
+    }
+    Ok(role)
+}
+
+/// Only owners make other owners, or touch them
+fn require_can_manage(own_role: OrgRole, target_role: OrgRole) -> AppResult<()> {
+    if target_role == OrgRole::Owner && own_role != OrgRole::Owner {
+        return Err(AppError::Forbiden(ErrorCode::OrganizationRoleInsufficient));
+    }
+    Ok(())
+}
+
+fn membership_result(change: MembershipChange) -> AppResult<()> {
+    match change {
+        MembershipChange::Done => Ok(()),
+        MembershipChange::NotMember => Err(AppError::NotFound(ErrorCode::NotFound)),
+        MembershipChange::LastOwner => Err(AppError::Conflict(ErrorCode::OrganizationLastOwner)),
+    }
+}
+
+/// The creator becomes owner, and the organization its active one if it had none
+pub async fn create_organization_service(
+    user_id: i32,
+    new_organization: NewOrganization,
+) -> AppResult<Organization> {
+    let name = new_organization.name.trim();
+    if name.is_empty()
+        || name.chars().count() > ORGANIZATION_NAME_MAX_LENGTH
+        || name.chars().any(char::is_control)
+    {
+        return Err(AppError::invalid_field("name", ErrorCode::ValidationFailed));
+    }
+    let organization = Organization::create(name, user_id).await?;
+    if User::get(user_id).await?.active_org_id.is_none() {
+        User::set_active_org(user_id, organization.id).await?;
+    }
+    Ok(organization)
+}
+
+pub async fn list_organizations_service(user_id: i32) -> AppResult<Vec<Organization>> {
+    Organization::list(user_id).await
+}
+
+pub async fn list_members_service(user_id: i32, org_id: i32) -> AppResult<Vec<Member>> {
+    require_role(org_id, user_id, OrgRole::Viewer).await?;
+    Member::list(org_id).await
+}
+
+pub async fn invite_member_service(
+    user_id: i32,
+    org_id: i32,
+    invitation: NewOrganizationInvitation,
+) -> AppResult<OrganizationInvitation> {
+    let own_role = require_role(org_id, user_id, OrgRole::Admin).await?;
+    require_can_manage(own_role, invitation.role)?;
+    let email = invitation.email.verify()?;
+    let organization = Organization::get(org_id, user_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))?;
+
+    let expires_at = OffsetDateTime::now_utc() + invitation_validity();
+    let (created, key) =
+        OrganizationInvitation::create(org_id, &email, invitation.role, user_id, expires_at)
+            .await?;
+    let inviter = User::get(user_id).await?;
+    send_organization_invitation_email(
+        &email,
+        inviter.email.as_ref(),
+        &organization.name,
+        &key,
+        expires_at,
+    )?;
+    Ok(created)
+}
+
+fn send_organization_invitation_email(
+    email: &Email,
+    inviter: &str,
+    organization: &str,
+    key: &str,
+    expires_at: OffsetDateTime,
+) -> AppResult<()> {
+    let join_url = format!("{APP_URL}/orgs/join?token={key}");
+
+    let html_template = include_str!("../templates/organization_invitation_email.html");
+    let html = html_template
+        .replace("__JOIN_URL__", &join_url)
+        .replace("__INVITER__", &escape_html(inviter))
+        .replace("__ORGANIZATION__", &escape_html(organization))
+        .replace("__EXPIRATION_DATE__", &mail_date(expires_at));
+
+    send_mail(email, format!("Rejoignez {organization}"), html)
+}
+
+/// Only the invited address may accept, an existing member keeps its role
+pub async fn accept_invitation_service(
+    user_id: i32,
+    invitation_key: InvitationKey,
+) -> AppResult<Organization> {
+    let user = User::get(user_id).await?;
+    let accepted = OrganizationInvitation::accept(&invitation_key.key, &user.email)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::TokenInvalid))?;
+    Member::add(accepted.org_id, user_id, accepted.role).await?;
+    if user.active_org_id.is_none() {
+        User::set_active_org(user_id, accepted.org_id).await?;
+    }
+    Organization::get(accepted.org_id, user_id)
+        .await?
+        .ok_or(AppError::Internal(format!(
+            "User {user_id} not a member of organization {} after joining it",
+            accepted.org_id
+        )))
+}
+
+pub async fn change_member_role_service(
+    user_id: i32,
+    org_id: i32,
+    member_id: i32,
+    change: RoleChange,
+) -> AppResult<Vec<Member>> {
+    let own_role = require_role(org_id, user_id, OrgRole::Admin).await?;
+    let member_role = Member::role(org_id, member_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))?;
+    require_can_manage(own_role, member_role)?;
+    require_can_manage(own_role, change.role)?;
+    membership_result(Member::set_role(org_id, member_id, change.role).await?)?;
+    Member::list(org_id).await
+}
+
+/// Admins remove members, anyone may leave
+pub async fn remove_member_service(user_id: i32, org_id: i32, member_id: i32) -> AppResult<()> {
+    if member_id != user_id {
+        let own_role = require_role(org_id, user_id, OrgRole::Admin).await?;
+        let member_role = Member::role(org_id, member_id)
+            .await?
+            .ok_or(AppError::NotFound(ErrorCode::NotFound))?;
+        require_can_manage(own_role, member_role)?;
+    }
+    membership_result(Member::remove(org_id, member_id).await?)
+}
+
+/// New access token whose claims carry `org_id` and the current role there
+pub async fn switch_organization_service(user_id: i32, org_id: i32) -> AppResult<Token> {
+    require_role(org_id, user_id, OrgRole::Viewer).await?;
+    User::set_active_org(user_id, org_id).await?;
+    User::get_token(user_id)
+        .await?
+        .ok_or(AppError::Internal(format!(
+            "Connected user {user_id} has no token"
+        )))
+}
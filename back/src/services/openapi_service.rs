@@ -1,14 +1,222 @@
 use std::fs::read_to_string;
 
 use actix_web::HttpResponse;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    account::account_service::{CreatedToken, DeletionStatus, PhoneStatus},
+    admin::admin_service::{ImpersonationSession, ImpersonationStatus},
+    auth::{
+        auth_models::{csrf_token::CsrfToken, sms_challenge::SmsChallenge, token::Token},
+        password_policy::PasswordPolicy,
+        registration_policy::RegistrationPolicy,
+    },
+    models::{
+        audit_event_model::AuditRecord,
+        blob_model::CorruptedBlob,
+        data_export_model::DataExport,
+        file_model::StoredFile,
+        invitation_model::Invitation,
+        node_model::Node,
+        organization_invitation_model::OrganizationInvitation,
+        organization_model::{Member, Organization},
+        personal_access_token_model::PersonalAccessToken,
+        profile_model::{Profile, UserView},
+        webhook_delivery_model::WebhookDelivery,
+        webhook_endpoint_model::WebhookEndpoint,
+    },
+    shared::{JsonResponse, Message, PageMeta},
+    webhooks::webhook_service::CreatedWebhook,
+};
+
+/// Types able to describe themselves as an OpenAPI component schema.
+/// Generated schemas are merged into `openapi.yaml` when it is served.
+pub trait ApiSchema {
+    fn schema_name() -> String;
+    fn schema() -> Value;
+
+    fn schema_ref() -> Value {
+        json!({ "$ref": format!("#/components/schemas/{}", Self::schema_name()) })
+    }
+    /// Insert the schema and every schema it references
+    fn register(schemas: &mut Map<String, Value>) {
+        schemas.insert(Self::schema_name(), Self::schema());
+    }
+}
+
+impl ApiSchema for () {
+    fn schema_name() -> String {
+        "Empty".to_string()
+    }
+    fn schema() -> Value {
+        json!({ "nullable": true, "description": "No payload" })
+    }
+}
+impl ApiSchema for Token {
+    fn schema_name() -> String {
+        "Token".to_string()
+    }
+    fn schema() -> Value {
+        json!({ "type": "string", "description": "Signed JWT, see the Claims schema for its payload" })
+    }
+}
+impl ApiSchema for Message {
+    fn schema_name() -> String {
+        "Message".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["code", "message"],
+            "properties": {
+                "code": {
+                    "type": "string",
+                    "enum": [
+                        "USER_CREATED",
+                        "USER_NOT_VERIFIED",
+                        "USER_VERIFIED",
+                        "USER_LOGGED_OUT",
+                        "PHONE_CODE_SENT",
+                        "PHONE_VERIFIED"
+                    ]
+                },
+                "message": { "type": "string" }
+            }
+        })
+    }
+}
+impl ApiSchema for PageMeta {
+    fn schema_name() -> String {
+        "PageMeta".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["page", "per_page", "total", "total_pages"],
+            "properties": {
+                "page": { "type": "integer", "format": "int32" },
+                "per_page": { "type": "integer", "format": "int32" },
+                "total": { "type": "integer", "format": "int64" },
+                "total_pages": { "type": "integer", "format": "int64" }
+            }
+        })
+    }
+}
+impl<T: ApiSchema> ApiSchema for Vec<T> {
+    fn schema_name() -> String {
+        format!("{}List", T::schema_name())
+    }
+    fn schema() -> Value {
+        json!({ "type": "array", "items": T::schema_ref() })
+    }
+    fn register(schemas: &mut Map<String, Value>) {
+        T::register(schemas);
+        schemas.insert(Self::schema_name(), Self::schema());
+    }
+}
+impl<T: ApiSchema> ApiSchema for JsonResponse<T> {
+    fn schema_name() -> String {
+        match T::schema_name().as_str() {
+            "Empty" => "ApiResponse".to_string(),
+            name => format!("ApiResponse{name}"),
+        }
+    }
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["type"],
+            "properties": {
+                "type": { "type": "string", "enum": ["Token", "Message", "Object"] },
+                "data": {
+                    "oneOf": [Token::schema_ref(), Message::schema_ref(), T::schema_ref()]
+                },
+                "meta": PageMeta::schema_ref()
+            }
+        })
+    }
+    fn register(schemas: &mut Map<String, Value>) {
+        Token::register(schemas);
+        Message::register(schemas);
+        PageMeta::register(schemas);
+        T::register(schemas);
+        schemas.insert(Self::schema_name(), Self::schema());
+    }
+}
+
+/// Every response payload exposed by the API, register new ones here
+fn documented_schemas() -> Map<String, Value> {
+    let mut schemas = Map::new();
+    JsonResponse::<()>::register(&mut schemas);
+    JsonResponse::<PasswordPolicy>::register(&mut schemas);
+    JsonResponse::<RegistrationPolicy>::register(&mut schemas);
+    JsonResponse::<SmsChallenge>::register(&mut schemas);
+    JsonResponse::<PhoneStatus>::register(&mut schemas);
+    JsonResponse::<CsrfToken>::register(&mut schemas);
+    JsonResponse::<CreatedToken>::register(&mut schemas);
+    JsonResponse::<PersonalAccessToken>::register(&mut schemas);
+    JsonResponse::<Vec<PersonalAccessToken>>::register(&mut schemas);
+    JsonResponse::<Invitation>::register(&mut schemas);
+    JsonResponse::<ImpersonationSession>::register(&mut schemas);
+    JsonResponse::<ImpersonationStatus>::register(&mut schemas);
+    JsonResponse::<Vec<Invitation>>::register(&mut schemas);
+    JsonResponse::<Vec<AuditRecord>>::register(&mut schemas);
+    JsonResponse::<CreatedWebhook>::register(&mut schemas);
+    JsonResponse::<WebhookEndpoint>::register(&mut schemas);
+    JsonResponse::<Vec<WebhookEndpoint>>::register(&mut schemas);
+    JsonResponse::<WebhookDelivery>::register(&mut schemas);
+    JsonResponse::<Vec<WebhookDelivery>>::register(&mut schemas);
+    JsonResponse::<DeletionStatus>::register(&mut schemas);
+    JsonResponse::<DataExport>::register(&mut schemas);
+    JsonResponse::<Profile>::register(&mut schemas);
+    JsonResponse::<UserView>::register(&mut schemas);
+    JsonResponse::<Organization>::register(&mut schemas);
+    JsonResponse::<Vec<Organization>>::register(&mut schemas);
+    JsonResponse::<Vec<Member>>::register(&mut schemas);
+    JsonResponse::<OrganizationInvitation>::register(&mut schemas);
+    JsonResponse::<StoredFile>::register(&mut schemas);
+    JsonResponse::<Vec<StoredFile>>::register(&mut schemas);
+    JsonResponse::<Node>::register(&mut schemas);
+    JsonResponse::<Vec<CorruptedBlob>>::register(&mut schemas);
+    JsonResponse::<Vec<Node>>::register(&mut schemas);
+    schemas
+}
+
+fn build_document() -> Result<Value, String> {
+    let file = read_to_string("openapi.yaml")
+        .map_err(|err| format!("An error occurs when opening the yaml file: {err}"))?;
+    let mut document: Value = serde_yaml::from_str(&file)
+        .map_err(|err| format!("An error occurs when parsing the yaml file: {err}"))?;
+
+    let schemas = document
+        .as_object_mut()
+        .ok_or("openapi.yaml root is not a mapping")?
+        .entry("components")
+        .or_insert_with(|| json!({}))
+        .as_object_mut()
+        .ok_or("components is not a mapping")?
+        .entry("schemas")
+        .or_insert_with(|| json!({}))
+        .as_object_mut()
+        .ok_or("components.schemas is not a mapping")?;
+    schemas.extend(documented_schemas());
+
+    Ok(document)
+}
 
 #[actix_web::get("/openapi.yaml")]
 async fn openapi_yaml() -> HttpResponse {
-    match read_to_string("openapi.yaml") {
+    match build_document().and_then(|doc| serde_yaml::to_string(&doc).map_err(|e| e.to_string())) {
         Ok(file) => HttpResponse::Ok()
             .content_type("application/yaml")
             .body(file),
-        Err(err) => HttpResponse::NotFound()
-            .body(format!("An error occurs when opening the yaml file: {err}")),
+        Err(err) => HttpResponse::NotFound().body(err),
+    }
+}
+
+#[actix_web::get("/swagger.json")]
+async fn openapi_json() -> HttpResponse {
+    match build_document() {
+        Ok(document) => HttpResponse::Ok().json(document),
+        Err(err) => HttpResponse::NotFound().body(err),
     }
 }
@@ -1,62 +1,185 @@
-use actix_web::{HttpResponse, Responder, http::StatusCode};
-use fancy_regex::Regex;
-use serde::Serialize;
-use std::{
-    sync::LazyLock,
-    time::{SystemTime, UNIX_EPOCH},
+use actix_web::{
+    HttpRequest, HttpResponse, Responder,
+    http::{
+        StatusCode,
+        header::{
+            self, CacheControl, CacheDirective, EntityTag, Header, HeaderName, HeaderValue,
+            IfNoneMatch, TryIntoHeaderPair,
+        },
+    },
 };
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::{
     auth::auth_models::token::Token,
-    constants::messages::TOKEN_INVALID,
+    constants::codes::{ErrorCode, MessageCode},
     errors::{AppError, AppResult},
 };
 
 pub type DynResult<T> = Result<T, Box<dyn std::error::Error>>;
-pub type APIResponse = AppResult<JsonResponse>;
+/// Return type of every handler, `T` is the payload of [`JsonData::Object`]
+pub type ApiResponse<T = ()> = AppResult<JsonResponse<T>>;
 
 #[derive(Serialize)]
 #[serde(tag = "type", content = "data")]
-pub enum JsonData {
+pub enum JsonData<T> {
     Token(Token),
-    Message(String),
-    Object(String),
+    Message(Message),
+    Object(T),
     Empty,
 }
 
-#[repr(transparent)]
+#[derive(Serialize)]
+pub struct Message {
+    code: MessageCode,
+    message: &'static str,
+}
+impl From<MessageCode> for Message {
+    fn from(code: MessageCode) -> Self {
+        Self {
+            code,
+            message: code.message(),
+        }
+    }
+}
+
+/// Pagination metadata sent next to `data` when the payload is a page of a collection
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct PageMeta {
+    pub page: u32,
+    pub per_page: u32,
+    pub total: u64,
+    pub total_pages: u64,
+}
+impl PageMeta {
+    pub fn new(page: u32, per_page: u32, total: u64) -> Self {
+        let total_pages = if per_page == 0 {
+            0
+        } else {
+            total.div_ceil(per_page as u64)
+        };
+        Self {
+            page,
+            per_page,
+            total,
+            total_pages,
+        }
+    }
+}
+
+/// `?page=&per_page=` of a paginated listing, pages start at 1
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+pub struct PageQuery {
+    pub page: Option<u32>,
+    pub per_page: Option<u32>,
+}
+impl PageQuery {
+    /// `(page, per_page)`, out of range values are clamped
+    pub fn resolve(&self, default_per_page: u32, max_per_page: u32) -> (u32, u32) {
+        let page = self.page.unwrap_or(1).max(1);
+        let per_page = self
+            .per_page
+            .unwrap_or(default_per_page)
+            .clamp(1, max_per_page);
+        (page, per_page)
+    }
+}
+
+/// `deserialize_with` of the optional fields of a PATCH body, with `#[serde(default)]`:
+/// absent gives `None`, `null` gives `Some(None)`
+pub fn patch_field<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Wire format: `{"type": .., "data": .., "meta": ..}`, `data` is serialized exactly once
+#[derive(Serialize)]
+struct Envelope<T> {
+    #[serde(flatten)]
+    data: JsonData<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<PageMeta>,
+}
+
 pub struct JsonResponseBuilder {
     status_code: StatusCode,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    etag: Option<EntityTag>,
+    header_error: Option<String>,
 }
 impl JsonResponseBuilder {
-    pub fn token(self, token: Token) -> APIResponse {
-        Ok(JsonResponse {
-            data: JsonData::Token(token),
-            status_code: self.status_code,
-        })
+    fn new(status_code: StatusCode) -> Self {
+        Self {
+            status_code,
+            headers: Vec::new(),
+            etag: None,
+            header_error: None,
+        }
     }
-    pub fn message(self, message: impl AsRef<str>) -> APIResponse {
-        Ok(JsonResponse {
-            data: JsonData::Message(message.as_ref().to_string()),
-            status_code: self.status_code,
-        })
+    pub fn insert_header(mut self, header: impl TryIntoHeaderPair) -> Self {
+        match header.try_into_pair() {
+            Ok(pair) => self.headers.push(pair),
+            Err(err) => {
+                let err: actix_web::error::HttpError = err.into();
+                self.header_error
+                    .get_or_insert_with(|| format!("Invalid response header: {err}"));
+            }
+        }
+        self
     }
-    pub fn object(self, object: impl Serialize) -> APIResponse {
-        Ok(JsonResponse {
-            data: JsonData::Object(serde_json::to_string(&object)?),
-            status_code: self.status_code,
-        })
+    /// Strong validator, a matching `If-None-Match` turns the response into a `304`
+    pub fn etag(mut self, tag: impl Into<String>) -> Self {
+        self.etag = Some(EntityTag::new_strong(tag.into()));
+        self
+    }
+    pub fn location(self, uri: impl AsRef<str>) -> Self {
+        self.insert_header((header::LOCATION, uri.as_ref().to_string()))
     }
-    pub fn empty(self) -> APIResponse {
+    pub fn cache_control(self, directives: Vec<CacheDirective>) -> Self {
+        self.insert_header(CacheControl(directives))
+    }
+    pub fn no_store(self) -> Self {
+        self.cache_control(vec![CacheDirective::NoStore])
+    }
+
+    fn finish<T>(self, data: JsonData<T>, meta: Option<PageMeta>) -> ApiResponse<T> {
+        if let Some(err) = self.header_error {
+            return Err(AppError::Internal(err));
+        }
         Ok(JsonResponse {
-            data: JsonData::Empty,
+            data,
+            meta,
             status_code: self.status_code,
+            headers: self.headers,
+            etag: self.etag,
         })
     }
+    pub fn token<T>(self, token: Token) -> ApiResponse<T> {
+        self.finish(JsonData::Token(token), None)
+    }
+    pub fn message<T>(self, code: MessageCode) -> ApiResponse<T> {
+        self.finish(JsonData::Message(code.into()), None)
+    }
+    pub fn object<T: Serialize>(self, object: T) -> ApiResponse<T> {
+        self.finish(JsonData::Object(object), None)
+    }
+    pub fn page<T: Serialize>(self, items: Vec<T>, meta: PageMeta) -> ApiResponse<Vec<T>> {
+        self.finish(JsonData::Object(items), Some(meta))
+    }
+    pub fn empty<T>(self) -> ApiResponse<T> {
+        self.finish(JsonData::Empty, None)
+    }
 }
-pub struct JsonResponse {
-    data: JsonData,
+pub struct JsonResponse<T = ()> {
+    data: JsonData<T>,
+    meta: Option<PageMeta>,
     status_code: StatusCode,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    etag: Option<EntityTag>,
 }
 
 impl JsonResponse {
@@ -67,45 +190,62 @@ impl JsonResponse {
                 status_code
             ))
         })?;
-        Ok(JsonResponseBuilder { status_code })
+        Ok(JsonResponseBuilder::new(status_code))
     }
     pub fn status(status_code: StatusCode) -> JsonResponseBuilder {
-        JsonResponseBuilder { status_code }
+        JsonResponseBuilder::new(status_code)
     }
     pub fn ok() -> JsonResponseBuilder {
-        JsonResponseBuilder {
-            status_code: StatusCode::OK,
-        }
+        JsonResponseBuilder::new(StatusCode::OK)
+    }
+    pub fn created() -> JsonResponseBuilder {
+        JsonResponseBuilder::new(StatusCode::CREATED)
     }
     pub fn not_found() -> JsonResponseBuilder {
-        JsonResponseBuilder {
-            status_code: StatusCode::NOT_FOUND,
-        }
+        JsonResponseBuilder::new(StatusCode::NOT_FOUND)
     }
     pub fn unauthorized() -> JsonResponseBuilder {
-        JsonResponseBuilder {
-            status_code: StatusCode::UNAUTHORIZED,
-        }
+        JsonResponseBuilder::new(StatusCode::UNAUTHORIZED)
     }
-    pub fn token(token: Token) -> APIResponse {
-        Ok(JsonResponse {
-            data: JsonData::Token(token),
-            status_code: StatusCode::OK,
-        })
+    pub fn token(token: Token) -> ApiResponse {
+        Self::ok().token(token)
     }
-    pub fn invalid_token() -> APIResponse {
-        Ok(JsonResponse {
-            data: JsonData::Message(TOKEN_INVALID.to_owned()),
-            status_code: StatusCode::UNAUTHORIZED,
-        })
+    pub fn invalid_token() -> ApiResponse {
+        Err(AppError::Unauthorized(ErrorCode::TokenInvalid))
     }
 }
-impl Responder for JsonResponse {
+impl<T: Serialize> Responder for JsonResponse<T> {
     type Body = actix_web::body::BoxBody;
-    fn respond_to(self, _req: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        let not_modified = self.etag.as_ref().is_some_and(|etag| {
+            match IfNoneMatch::parse(req) {
+                Ok(IfNoneMatch::Any) => true,
+                Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+                Err(_) => false,
+            }
+        });
+        let status_code = if not_modified {
+            StatusCode::NOT_MODIFIED
+        } else {
+            self.status_code
+        };
+
+        let mut builder = HttpResponse::build(status_code);
+        for pair in self.headers {
+            builder.insert_header(pair);
+        }
+        if let Some(etag) = self.etag {
+            builder.insert_header(header::ETag(etag));
+        }
+        if not_modified {
+            return builder.finish();
+        }
         match self.data {
-            JsonData::Empty => HttpResponse::build(self.status_code).finish(),
-            others => HttpResponse::build(self.status_code).json(others),
+            JsonData::Empty => builder.finish(),
+            data => builder.json(Envelope {
+                data,
+                meta: self.meta,
+            }),
         }
     }
 }
@@ -116,15 +256,3 @@ pub fn get_now_unix() -> u64 {
         .unwrap()
         .as_secs()
 }
-pub fn is_valid_email(email: &str) -> bool {
-    EMAIL_RE.is_match(email).unwrap_or(false)
-}
-
-pub fn is_valid_password(password: &str) -> bool {
-    PASSWORD_RE.is_match(password).unwrap_or(false)
-}
-pub static EMAIL_RE: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"^[\w\.-]+@[\w\.-]+\.\w{2,}$").unwrap());
-pub static PASSWORD_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"^(?=.*[a-z])(?=.*[A-Z])(?=.*\d)(?=.*[^A-Za-z\d]).{8,}$").unwrap()
-});
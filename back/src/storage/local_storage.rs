@@ -0,0 +1,323 @@
+use std::{
+    io::{ErrorKind, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use actix_web::web::Bytes;
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    storage::{
+        BodyStream, ByteRange, ObjectInfo, PartTag, StorageBackend, StorageKey, StoredContent,
+    },
+};
+
+/// Directories of the root that never hold objects
+const TMP_DIR: &str = "tmp";
+const UPLOADS_DIR: &str = "uploads";
+/// Written in the directory of a multipart upload, to check the key it was created for
+const UPLOAD_KEY_FILE: &str = "key";
+const MAX_PART_NUMBER: u32 = 10_000;
+
+/// Objects stored as `{root}/{first two characters of the key}/{key}`.
+/// Writes go to `{root}/tmp` then are renamed, a partial file is never visible.
+/// Multipart parts wait in `{root}/uploads/{upload_id}` until the upload is completed.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+fn not_found(err: &std::io::Error) -> bool {
+    err.kind() == ErrorKind::NotFound
+}
+
+/// Upload ids end up in paths, only the ones we generate are accepted
+fn valid_upload_id(upload_id: &str) -> bool {
+    upload_id.len() == 32
+        && upload_id
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &StorageKey) -> PathBuf {
+        self.root.join(&key.as_str()[..2]).join(key.as_str())
+    }
+
+    /// The two character directories objects are spread over
+    async fn fan_outs(&self) -> AppResult<Vec<PathBuf>> {
+        let mut fan_outs = Vec::new();
+        let mut entries = match fs::read_dir(&self.root).await {
+            Ok(entries) => entries,
+            Err(err) if not_found(&err) => return Ok(fan_outs),
+            Err(err) => return Err(err.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else { continue };
+            if name.len() == 2 && entry.file_type().await?.is_dir() {
+                fan_outs.push(entry.path());
+            }
+        }
+        Ok(fan_outs)
+    }
+
+    fn upload_dir(&self, upload_id: &str) -> AppResult<PathBuf> {
+        if !valid_upload_id(upload_id) {
+            return Err(AppError::NotFound(ErrorCode::NotFound));
+        }
+        Ok(self.root.join(UPLOADS_DIR).join(upload_id))
+    }
+
+    /// Fresh path under `{root}/tmp`
+    async fn tmp_path(&self) -> AppResult<PathBuf> {
+        let tmp_dir = self.root.join(TMP_DIR);
+        fs::create_dir_all(&tmp_dir).await?;
+        Ok(tmp_dir.join(format!("{}.part", StorageKey::generate())))
+    }
+
+    /// Moves a fully written temporary file in place of the object
+    async fn publish(&self, tmp_path: &Path, key: &StorageKey) -> AppResult<()> {
+        let path = self.path(key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::rename(tmp_path, &path).await?;
+        Ok(())
+    }
+
+    /// Runs `write` against a temporary file, published under `key` when it succeeds
+    async fn write_with<F>(&self, key: &StorageKey, write: F) -> AppResult<()>
+    where
+        F: AsyncFnOnce(&mut fs::File) -> AppResult<()>,
+    {
+        let tmp_path = self.tmp_path().await?;
+        let written = async {
+            let mut file = fs::File::create(&tmp_path).await?;
+            write(&mut file).await?;
+            file.sync_all().await?;
+            self.publish(&tmp_path, key).await
+        }
+        .await;
+        if written.is_err() {
+            let _ = fs::remove_file(&tmp_path).await;
+        }
+        written
+    }
+
+    async fn open(&self, key: &StorageKey) -> AppResult<Option<(fs::File, u64)>> {
+        let file = match fs::File::open(self.path(key)).await {
+            Ok(file) => file,
+            Err(err) if not_found(&err) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let size = file.metadata().await?.len();
+        Ok(Some((file, size)))
+    }
+
+    /// Checks the upload exists and was created for `key`
+    async fn check_upload(&self, key: &StorageKey, upload_id: &str) -> AppResult<PathBuf> {
+        let dir = self.upload_dir(upload_id)?;
+        match fs::read_to_string(dir.join(UPLOAD_KEY_FILE)).await {
+            Ok(owner) if owner == key.as_str() => Ok(dir),
+            Ok(_) => Err(AppError::NotFound(ErrorCode::NotFound)),
+            Err(err) if not_found(&err) => Err(AppError::NotFound(ErrorCode::NotFound)),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl StorageBackend for LocalStorage {
+    async fn put(&self, key: &StorageKey, mut body: BodyStream) -> AppResult<()> {
+        self.write_with(key, async |file: &mut fs::File| {
+            while let Some(chunk) = body.next().await {
+                file.write_all(&chunk?).await?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get(&self, key: &StorageKey) -> AppResult<Option<StoredContent>> {
+        Ok(self.open(key).await?.map(|(file, size)| StoredContent {
+            body: Box::pin(ReaderStream::new(file)),
+            size,
+            total_size: size,
+        }))
+    }
+
+    async fn get_range(
+        &self,
+        key: &StorageKey,
+        range: ByteRange,
+    ) -> AppResult<Option<StoredContent>> {
+        let Some((mut file, total_size)) = self.open(key).await? else {
+            return Ok(None);
+        };
+        if range.first >= total_size || range.last.is_some_and(|last| last < range.first) {
+            return Err(AppError::RangeNotSatisfiable(
+                ErrorCode::RangeNotSatisfiable,
+            ));
+        }
+        let last = range
+            .last
+            .map_or(total_size - 1, |last| last.min(total_size - 1));
+        let size = last - range.first + 1;
+        file.seek(SeekFrom::Start(range.first)).await?;
+        Ok(Some(StoredContent {
+            body: Box::pin(ReaderStream::new(file.take(size))),
+            size,
+            total_size,
+        }))
+    }
+
+    async fn delete(&self, key: &StorageKey) -> AppResult<()> {
+        match fs::remove_file(self.path(key)).await {
+            Err(err) if !not_found(&err) => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> AppResult<Vec<ObjectInfo>> {
+        let mut objects = Vec::new();
+        // a key under the prefix can only be in the directory of its first two characters
+        let fan_outs = match prefix.get(..2) {
+            Some(fan_out) if StorageKey::parse(fan_out).is_none() => return Ok(objects),
+            Some(fan_out) => vec![self.root.join(fan_out)],
+            None => self.fan_outs().await?,
+        };
+        for base in fan_outs {
+            // Keys keep their `/`, so objects of a fan-out directory can be nested
+            let mut pending = vec![base.clone()];
+            while let Some(dir) = pending.pop() {
+                let mut entries = match fs::read_dir(&dir).await {
+                    Ok(entries) => entries,
+                    Err(err) if not_found(&err) => continue,
+                    Err(err) => return Err(err.into()),
+                };
+                while let Some(entry) = entries.next_entry().await? {
+                    let file_type = entry.file_type().await?;
+                    if file_type.is_dir() {
+                        pending.push(entry.path());
+                        continue;
+                    }
+                    let path = entry.path();
+                    let Some(relative) = path.strip_prefix(&base).ok().and_then(Path::to_str)
+                    else {
+                        continue;
+                    };
+                    let relative = relative.replace(std::path::MAIN_SEPARATOR, "/");
+                    if !relative.starts_with(prefix) {
+                        continue;
+                    }
+                    if let Some(key) = StorageKey::parse(&relative) {
+                        let size = entry.metadata().await?.len();
+                        objects.push(ObjectInfo { key, size });
+                    }
+                }
+            }
+        }
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    async fn copy(&self, from: &StorageKey, to: &StorageKey) -> AppResult<bool> {
+        let Some((mut source, _)) = self.open(from).await? else {
+            return Ok(false);
+        };
+        self.write_with(to, async |file: &mut fs::File| {
+            tokio::io::copy(&mut source, file).await?;
+            Ok(())
+        })
+        .await?;
+        Ok(true)
+    }
+
+    async fn create_multipart(&self, key: &StorageKey) -> AppResult<String> {
+        let upload_id = StorageKey::generate().to_string();
+        let dir = self.upload_dir(&upload_id)?;
+        fs::create_dir_all(&dir).await?;
+        fs::write(dir.join(UPLOAD_KEY_FILE), key.as_str()).await?;
+        Ok(upload_id)
+    }
+
+    async fn upload_part(
+        &self,
+        key: &StorageKey,
+        upload_id: &str,
+        part_number: u32,
+        data: Bytes,
+    ) -> AppResult<PartTag> {
+        if !(1..=MAX_PART_NUMBER).contains(&part_number) {
+            return Err(AppError::invalid_field(
+                "part_number",
+                ErrorCode::ValidationFailed,
+            ));
+        }
+        let dir = self.check_upload(key, upload_id).await?;
+        let etag = hex::encode(Sha256::digest(&data));
+        let tmp_path = dir.join(format!("{part_number:05}.tmp"));
+        fs::write(&tmp_path, &data).await?;
+        fs::rename(&tmp_path, dir.join(format!("{part_number:05}"))).await?;
+        Ok(PartTag { part_number, etag })
+    }
+
+    async fn complete_multipart(
+        &self,
+        key: &StorageKey,
+        upload_id: &str,
+        parts: &[PartTag],
+    ) -> AppResult<()> {
+        let dir = self.check_upload(key, upload_id).await?;
+        let ascending = parts
+            .windows(2)
+            .all(|pair| pair[0].part_number < pair[1].part_number);
+        if parts.is_empty() || !ascending {
+            return Err(AppError::Validation(ErrorCode::ValidationFailed));
+        }
+        self.write_with(key, async |file: &mut fs::File| {
+            for part in parts {
+                let data = match fs::read(dir.join(format!("{:05}", part.part_number))).await {
+                    Ok(data) => data,
+                    Err(err) if not_found(&err) => {
+                        return Err(AppError::Validation(ErrorCode::ValidationFailed));
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+                if hex::encode(Sha256::digest(&data)) != part.etag {
+                    return Err(AppError::Validation(ErrorCode::ValidationFailed));
+                }
+                file.write_all(&data).await?;
+            }
+            Ok(())
+        })
+        .await?;
+        fs::remove_dir_all(&dir).await?;
+        Ok(())
+    }
+
+    async fn abort_multipart(&self, key: &StorageKey, upload_id: &str) -> AppResult<()> {
+        let dir = match self.check_upload(key, upload_id).await {
+            Ok(dir) => dir,
+            Err(AppError::NotFound(_)) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        match fs::remove_dir_all(&dir).await {
+            Err(err) if !not_found(&err) => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::{cell::RefCell, fmt::Display, pin::Pin, rc::Rc, sync::OnceLock};
+
+use actix_web::web::Bytes;
+use async_trait::async_trait;
+use futures_util::{Stream, StreamExt};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    storage::{
+        local_storage::LocalStorage,
+        s3_storage::{S3Config, S3Storage},
+    },
+    utils::env_utils::{env_opt, env_or},
+};
+
+pub mod local_storage;
+pub mod s3_storage;
+
+/// Content handed to a backend, its errors are the ones to report to the client
+pub type BodyStream = Pin<Box<dyn Stream<Item = AppResult<Bytes>>>>;
+/// Content read back from a backend, streamed to the client chunk by chunk
+pub type ByteStream = Pin<Box<dyn Stream<Item = Result<Bytes, std::io::Error>>>>;
+
+/// Largest accepted upload, 1 GiB unless `FILES_MAX_BYTES` says otherwise
+pub fn max_upload_bytes() -> u64 {
+    env_or("FILES_MAX_BYTES", 1024 * 1024 * 1024)
+}
+
+/// Name of an object: `/` separated segments of lowercase letters, digits, `-` and `_`,
+/// the first one at least two characters long. No client input ever ends up in a key.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StorageKey(String);
+impl StorageKey {
+    const MAX_LENGTH: usize = 255;
+
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().simple().to_string())
+    }
+    /// Keys read back from the database are checked again before touching a backend
+    pub fn parse(raw: &str) -> Option<Self> {
+        let valid_segment = |segment: &str| {
+            !segment.is_empty()
+                && segment
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_')
+        };
+        let valid = raw.len() <= Self::MAX_LENGTH
+            && raw.split('/').next().is_some_and(|first| first.len() >= 2)
+            && raw.split('/').all(valid_segment);
+        valid.then(|| Self(raw.to_string()))
+    }
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+impl Display for StorageKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Inclusive bounds, as in an HTTP `Range` header; no `last` reads to the end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub first: u64,
+    pub last: Option<u64>,
+}
+
+/// An object, or the requested range of it
+pub struct StoredContent {
+    pub body: ByteStream,
+    /// Length of `body`
+    pub size: u64,
+    /// Length of the whole object
+    pub total_size: u64,
+}
+
+pub struct ObjectInfo {
+    pub key: StorageKey,
+    pub size: u64,
+}
+
+/// Proof that a part of a multipart upload was received, given back to complete it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartTag {
+    pub part_number: u32,
+    pub etag: String,
+}
+
+/// Where user content lives. Every implementation passes `tests/storage_conformance.rs`.
+///
+/// Multipart uploads assemble an object from parts sent separately, possibly across
+/// requests: every part but the last must be at least 5 MiB, as S3 requires.
+#[async_trait(?Send)]
+pub trait StorageBackend: Send + Sync {
+    /// Writes the whole stream under `key`, replacing any object. Nothing is left
+    /// behind when the stream fails.
+    async fn put(&self, key: &StorageKey, body: BodyStream) -> AppResult<()>;
+    /// `None` when the object does not exist
+    async fn get(&self, key: &StorageKey) -> AppResult<Option<StoredContent>>;
+    /// Fails with `RANGE_NOT_SATISFIABLE` when `range` starts past the end, `last` is clamped
+    async fn get_range(
+        &self,
+        key: &StorageKey,
+        range: ByteRange,
+    ) -> AppResult<Option<StoredContent>>;
+    /// Deleting a missing object is not an error
+    async fn delete(&self, key: &StorageKey) -> AppResult<()>;
+    /// Objects whose key starts with `prefix`, sorted by key
+    async fn list(&self, prefix: &str) -> AppResult<Vec<ObjectInfo>>;
+    /// `false` when `from` does not exist
+    async fn copy(&self, from: &StorageKey, to: &StorageKey) -> AppResult<bool>;
+
+    /// Returns the upload id to give to the other multipart calls
+    async fn create_multipart(&self, key: &StorageKey) -> AppResult<String>;
+    /// `part_number` goes from 1 to 10000, sending a number again replaces the part
+    async fn upload_part(
+        &self,
+        key: &StorageKey,
+        upload_id: &str,
+        part_number: u32,
+        data: Bytes,
+    ) -> AppResult<PartTag>;
+    /// `parts` in ascending order, the object only appears now
+    async fn complete_multipart(
+        &self,
+        key: &StorageKey,
+        upload_id: &str,
+        parts: &[PartTag],
+    ) -> AppResult<()>;
+    /// Drops the parts received so far
+    async fn abort_multipart(&self, key: &StorageKey, upload_id: &str) -> AppResult<()>;
+}
+
+static STORAGE: OnceLock<Box<dyn StorageBackend>> = OnceLock::new();
+
+/// `STORAGE_BACKEND=s3` uses the `S3_*` settings, otherwise files go to `FILES_DIR`
+/// (`./data/files` by default)
+pub fn storage() -> &'static dyn StorageBackend {
+    STORAGE
+        .get_or_init(|| match env_opt("STORAGE_BACKEND").as_deref() {
+            Some("s3") => {
+                let config = S3Config::from_env()
+                    .expect("STORAGE_BACKEND=s3 needs S3_ENDPOINT, S3_BUCKET and the S3 keys");
+                Box::new(S3Storage::new(config))
+            }
+            _ => Box::new(LocalStorage::new(env_or(
+                "FILES_DIR",
+                String::from("./data/files"),
+            ))),
+        })
+        .as_ref()
+}
+
+/// Must be called before the first file is stored, otherwise the backend from the environment is in place
+pub fn init_storage(backend: Box<dyn StorageBackend>) {
+    let _ = STORAGE.set(backend);
+}
+
+/// What is known of an upload once stored
+pub struct StoredObject {
+    pub key: StorageKey,
+    pub size: u64,
+    /// Hex SHA-256 of the content
+    pub sha256: String,
+}
+
+/// Streams `body` to the backend under a new key, refusing it past `max_bytes`
+pub async fn store<S, E>(body: S, max_bytes: u64) -> AppResult<StoredObject>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin + 'static,
+    E: Display,
+{
+    let key = StorageKey::generate();
+    let digest = Rc::new(RefCell::new((Sha256::new(), 0u64)));
+    let tracked = Rc::clone(&digest);
+    let body = body.map(move |chunk| {
+        let chunk = chunk.map_err(|err| {
+            eprintln!("Upload interrupted: {err}");
+            AppError::Validation(ErrorCode::MalformedRequest)
+        })?;
+        let mut state = tracked.borrow_mut();
+        state.1 += chunk.len() as u64;
+        if state.1 > max_bytes {
+            return Err(AppError::PayloadTooLarge(ErrorCode::FileTooLarge));
+        }
+        state.0.update(&chunk);
+        Ok(chunk)
+    });
+    storage().put(&key, Box::pin(body)).await?;
+
+    let (hasher, size) = digest.take();
+    Ok(StoredObject {
+        key,
+        size,
+        sha256: hex::encode(hasher.finalize()),
+    })
+}
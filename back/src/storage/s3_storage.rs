@@ -0,0 +1,500 @@
+use actix_web::web::{Bytes, BytesMut};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use hmac::{Hmac, Mac};
+use reqwest::{Method, Response, StatusCode, Url};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+use crate::{
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    storage::{
+        BodyStream, ByteRange, ObjectInfo, PartTag, StorageBackend, StorageKey, StoredContent,
+    },
+    utils::env_utils::{env_opt, env_or},
+};
+
+/// Bodies are sent in parts of this size, above S3's 5 MiB minimum
+const PART_SIZE: usize = 8 * 1024 * 1024;
+const SERVICE: &str = "s3";
+
+/// Where the bucket lives, from `S3_ENDPOINT`, `S3_REGION` (`us-east-1` by default),
+/// `S3_BUCKET`, `S3_ACCESS_KEY_ID` and `S3_SECRET_ACCESS_KEY`
+#[derive(Clone)]
+pub struct S3Config {
+    /// Scheme, host and port, such as `http://minio:9000`
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+impl S3Config {
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            endpoint: env_opt("S3_ENDPOINT")?,
+            region: env_or("S3_REGION", String::from("us-east-1")),
+            bucket: env_opt("S3_BUCKET")?,
+            access_key_id: env_opt("S3_ACCESS_KEY_ID")?,
+            secret_access_key: env_opt("S3_SECRET_ACCESS_KEY")?,
+        })
+    }
+}
+
+/// Any S3 compatible service, addressed path style (`{endpoint}/{bucket}/{key}`)
+/// so MinIO-like stand-ins work without wildcard DNS. Requests are signed with SigV4.
+pub struct S3Storage {
+    config: S3Config,
+    client: reqwest::Client,
+}
+
+/// Percent-encodes everything but unreserved characters, and `/` when `keep_slash`
+fn uri_encode(raw: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(raw.len());
+    for byte in raw.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Text between the first `<tag>` and `</tag>`, enough for the few answers we read
+fn xml_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    Some(&xml[start..end])
+}
+
+fn xml_blocks<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let mut blocks = Vec::new();
+    let mut rest = xml;
+    while let Some(block) = xml_value(rest, tag) {
+        blocks.push(block);
+        let consumed = block.as_ptr() as usize - rest.as_ptr() as usize + block.len();
+        rest = &rest[consumed..];
+    }
+    blocks
+}
+
+/// `bytes 0-9/42` gives 42
+fn total_from_content_range(response: &Response) -> Option<u64> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .rsplit('/')
+        .next()?
+        .parse()
+        .ok()
+}
+
+fn content(response: Response, size: u64, total_size: u64) -> StoredContent {
+    StoredContent {
+        body: Box::pin(
+            response
+                .bytes_stream()
+                .map(|chunk| chunk.map_err(std::io::Error::other)),
+        ),
+        size,
+        total_size,
+    }
+}
+
+impl S3Storage {
+    pub fn new(config: S3Config) -> Self {
+        Self {
+            config: S3Config {
+                endpoint: config.endpoint.trim_end_matches('/').to_string(),
+                ..config
+            },
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Creates the bucket when missing, for local stand-ins started empty
+    pub async fn ensure_bucket(&self) -> AppResult<()> {
+        let response = self
+            .send(Method::HEAD, None, &[], &[], Bytes::new())
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            let response = self.send(Method::PUT, None, &[], &[], Bytes::new()).await?;
+            Self::check(response, "create the bucket").await?;
+        }
+        Ok(())
+    }
+
+    /// Signs and sends a request on the bucket, or on `key` in it
+    async fn send(
+        &self,
+        method: Method,
+        key: Option<&StorageKey>,
+        query: &[(&str, &str)],
+        headers: &[(&str, String)],
+        body: Bytes,
+    ) -> AppResult<Response> {
+        let mut path = format!("/{}", uri_encode(&self.config.bucket, false));
+        if let Some(key) = key {
+            path.push('/');
+            path.push_str(&uri_encode(key.as_str(), true));
+        }
+        let mut query: Vec<(String, String)> = query
+            .iter()
+            .map(|(name, value)| (uri_encode(name, false), uri_encode(value, false)))
+            .collect();
+        query.sort();
+        let canonical_query = query
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        let mut url = format!("{}{path}", self.config.endpoint);
+        if !canonical_query.is_empty() {
+            url.push('?');
+            url.push_str(&canonical_query);
+        }
+        let url = Url::parse(&url).map_err(|err| AppError::Internal(format!("S3 url: {err}")))?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            _ => return Err(AppError::Internal("S3 endpoint without host".to_string())),
+        };
+
+        let now = OffsetDateTime::now_utc();
+        let date = format!(
+            "{:04}{:02}{:02}",
+            now.year(),
+            u8::from(now.month()),
+            now.day()
+        );
+        let timestamp = format!(
+            "{date}T{:02}{:02}{:02}Z",
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+        let payload_hash = hex::encode(Sha256::digest(&body));
+
+        let mut signed: Vec<(String, String)> = vec![
+            ("host".to_string(), host),
+            ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+            ("x-amz-date".to_string(), timestamp.clone()),
+        ];
+        signed.extend(
+            headers
+                .iter()
+                .map(|(name, value)| (name.to_ascii_lowercase(), value.trim().to_string())),
+        );
+        signed.sort();
+        let canonical_headers: String = signed
+            .iter()
+            .map(|(name, value)| format!("{name}:{value}\n"))
+            .collect();
+        let signed_headers = signed
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "{method}\n{path}\n{canonical_query}\n{canonical_headers}\n{signed_headers}\n{payload_hash}"
+        );
+
+        let scope = format!("{date}/{}/{SERVICE}/aws4_request", self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+        let secret = format!("AWS4{}", self.config.secret_access_key);
+        let signing_key = [self.config.region.as_str(), SERVICE, "aws4_request"]
+            .iter()
+            .fold(hmac(secret.as_bytes(), &date), |key, part| hmac(&key, part));
+        let signature = hex::encode(hmac(&signing_key, &string_to_sign));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.config.access_key_id
+        );
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", timestamp)
+            .header(reqwest::header::AUTHORIZATION, authorization);
+        for (name, value) in headers {
+            request = request.header(*name, value.as_str());
+        }
+        request
+            .body(body)
+            .send()
+            .await
+            .map_err(|err| AppError::Internal(format!("S3 request: {err}")))
+    }
+
+    /// The body of a successful answer. S3 may report a failure in a 200 answer,
+    /// as an `<Error>` document, for copies and completions.
+    async fn check(response: Response, action: &str) -> AppResult<String> {
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|err| AppError::Internal(format!("S3 answer: {err}")))?;
+        if !status.is_success() || body.contains("<Error>") {
+            return Err(AppError::Internal(format!(
+                "S3 failed to {action} ({status}): {}",
+                xml_value(&body, "Message").unwrap_or(&body)
+            )));
+        }
+        Ok(body)
+    }
+
+    async fn put_object(&self, key: &StorageKey, data: Bytes) -> AppResult<()> {
+        let response = self.send(Method::PUT, Some(key), &[], &[], data).await?;
+        Self::check(response, "put an object").await?;
+        Ok(())
+    }
+
+    /// Parts are buffered then sent, one `PutObject` when the body fits in one part
+    async fn put_parts(
+        &self,
+        key: &StorageKey,
+        body: &mut BodyStream,
+        upload_id: &mut Option<String>,
+    ) -> AppResult<()> {
+        let mut buffer = BytesMut::new();
+        let mut parts = Vec::new();
+        while let Some(chunk) = body.next().await {
+            buffer.extend_from_slice(&chunk?);
+            if buffer.len() < PART_SIZE {
+                continue;
+            }
+            let id = match upload_id.as_ref() {
+                Some(id) => id.clone(),
+                None => upload_id.insert(self.create_multipart(key).await?).clone(),
+            };
+            let part_number = parts.len() as u32 + 1;
+            let data = buffer.split().freeze();
+            parts.push(self.upload_part(key, &id, part_number, data).await?);
+        }
+        let Some(id) = upload_id else {
+            return self.put_object(key, buffer.freeze()).await;
+        };
+        if !buffer.is_empty() {
+            let part_number = parts.len() as u32 + 1;
+            parts.push(
+                self.upload_part(key, id, part_number, buffer.freeze())
+                    .await?,
+            );
+        }
+        self.complete_multipart(key, id, &parts).await
+    }
+}
+
+#[async_trait(?Send)]
+impl StorageBackend for S3Storage {
+    async fn put(&self, key: &StorageKey, mut body: BodyStream) -> AppResult<()> {
+        let mut upload_id = None;
+        let result = self.put_parts(key, &mut body, &mut upload_id).await;
+        if result.is_err()
+            && let Some(id) = upload_id
+            && let Err(err) = self.abort_multipart(key, &id).await
+        {
+            eprintln!("Multipart upload {id} of {key} left behind: {err}");
+        }
+        result
+    }
+
+    async fn get(&self, key: &StorageKey) -> AppResult<Option<StoredContent>> {
+        let response = self
+            .send(Method::GET, Some(key), &[], &[], Bytes::new())
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Self::check(response, "get an object").await.map(|_| None);
+        }
+        let size = response
+            .content_length()
+            .ok_or(AppError::Internal("S3 object without length".to_string()))?;
+        Ok(Some(content(response, size, size)))
+    }
+
+    async fn get_range(
+        &self,
+        key: &StorageKey,
+        range: ByteRange,
+    ) -> AppResult<Option<StoredContent>> {
+        if range.last.is_some_and(|last| last < range.first) {
+            return Err(AppError::RangeNotSatisfiable(
+                ErrorCode::RangeNotSatisfiable,
+            ));
+        }
+        let bounds = match range.last {
+            Some(last) => format!("bytes={}-{last}", range.first),
+            None => format!("bytes={}-", range.first),
+        };
+        let headers = [("range", bounds)];
+        let response = self
+            .send(Method::GET, Some(key), &[], &headers, Bytes::new())
+            .await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            StatusCode::RANGE_NOT_SATISFIABLE => Err(AppError::RangeNotSatisfiable(
+                ErrorCode::RangeNotSatisfiable,
+            )),
+            StatusCode::PARTIAL_CONTENT => {
+                let size = response.content_length();
+                let total_size = total_from_content_range(&response);
+                let (Some(size), Some(total_size)) = (size, total_size) else {
+                    return Err(AppError::Internal("S3 range without length".to_string()));
+                };
+                Ok(Some(content(response, size, total_size)))
+            }
+            _ => Self::check(response, "get a range").await.map(|_| None),
+        }
+    }
+
+    async fn delete(&self, key: &StorageKey) -> AppResult<()> {
+        let response = self
+            .send(Method::DELETE, Some(key), &[], &[], Bytes::new())
+            .await?;
+        if response.status() != StatusCode::NOT_FOUND {
+            Self::check(response, "delete an object").await?;
+        }
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> AppResult<Vec<ObjectInfo>> {
+        let mut objects = Vec::new();
+        let mut continuation: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if let Some(token) = &continuation {
+                query.push(("continuation-token", token.as_str()));
+            }
+            let response = self
+                .send(Method::GET, None, &query, &[], Bytes::new())
+                .await?;
+            let body = Self::check(response, "list objects").await?;
+            for block in xml_blocks(&body, "Contents") {
+                let key = xml_value(block, "Key").and_then(StorageKey::parse);
+                let size = xml_value(block, "Size").and_then(|size| size.parse().ok());
+                if let (Some(key), Some(size)) = (key, size) {
+                    objects.push(ObjectInfo { key, size });
+                }
+            }
+            continuation = xml_value(&body, "NextContinuationToken").map(str::to_string);
+            if xml_value(&body, "IsTruncated") != Some("true") || continuation.is_none() {
+                break;
+            }
+        }
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    async fn copy(&self, from: &StorageKey, to: &StorageKey) -> AppResult<bool> {
+        let source = format!(
+            "/{}/{}",
+            uri_encode(&self.config.bucket, false),
+            uri_encode(from.as_str(), true)
+        );
+        let headers = [("x-amz-copy-source", source)];
+        let response = self
+            .send(Method::PUT, Some(to), &[], &headers, Bytes::new())
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        Self::check(response, "copy an object").await?;
+        Ok(true)
+    }
+
+    async fn create_multipart(&self, key: &StorageKey) -> AppResult<String> {
+        let query = [("uploads", "")];
+        let response = self
+            .send(Method::POST, Some(key), &query, &[], Bytes::new())
+            .await?;
+        let body = Self::check(response, "create a multipart upload").await?;
+        xml_value(&body, "UploadId")
+            .map(str::to_string)
+            .ok_or(AppError::Internal(
+                "S3 multipart upload without id".to_string(),
+            ))
+    }
+
+    async fn upload_part(
+        &self,
+        key: &StorageKey,
+        upload_id: &str,
+        part_number: u32,
+        data: Bytes,
+    ) -> AppResult<PartTag> {
+        let number = part_number.to_string();
+        let query = [("partNumber", number.as_str()), ("uploadId", upload_id)];
+        let response = self.send(Method::PUT, Some(key), &query, &[], data).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(AppError::NotFound(ErrorCode::NotFound));
+        }
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        Self::check(response, "upload a part").await?;
+        let etag = etag.ok_or(AppError::Internal("S3 part without ETag".to_string()))?;
+        Ok(PartTag { part_number, etag })
+    }
+
+    async fn complete_multipart(
+        &self,
+        key: &StorageKey,
+        upload_id: &str,
+        parts: &[PartTag],
+    ) -> AppResult<()> {
+        let mut document = String::from("<CompleteMultipartUpload>");
+        for part in parts {
+            document.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                part.part_number, part.etag
+            ));
+        }
+        document.push_str("</CompleteMultipartUpload>");
+        let query = [("uploadId", upload_id)];
+        let response = self
+            .send(Method::POST, Some(key), &query, &[], Bytes::from(document))
+            .await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Err(AppError::NotFound(ErrorCode::NotFound)),
+            StatusCode::BAD_REQUEST => Err(AppError::Validation(ErrorCode::ValidationFailed)),
+            _ => Self::check(response, "complete a multipart upload")
+                .await
+                .map(|_| ()),
+        }
+    }
+
+    async fn abort_multipart(&self, key: &StorageKey, upload_id: &str) -> AppResult<()> {
+        let query = [("uploadId", upload_id)];
+        let response = self
+            .send(Method::DELETE, Some(key), &query, &[], Bytes::new())
+            .await?;
+        if response.status() != StatusCode::NOT_FOUND {
+            Self::check(response, "abort a multipart upload").await?;
+        }
+        Ok(())
+    }
+}
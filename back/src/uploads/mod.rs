@@ -0,0 +1,3 @@
+pub mod tus_upload;
+pub mod uploads_controller;
+pub mod uploads_service;
@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{AppError, AppResult},
+    shared::get_now_unix,
+    storage::StorageKey,
+    utils::{
+        env_utils::env_or,
+        redis_utils::{redis_del, redis_del_if_eq, redis_get, redis_set_ex, redis_set_nx_ex},
+    },
+};
+
+/// Prefix of the storage keys of the chunks received so far
+pub const SEGMENTS_PREFIX: &str = "tus";
+
+/// Seconds an upload stays resumable after its last chunk, a day unless
+/// `TUS_UPLOAD_EXPIRATION_SECS` says otherwise
+pub fn upload_expiration_secs() -> u64 {
+    env_or("TUS_UPLOAD_EXPIRATION_SECS", 24 * 60 * 60)
+}
+
+/// The lock is only held while the upload is read and written back, never while
+/// its content moves. One left by a crash only blocks the upload that long
+const LOCK_SECS: u64 = 30;
+
+/// Chunk stored as is, the file is assembled from them once complete
+#[derive(Serialize, Deserialize)]
+pub struct Segment {
+    pub key: String,
+    pub size: u64,
+}
+
+/// A tus upload, kept in the cache until it expires
+#[derive(Serialize, Deserialize)]
+pub struct TusUpload {
+    pub id: String,
+    pub user_id: i32,
+    pub length: u64,
+    pub offset: u64,
+    /// `Upload-Metadata` as sent at creation, given back by `HEAD`
+    pub metadata: Option<String>,
+    pub name: String,
+    pub content_type: String,
+    pub segments: Vec<Segment>,
+    /// Set once assembled in `/files`
+    pub file_id: Option<i32>,
+    /// Set by the request assembling it, the others leave it alone until it is
+    /// done or has failed
+    #[serde(default)]
+    pub assembling: bool,
+    pub exp: u64,
+}
+
+fn cache_key(id: &str) -> String {
+    format!("tus:{id}")
+}
+fn lock_key(id: &str) -> String {
+    format!("tus-lock:{id}")
+}
+
+/// Ids end up in storage keys, only the ones we generate are looked up
+fn valid_id(id: &str) -> bool {
+    id.len() == 32
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+impl TusUpload {
+    pub fn new(
+        user_id: i32,
+        length: u64,
+        metadata: Option<String>,
+        name: String,
+        content_type: String,
+    ) -> Self {
+        Self {
+            id: StorageKey::generate().to_string(),
+            user_id,
+            length,
+            offset: 0,
+            metadata,
+            name,
+            content_type,
+            segments: Vec::new(),
+            file_id: None,
+            assembling: false,
+            exp: get_now_unix() + upload_expiration_secs(),
+        }
+    }
+
+    /// `None` when unknown or expired
+    pub async fn get(id: &str) -> AppResult<Option<Self>> {
+        if !valid_id(id) {
+            return Ok(None);
+        }
+        let upload = redis_get::<_, TusUpload>(&cache_key(id)).await?;
+        Ok(upload.filter(|upload| upload.exp > get_now_unix()))
+    }
+
+    /// Pushes the expiration back, then stores the upload until then
+    pub async fn save(&mut self) -> AppResult<()> {
+        let ttl = upload_expiration_secs();
+        self.exp = get_now_unix() + ttl;
+        redis_set_ex(&cache_key(&self.id), self, ttl).await
+    }
+
+    pub async fn forget(&self) -> AppResult<()> {
+        redis_del(&cache_key(&self.id)).await
+    }
+
+    /// Serializes the changes of an upload: its offset moves and the start of its
+    /// assembly. Returns the token to unlock it with, `None` when another request
+    /// holds the lock
+    pub async fn lock(id: &str) -> AppResult<Option<String>> {
+        let token = StorageKey::generate().to_string();
+        let locked = redis_set_nx_ex(&lock_key(id), &token, LOCK_SECS).await?;
+        Ok(locked.then_some(token))
+    }
+
+    /// A lock expired and taken by another request meanwhile is left to it
+    pub async fn unlock(id: &str, token: &str) -> AppResult<()> {
+        if !redis_del_if_eq(&lock_key(id), &token).await? {
+            eprintln!("Lock of upload {id} expired before its release");
+        }
+        Ok(())
+    }
+
+    /// Complete, not assembled and nobody at it
+    pub fn awaits_assembly(&self) -> bool {
+        self.is_complete() && self.file_id.is_none() && !self.assembling
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.offset == self.length
+    }
+
+    /// Unique key for a chunk starting at `offset`, two requests racing for the same
+    /// offset never write to the same object
+    pub fn segment_key(&self, offset: u64) -> StorageKey {
+        let raw = format!(
+            "{SEGMENTS_PREFIX}/{}/{offset:020}-{}",
+            self.id,
+            StorageKey::generate()
+        );
+        StorageKey::parse(&raw).expect("generated segment keys are valid")
+    }
+
+    pub fn segment_keys(&self) -> AppResult<Vec<StorageKey>> {
+        self.segments
+            .iter()
+            .map(|segment| {
+                StorageKey::parse(&segment.key).ok_or(AppError::Internal(format!(
+                    "Upload {} has an invalid segment key",
+                    self.id
+                )))
+            })
+            .collect()
+    }
+
+    /// The upload a segment key belongs to, used to find abandoned segments
+    pub fn id_of_segment(key: &StorageKey) -> Option<&str> {
+        let mut parts = key.as_str().split('/');
+        (parts.next() == Some(SEGMENTS_PREFIX))
+            .then(|| parts.next())
+            .flatten()
+    }
+}
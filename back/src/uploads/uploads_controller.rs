@@ -0,0 +1,152 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use actix_web::{
+    HttpRequest, HttpResponse, HttpResponseBuilder, delete, head,
+    http::header::{self, CacheControl, CacheDirective, HttpDate},
+    options, patch, post,
+    web::{Path, Payload},
+};
+
+use crate::{
+    APP_URL,
+    auth::auth_models::{claims::Claims, scope::Scope},
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    storage::max_upload_bytes,
+    uploads::{
+        tus_upload::TusUpload,
+        uploads_service::{
+            Checksum, ChecksumAlgorithm, append_chunk_service, create_upload_service,
+            terminate_upload_service, upload_status_service,
+        },
+    },
+};
+
+pub const TUS_VERSION: &str = "1.0.0";
+pub const TUS_RESUMABLE: &str = "Tus-Resumable";
+const TUS_EXTENSIONS: &str = "creation,termination,checksum,expiration";
+const OFFSET_CONTENT_TYPE: &str = "application/offset+octet-stream";
+pub const UPLOAD_OFFSET: &str = "Upload-Offset";
+pub const UPLOAD_LENGTH: &str = "Upload-Length";
+pub const UPLOAD_METADATA: &str = "Upload-Metadata";
+pub const UPLOAD_EXPIRES: &str = "Upload-Expires";
+pub const UPLOAD_CHECKSUM: &str = "Upload-Checksum";
+/// Not part of tus: id of the file in `/files` once every byte is received
+pub const UPLOAD_FILE_ID: &str = "Upload-File-Id";
+
+fn header_str<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+}
+
+fn header_u64(req: &HttpRequest, name: &str) -> Option<u64> {
+    header_str(req, name).and_then(|value| value.trim().parse().ok())
+}
+
+/// Every request but `OPTIONS` states the protocol version
+fn require_tus_version(req: &HttpRequest) -> AppResult<()> {
+    match header_str(req, TUS_RESUMABLE) {
+        Some(TUS_VERSION) => Ok(()),
+        _ => Err(AppError::PreconditionFailed(
+            ErrorCode::TusVersionUnsupported,
+        )),
+    }
+}
+
+/// Offset, length and expiration, plus the file once assembled
+fn with_upload_headers(mut response: HttpResponseBuilder, upload: &TusUpload) -> HttpResponse {
+    let expires = HttpDate::from(UNIX_EPOCH + Duration::from_secs(upload.exp));
+    response
+        .insert_header((UPLOAD_OFFSET, upload.offset.to_string()))
+        .insert_header((UPLOAD_LENGTH, upload.length.to_string()))
+        .insert_header((UPLOAD_EXPIRES, expires.to_string()))
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]));
+    if let Some(file_id) = upload.file_id {
+        response.insert_header((UPLOAD_FILE_ID, file_id.to_string()));
+    }
+    response.finish()
+}
+
+/// Mounted in the `/uploads` scope, for any connected user, which adds
+/// `Tus-Resumable` to every response. Describes what the server supports.
+#[options("")]
+pub async fn tus_options() -> HttpResponse {
+    HttpResponse::NoContent()
+        .insert_header(("Tus-Version", TUS_VERSION))
+        .insert_header(("Tus-Extension", TUS_EXTENSIONS))
+        .insert_header(("Tus-Max-Size", max_upload_bytes().to_string()))
+        .insert_header(("Tus-Checksum-Algorithm", ChecksumAlgorithm::SUPPORTED))
+        .finish()
+}
+
+/// tus creation: `Upload-Length` is required, deferred lengths are not supported
+#[post("")]
+pub async fn create_upload(claims: Claims, req: HttpRequest) -> AppResult<HttpResponse> {
+    require_tus_version(&req)?;
+    claims.require_scope(Scope::FilesWrite)?;
+    let length = header_u64(&req, UPLOAD_LENGTH)
+        .ok_or(AppError::Validation(ErrorCode::UploadLengthInvalid))?;
+    let upload =
+        create_upload_service(claims.user_id, length, header_str(&req, UPLOAD_METADATA)).await?;
+    let mut response = HttpResponse::Created();
+    response.insert_header((
+        header::LOCATION,
+        format!("{APP_URL}/api/uploads/{}", upload.id),
+    ));
+    Ok(with_upload_headers(response, &upload))
+}
+
+/// Where to resume from
+#[head("/{upload_id}")]
+pub async fn upload_status(
+    claims: Claims,
+    req: HttpRequest,
+    upload_id: Path<String>,
+) -> AppResult<HttpResponse> {
+    require_tus_version(&req)?;
+    claims.require_scope(Scope::FilesWrite)?;
+    let upload = upload_status_service(claims.user_id, &upload_id).await?;
+    let mut response = HttpResponse::Ok();
+    if let Some(metadata) = &upload.metadata {
+        response.insert_header((UPLOAD_METADATA, metadata.clone()));
+    }
+    Ok(with_upload_headers(response, &upload))
+}
+
+/// Appends the body at `Upload-Offset`, checked against `Upload-Checksum` when sent
+#[patch("/{upload_id}")]
+pub async fn append_upload(
+    claims: Claims,
+    req: HttpRequest,
+    upload_id: Path<String>,
+    body: Payload,
+) -> AppResult<HttpResponse> {
+    require_tus_version(&req)?;
+    claims.require_scope(Scope::FilesWrite)?;
+    if header_str(&req, header::CONTENT_TYPE.as_str()) != Some(OFFSET_CONTENT_TYPE) {
+        return Err(AppError::UnsupportedMediaType(
+            ErrorCode::UploadContentTypeInvalid,
+        ));
+    }
+    let offset =
+        header_u64(&req, UPLOAD_OFFSET).ok_or(AppError::Validation(ErrorCode::MalformedRequest))?;
+    let checksum = header_str(&req, UPLOAD_CHECKSUM)
+        .map(Checksum::parse)
+        .transpose()?;
+    let upload = append_chunk_service(claims.user_id, &upload_id, offset, checksum, body).await?;
+    Ok(with_upload_headers(HttpResponse::NoContent(), &upload))
+}
+
+/// tus termination
+#[delete("/{upload_id}")]
+pub async fn terminate_upload(
+    claims: Claims,
+    req: HttpRequest,
+    upload_id: Path<String>,
+) -> AppResult<HttpResponse> {
+    require_tus_version(&req)?;
+    claims.require_scope(Scope::FilesWrite)?;
+    terminate_upload_service(claims.user_id, &upload_id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,361 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+
+use actix_web::web::Bytes;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures_util::{Stream, StreamExt, stream};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    files::files_service::{sanitize_content_type, sanitize_file_name, store_file},
+    models::file_model::StoredFile,
+    storage::{ByteStream, StorageKey, max_upload_bytes, storage},
+    uploads::tus_upload::{Segment, TusUpload},
+};
+
+/// Metadata keys used by the tus clients, the first one present wins
+const NAME_KEYS: [&str; 2] = ["filename", "name"];
+const TYPE_KEYS: [&str; 2] = ["filetype", "type"];
+
+#[derive(Clone, Copy)]
+pub enum ChecksumAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// As listed in `Tus-Checksum-Algorithm`
+    pub const SUPPORTED: &str = "sha1,sha256";
+}
+
+/// `Upload-Checksum` of a chunk: the algorithm and the base64 digest
+pub struct Checksum {
+    algorithm: ChecksumAlgorithm,
+    digest: Vec<u8>,
+}
+
+impl Checksum {
+    pub fn parse(raw: &str) -> AppResult<Self> {
+        let (algorithm, digest) = raw
+            .trim()
+            .split_once(' ')
+            .ok_or(AppError::Validation(ErrorCode::MalformedRequest))?;
+        let algorithm = match algorithm {
+            "sha1" => ChecksumAlgorithm::Sha1,
+            "sha256" => ChecksumAlgorithm::Sha256,
+            _ => {
+                return Err(AppError::Validation(
+                    ErrorCode::ChecksumAlgorithmUnsupported,
+                ));
+            }
+        };
+        let digest = STANDARD
+            .decode(digest.trim())
+            .map_err(|_| AppError::Validation(ErrorCode::MalformedRequest))?;
+        Ok(Self { algorithm, digest })
+    }
+}
+
+enum ChunkHasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl ChunkHasher {
+    fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Sha1 => Self::Sha1(Sha1::new()),
+            ChecksumAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+        }
+    }
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha1(hasher) => hasher.update(data),
+            Self::Sha256(hasher) => hasher.update(data),
+        }
+    }
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            Self::Sha1(hasher) => hasher.finalize().to_vec(),
+            Self::Sha256(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
+
+/// `key base64,key base64,key`, values are optional
+fn parse_metadata(raw: &str) -> AppResult<HashMap<String, String>> {
+    let mut metadata = HashMap::new();
+    for pair in raw
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+    {
+        let (key, value) = match pair.split_once(' ') {
+            Some((key, encoded)) => {
+                let decoded = STANDARD
+                    .decode(encoded.trim())
+                    .ok()
+                    .and_then(|bytes| String::from_utf8(bytes).ok())
+                    .ok_or(AppError::Validation(ErrorCode::MalformedRequest))?;
+                (key, decoded)
+            }
+            None => (pair, String::new()),
+        };
+        metadata.insert(key.to_string(), value);
+    }
+    Ok(metadata)
+}
+
+fn first_of<'a>(metadata: &'a HashMap<String, String>, keys: &[&str]) -> Option<&'a str> {
+    keys.iter()
+        .find_map(|key| metadata.get(*key))
+        .map(String::as_str)
+}
+
+/// Concatenates the segments into a file of the owner, then drops them.
+/// Only called on an upload marked `assembling`, or before anyone else knows it
+async fn assemble(upload: &mut TusUpload) -> AppResult<()> {
+    let keys = upload.segment_keys()?;
+    let body = stream::iter(keys.clone())
+        .then(|key| async move {
+            let missing = format!("Segment {key} missing");
+            match storage().get(&key).await {
+                Ok(Some(content)) => content.body,
+                Ok(None) => Box::pin(stream::once(
+                    async move { Err(std::io::Error::other(missing)) },
+                )) as ByteStream,
+                Err(err) => Box::pin(stream::once(async move {
+                    Err(std::io::Error::other(err.to_string()))
+                })),
+            }
+        })
+        .flatten();
+    let file = store_file(
+        upload.user_id,
+        &upload.name,
+        &upload.content_type,
+        Box::pin(body),
+    )
+    .await?;
+    if file.size as u64 != upload.length {
+        // not kept, the next attempt would otherwise add one more file
+        if let Err(err) = StoredFile::delete(upload.user_id, file.id).await {
+            eprintln!("Truncated file {} left in /files: {err}", file.id);
+        }
+        return Err(AppError::Internal(format!(
+            "Upload {} assembled into {} bytes instead of {}",
+            upload.id, file.size, upload.length
+        )));
+    }
+
+    upload.file_id = Some(file.id);
+    upload.assembling = false;
+    upload.segments.clear();
+    upload.save().await?;
+    for key in &keys {
+        if let Err(err) = storage().delete(key).await {
+            eprintln!("Orphan segment {key} left in the store: {err}");
+        }
+    }
+    Ok(())
+}
+
+/// `Upload-Length` is checked against `FILES_MAX_BYTES`. The name comes from the
+/// `filename` metadata, the type from `filetype`
+pub async fn create_upload_service(
+    user_id: i32,
+    length: u64,
+    metadata: Option<&str>,
+) -> AppResult<TusUpload> {
+    if length > max_upload_bytes() {
+        return Err(AppError::PayloadTooLarge(ErrorCode::FileTooLarge));
+    }
+    let parsed = parse_metadata(metadata.unwrap_or_default())?;
+    let name = sanitize_file_name(first_of(&parsed, &NAME_KEYS).unwrap_or_default())
+        .map_err(|_| AppError::invalid_field("filename", ErrorCode::ValidationFailed))?;
+    let content_type = sanitize_content_type(first_of(&parsed, &TYPE_KEYS));
+
+    let mut upload = TusUpload::new(
+        user_id,
+        length,
+        metadata.map(str::to_string),
+        name,
+        content_type,
+    );
+    upload.save().await?;
+    if upload.is_complete() {
+        assemble(&mut upload).await?;
+    }
+    Ok(upload)
+}
+
+/// Assembles an upload marked `assembling`. The mark is lifted when it fails, so a
+/// later request tries again
+async fn assemble_claimed(mut upload: TusUpload) -> AppResult<TusUpload> {
+    if let Err(err) = assemble(&mut upload).await {
+        upload.assembling = false;
+        upload.save().await?;
+        return Err(err);
+    }
+    Ok(upload)
+}
+
+/// Marks the upload as being assembled if it awaits it. Called with its lock held
+async fn claim_assembly(upload_id: &str) -> AppResult<Option<TusUpload>> {
+    let Some(mut upload) = TusUpload::get(upload_id)
+        .await?
+        .filter(TusUpload::awaits_assembly)
+    else {
+        return Ok(None);
+    };
+    upload.assembling = true;
+    upload.save().await?;
+    Ok(Some(upload))
+}
+
+/// Unknown, expired and other users' uploads are not found alike.
+/// An upload complete but not assembled, after a failure, is assembled again unless
+/// another request is already at it.
+pub async fn upload_status_service(user_id: i32, upload_id: &str) -> AppResult<TusUpload> {
+    let upload = TusUpload::get(upload_id)
+        .await?
+        .filter(|upload| upload.user_id == user_id)
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))?;
+    if !upload.awaits_assembly() {
+        return Ok(upload);
+    }
+    let Some(token) = TusUpload::lock(upload_id).await? else {
+        return Ok(upload);
+    };
+    let claimed = claim_assembly(upload_id).await;
+    TusUpload::unlock(upload_id, &token).await?;
+    match claimed? {
+        Some(claimed) => assemble_claimed(claimed).await,
+        None => Ok(upload),
+    }
+}
+
+/// Records the chunk stored under `key` unless another request moved the offset
+/// meanwhile, and marks the upload `assembling` once complete. Called with its lock
+/// held
+async fn append_segment(
+    upload_id: &str,
+    offset: u64,
+    key: &StorageKey,
+    size: u64,
+) -> AppResult<Option<TusUpload>> {
+    let Some(mut upload) = TusUpload::get(upload_id)
+        .await?
+        .filter(|upload| upload.offset == offset)
+    else {
+        return Ok(None);
+    };
+    upload.segments.push(Segment {
+        key: key.to_string(),
+        size,
+    });
+    upload.offset += size;
+    upload.assembling = upload.is_complete();
+    upload.save().await?;
+    Ok(Some(upload))
+}
+
+/// Stores `body` as the chunk starting at `offset`.
+///
+/// Without checksum, what arrived before a dropped connection is kept so the client
+/// resumes from there. With one, the whole chunk is needed to check it and is
+/// otherwise discarded.
+pub async fn append_chunk_service<S, E>(
+    user_id: i32,
+    upload_id: &str,
+    offset: u64,
+    checksum: Option<Checksum>,
+    body: S,
+) -> AppResult<TusUpload>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin + 'static,
+    E: Display,
+{
+    let upload = upload_status_service(user_id, upload_id).await?;
+    if upload.offset != offset {
+        return Err(AppError::Conflict(ErrorCode::UploadOffsetMismatch));
+    }
+    let remaining = upload.length - upload.offset;
+    let key = upload.segment_key(offset);
+
+    let strict = checksum.is_some();
+    let hasher = checksum.as_ref().map(|sum| ChunkHasher::new(sum.algorithm));
+    let received = Rc::new(RefCell::new((hasher, 0u64)));
+    let tracked = Rc::clone(&received);
+    let chunk_body = body
+        .map(move |chunk| match chunk {
+            Ok(chunk) => {
+                let mut state = tracked.borrow_mut();
+                state.1 += chunk.len() as u64;
+                if state.1 > remaining {
+                    return Some(Err(AppError::PayloadTooLarge(ErrorCode::FileTooLarge)));
+                }
+                if let Some(hasher) = &mut state.0 {
+                    hasher.update(&chunk);
+                }
+                Some(Ok(chunk))
+            }
+            Err(err) => {
+                eprintln!("Chunk of upload interrupted: {err}");
+                strict.then(|| Err(AppError::Validation(ErrorCode::MalformedRequest)))
+            }
+        })
+        // an interruption ends the chunk, keeping what was received
+        .take_while(|chunk| std::future::ready(chunk.is_some()))
+        .filter_map(std::future::ready);
+    storage().put(&key, Box::pin(chunk_body)).await?;
+
+    let (hasher, size) = received.take();
+    let matches = match (checksum, hasher) {
+        (Some(checksum), Some(hasher)) => hasher.finalize() == checksum.digest,
+        _ => true,
+    };
+    if !matches || size == 0 {
+        storage().delete(&key).await?;
+        if !matches {
+            return Err(AppError::ChecksumMismatch(
+                ErrorCode::UploadChecksumMismatch,
+            ));
+        }
+        return Ok(upload);
+    }
+
+    // another request may have written this offset while we were receiving, the
+    // offset only moves with the lock held
+    let appended = match TusUpload::lock(upload_id).await? {
+        Some(token) => {
+            let appended = append_segment(upload_id, offset, &key, size).await;
+            TusUpload::unlock(upload_id, &token).await?;
+            appended?
+        }
+        None => None,
+    };
+    match appended {
+        Some(upload) if upload.assembling => assemble_claimed(upload).await,
+        Some(upload) => Ok(upload),
+        None => {
+            storage().delete(&key).await?;
+            Err(AppError::Conflict(ErrorCode::UploadOffsetMismatch))
+        }
+    }
+}
+
+/// Drops the chunks received so far, a file already assembled is kept
+pub async fn terminate_upload_service(user_id: i32, upload_id: &str) -> AppResult<()> {
+    let upload = TusUpload::get(upload_id)
+        .await?
+        .filter(|upload| upload.user_id == user_id)
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))?;
+    upload.forget().await?;
+    for key in upload.segment_keys()? {
+        storage().delete(&key).await?;
+    }
+    Ok(())
+}
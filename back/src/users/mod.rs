@@ -0,0 +1,2 @@
+pub mod users_controller;
+pub mod users_service;
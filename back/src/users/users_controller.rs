@@ -0,0 +1,34 @@
+use actix_web::web::Path;
+use actix_web::{
+    HttpResponse, get,
+    http::header::{CacheControl, CacheDirective},
+};
+
+use crate::{
+    errors::AppResult,
+    models::profile_model::UserView,
+    shared::{ApiResponse, JsonResponse},
+    users::users_service::{avatar_service, user_view_service},
+};
+
+/// Avatar URLs carry a version, a stale copy is never shown for long
+const AVATAR_MAX_AGE_SECS: u32 = 24 * 60 * 60;
+
+/// Mounted in the `/users` scope, for any connected user
+#[get("/{user_id}")]
+pub async fn get_user(user_id: Path<i32>) -> ApiResponse<UserView> {
+    JsonResponse::ok().object(user_view_service(user_id.into_inner()).await?)
+}
+
+/// Mounted in the public `/avatars` scope so that `<img>` tags can load it
+#[get("/{user_id}")]
+pub async fn get_avatar(user_id: Path<i32>) -> AppResult<HttpResponse> {
+    let avatar = avatar_service(user_id.into_inner()).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("image/png")
+        .insert_header(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(AVATAR_MAX_AGE_SECS),
+        ]))
+        .body(avatar))
+}
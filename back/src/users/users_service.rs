@@ -0,0 +1,17 @@
+use crate::{
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    models::profile_model::{Profile, UserView},
+};
+
+pub async fn user_view_service(user_id: i32) -> AppResult<UserView> {
+    UserView::get(user_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::UserNotFound))
+}
+
+pub async fn avatar_service(user_id: i32) -> AppResult<Vec<u8>> {
+    Profile::avatar(user_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))
+}
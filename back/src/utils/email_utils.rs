@@ -1,3 +1,4 @@
+use std::sync::{Mutex, OnceLock};
 use std::{env, sync::LazyLock};
 
 use lettre::Transport;
@@ -6,9 +7,10 @@ use lettre::{
     message::{Mailbox, header::ContentType},
     transport::smtp::authentication::Credentials,
 };
+use time::OffsetDateTime;
 
-use crate::auth::auth_models::credential::Email;
-use crate::errors::AppResult;
+use crate::auth::auth_models::email::Email;
+use crate::errors::{AppError, AppResult};
 
 pub static APP_MAIL_BOX: LazyLock<Mailbox> = LazyLock::new(|| {
     env::var("EMAIL")
@@ -17,20 +19,80 @@ pub static APP_MAIL_BOX: LazyLock<Mailbox> = LazyLock::new(|| {
         .unwrap()
 });
 
-pub static MAILER: LazyLock<SmtpTransport> = LazyLock::new(|| {
-    let password = env::var("EMAIL_TOKEN").expect("EMAIL_TOKEN not define");
-    let email = env::var("EMAIL").expect("EMAIL not define");
-    let creds = Credentials::new(email, password);
+#[derive(Clone, Debug)]
+pub struct SentMail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
 
-    // Create a connection to our email provider
-    // In this case, we are using Namecheap's Private Email
-    // You can use any email provider you want
-    let mailer = SmtpTransport::relay("smtp.gmail.com")
-        .unwrap()
-        .credentials(creds)
-        .build();
-    mailer
-});
+/// SMTP in production, the capturing variant keeps mails in memory for the tests
+pub enum Mailer {
+    Smtp(SmtpTransport),
+    Capture(Mutex<Vec<SentMail>>),
+}
+
+impl Mailer {
+    fn smtp_from_env() -> Self {
+        let password = env::var("EMAIL_TOKEN").expect("EMAIL_TOKEN not define");
+        let email = env::var("EMAIL").expect("EMAIL not define");
+        let creds = Credentials::new(email, password);
+
+        // Create a connection to our email provider
+        // In this case, we are using Namecheap's Private Email
+        // You can use any email provider you want
+        let mailer = SmtpTransport::relay("smtp.gmail.com")
+            .unwrap()
+            .credentials(creds)
+            .build();
+        Mailer::Smtp(mailer)
+    }
+}
+
+static MAILER: OnceLock<Mailer> = OnceLock::new();
+
+fn mailer() -> &'static Mailer {
+    MAILER.get_or_init(Mailer::smtp_from_env)
+}
+
+/// Must be called before the first mail is sent, otherwise SMTP is already in place
+pub fn init_capturing_mailer() {
+    let _ = MAILER.set(Mailer::Capture(Mutex::new(Vec::new())));
+}
+
+/// Mails sent so far, always empty when the SMTP mailer is used
+pub fn captured_mails() -> Vec<SentMail> {
+    match mailer() {
+        Mailer::Capture(mails) => mails.lock().map(|m| m.clone()).unwrap_or_default(),
+        Mailer::Smtp(_) => Vec::new(),
+    }
+}
+
+/// For text chosen by users, e.g. an organization name, put in a mail body
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Dates are written `dd/mm/yyyy` in the mails
+pub fn mail_date(date: OffsetDateTime) -> String {
+    format!(
+        "{:02}/{:02}/{}",
+        date.day(),
+        u8::from(date.month()),
+        date.year()
+    )
+}
 
 pub fn send_mail(
     destination: &Email,
@@ -38,14 +100,32 @@ pub fn send_mail(
     body: impl Into<String>,
 ) -> AppResult<()> {
     println!("sending mail to {}", destination.as_ref());
-    let msg = Message::builder()
-        .from(APP_MAIL_BOX.clone())
-        .to(destination.as_ref().parse().expect("Invalid email address"))
-        .subject(subject)
-        .header(ContentType::TEXT_HTML)
-        .body(body.into())?;
-
-    MAILER.send(&msg)?;
+    let (subject, body) = (subject.into(), body.into());
+    match mailer() {
+        Mailer::Smtp(transport) => {
+            let to = destination.to_ascii().parse().map_err(|err| {
+                AppError::Mail(format!("Invalid recipient {}: {err}", destination.as_ref()))
+            })?;
+            let msg = Message::builder()
+                .from(APP_MAIL_BOX.clone())
+                .to(to)
+                .subject(subject)
+                .header(ContentType::TEXT_HTML)
+                .body(body)?;
+
+            transport.send(&msg)?;
+        }
+        Mailer::Capture(mails) => {
+            mails
+                .lock()
+                .map_err(|_| AppError::Mail("capturing mailer poisoned".to_string()))?
+                .push(SentMail {
+                    to: destination.as_ref().to_string(),
+                    subject,
+                    body,
+                });
+        }
+    }
 
     Ok(())
 }
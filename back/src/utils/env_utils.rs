@@ -0,0 +1,27 @@
+use std::{env, str::FromStr};
+
+/// Parse an environment variable, falling back to `default` when absent or malformed
+pub fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(raw) => raw.trim().parse().unwrap_or_else(|_| {
+            eprintln!("Invalid value for {name}: {raw:?}, using default");
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+/// `true`, `1`, `yes` and `on` are truthy, anything else is falsy
+pub fn env_flag(name: &str, default: bool) -> bool {
+    match env::var(name) {
+        Ok(raw) => matches!(
+            raw.trim().to_ascii_lowercase().as_str(),
+            "true" | "1" | "yes" | "on"
+        ),
+        Err(_) => default,
+    }
+}
+
+pub fn env_opt(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
@@ -0,0 +1,49 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, ImageReader, Limits, imageops::FilterType};
+
+use crate::{
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+};
+
+/// Side of the stored avatars, in pixels
+pub const AVATAR_SIZE: u32 = 256;
+/// Larger uploads are refused before being decoded
+pub const AVATAR_MAX_BYTES: usize = 5 * 1024 * 1024;
+/// Bounds the memory taken by decoding, whatever the file claims
+const MAX_SOURCE_SIDE: u32 = 8192;
+
+/// Decodes a PNG, JPEG, WebP or GIF and re-encodes it as a square PNG, cropped to the
+/// center. Whatever the upload carried besides pixels (metadata, scripts) is dropped.
+pub fn resize_avatar(upload: &[u8]) -> AppResult<Vec<u8>> {
+    let mut reader = ImageReader::new(Cursor::new(upload))
+        .with_guessed_format()
+        .map_err(|_| AppError::Validation(ErrorCode::ImageInvalid))?;
+    let supported = [
+        ImageFormat::Png,
+        ImageFormat::Jpeg,
+        ImageFormat::WebP,
+        ImageFormat::Gif,
+    ];
+    if !reader
+        .format()
+        .is_some_and(|format| supported.contains(&format))
+    {
+        return Err(AppError::Validation(ErrorCode::ImageInvalid));
+    }
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_SIDE);
+    limits.max_image_height = Some(MAX_SOURCE_SIDE);
+    reader.limits(limits);
+    let image = reader
+        .decode()
+        .map_err(|_| AppError::Validation(ErrorCode::ImageInvalid))?;
+
+    let avatar = image.resize_to_fill(AVATAR_SIZE, AVATAR_SIZE, FilterType::Lanczos3);
+    let mut png = Cursor::new(Vec::new());
+    avatar
+        .write_to(&mut png, ImageFormat::Png)
+        .map_err(|err| AppError::Internal(format!("Avatar encoding failed: {err}")))?;
+    Ok(png.into_inner())
+}
@@ -1,2 +1,6 @@
 pub mod email_utils;
+pub mod env_utils;
+pub mod image_utils;
+pub mod password_utils;
 pub mod redis_utils;
+pub mod sms_utils;
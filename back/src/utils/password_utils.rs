@@ -0,0 +1,129 @@
+use std::sync::LazyLock;
+
+use argon2::{
+    ARGON2ID_IDENT, Algorithm, Argon2, KeyId, Params, ParamsBuilder, PasswordHash, PasswordHasher,
+    PasswordVerifier, Version,
+};
+use password_hash::{SaltString, rand_core};
+
+use crate::{
+    errors::{AppError, AppResult},
+    utils::env_utils::{env_opt, env_or},
+};
+
+/// Argon2id parameters and optional pepper, read once from the environment:
+/// `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS`, `ARGON2_PARALLELISM`,
+/// `PASSWORD_PEPPER` and `PASSWORD_PEPPER_ID` (stored as the hash `keyid`, max 8 bytes)
+struct HashingConfig {
+    params: Params,
+    pepper: Option<Vec<u8>>,
+}
+
+static HASHING: LazyLock<Result<HashingConfig, String>> = LazyLock::new(HashingConfig::from_env);
+
+impl HashingConfig {
+    fn from_env() -> Result<Self, String> {
+        let pepper = env_opt("PASSWORD_PEPPER").map(String::into_bytes);
+        let mut builder = ParamsBuilder::new();
+        builder
+            .m_cost(env_or("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST))
+            .t_cost(env_or("ARGON2_ITERATIONS", Params::DEFAULT_T_COST))
+            .p_cost(env_or("ARGON2_PARALLELISM", Params::DEFAULT_P_COST));
+        if pepper.is_some() {
+            let pepper_id = env_or("PASSWORD_PEPPER_ID", String::from("pepper1"));
+            let keyid = KeyId::new(pepper_id.as_bytes())
+                .map_err(|err| format!("PASSWORD_PEPPER_ID invalid: {err}"))?;
+            builder.keyid(keyid);
+        }
+        let params = builder
+            .build()
+            .map_err(|err| format!("Argon2 parameters invalid: {err}"))?;
+        Ok(Self { params, pepper })
+    }
+
+    /// Hashes carrying a `keyid` were peppered, the others were not
+    fn argon2(&self, params: Params) -> AppResult<Argon2<'_>> {
+        if params.keyid().is_empty() {
+            return Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params));
+        }
+        let pepper = self.pepper.as_deref().ok_or_else(|| {
+            AppError::Internal("Peppered hash found but PASSWORD_PEPPER is not set".to_string())
+        })?;
+        Argon2::new_with_secret(pepper, Algorithm::Argon2id, Version::V0x13, params)
+            .map_err(|err| AppError::Internal(format!("Argon2 pepper rejected: {err}")))
+    }
+
+    fn is_current(&self, hash: &PasswordHash) -> bool {
+        let Ok(params) = Params::try_from(hash) else {
+            return false;
+        };
+        hash.algorithm == ARGON2ID_IDENT
+            && hash.version == Some(Version::V0x13.into())
+            && params.m_cost() == self.params.m_cost()
+            && params.t_cost() == self.params.t_cost()
+            && params.p_cost() == self.params.p_cost()
+            && params.keyid() == self.params.keyid()
+    }
+}
+
+fn hashing() -> AppResult<&'static HashingConfig> {
+    HASHING
+        .as_ref()
+        .map_err(|err| AppError::Internal(err.clone()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordCheck {
+    Invalid,
+    Valid,
+    /// Correct password stored with outdated parameters or a legacy algorithm
+    ValidNeedsRehash,
+}
+
+pub fn hash_password(password: &str) -> AppResult<String> {
+    let config = hashing()?;
+    let salt = SaltString::generate(&mut rand_core::OsRng);
+    let password_hash = config
+        .argon2(config.params.clone())?
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| AppError::Internal(format!("Password hashing failed: {err}")))?;
+    Ok(password_hash.to_string()) // contient hash + salt + paramètres encodés
+}
+
+/// Accepts Argon2 PHC strings and bcrypt hashes imported from the legacy system
+pub fn verify_password(password: &str, stored_hash: &str) -> AppResult<PasswordCheck> {
+    if is_bcrypt_hash(stored_hash) {
+        return match bcrypt::verify(password, stored_hash) {
+            Ok(true) => Ok(PasswordCheck::ValidNeedsRehash),
+            Ok(false) => Ok(PasswordCheck::Invalid),
+            Err(err) => Err(AppError::Internal(format!("Malformed bcrypt hash: {err}"))),
+        };
+    }
+
+    let config = hashing()?;
+    let parsed_hash = PasswordHash::new(stored_hash)
+        .map_err(|err| AppError::Internal(format!("Malformed password hash: {err}")))?;
+    let params = Params::try_from(&parsed_hash)
+        .map_err(|err| AppError::Internal(format!("Malformed Argon2 parameters: {err}")))?;
+
+    match config
+        .argon2(params)?
+        .verify_password(password.as_bytes(), &parsed_hash)
+    {
+        Ok(()) if config.is_current(&parsed_hash) => Ok(PasswordCheck::Valid),
+        Ok(()) => Ok(PasswordCheck::ValidNeedsRehash),
+        Err(password_hash::Error::Password) => Ok(PasswordCheck::Invalid),
+        Err(err) => Err(AppError::Internal(format!(
+            "Password verification failed: {err}"
+        ))),
+    }
+}
+
+/// `$2a$`, `$2b$` and `$2y$` modular crypt strings
+pub fn is_bcrypt_hash(hash: &str) -> bool {
+    let mut parts = hash.split('$');
+    matches!(
+        (parts.next(), parts.next(), parts.next()),
+        (Some(""), Some("2a" | "2b" | "2y"), Some(cost)) if cost.len() == 2 && cost.chars().all(|c| c.is_ascii_digit())
+    ) && hash.len() == 60
+}
@@ -1,24 +1,68 @@
 use bb8_redis::RedisConnectionManager;
-use redis::AsyncCommands;
+use redis::{AsyncCommands, ExistenceCheck, Script, SetExpiry, SetOptions};
 use serde::{Serialize, de::DeserializeOwned};
-use std::sync::OnceLock;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
 
-use crate::errors::AppResult;
+use crate::errors::{AppError, AppResult};
 
-// Redis pool global initialisé de manière synchrone
-pub static REDIS_POOL: OnceLock<bb8::Pool<RedisConnectionManager>> = OnceLock::new();
+/// Redis in production, a process local map for the integration tests
+pub enum Cache {
+    Redis(bb8::Pool<RedisConnectionManager>),
+    Memory(Mutex<HashMap<String, (String, Option<Instant>)>>),
+}
+
+// Cache global initialisé au démarrage
+pub static CACHE: OnceLock<Cache> = OnceLock::new();
 
 pub async fn init_redis_pool() {
     println!("Initialising pool");
     let manager = RedisConnectionManager::new("redis://redis/").unwrap();
-    REDIS_POOL
-        .set(
-            bb8::Pool::builder()
-                .build(manager)
-                .await
-                .expect("can't build the redis pool"),
-        )
-        .expect("set error in redis pool");
+    let pool = bb8::Pool::builder()
+        .build(manager)
+        .await
+        .expect("can't build the redis pool");
+    if CACHE.set(Cache::Redis(pool)).is_err() {
+        panic!("set error in redis pool");
+    }
+}
+
+/// In-process substitute for Redis, keys honour their expiration lazily on read
+pub fn init_memory_cache() {
+    let _ = CACHE.set(Cache::Memory(Mutex::new(HashMap::new())));
+}
+
+fn cache() -> AppResult<&'static Cache> {
+    CACHE
+        .get()
+        .ok_or_else(|| AppError::Cache("cache used before initialisation".to_string()))
+}
+
+fn lock_memory(
+    map: &Mutex<HashMap<String, (String, Option<Instant>)>>,
+) -> AppResult<std::sync::MutexGuard<'_, HashMap<String, (String, Option<Instant>)>>> {
+    map.lock()
+        .map_err(|_| AppError::Cache("memory cache poisoned".to_string()))
+}
+
+async fn set_raw(key: String, value: String, ttl: Option<Duration>) -> AppResult<()> {
+    match cache()? {
+        Cache::Redis(pool) => {
+            let mut conn = pool.get().await?;
+            let (): () = match ttl {
+                Some(ttl) => conn.set_ex(key, value, ttl.as_secs()).await?,
+                None => conn.set(key, value).await?,
+            };
+        }
+        Cache::Memory(map) => {
+            let deadline = ttl.map(|ttl| Instant::now() + ttl);
+            lock_memory(map)?.insert(key, (value, deadline));
+        }
+    }
+    Ok(())
 }
 
 /// Set a value in Redis with expiration (seconds)
@@ -29,25 +73,70 @@ pub async fn redis_set_ex<K: Serialize, V: Serialize>(
 ) -> AppResult<()> {
     let key_str = serde_json::to_string(key)?;
     let value_str = serde_json::to_string(value)?;
-    let mut conn = REDIS_POOL.get().unwrap().get().await?;
-    let (): () = conn.set_ex(key_str, value_str, seconds).await?;
-    Ok(())
+    set_raw(key_str, value_str, Some(Duration::from_secs(seconds))).await
+}
+
+/// Set a value in Redis with expiration (seconds) unless the key exists.
+/// Returns whether it was set, the base of the locks
+pub async fn redis_set_nx_ex<K: Serialize, V: Serialize>(
+    key: &K,
+    value: &V,
+    seconds: u64,
+) -> AppResult<bool> {
+    let key_str = serde_json::to_string(key)?;
+    let value_str = serde_json::to_string(value)?;
+    match cache()? {
+        Cache::Redis(pool) => {
+            let mut conn = pool.get().await?;
+            let options = SetOptions::default()
+                .conditional_set(ExistenceCheck::NX)
+                .with_expiration(SetExpiry::EX(seconds));
+            let reply: Option<String> = conn.set_options(key_str, value_str, options).await?;
+            Ok(reply.is_some())
+        }
+        Cache::Memory(map) => {
+            let mut map = lock_memory(map)?;
+            let now = Instant::now();
+            if map
+                .get(&key_str)
+                .is_some_and(|(_, deadline)| deadline.is_none_or(|deadline| deadline > now))
+            {
+                return Ok(false);
+            }
+            let deadline = now + Duration::from_secs(seconds);
+            map.insert(key_str, (value_str, Some(deadline)));
+            Ok(true)
+        }
+    }
 }
 
 /// Set a value in Redis without expiration
 pub async fn redis_set<K: Serialize, V: Serialize>(key: &K, value: &V) -> AppResult<()> {
     let key_str = serde_json::to_string(key)?;
     let value_str = serde_json::to_string(value)?;
-    let mut conn = REDIS_POOL.get().unwrap().get().await?;
-    let (): () = conn.set(key_str, value_str).await?;
-    Ok(())
+    set_raw(key_str, value_str, None).await
 }
 
 /// Get a value from Redis
 pub async fn redis_get<K: Serialize, V: DeserializeOwned>(key: &K) -> AppResult<Option<V>> {
     let key_str = serde_json::to_string(key)?;
-    let mut conn = REDIS_POOL.get().unwrap().get().await?;
-    let value: Option<String> = conn.get(key_str).await?;
+    let value: Option<String> = match cache()? {
+        Cache::Redis(pool) => {
+            let mut conn = pool.get().await?;
+            conn.get(key_str).await?
+        }
+        Cache::Memory(map) => {
+            let mut map = lock_memory(map)?;
+            match map.get(&key_str) {
+                Some((_, Some(deadline))) if *deadline <= Instant::now() => {
+                    map.remove(&key_str);
+                    None
+                }
+                Some((value, _)) => Some(value.clone()),
+                None => None,
+            }
+        }
+    };
     match value {
         Some(json) => Ok(Some(serde_json::from_str(&json)?)),
         None => Ok(None),
@@ -57,7 +146,47 @@ pub async fn redis_get<K: Serialize, V: DeserializeOwned>(key: &K) -> AppResult<
 /// Delete a key from Redis
 pub async fn redis_del<K: Serialize>(key: &K) -> AppResult<()> {
     let key_str = serde_json::to_string(key)?;
-    let mut conn = REDIS_POOL.get().unwrap().get().await?;
-    let _: () = conn.del(key_str).await?;
+    match cache()? {
+        Cache::Redis(pool) => {
+            let mut conn = pool.get().await?;
+            let _: () = conn.del(key_str).await?;
+        }
+        Cache::Memory(map) => {
+            lock_memory(map)?.remove(&key_str);
+        }
+    }
     Ok(())
 }
+
+/// Delete a key from Redis if it still holds `value`, in one step.
+/// Returns whether it was deleted, a lock is only released by its holder
+pub async fn redis_del_if_eq<K: Serialize, V: Serialize>(key: &K, value: &V) -> AppResult<bool> {
+    let key_str = serde_json::to_string(key)?;
+    let value_str = serde_json::to_string(value)?;
+    match cache()? {
+        Cache::Redis(pool) => {
+            let mut conn = pool.get().await?;
+            let script = Script::new(
+                "if redis.call('GET', KEYS[1]) == ARGV[1] then \
+                 return redis.call('DEL', KEYS[1]) else return 0 end",
+            );
+            let deleted: i64 = script
+                .key(key_str)
+                .arg(value_str)
+                .invoke_async(&mut *conn)
+                .await?;
+            Ok(deleted == 1)
+        }
+        Cache::Memory(map) => {
+            let mut map = lock_memory(map)?;
+            let now = Instant::now();
+            let held = map.get(&key_str).is_some_and(|(held, deadline)| {
+                *held == value_str && deadline.is_none_or(|deadline| deadline > now)
+            });
+            if held {
+                map.remove(&key_str);
+            }
+            Ok(held)
+        }
+    }
+}
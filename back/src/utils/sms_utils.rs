@@ -0,0 +1,137 @@
+use std::sync::{Mutex, OnceLock};
+
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::{
+    account::account_models::phone_number::PhoneNumber,
+    errors::{AppError, AppResult},
+    utils::env_utils::env_opt,
+};
+
+#[derive(Clone, Debug)]
+pub struct SentSms {
+    pub to: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait SmsSender: Send + Sync {
+    async fn send(&self, to: &PhoneNumber, body: &str) -> AppResult<()>;
+
+    /// Messages kept by in-memory implementations, empty for real gateways
+    fn sent(&self) -> Vec<SentSms> {
+        Vec::new()
+    }
+}
+
+/// Prints every message instead of sending it, for local development and the tests
+#[derive(Default)]
+pub struct ConsoleSmsSender {
+    sent: Mutex<Vec<SentSms>>,
+}
+
+#[async_trait]
+impl SmsSender for ConsoleSmsSender {
+    async fn send(&self, to: &PhoneNumber, body: &str) -> AppResult<()> {
+        println!("[sms to {}] {body}", to.as_ref());
+        self.sent
+            .lock()
+            .map_err(|_| AppError::Sms("console sender poisoned".to_string()))?
+            .push(SentSms {
+                to: to.as_ref().to_string(),
+                body: body.to_string(),
+            });
+        Ok(())
+    }
+    fn sent(&self) -> Vec<SentSms> {
+        self.sent.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+}
+
+#[derive(Serialize)]
+struct GatewayMessage<'a> {
+    to: &'a str,
+    body: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<&'a str>,
+}
+
+/// POSTs `{"to", "body", "from"}` as JSON to the gateway, any 2xx is a success.
+/// Configured by `SMS_GATEWAY_URL`, `SMS_GATEWAY_TOKEN` (bearer) and `SMS_SENDER_ID`.
+pub struct HttpSmsSender {
+    url: String,
+    token: Option<String>,
+    sender_id: Option<String>,
+    client: reqwest::Client,
+}
+
+impl HttpSmsSender {
+    pub fn new(url: impl Into<String>, token: Option<String>, sender_id: Option<String>) -> Self {
+        Self {
+            url: url.into(),
+            token,
+            sender_id,
+            client: reqwest::Client::new(),
+        }
+    }
+    fn from_env() -> Option<Self> {
+        let url = env_opt("SMS_GATEWAY_URL")?;
+        Some(Self::new(
+            url,
+            env_opt("SMS_GATEWAY_TOKEN"),
+            env_opt("SMS_SENDER_ID"),
+        ))
+    }
+}
+
+#[async_trait]
+impl SmsSender for HttpSmsSender {
+    async fn send(&self, to: &PhoneNumber, body: &str) -> AppResult<()> {
+        let mut request = self.client.post(&self.url).json(&GatewayMessage {
+            to: to.as_ref(),
+            body,
+            from: self.sender_id.as_deref(),
+        });
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|err| AppError::Sms(format!("SMS gateway unreachable: {err}")))?;
+        if !response.status().is_success() {
+            return Err(AppError::Sms(format!(
+                "SMS gateway answered {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+static SMS_SENDER: OnceLock<Box<dyn SmsSender>> = OnceLock::new();
+
+fn sms_sender() -> &'static dyn SmsSender {
+    SMS_SENDER
+        .get_or_init(|| match HttpSmsSender::from_env() {
+            Some(gateway) => Box::new(gateway),
+            None => Box::new(ConsoleSmsSender::default()),
+        })
+        .as_ref()
+}
+
+/// Must be called before the first SMS is sent, otherwise the sender from the environment is in place
+pub fn init_sms_sender(sender: Box<dyn SmsSender>) {
+    let _ = SMS_SENDER.set(sender);
+}
+
+/// Messages sent so far, always empty when a real gateway is used
+pub fn captured_sms() -> Vec<SentSms> {
+    sms_sender().sent()
+}
+
+pub async fn send_sms(destination: &PhoneNumber, body: &str) -> AppResult<()> {
+    println!("sending sms to {}", destination.masked());
+    sms_sender().send(destination, body).await
+}
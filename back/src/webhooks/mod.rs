@@ -0,0 +1,4 @@
+pub mod webhook_controller;
+pub mod webhook_event;
+pub mod webhook_service;
+pub mod webhook_signature;
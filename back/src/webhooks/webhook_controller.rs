@@ -0,0 +1,66 @@
+use actix_web::web::{Json, Path, Query};
+use actix_web::{delete, get, post};
+
+use crate::{
+    auth::auth_models::claims::Claims,
+    models::{webhook_delivery_model::WebhookDelivery, webhook_endpoint_model::WebhookEndpoint},
+    shared::{ApiResponse, JsonResponse, PageQuery},
+    webhooks::webhook_service::{
+        CreatedWebhook, NewWebhook, create_webhook_service, delete_webhook_service,
+        get_delivery_service, list_deliveries_service, list_webhooks_service,
+        replay_delivery_service,
+    },
+};
+
+/// Mounted in the `/admin` scope, guarded by `RequireAuth::role(Role::Admin)`
+#[post("/webhooks")]
+pub async fn create_webhook(
+    claims: Claims,
+    Json(req): Json<NewWebhook>,
+) -> ApiResponse<CreatedWebhook> {
+    claims.require_interactive()?;
+    let created = create_webhook_service(claims.user_id, req).await?;
+    JsonResponse::created()
+        .location(format!("/admin/webhooks/{}", created.endpoint.id))
+        .no_store()
+        .object(created)
+}
+
+#[get("/webhooks")]
+pub async fn list_webhooks() -> ApiResponse<Vec<WebhookEndpoint>> {
+    JsonResponse::ok().object(list_webhooks_service().await?)
+}
+
+#[delete("/webhooks/{webhook_id}")]
+pub async fn delete_webhook(webhook_id: Path<i32>) -> ApiResponse<WebhookEndpoint> {
+    JsonResponse::ok().object(delete_webhook_service(webhook_id.into_inner()).await?)
+}
+
+/// Newest first, `?page=&per_page=`
+#[get("/webhooks/{webhook_id}/deliveries")]
+pub async fn list_webhook_deliveries(
+    webhook_id: Path<i32>,
+    Query(page): Query<PageQuery>,
+) -> ApiResponse<Vec<WebhookDelivery>> {
+    let (deliveries, meta) = list_deliveries_service(webhook_id.into_inner(), page).await?;
+    JsonResponse::ok().page(deliveries, meta)
+}
+
+#[get("/webhooks/{webhook_id}/deliveries/{delivery_id}")]
+pub async fn get_webhook_delivery(path: Path<(i32, i64)>) -> ApiResponse<WebhookDelivery> {
+    let (webhook_id, delivery_id) = path.into_inner();
+    JsonResponse::ok().object(get_delivery_service(webhook_id, delivery_id).await?)
+}
+
+/// Queues a new delivery of the same event, e.g. once the receiver is fixed
+#[post("/webhooks/{webhook_id}/deliveries/{delivery_id}/replay")]
+pub async fn replay_webhook_delivery(path: Path<(i32, i64)>) -> ApiResponse<WebhookDelivery> {
+    let (webhook_id, delivery_id) = path.into_inner();
+    let replay = replay_delivery_service(webhook_id, delivery_id).await?;
+    JsonResponse::created()
+        .location(format!(
+            "/admin/webhooks/{webhook_id}/deliveries/{}",
+            replay.id
+        ))
+        .object(replay)
+}
@@ -0,0 +1,83 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::shared::get_now_unix;
+
+/// Account lifecycle events sent to the webhook endpoints.
+/// The string form is stored and sent to receivers: never rename one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEventType {
+    #[serde(rename = "user.registered")]
+    UserRegistered,
+    #[serde(rename = "user.verified")]
+    UserVerified,
+    #[serde(rename = "user.email_changed")]
+    UserEmailChanged,
+    #[serde(rename = "user.deleted")]
+    UserDeleted,
+}
+
+impl WebhookEventType {
+    pub const ALL: [WebhookEventType; 4] = [
+        WebhookEventType::UserRegistered,
+        WebhookEventType::UserVerified,
+        WebhookEventType::UserEmailChanged,
+        WebhookEventType::UserDeleted,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEventType::UserRegistered => "user.registered",
+            WebhookEventType::UserVerified => "user.verified",
+            WebhookEventType::UserEmailChanged => "user.email_changed",
+            WebhookEventType::UserDeleted => "user.deleted",
+        }
+    }
+    pub fn parse(raw: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|event| event.as_str() == raw)
+    }
+    /// Space separated, the storage format
+    pub fn join(events: &[WebhookEventType]) -> String {
+        events
+            .iter()
+            .map(WebhookEventType::as_str)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+    /// Unknown types, e.g. removed ones, are dropped
+    pub fn split(raw: &str) -> Vec<WebhookEventType> {
+        raw.split_whitespace()
+            .filter_map(WebhookEventType::parse)
+            .collect()
+    }
+}
+impl Display for WebhookEventType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Body POSTed to the endpoints: `{"id", "type", "created_at", "data"}`
+#[derive(Serialize)]
+pub struct WebhookEvent {
+    /// Same for every delivery of the event, replays included
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: WebhookEventType,
+    /// UNIX timestamp
+    pub created_at: u64,
+    pub data: Value,
+}
+
+impl WebhookEvent {
+    pub fn new(event_type: WebhookEventType, data: Value) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            event_type,
+            created_at: get_now_unix(),
+            data,
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    constants::codes::ErrorCode,
+    errors::{AppError, AppResult},
+    models::{webhook_delivery_model::WebhookDelivery, webhook_endpoint_model::WebhookEndpoint},
+    services::openapi_service::ApiSchema,
+    shared::{PageMeta, PageQuery},
+    utils::env_utils::env_flag,
+    webhooks::webhook_event::{WebhookEvent, WebhookEventType},
+};
+
+const URL_MAX_LENGTH: usize = 2048;
+const DESCRIPTION_MAX_LENGTH: usize = 255;
+const DELIVERIES_PER_PAGE: u32 = 50;
+const DELIVERIES_MAX_PER_PAGE: u32 = 200;
+
+/// Queues `event_type` for every endpoint listening to it, the worker of
+/// `jobs::webhook_worker` sends it. Emitted once the change is done, a failure is
+/// logged rather than failing the change
+pub async fn emit_webhook(event_type: WebhookEventType, data: Value) {
+    if let Err(err) = enqueue_webhook(event_type, data).await {
+        eprintln!("{} webhook not queued: {err}", event_type.as_str());
+    }
+}
+
+async fn enqueue_webhook(event_type: WebhookEventType, data: Value) -> AppResult<()> {
+    let endpoint_ids = WebhookEndpoint::subscribed(event_type).await?;
+    if endpoint_ids.is_empty() {
+        return Ok(());
+    }
+    let event = WebhookEvent::new(event_type, data);
+    let payload = serde_json::to_string(&event)?;
+    for endpoint_id in endpoint_ids {
+        WebhookDelivery::enqueue(endpoint_id, &event.id, event_type.as_str(), &payload).await?;
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct NewWebhook {
+    url: String,
+    event_types: Vec<String>,
+    description: Option<String>,
+}
+
+/// The only response carrying the signing secret
+#[derive(Serialize)]
+pub struct CreatedWebhook {
+    pub secret: String,
+    #[serde(flatten)]
+    pub endpoint: WebhookEndpoint,
+}
+impl ApiSchema for CreatedWebhook {
+    fn schema_name() -> String {
+        "CreatedWebhook".to_string()
+    }
+    fn schema() -> Value {
+        json!({
+            "allOf": [
+                WebhookEndpoint::schema_ref(),
+                {
+                    "type": "object",
+                    "required": ["secret"],
+                    "properties": {
+                        "secret": {
+                            "type": "string",
+                            "description": "HMAC-SHA256 key of `X-Webhook-Signature`, shown once"
+                        }
+                    }
+                }
+            ]
+        })
+    }
+    fn register(schemas: &mut serde_json::Map<String, Value>) {
+        WebhookEndpoint::register(schemas);
+        schemas.insert(Self::schema_name(), Self::schema());
+    }
+}
+
+/// HTTPS only, plain HTTP is accepted when `WEBHOOK_ALLOW_HTTP` is set (local receivers)
+fn verify_url(raw: &str) -> AppResult<Url> {
+    let invalid = || AppError::invalid_field("url", ErrorCode::ValidationFailed);
+    if raw.len() > URL_MAX_LENGTH {
+        return Err(invalid());
+    }
+    let url = Url::parse(raw.trim()).map_err(|_| invalid())?;
+    let scheme_allowed = match url.scheme() {
+        "https" => true,
+        "http" => env_flag("WEBHOOK_ALLOW_HTTP", false),
+        _ => false,
+    };
+    if !scheme_allowed || url.host_str().is_none() || !url.username().is_empty() {
+        return Err(invalid());
+    }
+    Ok(url)
+}
+
+pub async fn create_webhook_service(
+    admin_id: i32,
+    new_webhook: NewWebhook,
+) -> AppResult<CreatedWebhook> {
+    let url = verify_url(&new_webhook.url)?;
+    let invalid_events = || AppError::invalid_field("event_types", ErrorCode::ValidationFailed);
+    let mut event_types = Vec::new();
+    for raw in &new_webhook.event_types {
+        let event_type = WebhookEventType::parse(raw).ok_or_else(invalid_events)?;
+        if !event_types.contains(&event_type) {
+            event_types.push(event_type);
+        }
+    }
+    if event_types.is_empty() {
+        return Err(invalid_events());
+    }
+    let description = new_webhook
+        .description
+        .as_deref()
+        .map(str::trim)
+        .filter(|description| !description.is_empty());
+    if description.is_some_and(|d| d.chars().count() > DESCRIPTION_MAX_LENGTH) {
+        return Err(AppError::invalid_field(
+            "description",
+            ErrorCode::ValidationFailed,
+        ));
+    }
+
+    let (endpoint, secret) =
+        WebhookEndpoint::create(admin_id, url.as_str(), &event_types, description).await?;
+    Ok(CreatedWebhook { secret, endpoint })
+}
+
+pub async fn list_webhooks_service() -> AppResult<Vec<WebhookEndpoint>> {
+    WebhookEndpoint::list().await
+}
+
+pub async fn delete_webhook_service(webhook_id: i32) -> AppResult<WebhookEndpoint> {
+    WebhookEndpoint::delete(webhook_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))
+}
+
+pub async fn list_deliveries_service(
+    webhook_id: i32,
+    page: PageQuery,
+) -> AppResult<(Vec<WebhookDelivery>, PageMeta)> {
+    if WebhookEndpoint::get(webhook_id).await?.is_none() {
+        return Err(AppError::NotFound(ErrorCode::NotFound));
+    }
+    let (page, per_page) = page.resolve(DELIVERIES_PER_PAGE, DELIVERIES_MAX_PER_PAGE);
+    let total = WebhookDelivery::count(webhook_id).await?;
+    let offset = (page as u64 - 1) * per_page as u64;
+    let deliveries = WebhookDelivery::list(webhook_id, per_page as u64, offset).await?;
+    Ok((deliveries, PageMeta::new(page, per_page, total)))
+}
+
+pub async fn get_delivery_service(webhook_id: i32, delivery_id: i64) -> AppResult<WebhookDelivery> {
+    WebhookDelivery::get(webhook_id, delivery_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))
+}
+
+/// Sends the payload of a delivery again, whatever became of it
+pub async fn replay_delivery_service(
+    webhook_id: i32,
+    delivery_id: i64,
+) -> AppResult<WebhookDelivery> {
+    WebhookDelivery::replay(webhook_id, delivery_id)
+        .await?
+        .ok_or(AppError::NotFound(ErrorCode::NotFound))
+}
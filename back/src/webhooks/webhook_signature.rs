@@ -0,0 +1,29 @@
+use hmac::{Hmac, Mac};
+use password_hash::rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+
+pub const SECRET_PREFIX: &str = "whsec_";
+
+pub const EVENT_ID_HEADER: &str = "X-Webhook-Id";
+pub const DELIVERY_ID_HEADER: &str = "X-Webhook-Delivery";
+pub const EVENT_TYPE_HEADER: &str = "X-Webhook-Event";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+/// `sha256=<hex>`, receivers should also reject stale timestamps
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    format!("{SECRET_PREFIX}{}", hex::encode(bytes))
+}
+
+/// HMAC-SHA256 of `{timestamp}.{payload}` keyed by the endpoint secret,
+/// the value of [`SIGNATURE_HEADER`]
+pub fn sign(secret: &str, timestamp: u64, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
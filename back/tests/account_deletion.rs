@@ -0,0 +1,159 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use back::{
+    DB_POOL, auth::auth_models::email::Email, jobs::account_purge::purge_deleted_accounts,
+    models::user_model::User,
+};
+use common::{PASSWORD, last_link_key, run, spawn_app, unique_email};
+use serde_json::json;
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn deletion_can_be_restored_then_purged() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        client.register_verified(&email).await;
+
+        let res = client
+            .post(
+                "/account/deletion",
+                json!({ "password": "not the password" }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::FORBIDDEN);
+        let res = client
+            .post("/account/deletion", json!({ "password": PASSWORD }))
+            .await;
+        assert_eq!(res.status, StatusCode::ACCEPTED);
+        assert!(res.data()["scheduled_at"].as_i64() > res.data()["requested_at"].as_i64());
+
+        // every session is revoked
+        assert_eq!(
+            client.get("/account/tokens").await.status,
+            StatusCode::UNAUTHORIZED
+        );
+        client.forget();
+        let res = client.login(&email, PASSWORD).await;
+        assert_eq!(res.status, StatusCode::FORBIDDEN);
+        assert_eq!(res.code(), "ACCOUNT_PENDING_DELETION");
+
+        let key = last_link_key(&email, "/auth/restore").expect("deletion mail not sent");
+        // not a password reset nor a verification link
+        let res = client
+            .post("/auth/reset/validate", json!({ "key": key }))
+            .await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        let res = client.post("/auth/verify", json!({ "key": key })).await;
+        assert_ne!(res.status, StatusCode::OK);
+        let res = client.post("/auth/restore", json!({ "key": key })).await;
+        assert_eq!(res.status, StatusCode::OK);
+        let res = client.post("/auth/restore", json!({ "key": key })).await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        // nor is a reset key a restore link
+        client.forgot(&email).await;
+        let reset_key = last_link_key(&email, "/auth/reset").expect("reset mail not sent");
+        let res = client
+            .post("/auth/restore", json!({ "key": reset_key }))
+            .await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        // tokens issued in the same second as the revocation are refused
+        actix_rt::time::sleep(Duration::from_secs(1)).await;
+        client.bearer = client.login(&email, PASSWORD).await.token();
+        assert!(client.bearer.is_some());
+        assert_eq!(client.get("/account/tokens").await.status, StatusCode::OK);
+
+        let res = client
+            .post("/account/deletion", json!({ "password": PASSWORD }))
+            .await;
+        assert_eq!(res.status, StatusCode::ACCEPTED);
+        let id = User::get_user_id_from_email(&Email::new(&email).unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        sqlx::query(
+            "UPDATE users SET deletion_scheduled_at = NOW() - INTERVAL 1 MINUTE WHERE id = ?",
+        )
+        .bind(id)
+        .execute(&*DB_POOL)
+        .await
+        .unwrap();
+
+        assert!(purge_deleted_accounts().await.unwrap() >= 1);
+        assert!(User::try_get(id).await.unwrap().is_none());
+        let key = last_link_key(&email, "/auth/restore").unwrap();
+        let res = client.post("/auth/restore", json!({ "key": key })).await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn last_owners_hand_their_organizations_over() {
+    run(async {
+        let mut owner = spawn_app().await;
+        let mut member = spawn_app().await;
+        let owner_email = unique_email();
+        owner.register_verified(&owner_email).await;
+        let member_email = unique_email();
+        member.register_verified(&member_email).await;
+        let id_of = |email: String| async move {
+            User::get_user_id_from_email(&Email::new(&email).unwrap())
+                .await
+                .unwrap()
+                .unwrap()
+        };
+        let owner_id = id_of(owner_email).await;
+        let member_id = id_of(member_email.clone()).await;
+
+        let res = owner.post("/orgs", json!({ "name": "Shared" })).await;
+        let shared_id = res.data()["id"].as_i64().unwrap();
+        let res = owner.post("/orgs", json!({ "name": "Solo" })).await;
+        let solo_id = res.data()["id"].as_i64().unwrap();
+        owner
+            .post(
+                &format!("/orgs/{shared_id}/invitations"),
+                json!({ "email": member_email, "role": "member" }),
+            )
+            .await;
+        let key = last_link_key(&member_email, "/orgs/join").expect("invitation not mailed");
+        let res = member
+            .post("/orgs/invitations/accept", json!({ "key": key }))
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+
+        let res = owner
+            .post("/account/deletion", json!({ "password": PASSWORD }))
+            .await;
+        assert_eq!(res.status, StatusCode::CONFLICT);
+        assert_eq!(res.code(), "ORGANIZATION_LAST_OWNER");
+
+        // scheduled before the other owners left
+        sqlx::query(
+            "UPDATE users SET deletion_scheduled_at = NOW() - INTERVAL 1 MINUTE WHERE id = ?",
+        )
+        .bind(owner_id)
+        .execute(&*DB_POOL)
+        .await
+        .unwrap();
+        assert!(purge_deleted_accounts().await.unwrap() >= 1);
+        let role: String = sqlx::query_scalar(
+            "SELECT role FROM organization_members WHERE org_id = ? AND user_id = ?",
+        )
+        .bind(shared_id)
+        .bind(member_id)
+        .fetch_one(&*DB_POOL)
+        .await
+        .unwrap();
+        assert_eq!(role, "owner");
+        let solo: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM organizations WHERE id = ?")
+            .bind(solo_id)
+            .fetch_one(&*DB_POOL)
+            .await
+            .unwrap();
+        assert_eq!(solo, 0);
+    });
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use actix_web::http::{StatusCode, header};
+use back::{DB_POOL, auth::auth_models::email::Email, models::user_model::User};
+use common::{PASSWORD, run, spawn_app, unique_email};
+
+async fn user_id(email: &str) -> i32 {
+    User::get_user_id_from_email(&Email::new(email).unwrap())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn authentication_events_are_audited() {
+    run(async {
+        let mut admin = spawn_app().await;
+        let mut user = spawn_app().await;
+        let admin_email = unique_email();
+        admin.register_verified(&admin_email).await;
+        sqlx::query("UPDATE users SET admin = 1 WHERE id = ?")
+            .bind(user_id(&admin_email).await)
+            .execute(&*DB_POOL)
+            .await
+            .unwrap();
+        admin.forget();
+        admin.bearer = admin.login(&admin_email, PASSWORD).await.token();
+
+        let email = unique_email();
+        user.register_verified(&email).await;
+        let id = user_id(&email).await;
+        user.forget();
+        let res = user.login(&email, "not the password").await;
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+        user.bearer = user.login(&email, PASSWORD).await.token();
+        user.logout().await;
+
+        assert_eq!(
+            user.get("/admin/audit-events").await.status,
+            StatusCode::FORBIDDEN
+        );
+
+        let res = admin
+            .get(&format!("/admin/audit-events?subject_id={id}&per_page=10"))
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+        let actions: Vec<_> = res
+            .data()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| event["action"].as_str().unwrap())
+            .collect();
+        // newest first
+        assert_eq!(
+            actions,
+            [
+                "auth.logout",
+                "auth.login",
+                "auth.login",
+                "auth.email_verification",
+                "auth.register"
+            ]
+        );
+        assert_eq!(res.body["meta"]["total"], 5);
+
+        let res = admin
+            .get(&format!(
+                "/admin/audit-events?subject_id={id}&action=auth.login&outcome=failure"
+            ))
+            .await;
+        assert_eq!(res.body["meta"]["total"], 1);
+        let failed = &res.data()[0];
+        assert_eq!(failed["metadata"]["email"], email.as_str());
+        assert_eq!(failed["metadata"]["method"], "password");
+
+        let res = admin
+            .get(&format!(
+                "/admin/audit-events/export?format=csv&subject_id={id}"
+            ))
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+        let disposition = res.headers[header::CONTENT_DISPOSITION].to_str().unwrap();
+        assert!(disposition.starts_with("attachment"));
+        assert!(disposition.contains(".csv"));
+    });
+}
@@ -0,0 +1,456 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use back::{
+    auth::auth_models::{email::Email, token::TokenAble},
+    models::user_model::User,
+    shared::get_now_unix,
+    utils::{
+        password_utils::{PasswordCheck, verify_password},
+        redis_utils::redis_set,
+    },
+};
+use common::{PASSWORD, last_link_key, mail_count, run, spawn_app, unique_email};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Same shape as `InternalUserClaim`, which cannot be built already expired
+#[derive(Serialize, Deserialize)]
+struct ExpiredLinkClaim {
+    user_id: i32,
+    exp: u64,
+}
+impl TokenAble for ExpiredLinkClaim {}
+
+async fn user_id(email: &str) -> i32 {
+    User::get_user_id_from_email(&Email::new(email).unwrap())
+        .await
+        .unwrap()
+        .expect("user should exist")
+}
+
+/// Put an already expired link key in the cache, as if the mail was opened too late
+async fn expired_link_key(email: &str) -> String {
+    let claim = ExpiredLinkClaim {
+        user_id: user_id(email).await,
+        exp: get_now_unix() - 3600,
+    };
+    let key = uuid::Uuid::new_v4().to_string();
+    redis_set(&key, &claim.encode().unwrap().as_ref())
+        .await
+        .unwrap();
+    key
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn register_sends_link_and_login_reports_not_verified() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+
+        let res = client.register(&email).await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.code(), "USER_NOT_VERIFIED");
+        assert!(last_link_key(&email, "/auth/verify").is_some());
+
+        // LoginResult::NotVerified(Some(_)) from a new browser
+        client.forget();
+        let res = client.login(&email, PASSWORD).await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.code(), "USER_NOT_VERIFIED");
+        assert!(client.session.is_some());
+
+        // LoginResult::NotVerified(None) once the session exists
+        let res = client.login(&email, PASSWORD).await;
+        assert_eq!(res.code(), "USER_NOT_VERIFIED");
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn register_twice_conflicts() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        client.register(&email).await;
+
+        client.forget();
+        let res = client.register(&email).await;
+        assert_eq!(res.status, StatusCode::CONFLICT);
+        assert_eq!(res.code(), "EMAIL_ALREADY_EXISTS");
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn email_case_does_not_create_a_second_account() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        client.register_verified(&email).await;
+
+        let shouted = email.to_uppercase();
+
+        client.forget();
+        let res = client.register(&shouted).await;
+        assert_eq!(res.status, StatusCode::CONFLICT);
+
+        client.forget();
+        assert!(client.login(&shouted, PASSWORD).await.token().is_some());
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn register_with_weak_password_lists_every_violation() {
+    run(async {
+        let mut client = spawn_app().await;
+        let res = client
+            .post(
+                "/auth/register",
+                json!({ "email": unique_email(), "password": "aaaaaa" }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let codes: Vec<_> = res.body["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| error["code"].as_str().unwrap().to_string())
+            .collect();
+        for expected in [
+            "PASSWORD_TOO_SHORT",
+            "PASSWORD_MISSING_UPPERCASE",
+            "PASSWORD_MISSING_DIGIT",
+            "PASSWORD_MISSING_SYMBOL",
+            "PASSWORD_TOO_PREDICTABLE",
+        ] {
+            assert!(
+                codes.iter().any(|code| code == expected),
+                "{expected} missing"
+            );
+        }
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn verify_from_registering_session_returns_token() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        client.register(&email).await;
+        let key = last_link_key(&email, "/auth/verify").unwrap();
+
+        // VerifyResult::Token
+        let res = client.verify(&key).await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert!(res.token().is_some());
+
+        // the link is single use: VerifyResult::Invalid
+        let res = client.verify(&key).await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        assert_eq!(res.code(), "TOKEN_INVALID");
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn verify_from_another_browser_reports_verified() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        client.register(&email).await;
+        let key = last_link_key(&email, "/auth/verify").unwrap();
+
+        // VerifyResult::Verified
+        client.forget();
+        let res = client.verify(&key).await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.code(), "USER_VERIFIED");
+
+        // LoginResult::Connected(_, Some(_))
+        let res = client.login(&email, PASSWORD).await;
+        assert!(res.token().is_some());
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn verify_with_expired_link_sends_a_new_one() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        client.register(&email).await;
+        let mails_before = mail_count(&email);
+
+        // VerifyResult::Expired
+        let key = expired_link_key(&email).await;
+        let res = client.verify(&key).await;
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(res.code(), "TOKEN_EXPIRED");
+        assert_eq!(mail_count(&email), mails_before + 1);
+
+        let fresh_key = last_link_key(&email, "/auth/verify").unwrap();
+        assert!(client.verify(&fresh_key).await.token().is_some());
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn login_with_bad_credentials_is_rejected() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        client.register_verified(&email).await;
+        client.forget();
+
+        // LoginResult::CredentialsIncorect
+        let res = client.login(&email, "Wr0ng!Password").await;
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(res.code(), "CREDENTIALS_INCORRECT");
+        assert_eq!(
+            res.headers.get("content-type").unwrap(),
+            "application/problem+json"
+        );
+
+        // the password policy is not applied at login, only the email shape is
+        let res = client.login("not-an-email", "short").await;
+        assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(res.code(), "VALIDATION_FAILED");
+        assert_eq!(res.body["errors"].as_array().unwrap().len(), 1);
+        assert_eq!(res.body["errors"][0]["code"], "EMAIL_INVALID");
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn connected_user_refreshes_and_logs_out() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        client.register_verified(&email).await;
+
+        // LoginResult::Connected(_, None) through the bearer token
+        let res = client.login(&email, PASSWORD).await;
+        assert!(res.token().is_some());
+
+        // the session alone is enough to get a new access token
+        client.bearer = None;
+        let res = client.refresh().await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert!(res.token().is_some());
+
+        let res = client.logout().await;
+        assert_eq!(res.code(), "USER_LOGGED_OUT");
+        let res = client.refresh().await;
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(res.code(), "USER_NOT_LOGGED_IN");
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn password_reset_flow() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        client.register_verified(&email).await;
+        client.forget();
+
+        let res = client.forgot(&email).await;
+        assert_eq!(res.status, StatusCode::OK);
+        let key = last_link_key(&email, "/auth/reset").expect("reset mail not sent");
+
+        let res = client
+            .post("/auth/reset/validate", json!({ "key": key }))
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+
+        // ChangePasswordResult::PasswordInvalid, the key stays usable
+        let res = client.reset_password(&key, "weak").await;
+        assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(res.body["errors"][0]["field"], "raw_password");
+        let local_part = email.split('@').next().unwrap();
+        let res = client
+            .reset_password(&key, &format!("X9!{local_part}"))
+            .await;
+        assert_eq!(res.body["errors"][0]["code"], "PASSWORD_CONTAINS_EMAIL");
+
+        // ChangePasswordResult::PasswordChanged
+        let new_password = "An0ther!Password";
+        let res = client.reset_password(&key, new_password).await;
+        assert_eq!(res.status, StatusCode::OK);
+
+        // ChangePasswordResult::KeyInvalid, the key was consumed
+        let res = client.reset_password(&key, new_password).await;
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(res.code(), "TOKEN_INVALID");
+
+        assert_eq!(
+            client.login(&email, PASSWORD).await.code(),
+            "CREDENTIALS_INCORRECT"
+        );
+        client.forget();
+        assert!(client.login(&email, new_password).await.token().is_some());
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn password_reset_with_expired_key_sends_a_new_link() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        client.register_verified(&email).await;
+        client.forget();
+        let mails_before = mail_count(&email);
+
+        // ChangePasswordResult::KeyExpired
+        let key = expired_link_key(&email).await;
+        let res = client.reset_password(&key, "An0ther!Password").await;
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(res.code(), "TOKEN_EXPIRED");
+        assert_eq!(mail_count(&email), mails_before + 1);
+        assert!(last_link_key(&email, "/auth/reset").is_some());
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn imported_bcrypt_user_is_upgraded_on_login() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        let legacy_hash = bcrypt::hash(PASSWORD, 4).unwrap();
+        let id = User::import_legacy(&Email::new(&email).unwrap(), &legacy_hash, true)
+            .await
+            .unwrap();
+
+        assert!(client.login(&email, PASSWORD).await.token().is_some());
+
+        let stored: String = sqlx::query_scalar("SELECT password FROM users WHERE id=?")
+            .bind(id)
+            .fetch_one(&*back::DB_POOL)
+            .await
+            .unwrap();
+        assert!(stored.starts_with("$argon2id$"));
+        assert_eq!(
+            verify_password(PASSWORD, &stored).unwrap(),
+            PasswordCheck::Valid
+        );
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn session_authenticated_requests_need_the_csrf_token() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        let token = client.register_verified(&email).await;
+        client.auto_csrf = false;
+
+        // the bearer header is not sent by a cross-site form, no token needed
+        assert_eq!(client.refresh().await.status, StatusCode::OK);
+
+        client.bearer = None;
+        let res = client.refresh().await;
+        assert_eq!(res.status, StatusCode::FORBIDDEN);
+        assert_eq!(res.code(), "CSRF_TOKEN_INVALID");
+
+        let res = client.get("/auth/csrf").await;
+        assert_eq!(res.data()["header"], "X-CSRF-Token");
+        let csrf = res.data()["csrf_token"].as_str().unwrap().to_string();
+        assert_eq!(client.get("/auth/csrf").await.data()["csrf_token"], csrf);
+
+        client.csrf = Some("0".repeat(csrf.len()));
+        client.auto_csrf = true;
+        assert_eq!(client.refresh().await.code(), "CSRF_TOKEN_INVALID");
+        client.csrf = Some(csrf);
+        assert!(client.refresh().await.token().is_some());
+
+        // a guest has nothing to protect
+        client.forget();
+        client.auto_csrf = false;
+        assert!(client.login(&email, PASSWORD).await.token().is_some());
+        client.bearer = Some(token);
+        assert_eq!(client.logout().await.code(), "USER_LOGGED_OUT");
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn protected_scopes_need_the_csrf_token_without_bearer() {
+    run(async {
+        let mut client = spawn_app().await;
+        let token = client.register_verified(&unique_email()).await;
+        client.auto_csrf = false;
+        client.bearer = None;
+
+        let res = client.post_empty("/account/export").await;
+        assert_eq!(res.status, StatusCode::FORBIDDEN);
+        assert_eq!(res.code(), "CSRF_TOKEN_INVALID");
+        let res = client
+            .post("/nodes/folders", json!({ "name": "docs" }))
+            .await;
+        assert_eq!(res.code(), "CSRF_TOKEN_INVALID");
+        // reading is not a forgery target
+        assert_eq!(client.get("/account/profile").await.status, StatusCode::OK);
+
+        client.auto_csrf = true;
+        let res = client.post_empty("/account/export").await;
+        assert_eq!(res.status, StatusCode::ACCEPTED);
+        client.auto_csrf = false;
+        client.bearer = Some(token);
+        let res = client
+            .post("/nodes/folders", json!({ "name": "docs" }))
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn magic_link_signs_in_only_the_requesting_browser() {
+    run(async {
+        let mut client = spawn_app().await;
+        let mut other_browser = spawn_app().await;
+        let email = unique_email();
+        client.register(&email).await;
+        client.forget();
+
+        let res = client.post("/auth/magic-link", json!(email)).await;
+        assert_eq!(res.status, StatusCode::OK);
+        let key = last_link_key(&email, "/auth/magic-link").expect("magic link not sent");
+
+        // forwarded link
+        let res = other_browser
+            .post("/auth/magic-link/verify", json!({ "key": key }))
+            .await;
+        assert_eq!(res.status, StatusCode::FORBIDDEN);
+        assert_eq!(res.code(), "MAGIC_LINK_OTHER_DEVICE");
+
+        // following the link also verifies the address
+        let res = client
+            .post("/auth/magic-link/verify", json!({ "key": key }))
+            .await;
+        assert!(res.token().is_some());
+        assert!(client.session.is_some());
+        assert_eq!(client.refresh().await.status, StatusCode::OK);
+
+        let res = client
+            .post("/auth/magic-link/verify", json!({ "key": key }))
+            .await;
+        assert_eq!(res.code(), "TOKEN_INVALID");
+
+        // unknown addresses look the same
+        let before = mail_count("nobody@example.com");
+        let res = other_browser
+            .post("/auth/magic-link", json!("nobody@example.com"))
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(mail_count("nobody@example.com"), before);
+    });
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use actix_web::{http::StatusCode, web::Bytes};
+use back::{
+    DB_POOL,
+    auth::auth_models::email::Email,
+    jobs::{blob_gc::collect_garbage, blob_scrub::scrub_blobs},
+    models::user_model::User,
+    storage::{StorageKey, storage},
+};
+use common::{PASSWORD, run, spawn_app, unique_email};
+use futures_util::stream;
+
+/// `(ref_count, storage_key)` of the blob, `None` once collected
+async fn blob(sha256: &str) -> Option<(i32, StorageKey)> {
+    let row: Option<(i32, String)> =
+        sqlx::query_as("SELECT ref_count, storage_key FROM blobs WHERE sha256 = ?")
+            .bind(sha256)
+            .fetch_optional(&*DB_POOL)
+            .await
+            .unwrap();
+    row.map(|(count, key)| (count, StorageKey::parse(&key).unwrap()))
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn identical_contents_are_stored_once_and_collected() {
+    run(async {
+        let mut first = spawn_app().await;
+        let mut second = spawn_app().await;
+        first.register_verified(&unique_email()).await;
+        second.register_verified(&unique_email()).await;
+        let content = format!("shared by {}", unique_email()).into_bytes();
+
+        let res = first
+            .post_bytes("/files/raw?name=a.txt", "text/plain", content.clone())
+            .await;
+        let first_file = res.data()["id"].as_i64().unwrap();
+        let sha256 = res.data()["sha256"].as_str().unwrap().to_string();
+        let res = second
+            .post_bytes("/files/raw?name=b.txt", "text/plain", content.clone())
+            .await;
+        assert_eq!(res.data()["sha256"], sha256.as_str());
+        let second_file = res.data()["id"].as_i64().unwrap();
+        let (count, key) = blob(&sha256).await.unwrap();
+        assert_eq!(count, 2);
+
+        let res = first.delete(&format!("/files/{first_file}")).await;
+        assert_eq!(res.status, StatusCode::OK);
+        collect_garbage().await.unwrap();
+        assert_eq!(blob(&sha256).await.unwrap().0, 1);
+        let res = second.get(&format!("/files/{second_file}/content")).await;
+        assert_eq!(res.bytes, content);
+
+        second.delete(&format!("/files/{second_file}")).await;
+        assert_eq!(blob(&sha256).await.unwrap().0, 0);
+        collect_garbage().await.unwrap();
+        assert!(blob(&sha256).await.is_none());
+        assert!(storage().get(&key).await.unwrap().is_none());
+
+        // uploaded again after collection, stored anew
+        let res = first
+            .post_bytes("/files/raw?name=a.txt", "text/plain", content.clone())
+            .await;
+        let file_id = res.data()["id"].as_i64().unwrap();
+        let res = first.get(&format!("/files/{file_id}/content")).await;
+        assert_eq!(res.bytes, content);
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn scrub_reports_corrupted_contents_until_healed() {
+    run(async {
+        let mut admin = spawn_app().await;
+        let mut user = spawn_app().await;
+        let admin_email = unique_email();
+        admin.register_verified(&admin_email).await;
+        let admin_id = User::get_user_id_from_email(&Email::new(&admin_email).unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        sqlx::query("UPDATE users SET admin = 1 WHERE id = ?")
+            .bind(admin_id)
+            .execute(&*DB_POOL)
+            .await
+            .unwrap();
+        admin.forget();
+        admin.bearer = admin.login(&admin_email, PASSWORD).await.token();
+        user.register_verified(&unique_email()).await;
+
+        let content = format!("scrubbed for {}", unique_email()).into_bytes();
+        let res = user
+            .post_bytes("/files/raw?name=c.txt", "text/plain", content.clone())
+            .await;
+        let file_id = res.data()["id"].as_i64().unwrap();
+        let sha256 = res.data()["sha256"].as_str().unwrap().to_string();
+        assert!(!scrub_blobs(u64::MAX).await.unwrap().contains(&sha256));
+
+        let (_, key) = blob(&sha256).await.unwrap();
+        storage()
+            .put(
+                &key,
+                Box::pin(stream::once(async { Ok(Bytes::from_static(b"bit rot")) })),
+            )
+            .await
+            .unwrap();
+        assert!(scrub_blobs(u64::MAX).await.unwrap().contains(&sha256));
+        let res = admin.get("/admin/blobs/corrupted").await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert!(
+            res.data()
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|blob| blob["sha256"] == sha256.as_str())
+        );
+        assert_eq!(
+            user.get("/admin/blobs/corrupted").await.status,
+            StatusCode::FORBIDDEN
+        );
+
+        // the same bytes uploaded again replace the damaged copy
+        user.post_bytes("/files/raw?name=d.txt", "text/plain", content.clone())
+            .await;
+        let (count, healed) = blob(&sha256).await.unwrap();
+        assert_eq!(count, 2);
+        assert_ne!(healed, key);
+        let res = user.get(&format!("/files/{file_id}/content")).await;
+        assert_eq!(res.bytes, content);
+        let res = admin.get("/admin/blobs/corrupted").await;
+        assert!(
+            !res.data()
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|blob| blob["sha256"] == sha256.as_str())
+        );
+        collect_garbage().await.unwrap();
+        assert!(storage().get(&key).await.unwrap().is_none());
+    });
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use std::io::{Cursor, Read};
+
+use actix_web::http::{StatusCode, header};
+use back::jobs::data_export::build_pending_exports;
+use common::{last_link_key, run, spawn_app, unique_email};
+use serde_json::{Value, json};
+use zip::ZipArchive;
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn export_is_built_then_downloaded_once() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        client.register_verified(&email).await;
+        let res = client
+            .post_bytes(
+                "/files/raw?name=report.txt",
+                "text/plain",
+                b"report".to_vec(),
+            )
+            .await;
+        let file_id = res.data()["id"].clone();
+        let sha256 = res.data()["sha256"].clone();
+        let res = client
+            .post("/nodes/folders", json!({ "name": "docs" }))
+            .await;
+        let docs = res.data()["id"].clone();
+        client
+            .post(
+                "/nodes/files",
+                json!({ "file_id": file_id, "parent_id": docs }),
+            )
+            .await;
+        client.post("/orgs", json!({ "name": "Acme" })).await;
+
+        assert_eq!(
+            client.get("/account/export").await.status,
+            StatusCode::NOT_FOUND
+        );
+        let res = client.post_empty("/account/export").await;
+        assert_eq!(res.status, StatusCode::ACCEPTED);
+        assert_eq!(res.data()["status"], "pending");
+        let id = res.data()["id"].clone();
+        // not queued twice
+        let res = client.post_empty("/account/export").await;
+        assert_eq!(res.data()["id"], id);
+
+        assert!(build_pending_exports().await.unwrap() >= 1);
+        let res = client.get("/account/export").await;
+        assert_eq!(res.data()["status"], "ready");
+
+        let key = last_link_key(&email, "/account/export/download").expect("export mail not sent");
+        let res = client
+            .get(&format!("/account/export/download?key={key}"))
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.headers[header::CONTENT_TYPE], "application/zip");
+
+        let mut archive = ZipArchive::new(Cursor::new(res.bytes.to_vec())).unwrap();
+        let mut document = |name: &str| -> Value {
+            let mut raw = String::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut raw)
+                .unwrap();
+            serde_json::from_str(&raw).unwrap()
+        };
+        let profile = document("profile.json");
+        assert_eq!(profile["email"], email.as_str());
+        assert!(profile.get("password").is_none());
+        let files = document("files.json");
+        assert_eq!(files[0]["name"], "report.txt");
+        assert_eq!(files[0]["size"], 6);
+        assert_eq!(files[0]["sha256"], sha256);
+        assert_eq!(files[0]["path"], "/docs/report.txt");
+        assert_eq!(document("folders.json")[0]["path"], "/docs");
+        let organizations = document("organizations.json");
+        assert_eq!(organizations[0]["name"], "Acme");
+        assert_eq!(organizations[0]["role"], "owner");
+        for name in [
+            "README.txt",
+            "sessions.json",
+            "audit_events.json",
+            "invitations.json",
+        ] {
+            assert!(archive.by_name(name).is_ok(), "{name} missing");
+        }
+
+        let res = client
+            .get(&format!("/account/export/download?key={key}"))
+            .await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        let res = client.get("/account/export").await;
+        assert_eq!(res.data()["status"], "downloaded");
+    });
+}
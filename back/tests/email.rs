@@ -0,0 +1,63 @@
+use back::auth::auth_models::email::Email;
+
+fn canonical(raw: &str) -> Option<String> {
+    Email::parse(raw).map(|email| email.canonical().to_string())
+}
+
+#[test]
+fn case_only_differences_share_the_canonical_form() {
+    assert_eq!(canonical("Bob@X.com"), canonical("bob@x.com"));
+    assert_eq!(Email::parse("Bob@X.COM").unwrap().as_ref(), "Bob@x.com");
+}
+
+#[test]
+fn international_domains_are_idna_normalized() {
+    let email = Email::parse("user@Exämple.com").unwrap();
+    assert_eq!(email.as_ref(), "user@exämple.com");
+    assert_eq!(email.canonical(), "user@xn--exmple-cua.com");
+    assert_eq!(
+        canonical("user@xn--exmple-cua.com").unwrap(),
+        email.canonical()
+    );
+    assert!(Email::parse("用户@例子.广告").is_some());
+}
+
+#[test]
+fn rfc_5321_local_parts_are_accepted() {
+    for valid in [
+        "first.last+tag@example.com",
+        "o'brien@example.org",
+        "x@example.museum",
+        "\"john doe\"@example.com",
+        "\"a@b\"@example.com",
+    ] {
+        assert!(Email::parse(valid).is_some(), "{valid} rejected");
+    }
+    // needless quotes do not make another mailbox
+    assert_eq!(
+        canonical("\"bob\"@example.com"),
+        canonical("bob@example.com")
+    );
+}
+
+#[test]
+fn malformed_addresses_are_rejected() {
+    for invalid in [
+        "plainaddress",
+        "@example.com",
+        "bob@",
+        "bob@localhost",
+        ".bob@example.com",
+        "bob..smith@example.com",
+        "bob smith@example.com",
+        "bob@exa mple.com",
+        "bob@-example.com",
+        "bob@192.168.0.1",
+        "bob@[192.168.0.1]",
+        "\"unterminated@example.com",
+    ] {
+        assert!(Email::parse(invalid).is_none(), "{invalid} accepted");
+    }
+    let long_local = format!("{}@example.com", "a".repeat(65));
+    assert!(Email::parse(&long_local).is_none());
+}
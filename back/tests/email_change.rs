@@ -0,0 +1,79 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use common::{PASSWORD, last_link_key, run, spawn_app, unique_email};
+use serde_json::json;
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn email_changes_once_the_new_address_is_confirmed() {
+    run(async {
+        let mut client = spawn_app().await;
+        let mut other_browser = spawn_app().await;
+        let email = unique_email();
+        client.register_verified(&email).await;
+        let taken = unique_email();
+        other_browser.register_verified(&taken).await;
+        other_browser.forget();
+
+        let new_email = unique_email();
+        let res = client
+            .post(
+                "/account/email",
+                json!({ "email": new_email, "password": "not the password" }),
+            )
+            .await;
+        assert_eq!(res.code(), "CREDENTIALS_INCORRECT");
+        let res = client
+            .post(
+                "/account/email",
+                json!({ "email": taken.to_uppercase(), "password": PASSWORD }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CONFLICT);
+        assert_eq!(res.code(), "EMAIL_ALREADY_EXISTS");
+        let res = client
+            .post(
+                "/account/email",
+                json!({ "email": new_email, "password": PASSWORD }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::ACCEPTED);
+
+        // nothing changes until the new address is proven
+        let key = last_link_key(&new_email, "/auth/email/confirm").expect("confirmation not sent");
+        assert!(last_link_key(&email, "/auth/email/confirm").is_none());
+        assert!(
+            other_browser
+                .login(&email, PASSWORD)
+                .await
+                .token()
+                .is_some()
+        );
+        other_browser.forget();
+
+        // not a restore link either
+        let res = other_browser
+            .post("/auth/restore", json!({ "key": key }))
+            .await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        let res = other_browser
+            .post("/auth/email/confirm", json!({ "key": key }))
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+        let res = other_browser
+            .post("/auth/email/confirm", json!({ "key": key }))
+            .await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+        let res = other_browser.login(&email, PASSWORD).await;
+        assert_eq!(res.code(), "CREDENTIALS_INCORRECT");
+        assert!(
+            other_browser
+                .login(&new_email, PASSWORD)
+                .await
+                .token()
+                .is_some()
+        );
+    });
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use actix_web::http::{StatusCode, header};
+use common::{run, spawn_app, unique_email};
+use sha2::{Digest, Sha256};
+
+const BOUNDARY: &str = "test-boundary";
+
+fn multipart(file_name: &str, content: &[u8]) -> Vec<u8> {
+    let mut body = format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nignored\r\n\
+         --{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{file_name}\"\r\n\
+         Content-Type: text/plain\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend_from_slice(content);
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+    body
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn files_are_uploaded_downloaded_and_deleted_by_their_owner() {
+    run(async {
+        let mut owner = spawn_app().await;
+        let mut other = spawn_app().await;
+        owner.register_verified(&unique_email()).await;
+        other.register_verified(&unique_email()).await;
+
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let res = owner
+            .post_bytes(
+                "/files/raw?name=..%2F..%2Fetc%2Fdata.bin",
+                "application/octet-stream",
+                content.clone(),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        assert_eq!(res.data()["name"], "data.bin");
+        assert_eq!(res.data()["size"], content.len());
+        assert_eq!(
+            res.data()["sha256"],
+            hex::encode(Sha256::digest(&content)).as_str()
+        );
+        let raw_id = res.data()["id"].as_i64().unwrap();
+
+        let res = owner
+            .post_bytes(
+                "/files",
+                &format!("multipart/form-data; boundary={BOUNDARY}"),
+                multipart("notes été.txt", b"hello"),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        assert_eq!(res.data()["name"], "notes été.txt");
+        assert_eq!(res.data()["content_type"], "text/plain");
+        let res = owner
+            .post_bytes(
+                "/files",
+                &format!("multipart/form-data; boundary={BOUNDARY}"),
+                format!("--{BOUNDARY}--\r\n").into_bytes(),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let res = owner.get("/files").await;
+        assert_eq!(res.body["meta"]["total"], 2);
+
+        let res = owner.get(&format!("/files/{raw_id}/content")).await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.bytes, content);
+        assert_eq!(res.headers[header::ACCEPT_RANGES], "bytes");
+        let disposition = res.headers[header::CONTENT_DISPOSITION].to_str().unwrap();
+        assert!(disposition.starts_with("attachment"));
+        let csp = res.headers[header::CONTENT_SECURITY_POLICY]
+            .to_str()
+            .unwrap();
+        assert!(csp.contains("sandbox"));
+
+        let path = format!("/files/{raw_id}/content");
+        let res = owner.get_with_header(&path, "Range", "bytes=100-199").await;
+        assert_eq!(res.status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.bytes, content[100..200]);
+        assert_eq!(
+            res.headers[header::CONTENT_RANGE],
+            format!("bytes 100-199/{}", content.len()).as_str()
+        );
+        let res = owner.get_with_header(&path, "Range", "bytes=-10").await;
+        assert_eq!(res.status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.bytes, content[content.len() - 10..]);
+        let res = owner
+            .get_with_header(&path, "Range", &format!("bytes={}-", content.len()))
+            .await;
+        assert_eq!(res.status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(res.code(), "RANGE_NOT_SATISFIABLE");
+
+        assert_eq!(
+            other.get(&format!("/files/{raw_id}")).await.status,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            other.delete(&format!("/files/{raw_id}")).await.status,
+            StatusCode::NOT_FOUND
+        );
+
+        assert_eq!(
+            owner.delete(&format!("/files/{raw_id}")).await.status,
+            StatusCode::OK
+        );
+        assert_eq!(
+            owner.get(&format!("/files/{raw_id}/content")).await.status,
+            StatusCode::NOT_FOUND
+        );
+    });
+}
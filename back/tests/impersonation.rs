@@ -0,0 +1,76 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use back::{DB_POOL, auth::auth_models::email::Email, models::user_model::User};
+use common::{PASSWORD, run, spawn_app, unique_email};
+use serde_json::json;
+
+async fn user_id(email: &str) -> i32 {
+    User::get_user_id_from_email(&Email::new(email).unwrap())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn admin_impersonates_then_stops() {
+    run(async {
+        let mut admin = spawn_app().await;
+        let mut target = spawn_app().await;
+        let mut support_tab = spawn_app().await;
+        let admin_email = unique_email();
+        admin.register_verified(&admin_email).await;
+        let admin_id = user_id(&admin_email).await;
+        sqlx::query("UPDATE users SET admin = 1 WHERE id = ?")
+            .bind(admin_id)
+            .execute(&*DB_POOL)
+            .await
+            .unwrap();
+        admin.forget();
+        admin.bearer = admin.login(&admin_email, PASSWORD).await.token();
+
+        let target_email = unique_email();
+        target.register_verified(&target_email).await;
+        let target_id = user_id(&target_email).await;
+        let path = format!("/admin/users/{target_id}/impersonate");
+
+        let res = target.post(&path, json!({ "reason": "curious" })).await;
+        assert_eq!(res.status, StatusCode::FORBIDDEN);
+
+        let res = admin.post(&path, json!({ "reason": "Ticket #12" })).await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        assert_eq!(res.data()["impersonator_id"], admin_id);
+        support_tab.bearer = res.data()["token"].as_str().map(str::to_string);
+
+        let res = support_tab.get("/impersonation").await;
+        assert_eq!(res.data()["user_id"], target_id);
+        assert_eq!(res.data()["impersonator_id"], admin_id);
+        assert_eq!(
+            support_tab.get("/account/phone").await.status,
+            StatusCode::OK
+        );
+        let res = support_tab
+            .put("/account/two-factor", json!({ "enabled": false }))
+            .await;
+        assert_eq!(res.code(), "IMPERSONATION_FORBIDDEN");
+        // the impersonation token is not an admin one
+        let res = support_tab.post(&path, json!({ "reason": "nested" })).await;
+        assert_eq!(res.status, StatusCode::FORBIDDEN);
+
+        let res = support_tab.delete("/impersonation").await;
+        assert_eq!(res.status, StatusCode::OK);
+        let res = support_tab.get("/account/phone").await;
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+
+        let actions: Vec<String> = sqlx::query_scalar(
+            "SELECT action FROM audit_events WHERE actor_id = ? AND subject_id = ? ORDER BY id",
+        )
+        .bind(admin_id)
+        .bind(target_id)
+        .fetch_all(&*DB_POOL)
+        .await
+        .unwrap();
+        assert_eq!(actions, ["impersonation.started", "impersonation.stopped"]);
+    });
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use back::{
+    auth::auth_models::{credential::RawLoginCredential, email::Email},
+    models::user_model::User,
+};
+use common::{PASSWORD, last_link_key, run, spawn_app, unique_email};
+use serde_json::{Value, json};
+
+/// Nobody can register without an invitation, so the inviter is created directly
+async fn create_verified_user(email: &str) -> i32 {
+    let raw: RawLoginCredential =
+        serde_json::from_value(json!({ "email": email, "password": PASSWORD })).unwrap();
+    let user_id = User::create(&raw.verify().unwrap(), None).await.unwrap();
+    User::verify_user(user_id).await.unwrap();
+    user_id
+}
+
+fn registration(email: &str, code: Option<&str>) -> Value {
+    json!({ "email": email, "password": PASSWORD, "invitation_code": code })
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn invite_only_registration() {
+    // SAFETY: the only test of this binary, before the policy is first read
+    unsafe { std::env::set_var("REGISTRATION_MODE", "invite_only") };
+    run(async {
+        let mut client = spawn_app().await;
+        let res = client.get("/auth/registration-policy").await;
+        assert_eq!(res.data()["mode"], "invite_only");
+
+        let invitee = unique_email();
+        let res = client
+            .post("/auth/register", registration(&invitee, None))
+            .await;
+        assert_eq!(res.body["errors"][0]["code"], "INVITATION_REQUIRED");
+
+        let inviter = unique_email();
+        let inviter_id = create_verified_user(&inviter).await;
+        let token = client.login(&inviter, PASSWORD).await.token().unwrap();
+        client.bearer = Some(token);
+
+        let res = client
+            .post("/account/invitations", json!({ "max_uses": 50 }))
+            .await;
+        assert_eq!(res.body["errors"][0]["field"], "max_uses");
+        let res = client
+            .post("/account/invitations", json!({ "email": invitee }))
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        let code = res.data()["code"].as_str().unwrap().to_string();
+        assert_eq!(
+            last_link_key(&invitee, "/auth/register").as_deref(),
+            Some(code.as_str())
+        );
+
+        // bound to the invited address
+        let mut stranger = spawn_app().await;
+        let res = stranger
+            .post("/auth/register", registration(&unique_email(), Some(&code)))
+            .await;
+        assert_eq!(res.body["errors"][0]["code"], "INVITATION_INVALID");
+
+        // typed by hand
+        let typed = code
+            .to_lowercase()
+            .as_bytes()
+            .chunks(4)
+            .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+            .collect::<Vec<_>>()
+            .join("-");
+        let mut newcomer = spawn_app().await;
+        let res = newcomer
+            .post("/auth/register", registration(&invitee, Some(&typed)))
+            .await;
+        assert_eq!(res.code(), "USER_NOT_VERIFIED");
+        let user_id = User::get_user_id_from_email(&Email::new(invitee.as_str()).unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            User::get(user_id).await.unwrap().invited_by,
+            Some(inviter_id)
+        );
+
+        let res = stranger
+            .post("/auth/register", registration(&invitee, Some(&code)))
+            .await;
+        assert_eq!(res.body["errors"][0]["code"], "INVITATION_INVALID");
+
+        let res = client.get("/account/invitations").await;
+        assert_eq!(res.data()[0]["uses"], 1);
+    });
+}
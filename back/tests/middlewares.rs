@@ -0,0 +1,175 @@
+use actix_web::{
+    App, HttpResponse,
+    http::{Method, StatusCode, header},
+    test::{self, TestRequest},
+    web,
+};
+use back::{
+    auth::{
+        auth_models::{claims::Claims, token::TokenAble},
+        middlewares::auth_middleware::{RequireAuth, Role},
+    },
+    middlewares::{cors::CorsConfig, security_headers::SecurityHeaders},
+};
+
+fn cors_config(origins: &[&str], allow_credentials: bool) -> CorsConfig {
+    CorsConfig {
+        allowed_origins: origins.iter().map(|origin| origin.to_string()).collect(),
+        allow_credentials,
+        allowed_methods: vec![Method::GET, Method::POST],
+        max_age: 600,
+    }
+}
+
+async fn ok() -> HttpResponse {
+    HttpResponse::Ok().finish()
+}
+
+async fn whoami(claims: Claims) -> HttpResponse {
+    HttpResponse::Ok().body(claims.user_id.to_string())
+}
+
+#[actix_web::test]
+async fn each_scope_gets_its_own_security_headers() {
+    let app = test::init_service(
+        App::new()
+            .service(
+                web::scope("/auth")
+                    .wrap(SecurityHeaders::api().build())
+                    .route("", web::get().to(ok)),
+            )
+            .service(
+                web::scope("/public")
+                    .wrap(SecurityHeaders::public().hsts(None).build())
+                    .route("", web::get().to(ok)),
+            ),
+    )
+    .await;
+
+    let res = test::call_service(&app, TestRequest::get().uri("/auth").to_request()).await;
+    let headers = res.headers();
+    assert_eq!(
+        headers.get(header::X_CONTENT_TYPE_OPTIONS).unwrap(),
+        "nosniff"
+    );
+    assert_eq!(headers.get(header::X_FRAME_OPTIONS).unwrap(), "DENY");
+    assert_eq!(headers.get(header::REFERRER_POLICY).unwrap(), "no-referrer");
+    assert!(headers.contains_key("permissions-policy"));
+    assert!(
+        headers
+            .get(header::STRICT_TRANSPORT_SECURITY)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("max-age=")
+    );
+
+    let res = test::call_service(&app, TestRequest::get().uri("/public").to_request()).await;
+    let headers = res.headers();
+    assert_eq!(headers.get(header::X_FRAME_OPTIONS).unwrap(), "SAMEORIGIN");
+    assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+}
+
+#[actix_web::test]
+async fn cors_allows_only_configured_origins() {
+    let app = test::init_service(
+        App::new()
+            .wrap(cors_config(&["https://app.example.com"], true).middleware())
+            .route("/ping", web::get().to(ok)),
+    )
+    .await;
+
+    let preflight = TestRequest::default()
+        .method(Method::OPTIONS)
+        .uri("/ping")
+        .insert_header((header::ORIGIN, "https://app.example.com"))
+        .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "POST"))
+        .insert_header((header::ACCESS_CONTROL_REQUEST_HEADERS, "x-csrf-token"))
+        .to_request();
+    let res = test::call_service(&app, preflight).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let headers = res.headers();
+    assert_eq!(
+        headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+        "https://app.example.com"
+    );
+    assert_eq!(
+        headers
+            .get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS)
+            .unwrap(),
+        "true"
+    );
+
+    let foreign = TestRequest::get()
+        .uri("/ping")
+        .insert_header((header::ORIGIN, "https://evil.example.net"))
+        .to_request();
+    let res = test::try_call_service(&app, foreign).await;
+    assert!(res.map_or(true, |res| {
+        !res.headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+    }));
+}
+
+#[actix_web::test]
+async fn wildcard_origin_never_shares_credentials() {
+    let app = test::init_service(
+        App::new()
+            .wrap(cors_config(&["*"], true).middleware())
+            .route("/ping", web::get().to(ok)),
+    )
+    .await;
+    let req = TestRequest::get()
+        .uri("/ping")
+        .insert_header((header::ORIGIN, "https://anywhere.example.org"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert!(
+        res.headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+    );
+    assert!(
+        !res.headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS)
+    );
+}
+
+#[actix_web::test]
+async fn require_auth_guards_the_whole_scope() {
+    let app = test::init_service(
+        App::new().service(
+            web::scope("/admin")
+                .wrap(RequireAuth::role(Role::Admin))
+                .route("", web::get().to(whoami)),
+        ),
+    )
+    .await;
+    let bearer = |claims: Claims| {
+        let token = claims.encode().unwrap();
+        ("Authorization", format!("Bearer {}", token.as_ref()))
+    };
+
+    let res = test::call_service(&app, TestRequest::get().uri("/admin").to_request()).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        res.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/problem+json"
+    );
+    let body: serde_json::Value = test::read_body_json(res).await;
+    assert_eq!(body["code"], "USER_NOT_LOGGED_IN");
+
+    let req = TestRequest::get()
+        .uri("/admin")
+        .insert_header(bearer(Claims::new_user_claim(7)))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let req = TestRequest::get()
+        .uri("/admin")
+        .insert_header(bearer(Claims::new_admin_claim(7)))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(test::read_body(res).await, "7");
+}
@@ -0,0 +1,219 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use common::{TestResponse, run, spawn_app, unique_email};
+use serde_json::{Value, json};
+
+fn id(res: &TestResponse) -> i64 {
+    res.data()["id"].as_i64().unwrap()
+}
+
+fn names(res: &Value) -> Vec<&str> {
+    res["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|node| node["name"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn folders_hold_files_and_resolve_by_path() {
+    run(async {
+        let mut owner = spawn_app().await;
+        let mut other = spawn_app().await;
+        owner.register_verified(&unique_email()).await;
+        other.register_verified(&unique_email()).await;
+
+        let docs = id(&owner
+            .post("/nodes/folders", json!({ "name": "docs" }))
+            .await);
+        let year = id(&owner
+            .post(
+                "/nodes/folders",
+                json!({ "parent_id": docs, "name": "2026" }),
+            )
+            .await);
+        let res = owner
+            .post("/nodes/folders", json!({ "name": "DOCS" }))
+            .await;
+        assert_eq!(res.status, StatusCode::CONFLICT);
+        assert_eq!(res.code(), "NODE_NAME_TAKEN");
+        let res = owner.post("/nodes/folders", json!({ "name": "a/b" })).await;
+        assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let file_id = id(&owner
+            .post_bytes(
+                "/files/raw?name=report.pdf",
+                "text/plain",
+                b"report".to_vec(),
+            )
+            .await);
+        let res = owner
+            .post(
+                "/nodes/files",
+                json!({ "file_id": file_id, "parent_id": year }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        assert_eq!(res.data()["kind"], "file");
+        assert_eq!(res.data()["size"], 6);
+        let report = id(&res);
+        let res = owner
+            .post("/nodes/files", json!({ "file_id": file_id }))
+            .await;
+        assert_eq!(res.code(), "NODE_FILE_ALREADY_PLACED");
+        let res = owner
+            .post(
+                "/nodes/folders",
+                json!({ "parent_id": report, "name": "inside" }),
+            )
+            .await;
+        assert_eq!(res.code(), "NODE_PARENT_INVALID");
+        let other_file = id(&other
+            .post_bytes(
+                "/files/raw?name=theirs.txt",
+                "text/plain",
+                b"theirs".to_vec(),
+            )
+            .await);
+        let res = owner
+            .post("/nodes/files", json!({ "file_id": other_file }))
+            .await;
+        assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let res = owner.get("/nodes/resolve?path=/Docs/2026/report.pdf").await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.data()["id"], report);
+        let res = owner
+            .get("/nodes/resolve?path=/docs/2026/report.pdf/more")
+            .await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        let res = other.get("/nodes/resolve?path=/docs").await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        let res = other.get(&format!("/nodes/{docs}")).await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+        owner
+            .post(
+                "/nodes/folders",
+                json!({ "parent_id": year, "name": "b-folder" }),
+            )
+            .await;
+        let small = id(&owner
+            .post_bytes("/files/raw?name=a.txt", "text/plain", b"a".to_vec())
+            .await);
+        owner
+            .post(
+                "/nodes/files",
+                json!({ "file_id": small, "parent_id": year }),
+            )
+            .await;
+        let res = owner.get(&format!("/nodes?parent_id={year}")).await;
+        assert_eq!(res.body["meta"]["total"], 3);
+        assert_eq!(names(&res.body), ["b-folder", "a.txt", "report.pdf"]);
+        let res = owner
+            .get(&format!(
+                "/nodes?parent_id={year}&sort=size&order=desc&per_page=2"
+            ))
+            .await;
+        assert_eq!(names(&res.body), ["b-folder", "report.pdf"]);
+        let res = owner.get("/nodes").await;
+        assert_eq!(names(&res.body), ["docs"]);
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn nodes_are_moved_copied_and_deleted() {
+    run(async {
+        let mut app = spawn_app().await;
+        app.register_verified(&unique_email()).await;
+
+        let docs = id(&app.post("/nodes/folders", json!({ "name": "docs" })).await);
+        let year = id(&app
+            .post(
+                "/nodes/folders",
+                json!({ "parent_id": docs, "name": "2026" }),
+            )
+            .await);
+        let archive = id(&app
+            .post("/nodes/folders", json!({ "name": "archive" }))
+            .await);
+        let file_id = id(&app
+            .post_bytes(
+                "/files/raw?name=report.pdf",
+                "text/plain",
+                b"report".to_vec(),
+            )
+            .await);
+        let res = app
+            .post(
+                "/nodes/files",
+                json!({ "file_id": file_id, "parent_id": year, "name": "q1.pdf" }),
+            )
+            .await;
+        let report = id(&res);
+        let res = app.get(&format!("/files/{file_id}")).await;
+        assert_eq!(res.data()["name"], "q1.pdf");
+
+        // a folder never goes inside itself
+        let res = app
+            .patch(&format!("/nodes/{docs}"), json!({ "parent_id": year }))
+            .await;
+        assert_eq!(res.code(), "NODE_PARENT_INVALID");
+        let res = app
+            .patch(&format!("/nodes/{docs}"), json!({ "parent_id": docs }))
+            .await;
+        assert_eq!(res.code(), "NODE_PARENT_INVALID");
+
+        let res = app
+            .patch(
+                &format!("/nodes/{year}"),
+                json!({ "parent_id": archive, "name": "y2026" }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.data()["parent_id"], archive);
+        let res = app.get("/nodes/resolve?path=archive/y2026/q1.pdf").await;
+        assert_eq!(res.data()["id"], report);
+        let res = app
+            .patch(&format!("/nodes/{year}"), json!({ "parent_id": null }))
+            .await;
+        assert_eq!(res.data()["parent_id"], Value::Null);
+        let res = app
+            .patch(&format!("/nodes/{year}"), json!({ "name": "Docs" }))
+            .await;
+        assert_eq!(res.code(), "NODE_NAME_TAKEN");
+
+        let res = app
+            .post(&format!("/nodes/{year}/copy"), json!({ "parent_id": docs }))
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        assert_eq!(res.data()["name"], "y2026");
+        let res = app.get("/nodes/resolve?path=docs/y2026/q1.pdf").await;
+        let copied_file = res.data()["file_id"].as_i64().unwrap();
+        assert_ne!(copied_file, file_id);
+        let res = app.get(&format!("/files/{copied_file}/content")).await;
+        assert_eq!(res.bytes, b"report");
+        let res = app
+            .post(&format!("/nodes/{year}/copy"), json!({ "parent_id": year }))
+            .await;
+        assert_eq!(res.code(), "NODE_PARENT_INVALID");
+        let res = app.post(&format!("/nodes/{year}/copy"), json!({})).await;
+        assert_eq!(res.code(), "NODE_NAME_TAKEN");
+
+        let res = app.delete(&format!("/nodes/{docs}")).await;
+        assert_eq!(res.status, StatusCode::OK);
+        let res = app.get(&format!("/files/{copied_file}")).await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        let res = app.get(&format!("/files/{file_id}")).await;
+        assert_eq!(res.status, StatusCode::OK);
+
+        // deleting the file takes its node away
+        app.delete(&format!("/files/{file_id}")).await;
+        let res = app.get(&format!("/nodes/{report}")).await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+    });
+}
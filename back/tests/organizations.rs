@@ -0,0 +1,120 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use back::{
+    auth::auth_models::{claims::Claims, token::TokenAble},
+    models::organization_model::OrgRole,
+};
+use common::{last_link_key, run, spawn_app, unique_email};
+use serde_json::json;
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn members_are_invited_managed_and_carried_in_claims() {
+    run(async {
+        let mut owner = spawn_app().await;
+        let mut member = spawn_app().await;
+        let mut stranger = spawn_app().await;
+        owner.register_verified(&unique_email()).await;
+        let member_email = unique_email();
+        member.register_verified(&member_email).await;
+        stranger.register_verified(&unique_email()).await;
+
+        let res = owner.post("/orgs", json!({ "name": "  " })).await;
+        assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let res = owner.post("/orgs", json!({ "name": "Acme" })).await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        assert_eq!(res.data()["role"], "owner");
+        let org_id = res.data()["id"].as_i64().unwrap();
+
+        let token = owner
+            .post_empty(&format!("/orgs/{org_id}/switch"))
+            .await
+            .token()
+            .unwrap();
+        let org = Claims::decode(&token).unwrap().org.unwrap();
+        assert_eq!((org.id as i64, org.role), (org_id, OrgRole::Owner));
+        owner.bearer = Some(token);
+
+        let res = owner
+            .post(
+                &format!("/orgs/{org_id}/invitations"),
+                json!({ "email": member_email, "role": "member" }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        let key = last_link_key(&member_email, "/orgs/join").expect("invitation not mailed");
+        // only the invited address may use it
+        let res = stranger
+            .post("/orgs/invitations/accept", json!({ "key": key }))
+            .await;
+        assert_eq!(res.code(), "TOKEN_INVALID");
+        let res = member
+            .post("/orgs/invitations/accept", json!({ "key": key }))
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.data()["role"], "member");
+        let member_id = Claims::decode(member.bearer.as_deref().unwrap())
+            .unwrap()
+            .user_id;
+
+        let members_path = format!("/orgs/{org_id}/members");
+        assert_eq!(
+            member
+                .get(&members_path)
+                .await
+                .data()
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            stranger.get(&members_path).await.status,
+            StatusCode::NOT_FOUND
+        );
+        let res = member
+            .post(
+                &format!("/orgs/{org_id}/invitations"),
+                json!({ "email": unique_email(), "role": "viewer" }),
+            )
+            .await;
+        assert_eq!(res.code(), "ORGANIZATION_ROLE_INSUFFICIENT");
+
+        let member_path = format!("{members_path}/{member_id}");
+        let res = owner.put(&member_path, json!({ "role": "admin" })).await;
+        assert_eq!(res.status, StatusCode::OK);
+        // management reads roles from the database, not from the claims
+        let owner_id = Claims::decode(owner.bearer.as_deref().unwrap())
+            .unwrap()
+            .user_id;
+        let res = member
+            .put(
+                &format!("{members_path}/{owner_id}"),
+                json!({ "role": "viewer" }),
+            )
+            .await;
+        assert_eq!(res.code(), "ORGANIZATION_ROLE_INSUFFICIENT");
+        let res = owner.delete(&format!("{members_path}/{owner_id}")).await;
+        assert_eq!(res.status, StatusCode::CONFLICT);
+        assert_eq!(res.code(), "ORGANIZATION_LAST_OWNER");
+
+        // the organization joined first became the active one, the session reads it
+        member.bearer = None;
+        let token = member.refresh().await.token().unwrap();
+        assert_eq!(
+            Claims::decode(&token).unwrap().org.unwrap().role,
+            OrgRole::Admin
+        );
+        member.bearer = Some(token);
+
+        assert_eq!(owner.delete(&member_path).await.status, StatusCode::OK);
+        assert_eq!(
+            member.get(&members_path).await.status,
+            StatusCode::NOT_FOUND
+        );
+        member.bearer = None;
+        let token = member.refresh().await.token().unwrap();
+        assert!(Claims::decode(&token).unwrap().org.is_none());
+    });
+}
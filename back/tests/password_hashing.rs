@@ -0,0 +1,54 @@
+use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
+use back::utils::password_utils::{PasswordCheck, hash_password, verify_password};
+use password_hash::{SaltString, rand_core::OsRng};
+
+const PASSWORD: &str = "Str0ng!Password";
+
+#[test]
+fn fresh_hash_verifies_without_rehash() {
+    let hash = hash_password(PASSWORD).unwrap();
+    assert!(hash.starts_with("$argon2id$v=19$"));
+    assert_eq!(
+        verify_password(PASSWORD, &hash).unwrap(),
+        PasswordCheck::Valid
+    );
+    assert_eq!(
+        verify_password("Wr0ng!Password", &hash).unwrap(),
+        PasswordCheck::Invalid
+    );
+}
+
+#[test]
+fn outdated_parameters_ask_for_a_rehash() {
+    let weak = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(8, 1, 1, None).unwrap(),
+    );
+    let hash = weak
+        .hash_password(PASSWORD.as_bytes(), &SaltString::generate(&mut OsRng))
+        .unwrap()
+        .to_string();
+    assert_eq!(
+        verify_password(PASSWORD, &hash).unwrap(),
+        PasswordCheck::ValidNeedsRehash
+    );
+}
+
+#[test]
+fn legacy_bcrypt_hashes_are_accepted_then_upgraded() {
+    let legacy = bcrypt::hash(PASSWORD, 4).unwrap();
+    assert_eq!(
+        verify_password(PASSWORD, &legacy).unwrap(),
+        PasswordCheck::ValidNeedsRehash
+    );
+    assert_eq!(
+        verify_password("Wr0ng!Password", &legacy).unwrap(),
+        PasswordCheck::Invalid
+    );
+}
+
+#[test]
+fn malformed_hash_is_an_error_not_a_panic() {
+    assert!(verify_password(PASSWORD, "not a hash").is_err());
+}
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use back::auth::{
+    auth_models::email::Email,
+    password_policy::{PasswordPolicy, PolicyViolation, estimate_entropy_bits},
+};
+use sha1::{Digest, Sha1};
+
+fn policy(breached_dataset: Option<PathBuf>) -> PasswordPolicy {
+    PasswordPolicy {
+        min_length: 8,
+        max_length: 128,
+        require_lowercase: true,
+        require_uppercase: true,
+        require_digit: true,
+        require_symbol: true,
+        min_entropy_bits: 35.0,
+        reject_email: true,
+        breached_check: breached_dataset.is_some(),
+        breached_dataset,
+    }
+}
+
+fn email() -> Email {
+    Email::new("alice@example.com").unwrap()
+}
+
+#[test]
+fn sequences_and_repetitions_lower_the_entropy() {
+    assert!(estimate_entropy_bits("Abcdefg1!") < estimate_entropy_bits("Kq7!vZ2m"));
+    assert!(estimate_entropy_bits("aaaaaaaa") < 10.0);
+}
+
+#[test]
+fn email_local_part_is_rejected() {
+    let violations = policy(None).check_rules("Alice#2024!x", &email());
+    assert_eq!(violations, vec![PolicyViolation::ContainsEmail]);
+}
+
+#[actix_web::test]
+async fn breached_dataset_is_looked_up_by_prefix() {
+    let dataset = std::env::temp_dir().join(format!("breached-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dataset).unwrap();
+    let breached = "Summer2024!Pass";
+    let hash = hex::encode_upper(Sha1::digest(breached.as_bytes()));
+    std::fs::write(
+        dataset.join(&hash[..5]),
+        format!(
+            "0000000000000000000000000000000000A:3\r\n{}:42\r\n",
+            &hash[5..]
+        ),
+    )
+    .unwrap();
+
+    let policy = policy(Some(dataset.clone()));
+    assert_eq!(
+        policy.check(breached, &email()).await.unwrap(),
+        vec![PolicyViolation::Breached]
+    );
+    assert!(
+        policy
+            .check("Kq7!vZ2m#Lp", &email())
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    std::fs::remove_dir_all(dataset).unwrap();
+}
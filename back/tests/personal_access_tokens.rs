@@ -0,0 +1,83 @@
+mod common;
+
+use actix_web::http::{StatusCode, header};
+use common::{run, spawn_app, unique_email};
+use serde_json::json;
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn scoped_token_is_limited_then_revoked() {
+    run(async {
+        let mut client = spawn_app().await;
+        client.register_verified(&unique_email()).await;
+        let jwt = client.bearer.clone().unwrap();
+
+        let res = client
+            .post(
+                "/account/tokens",
+                json!({ "name": "CI", "scopes": ["account:delete"] }),
+            )
+            .await;
+        assert_eq!(res.body["errors"][0]["field"], "scopes");
+
+        let res = client
+            .post(
+                "/account/tokens",
+                json!({ "name": "CI", "scopes": ["account:read"], "expires_in_days": 30 }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        let token = res.data()["token"].as_str().unwrap().to_string();
+        let id = res.data()["id"].as_i64().unwrap();
+        assert!(token.starts_with("pat_"));
+        assert_eq!(res.data()["token_prefix"], &token[..12]);
+        assert_eq!(
+            res.headers.get(header::LOCATION).unwrap(),
+            &format!("/account/tokens/{id}")
+        );
+
+        // the token is enough on its own, without the session
+        client.forget();
+        client.bearer = Some(token.clone());
+        let res = client.get("/account/phone").await;
+        assert_eq!(res.status, StatusCode::OK);
+        let res = client
+            .put("/account/phone", json!({ "phone_number": "+33612345678" }))
+            .await;
+        assert_eq!(res.status, StatusCode::FORBIDDEN);
+        assert_eq!(res.code(), "INSUFFICIENT_SCOPE");
+        let res = client
+            .post(
+                "/account/tokens",
+                json!({ "name": "escalation", "scopes": ["account:write"] }),
+            )
+            .await;
+        assert_eq!(res.code(), "INSUFFICIENT_SCOPE");
+        // nor is it a session that can be swapped for a JWT
+        let res = client.refresh().await;
+        assert_eq!(res.code(), "INSUFFICIENT_SCOPE");
+        let res = client.forgot("someone@example.com").await;
+        assert_eq!(res.code(), "INSUFFICIENT_SCOPE");
+        let res = client
+            .post(
+                "/auth/login",
+                json!({ "email": "someone@example.com", "password": "x" }),
+            )
+            .await;
+        assert_eq!(res.code(), "INSUFFICIENT_SCOPE");
+
+        client.bearer = Some(jwt.clone());
+        let res = client.get("/account/tokens").await;
+        let listed = &res.data()[0];
+        assert_eq!(listed["id"], id);
+        assert!(listed["last_used_at"].is_i64());
+        assert!(listed.get("token").is_none());
+
+        let res = client.delete(&format!("/account/tokens/{id}")).await;
+        assert!(res.data()["revoked_at"].is_i64());
+
+        client.bearer = Some(token);
+        let res = client.get("/account/phone").await;
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+    });
+}
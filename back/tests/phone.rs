@@ -0,0 +1,167 @@
+mod common;
+
+use actix_web::{App, HttpResponse, HttpServer, http::StatusCode, web};
+use back::{
+    account::account_models::phone_number::PhoneNumber,
+    utils::sms_utils::{HttpSmsSender, SmsSender},
+};
+use common::{PASSWORD, last_sms_code, run, spawn_app, unique_email};
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+
+const PHONE: &str = "+33612345678";
+
+#[test]
+fn phone_numbers_are_normalized_to_e164() {
+    for raw in ["+33 6 12 34 56 78", "0033612345678", "+33 (6) 12.34.56-78"] {
+        assert_eq!(PhoneNumber::parse(raw).unwrap().as_ref(), PHONE);
+    }
+    for invalid in [
+        "0612345678",
+        "+0612345678",
+        "+33 6 12 ab 56 78",
+        "+1234",
+        "+1234567890123456",
+    ] {
+        assert!(PhoneNumber::parse(invalid).is_none(), "{invalid} accepted");
+    }
+    assert!(PhoneNumber::parse(PHONE).unwrap().masked().ends_with("78"));
+}
+
+#[test]
+fn http_gateway_posts_the_message() {
+    run(async {
+        let received: Arc<Mutex<Vec<(Option<String>, Value)>>> = Arc::default();
+        let stub_received = received.clone();
+        let server = HttpServer::new(move || {
+            let received = stub_received.clone();
+            App::new().route(
+                "/sms",
+                web::post().to(move |req: actix_web::HttpRequest, body: web::Json<Value>| {
+                    let received = received.clone();
+                    async move {
+                        let auth = req
+                            .headers()
+                            .get("Authorization")
+                            .and_then(|value| value.to_str().ok())
+                            .map(str::to_string);
+                        received.lock().unwrap().push((auth, body.into_inner()));
+                        HttpResponse::Accepted().finish()
+                    }
+                }),
+            )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        let handle = server.run();
+        let stop = handle.handle();
+        tokio::task::spawn_local(handle);
+
+        let phone = PhoneNumber::new(PHONE).unwrap();
+        let gateway = HttpSmsSender::new(
+            format!("http://{address}/sms"),
+            Some("secret".to_string()),
+            Some("CloudApp".to_string()),
+        );
+        gateway.send(&phone, "Votre code : 123456").await.unwrap();
+
+        let missing = HttpSmsSender::new(format!("http://{address}/missing"), None, None);
+        assert!(missing.send(&phone, "lost").await.is_err());
+        stop.stop(true).await;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].0.as_deref(), Some("Bearer secret"));
+        assert_eq!(
+            received[0].1,
+            json!({ "to": PHONE, "body": "Votre code : 123456", "from": "CloudApp" })
+        );
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn phone_verification_then_sms_second_factor() {
+    run(async {
+        let mut client = spawn_app().await;
+        let email = unique_email();
+        client.register_verified(&email).await;
+
+        let res = client
+            .put(
+                "/account/phone",
+                json!({ "phone_number": "06 12 34 56 78" }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(res.body["errors"][0]["code"], "PHONE_INVALID");
+
+        // the second factor needs a verified number
+        let res = client
+            .put("/account/two-factor", json!({ "enabled": true }))
+            .await;
+        assert_eq!(res.code(), "PHONE_NOT_VERIFIED");
+
+        let res = client
+            .put(
+                "/account/phone",
+                json!({ "phone_number": "+33 6 12 34 56 78" }),
+            )
+            .await;
+        assert_eq!(res.code(), "PHONE_CODE_SENT");
+        let code = last_sms_code(PHONE).unwrap();
+
+        let wrong = if code == "000000" { "111111" } else { "000000" };
+        let res = client
+            .post("/account/phone/verify", json!({ "code": wrong }))
+            .await;
+        assert_eq!(res.body["errors"][0]["code"], "OTP_INVALID");
+        let res = client
+            .post("/account/phone/verify", json!({ "code": code }))
+            .await;
+        assert_eq!(res.code(), "PHONE_VERIFIED");
+
+        let res = client
+            .put("/account/two-factor", json!({ "enabled": true }))
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.data()["sms_two_factor"], true);
+        assert_eq!(res.data()["phone_number"], PHONE);
+
+        // the password alone no longer opens a session
+        client.forget();
+        let res = client.login(&email, PASSWORD).await;
+        assert!(res.token().is_none());
+        assert!(client.session.is_none());
+        let challenge_id = res.data()["challenge_id"].as_str().unwrap().to_string();
+        let code = last_sms_code(PHONE).unwrap();
+
+        let res = client
+            .post(
+                "/auth/login/sms",
+                json!({ "challenge_id": challenge_id, "code": wrong }),
+            )
+            .await;
+        assert_eq!(res.code(), "OTP_INVALID");
+        let res = client
+            .post(
+                "/auth/login/sms",
+                json!({ "challenge_id": challenge_id, "code": code }),
+            )
+            .await;
+        assert!(res.token().is_some());
+        assert!(client.session.is_some());
+
+        // the code is single use
+        client.forget();
+        let res = client
+            .post(
+                "/auth/login/sms",
+                json!({ "challenge_id": challenge_id, "code": code }),
+            )
+            .await;
+        assert_eq!(res.code(), "OTP_EXPIRED");
+    });
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use std::io::Cursor;
+
+use actix_web::http::{StatusCode, header};
+use common::{run, spawn_app, unique_email};
+use image::{GenericImageView, ImageFormat, RgbImage};
+use serde_json::json;
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = Cursor::new(Vec::new());
+    RgbImage::new(width, height)
+        .write_to(&mut bytes, ImageFormat::Png)
+        .unwrap();
+    bytes.into_inner()
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn profile_is_edited_and_shown_without_credentials() {
+    run(async {
+        let mut owner = spawn_app().await;
+        let mut other = spawn_app().await;
+        owner.register_verified(&unique_email()).await;
+        other.register_verified(&unique_email()).await;
+
+        let res = owner.get("/account/profile").await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert!(res.data()["display_name"].is_null());
+        let id = res.data()["user_id"].as_i64().unwrap();
+
+        let res = owner
+            .patch(
+                "/account/profile",
+                json!({ "display_name": "  Ada  ", "locale": "fr-FR", "timezone": "Europe/Paris" }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.data()["display_name"], "Ada");
+        assert_eq!(res.data()["timezone"], "Europe/Paris");
+
+        let res = owner
+            .patch(
+                "/account/profile",
+                json!({ "locale": null, "timezone": "Mars/Olympus" }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(res.body["errors"][0]["field"], "timezone");
+        let res = owner
+            .patch("/account/profile", json!({ "locale": null }))
+            .await;
+        assert!(res.data()["locale"].is_null());
+        assert_eq!(res.data()["display_name"], "Ada");
+
+        let res = owner
+            .put_bytes(
+                "/account/profile/avatar",
+                "image/png",
+                b"not an image".to_vec(),
+            )
+            .await;
+        assert_eq!(res.code(), "IMAGE_INVALID");
+        let res = owner
+            .put_bytes("/account/profile/avatar", "image/png", png(600, 400))
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+        let avatar_url = res.data()["avatar_url"].as_str().unwrap().to_string();
+
+        let res = other.get(&format!("/users/{id}")).await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.data()["display_name"], "Ada");
+        assert_eq!(res.data()["avatar_url"], avatar_url.as_str());
+        assert!(res.data().get("email").is_none());
+        assert!(res.data().get("password").is_none());
+
+        other.forget();
+        let res = other.get(&avatar_url).await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.headers[header::CONTENT_TYPE], "image/png");
+        let avatar = image::load_from_memory(&res.bytes).unwrap();
+        assert_eq!(avatar.dimensions(), (256, 256));
+
+        let res = owner.delete("/account/profile/avatar").await;
+        assert!(res.data()["avatar_url"].is_null());
+        assert_eq!(
+            other.get(&format!("/avatars/{id}")).await.status,
+            StatusCode::NOT_FOUND
+        );
+    });
+}
@@ -0,0 +1,507 @@
+//! The same checks against every `StorageBackend`.
+//!
+//! The S3 backend always runs against an in-process stub of the S3 API. It is also
+//! exercised against a real server when `S3_TEST_ENDPOINT`, `S3_TEST_ACCESS_KEY_ID` and
+//! `S3_TEST_SECRET_ACCESS_KEY` point to one, such as the `minio` service of
+//! docker-compose (`S3_TEST_BUCKET` defaults to `conformance`).
+mod common;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use actix_web::{
+    App, HttpRequest, HttpResponse, HttpServer,
+    http::{Method, StatusCode},
+    web::{self, Bytes, BytesMut},
+};
+use back::{
+    errors::AppError,
+    storage::{
+        BodyStream, ByteRange, StorageBackend, StorageKey,
+        local_storage::LocalStorage,
+        s3_storage::{S3Config, S3Storage},
+    },
+};
+use common::run;
+use futures_util::{StreamExt, stream};
+use sha2::{Digest, Sha256};
+
+/// Every part of a multipart upload but the last must be this large on S3
+const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+fn body(chunks: Vec<Vec<u8>>) -> BodyStream {
+    Box::pin(stream::iter(
+        chunks.into_iter().map(|chunk| Ok(Bytes::from(chunk))),
+    ))
+}
+
+fn pattern(len: usize, seed: u8) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8 ^ seed).collect()
+}
+
+async fn read(backend: &dyn StorageBackend, key: &StorageKey) -> Option<Vec<u8>> {
+    let content = backend.get(key).await.unwrap()?;
+    let mut bytes = Vec::new();
+    let mut body = content.body;
+    while let Some(chunk) = body.next().await {
+        bytes.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(bytes.len() as u64, content.size);
+    Some(bytes)
+}
+
+async fn read_range(backend: &dyn StorageBackend, key: &StorageKey, range: ByteRange) -> Vec<u8> {
+    let content = backend.get_range(key, range).await.unwrap().unwrap();
+    let mut bytes = Vec::new();
+    let mut body = content.body;
+    while let Some(chunk) = body.next().await {
+        bytes.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(bytes.len() as u64, content.size);
+    bytes
+}
+
+async fn check_backend(backend: &dyn StorageBackend) {
+    let prefix = format!("conformance-{}", uuid::Uuid::new_v4().simple());
+    let key = |name: &str| StorageKey::parse(&format!("{prefix}/{name}")).unwrap();
+    let object = key("object");
+    let data = pattern(300_000, 0);
+
+    // put, get and overwrite
+    assert!(read(backend, &object).await.is_none());
+    let chunks = data.chunks(70_000).map(<[u8]>::to_vec).collect();
+    backend.put(&object, body(chunks)).await.unwrap();
+    assert_eq!(read(backend, &object).await.unwrap(), data);
+    let replaced = pattern(1000, 7);
+    backend
+        .put(&object, body(vec![replaced.clone()]))
+        .await
+        .unwrap();
+    assert_eq!(read(backend, &object).await.unwrap(), replaced);
+    backend
+        .put(&object, body(vec![data.clone()]))
+        .await
+        .unwrap();
+
+    // empty objects
+    let empty = key("empty");
+    backend.put(&empty, body(Vec::new())).await.unwrap();
+    assert_eq!(read(backend, &empty).await.unwrap(), Vec::<u8>::new());
+
+    // a failing body leaves nothing behind
+    let broken = key("broken");
+    let failing: BodyStream = Box::pin(stream::iter(vec![
+        Ok(Bytes::from(pattern(1000, 1))),
+        Err(AppError::Internal("connection lost".to_string())),
+    ]));
+    assert!(backend.put(&broken, failing).await.is_err());
+    assert!(read(backend, &broken).await.is_none());
+
+    // ranges
+    let range = ByteRange {
+        first: 10,
+        last: Some(19),
+    };
+    assert_eq!(read_range(backend, &object, range).await, data[10..20]);
+    let content = backend.get_range(&object, range).await.unwrap().unwrap();
+    assert_eq!(content.total_size, data.len() as u64);
+    let tail = ByteRange {
+        first: 299_990,
+        last: None,
+    };
+    assert_eq!(read_range(backend, &object, tail).await, data[299_990..]);
+    let clamped = ByteRange {
+        first: 299_990,
+        last: Some(1_000_000),
+    };
+    assert_eq!(read_range(backend, &object, clamped).await, data[299_990..]);
+    let past_end = ByteRange {
+        first: data.len() as u64,
+        last: None,
+    };
+    assert!(matches!(
+        backend.get_range(&object, past_end).await,
+        Err(AppError::RangeNotSatisfiable(_))
+    ));
+    assert!(
+        backend
+            .get_range(&key("missing"), range)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // copy
+    let copy = key("copy");
+    assert!(backend.copy(&object, &copy).await.unwrap());
+    assert_eq!(read(backend, &copy).await.unwrap(), data);
+    assert!(
+        !backend
+            .copy(&key("missing"), &key("nowhere"))
+            .await
+            .unwrap()
+    );
+    assert!(read(backend, &key("nowhere")).await.is_none());
+
+    // list
+    let listed = backend.list(&prefix).await.unwrap();
+    let listed: Vec<_> = listed
+        .iter()
+        .map(|info| (info.key.as_str().to_string(), info.size))
+        .collect();
+    assert_eq!(
+        listed,
+        vec![
+            (copy.to_string(), data.len() as u64),
+            (empty.to_string(), 0),
+            (object.to_string(), data.len() as u64),
+        ]
+    );
+    let only_copy = backend.list(&format!("{prefix}/co")).await.unwrap();
+    assert_eq!(only_copy.len(), 1);
+    let shorter = backend.list(&prefix[..1]).await.unwrap();
+    assert!(shorter.len() >= listed.len());
+    assert!(backend.list("..").await.unwrap().is_empty());
+
+    // multipart, the object only appears once completed
+    let assembled = key("assembled");
+    let first = pattern(MIN_PART_SIZE, 2);
+    let second = pattern(1234, 3);
+    let upload_id = backend.create_multipart(&assembled).await.unwrap();
+    let part_two = backend
+        .upload_part(&assembled, &upload_id, 2, Bytes::from(second.clone()))
+        .await
+        .unwrap();
+    let part_one = backend
+        .upload_part(&assembled, &upload_id, 1, Bytes::from(first.clone()))
+        .await
+        .unwrap();
+    assert!(read(backend, &assembled).await.is_none());
+    backend
+        .complete_multipart(&assembled, &upload_id, &[part_one, part_two])
+        .await
+        .unwrap();
+    let mut expected = first;
+    expected.extend_from_slice(&second);
+    assert_eq!(read(backend, &assembled).await.unwrap(), expected);
+
+    let aborted = key("aborted");
+    let upload_id = backend.create_multipart(&aborted).await.unwrap();
+    backend
+        .upload_part(&aborted, &upload_id, 1, Bytes::from(pattern(100, 4)))
+        .await
+        .unwrap();
+    backend.abort_multipart(&aborted, &upload_id).await.unwrap();
+    assert!(read(backend, &aborted).await.is_none());
+
+    // delete, twice
+    for info in backend.list(&prefix).await.unwrap() {
+        backend.delete(&info.key).await.unwrap();
+    }
+    backend.delete(&object).await.unwrap();
+    assert!(read(backend, &object).await.is_none());
+    assert!(backend.list(&prefix).await.unwrap().is_empty());
+}
+
+#[test]
+fn keys_outside_the_allowed_alphabet_are_rejected() {
+    for valid in ["0a1b2c", "blobs/ab/cdef", "tus_uploads/x-1"] {
+        assert!(StorageKey::parse(valid).is_some(), "{valid}");
+    }
+    for invalid in [
+        "", "a", "../etc", "ab/../cd", "ab//cd", "ab/", "AB", "ab/c.d",
+    ] {
+        assert!(StorageKey::parse(invalid).is_none(), "{invalid}");
+    }
+}
+
+#[test]
+fn local_storage_conforms() {
+    run(async {
+        let root = std::env::temp_dir().join(format!(
+            "storage_conformance_{}",
+            uuid::Uuid::new_v4().simple()
+        ));
+        check_backend(&LocalStorage::new(&root)).await;
+        let _ = std::fs::remove_dir_all(root);
+    });
+}
+
+const STUB_BUCKET: &str = "conformance";
+const STUB_ACCESS_KEY_ID: &str = "stub-key";
+/// Keys per listing page, small so that continuation tokens are followed
+const STUB_PAGE_SIZE: usize = 2;
+
+/// The single bucket the stub serves, created empty by `ensure_bucket`
+#[derive(Default)]
+struct StubBucket {
+    created: bool,
+    objects: BTreeMap<String, Bytes>,
+    /// Parts of the pending multipart uploads, by upload id
+    uploads: HashMap<String, BTreeMap<u32, Bytes>>,
+}
+
+fn xml(status: StatusCode, document: String) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type("application/xml")
+        .body(document)
+}
+
+fn s3_error(status: StatusCode, code: &str) -> HttpResponse {
+    xml(
+        status,
+        format!("<Error><Code>{code}</Code><Message>{code}</Message></Error>"),
+    )
+}
+
+/// Text of every `<tag>` of `document`
+fn tag_values<'a>(document: &'a str, tag: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{tag}>"), format!("</{tag}>"));
+    document
+        .split(open.as_str())
+        .skip(1)
+        .filter_map(|rest| rest.split_once(close.as_str()).map(|(value, _)| value))
+        .collect()
+}
+
+fn part_etag(part: &Bytes) -> String {
+    format!("\"{}\"", hex::encode(Sha256::digest(part)))
+}
+
+/// `ListObjectsV2`, `STUB_PAGE_SIZE` keys at a time
+fn list_objects(bucket: &StubBucket, query: &HashMap<String, String>) -> HttpResponse {
+    let prefix = query.get("prefix").map(String::as_str).unwrap_or_default();
+    let after = query.get("continuation-token");
+    let mut matching = bucket.objects.iter().filter(|(key, _)| {
+        key.starts_with(prefix) && after.is_none_or(|after| key.as_str() > after.as_str())
+    });
+    let page: Vec<_> = matching.by_ref().take(STUB_PAGE_SIZE).collect();
+    let truncated = matching.next().is_some();
+    let mut document = String::from("<ListBucketResult>");
+    for (key, object) in &page {
+        document.push_str(&format!(
+            "<Contents><Key>{key}</Key><Size>{}</Size></Contents>",
+            object.len()
+        ));
+    }
+    document.push_str(&format!("<IsTruncated>{truncated}</IsTruncated>"));
+    if truncated && let Some((last, _)) = page.last() {
+        document.push_str(&format!(
+            "<NextContinuationToken>{last}</NextContinuationToken>"
+        ));
+    }
+    document.push_str("</ListBucketResult>");
+    xml(StatusCode::OK, document)
+}
+
+/// The whole object, or the `bytes=first-[last]` range of it
+fn get_object(object: &Bytes, range: Option<&str>) -> HttpResponse {
+    let Some((first, last)) = range
+        .and_then(|range| range.strip_prefix("bytes="))
+        .and_then(|bounds| bounds.split_once('-'))
+    else {
+        return HttpResponse::Ok().body(object.clone());
+    };
+    let len = object.len();
+    let Some(first) = first.parse::<usize>().ok().filter(|first| *first < len) else {
+        return s3_error(StatusCode::RANGE_NOT_SATISFIABLE, "InvalidRange");
+    };
+    let last = last
+        .parse::<usize>()
+        .map_or(len - 1, |last| last.min(len - 1));
+    HttpResponse::PartialContent()
+        .insert_header(("Content-Range", format!("bytes {first}-{last}/{len}")))
+        .body(object.slice(first..=last))
+}
+
+/// `CompleteMultipartUpload`, parts in order and all but the last one large enough
+fn complete_multipart(
+    bucket: &mut StubBucket,
+    key: &str,
+    upload_id: &str,
+    document: &str,
+) -> HttpResponse {
+    let Some(parts) = bucket.uploads.get(upload_id) else {
+        return s3_error(StatusCode::NOT_FOUND, "NoSuchUpload");
+    };
+    let numbers = tag_values(document, "PartNumber");
+    let etags = tag_values(document, "ETag");
+    let mut content = BytesMut::new();
+    let mut previous = 0;
+    for (index, (number, etag)) in numbers.iter().zip(&etags).enumerate() {
+        let number: u32 = number.parse().unwrap_or_default();
+        let Some(part) = parts.get(&number).filter(|part| *etag == part_etag(part)) else {
+            return s3_error(StatusCode::BAD_REQUEST, "InvalidPart");
+        };
+        if number <= previous {
+            return s3_error(StatusCode::BAD_REQUEST, "InvalidPartOrder");
+        }
+        if index + 1 < numbers.len() && part.len() < MIN_PART_SIZE {
+            return s3_error(StatusCode::BAD_REQUEST, "EntityTooSmall");
+        }
+        previous = number;
+        content.extend_from_slice(part);
+    }
+    bucket.uploads.remove(upload_id);
+    bucket.objects.insert(key.to_string(), content.freeze());
+    xml(
+        StatusCode::OK,
+        "<CompleteMultipartUploadResult></CompleteMultipartUploadResult>".to_string(),
+    )
+}
+
+/// The calls `S3Storage` makes, path style. Signatures are not verified, only their
+/// presence and the payload hash
+async fn s3_stub(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    body: Bytes,
+    bucket: web::Data<Mutex<StubBucket>>,
+) -> HttpResponse {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let credential = format!("AWS4-HMAC-SHA256 Credential={STUB_ACCESS_KEY_ID}/");
+    if !header("authorization").is_some_and(|value| value.starts_with(&credential))
+        || header("x-amz-content-sha256") != Some(hex::encode(Sha256::digest(&body)).as_str())
+    {
+        return s3_error(StatusCode::FORBIDDEN, "SignatureDoesNotMatch");
+    }
+    let path = req.path().trim_start_matches('/');
+    let (name, key) = path.split_once('/').unwrap_or((path, ""));
+    let mut bucket = bucket.lock().unwrap();
+    let creating = key.is_empty() && req.method() == Method::PUT;
+    if name != STUB_BUCKET || !(bucket.created || creating) {
+        return s3_error(StatusCode::NOT_FOUND, "NoSuchBucket");
+    }
+
+    let upload_id = query.get("uploadId").map(String::as_str);
+    match (req.method().clone(), key, upload_id) {
+        (Method::HEAD, "", _) => HttpResponse::Ok().finish(),
+        (Method::PUT, "", _) => {
+            bucket.created = true;
+            HttpResponse::Ok().finish()
+        }
+        (Method::GET, "", _) if query.get("list-type").is_some_and(|kind| kind == "2") => {
+            list_objects(&bucket, &query)
+        }
+        (_, "", _) => s3_error(StatusCode::METHOD_NOT_ALLOWED, "MethodNotAllowed"),
+        (Method::POST, _, None) if query.contains_key("uploads") => {
+            let upload_id = uuid::Uuid::new_v4().simple().to_string();
+            bucket.uploads.insert(upload_id.clone(), BTreeMap::new());
+            xml(
+                StatusCode::OK,
+                format!(
+                    "<InitiateMultipartUploadResult><UploadId>{upload_id}</UploadId></InitiateMultipartUploadResult>"
+                ),
+            )
+        }
+        (Method::PUT, _, Some(upload_id)) => {
+            let number = query
+                .get("partNumber")
+                .and_then(|number| number.parse().ok());
+            match (bucket.uploads.get_mut(upload_id), number) {
+                (Some(parts), Some(number)) => {
+                    let etag = part_etag(&body);
+                    parts.insert(number, body);
+                    HttpResponse::Ok().insert_header(("ETag", etag)).finish()
+                }
+                (None, _) => s3_error(StatusCode::NOT_FOUND, "NoSuchUpload"),
+                (_, None) => s3_error(StatusCode::BAD_REQUEST, "InvalidArgument"),
+            }
+        }
+        (Method::POST, _, Some(upload_id)) => {
+            complete_multipart(&mut bucket, key, upload_id, &String::from_utf8_lossy(&body))
+        }
+        (Method::DELETE, _, Some(upload_id)) => match bucket.uploads.remove(upload_id) {
+            Some(_) => HttpResponse::NoContent().finish(),
+            None => s3_error(StatusCode::NOT_FOUND, "NoSuchUpload"),
+        },
+        (Method::PUT, _, None) => match header("x-amz-copy-source") {
+            Some(source) => {
+                let source = source
+                    .strip_prefix(&format!("/{STUB_BUCKET}/"))
+                    .unwrap_or(source);
+                let Some(object) = bucket.objects.get(source).cloned() else {
+                    return s3_error(StatusCode::NOT_FOUND, "NoSuchKey");
+                };
+                bucket.objects.insert(key.to_string(), object);
+                xml(
+                    StatusCode::OK,
+                    "<CopyObjectResult></CopyObjectResult>".to_string(),
+                )
+            }
+            None => {
+                bucket.objects.insert(key.to_string(), body);
+                HttpResponse::Ok().finish()
+            }
+        },
+        (Method::GET, _, None) => match bucket.objects.get(key) {
+            Some(object) => get_object(object, header("range")),
+            None => s3_error(StatusCode::NOT_FOUND, "NoSuchKey"),
+        },
+        (Method::DELETE, _, None) => {
+            bucket.objects.remove(key);
+            HttpResponse::NoContent().finish()
+        }
+        _ => s3_error(StatusCode::METHOD_NOT_ALLOWED, "MethodNotAllowed"),
+    }
+}
+
+#[test]
+fn s3_storage_conforms() {
+    run(async {
+        let bucket = web::Data::new(Mutex::new(StubBucket::default()));
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(bucket.clone())
+                .app_data(web::PayloadConfig::new(4 * MIN_PART_SIZE))
+                .default_service(web::to(s3_stub))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        let handle = server.run();
+        let stop = handle.handle();
+        tokio::task::spawn_local(handle);
+
+        let storage = S3Storage::new(S3Config {
+            endpoint: format!("http://{address}"),
+            region: "us-east-1".to_string(),
+            bucket: STUB_BUCKET.to_string(),
+            access_key_id: STUB_ACCESS_KEY_ID.to_string(),
+            secret_access_key: "stub-secret".to_string(),
+        });
+        storage.ensure_bucket().await.unwrap();
+        check_backend(&storage).await;
+        stop.stop(true).await;
+    });
+}
+
+#[test]
+fn s3_compatible_server_conforms() {
+    let env = |name: &str| std::env::var(format!("S3_TEST_{name}")).ok();
+    let (Some(endpoint), Some(access_key_id), Some(secret_access_key)) = (
+        env("ENDPOINT"),
+        env("ACCESS_KEY_ID"),
+        env("SECRET_ACCESS_KEY"),
+    ) else {
+        return;
+    };
+    run(async {
+        let storage = S3Storage::new(S3Config {
+            endpoint,
+            region: env("REGION").unwrap_or_else(|| "us-east-1".to_string()),
+            bucket: env("BUCKET").unwrap_or_else(|| "conformance".to_string()),
+            access_key_id,
+            secret_access_key,
+        });
+        storage.ensure_bucket().await.unwrap();
+        check_backend(&storage).await;
+    });
+}
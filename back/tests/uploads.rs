@@ -0,0 +1,365 @@
+mod common;
+
+use actix_web::{
+    http::{Method, StatusCode, header},
+    web::Bytes,
+};
+use back::{storage::storage, uploads::tus_upload::TusUpload};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use common::{TestResponse, run, spawn_app, unique_email};
+use futures_util::stream;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+const TUS: (&str, &str) = ("Tus-Resumable", "1.0.0");
+const CHUNK_TYPE: (&str, &str) = ("Content-Type", "application/offset+octet-stream");
+
+fn header<'a>(res: &'a TestResponse, name: &str) -> &'a str {
+    res.headers[name].to_str().unwrap()
+}
+
+/// The path of the upload, from its absolute `Location`
+fn upload_path(res: &TestResponse) -> String {
+    let location = res.headers[header::LOCATION].to_str().unwrap();
+    let id = location.rsplit('/').next().unwrap();
+    format!("/uploads/{id}")
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn tus_uploads_resume_and_become_files() {
+    run(async {
+        let mut owner = spawn_app().await;
+        let mut other = spawn_app().await;
+        owner.register_verified(&unique_email()).await;
+        other.register_verified(&unique_email()).await;
+
+        let res = owner
+            .request_with_headers(Method::OPTIONS, "/uploads", &[], Vec::new())
+            .await;
+        assert_eq!(res.status, StatusCode::NO_CONTENT);
+        assert!(header(&res, "Tus-Extension").contains("checksum"));
+
+        let content: Vec<u8> = (0..100_000u32).map(|i| (i % 253) as u8).collect();
+        let metadata = format!(
+            "filename {},filetype {}",
+            STANDARD.encode("rapport été.bin"),
+            STANDARD.encode("application/octet-stream")
+        );
+        let length = content.len().to_string();
+        let res = owner
+            .request_with_headers(
+                Method::POST,
+                "/uploads",
+                &[
+                    ("Upload-Length", length.as_str()),
+                    ("Upload-Metadata", &metadata),
+                ],
+                Vec::new(),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::PRECONDITION_FAILED);
+        assert_eq!(res.code(), "TUS_VERSION_UNSUPPORTED");
+
+        let res = owner
+            .request_with_headers(
+                Method::POST,
+                "/uploads",
+                &[
+                    TUS,
+                    ("Upload-Length", length.as_str()),
+                    ("Upload-Metadata", &metadata),
+                ],
+                Vec::new(),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        assert_eq!(header(&res, "Tus-Resumable"), "1.0.0");
+        assert!(res.headers.contains_key("Upload-Expires"));
+        let path = upload_path(&res);
+
+        let res = owner
+            .request_with_headers(Method::HEAD, &path, &[TUS], Vec::new())
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(header(&res, "Upload-Offset"), "0");
+        assert_eq!(header(&res, "Upload-Metadata"), metadata);
+        let res = other
+            .request_with_headers(Method::HEAD, &path, &[TUS], Vec::new())
+            .await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+        let (first, rest) = content.split_at(40_000);
+        let res = owner
+            .request_with_headers(
+                Method::PATCH,
+                &path,
+                &[TUS, ("Upload-Offset", "0")],
+                first.to_vec(),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let wrong = format!("sha1 {}", STANDARD.encode(Sha1::digest(rest)));
+        let res = owner
+            .request_with_headers(
+                Method::PATCH,
+                &path,
+                &[
+                    TUS,
+                    CHUNK_TYPE,
+                    ("Upload-Offset", "0"),
+                    ("Upload-Checksum", &wrong),
+                ],
+                first.to_vec(),
+            )
+            .await;
+        assert_eq!(res.status.as_u16(), 460);
+        assert_eq!(res.code(), "UPLOAD_CHECKSUM_MISMATCH");
+        let res = owner
+            .request_with_headers(
+                Method::PATCH,
+                &path,
+                &[
+                    TUS,
+                    CHUNK_TYPE,
+                    ("Upload-Offset", "0"),
+                    ("Upload-Checksum", "md5 AAAA"),
+                ],
+                first.to_vec(),
+            )
+            .await;
+        assert_eq!(res.code(), "CHECKSUM_ALGORITHM_UNSUPPORTED");
+
+        let checksum = format!("sha256 {}", STANDARD.encode(Sha256::digest(first)));
+        let res = owner
+            .request_with_headers(
+                Method::PATCH,
+                &path,
+                &[
+                    TUS,
+                    CHUNK_TYPE,
+                    ("Upload-Offset", "0"),
+                    ("Upload-Checksum", &checksum),
+                ],
+                first.to_vec(),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::NO_CONTENT);
+        assert_eq!(header(&res, "Upload-Offset"), "40000");
+        assert!(!res.headers.contains_key("Upload-File-Id"));
+
+        // a chunk sent again after a lost response
+        let res = owner
+            .request_with_headers(
+                Method::PATCH,
+                &path,
+                &[TUS, CHUNK_TYPE, ("Upload-Offset", "0")],
+                first.to_vec(),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CONFLICT);
+        assert_eq!(res.code(), "UPLOAD_OFFSET_MISMATCH");
+
+        let mut too_long = rest.to_vec();
+        too_long.push(0);
+        let res = owner
+            .request_with_headers(
+                Method::PATCH,
+                &path,
+                &[TUS, CHUNK_TYPE, ("Upload-Offset", "40000")],
+                too_long,
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        let res = owner
+            .request_with_headers(
+                Method::PATCH,
+                &path,
+                &[TUS, CHUNK_TYPE, ("Upload-Offset", "40000")],
+                rest.to_vec(),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::NO_CONTENT);
+        assert_eq!(header(&res, "Upload-Offset"), length);
+        let file_id = header(&res, "Upload-File-Id").to_string();
+
+        let res = owner
+            .request_with_headers(Method::HEAD, &path, &[TUS], Vec::new())
+            .await;
+        assert_eq!(header(&res, "Upload-Offset"), length);
+        assert_eq!(header(&res, "Upload-File-Id"), file_id);
+
+        let res = owner.get(&format!("/files/{file_id}")).await;
+        assert_eq!(res.data()["name"], "rapport été.bin");
+        assert_eq!(
+            res.data()["sha256"],
+            hex::encode(Sha256::digest(&content)).as_str()
+        );
+        let res = owner.get(&format!("/files/{file_id}/content")).await;
+        assert_eq!(res.bytes, content);
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn tus_uploads_are_terminated_and_bounded() {
+    run(async {
+        let mut app = spawn_app().await;
+        app.register_verified(&unique_email()).await;
+        let metadata = format!("filename {}", STANDARD.encode("big.iso"));
+
+        let res = app
+            .request_with_headers(
+                Method::POST,
+                "/uploads",
+                &[
+                    TUS,
+                    ("Upload-Length", "1099511627776"),
+                    ("Upload-Metadata", &metadata),
+                ],
+                Vec::new(),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::PAYLOAD_TOO_LARGE);
+        let res = app
+            .request_with_headers(Method::POST, "/uploads", &[TUS], Vec::new())
+            .await;
+        assert_eq!(res.code(), "UPLOAD_LENGTH_INVALID");
+        let res = app
+            .request_with_headers(
+                Method::POST,
+                "/uploads",
+                &[TUS, ("Upload-Length", "10")],
+                Vec::new(),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let res = app
+            .request_with_headers(
+                Method::POST,
+                "/uploads",
+                &[TUS, ("Upload-Length", "10"), ("Upload-Metadata", &metadata)],
+                Vec::new(),
+            )
+            .await;
+        let path = upload_path(&res);
+        let res = app
+            .request_with_headers(
+                Method::PATCH,
+                &path,
+                &[TUS, CHUNK_TYPE, ("Upload-Offset", "0")],
+                b"01234".to_vec(),
+            )
+            .await;
+        assert_eq!(header(&res, "Upload-Offset"), "5");
+
+        let res = app
+            .request_with_headers(Method::DELETE, &path, &[TUS], Vec::new())
+            .await;
+        assert_eq!(res.status, StatusCode::NO_CONTENT);
+        let res = app
+            .request_with_headers(Method::HEAD, &path, &[TUS], Vec::new())
+            .await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        assert_eq!(app.get("/files").await.body["meta"]["total"], 0);
+
+        // an empty file is complete at creation
+        let res = app
+            .request_with_headers(
+                Method::POST,
+                "/uploads",
+                &[TUS, ("Upload-Length", "0"), ("Upload-Metadata", &metadata)],
+                Vec::new(),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        let file_id = header(&res, "Upload-File-Id").to_string();
+        let res = app.get(&format!("/files/{file_id}")).await;
+        assert_eq!(res.data()["size"], 0);
+    });
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn tus_chunks_wait_for_the_upload_lock_and_bad_assemblies_are_dropped() {
+    run(async {
+        let mut app = spawn_app().await;
+        app.register_verified(&unique_email()).await;
+        let metadata = format!("filename {}", STANDARD.encode("notes.txt"));
+        let res = app
+            .request_with_headers(
+                Method::POST,
+                "/uploads",
+                &[TUS, ("Upload-Length", "10"), ("Upload-Metadata", &metadata)],
+                Vec::new(),
+            )
+            .await;
+        let path = upload_path(&res);
+        let upload_id = path.rsplit('/').next().unwrap().to_string();
+
+        // another request is moving the offset
+        let token = TusUpload::lock(&upload_id).await.unwrap().unwrap();
+        assert!(TusUpload::lock(&upload_id).await.unwrap().is_none());
+        let res = app
+            .request_with_headers(
+                Method::PATCH,
+                &path,
+                &[TUS, CHUNK_TYPE, ("Upload-Offset", "0")],
+                b"01234".to_vec(),
+            )
+            .await;
+        assert_eq!(res.code(), "UPLOAD_OFFSET_MISMATCH");
+        // only its holder releases it
+        TusUpload::unlock(&upload_id, "someone else").await.unwrap();
+        assert!(TusUpload::lock(&upload_id).await.unwrap().is_none());
+        TusUpload::unlock(&upload_id, &token).await.unwrap();
+        let res = app
+            .request_with_headers(
+                Method::PATCH,
+                &path,
+                &[TUS, CHUNK_TYPE, ("Upload-Offset", "0")],
+                b"01234".to_vec(),
+            )
+            .await;
+        assert_eq!(header(&res, "Upload-Offset"), "5");
+
+        // a segment lost some bytes in the store
+        let upload = TusUpload::get(&upload_id).await.unwrap().unwrap();
+        let key = upload.segment_keys().unwrap().remove(0);
+        storage()
+            .put(
+                &key,
+                Box::pin(stream::once(async { Ok(Bytes::from_static(b"01")) })),
+            )
+            .await
+            .unwrap();
+        let res = app
+            .request_with_headers(
+                Method::PATCH,
+                &path,
+                &[TUS, CHUNK_TYPE, ("Upload-Offset", "5")],
+                b"56789".to_vec(),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::INTERNAL_SERVER_ERROR);
+        let res = app
+            .request_with_headers(Method::HEAD, &path, &[TUS], Vec::new())
+            .await;
+        assert_eq!(res.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(app.get("/files").await.body["meta"]["total"], 0);
+
+        // an assembly under way is left to the request running it
+        let mut upload = TusUpload::get(&upload_id).await.unwrap().unwrap();
+        assert!(!upload.assembling);
+        upload.assembling = true;
+        upload.save().await.unwrap();
+        let res = app
+            .request_with_headers(Method::HEAD, &path, &[TUS], Vec::new())
+            .await;
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(header(&res, "Upload-Offset"), "10");
+    });
+}
@@ -0,0 +1,223 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use actix_web::http::StatusCode;
+use back::{
+    DB_POOL, auth::auth_models::email::Email, jobs::webhook_worker::deliver_due_webhooks,
+    models::user_model::User,
+};
+use common::{PASSWORD, last_link_key, run, spawn_app, unique_email};
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+struct Received {
+    headers: Vec<(String, String)>,
+    body: String,
+}
+impl Received {
+    fn header(&self, name: &str) -> &str {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_default()
+    }
+}
+
+/// Minimal HTTP receiver answering the given statuses in turn, then 200
+async fn spawn_receiver(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<Received>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let log = received.clone();
+    tokio::spawn(async move {
+        let mut statuses = statuses.into_iter();
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                return;
+            };
+            let mut raw = Vec::new();
+            let mut buffer = [0u8; 4096];
+            let (head_end, length) = loop {
+                let read = stream.read(&mut buffer).await.unwrap();
+                raw.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&raw);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    break (end + 4, length);
+                }
+            };
+            while raw.len() < head_end + length {
+                let read = stream.read(&mut buffer).await.unwrap();
+                raw.extend_from_slice(&buffer[..read]);
+            }
+            let head = String::from_utf8_lossy(&raw[..head_end]).to_string();
+            log.lock().unwrap().push(Received {
+                headers: head
+                    .lines()
+                    .skip(1)
+                    .filter_map(|line| line.split_once(':'))
+                    .map(|(name, value)| (name.to_string(), value.trim().to_string()))
+                    .collect(),
+                body: String::from_utf8_lossy(&raw[head_end..]).to_string(),
+            });
+            let status = statuses.next().unwrap_or(200);
+            let response =
+                format!("HTTP/1.1 {status} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    });
+    (url, received)
+}
+
+fn signature(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn registrations_are_signed_retried_and_replayed() {
+    // SAFETY: the only test of this binary, before any webhook is registered
+    unsafe { std::env::set_var("WEBHOOK_ALLOW_HTTP", "true") };
+    run(async {
+        let mut admin = spawn_app().await;
+        let mut user = spawn_app().await;
+        let admin_email = unique_email();
+        admin.register_verified(&admin_email).await;
+        let admin_id = User::get_user_id_from_email(&Email::new(&admin_email).unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        sqlx::query("UPDATE users SET admin = 1 WHERE id = ?")
+            .bind(admin_id)
+            .execute(&*DB_POOL)
+            .await
+            .unwrap();
+        admin.forget();
+        admin.bearer = admin.login(&admin_email, PASSWORD).await.token();
+        // nothing subscribed yet
+        deliver_due_webhooks().await.unwrap();
+
+        let (url, received) = spawn_receiver(vec![500]).await;
+        let res = admin
+            .post(
+                "/admin/webhooks",
+                json!({ "url": "ftp://example.com", "event_types": ["user.registered"] }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+        let res = admin
+            .post(
+                "/admin/webhooks",
+                json!({ "url": url, "event_types": ["user.registered"] }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        let webhook_id = res.data()["id"].as_i64().unwrap();
+        let secret = res.data()["secret"].as_str().unwrap().to_string();
+        let res = admin.get("/admin/webhooks").await;
+        assert!(res.data()[0].get("secret").is_none());
+
+        // verification is not subscribed to
+        let email = unique_email();
+        user.register(&email).await;
+        let key = last_link_key(&email, "/auth/verify").unwrap();
+        user.verify(&key).await;
+
+        assert_eq!(deliver_due_webhooks().await.unwrap(), 1);
+        assert_eq!(received.lock().unwrap().len(), 1);
+        let deliveries = format!("/admin/webhooks/{webhook_id}/deliveries");
+        let res = admin.get(&deliveries).await;
+        let delivery = &res.data()[0];
+        assert_eq!(delivery["status"], "pending");
+        assert_eq!(delivery["attempts"], 1);
+        assert_eq!(delivery["last_status_code"], 500);
+        let delivery_id = delivery["id"].as_i64().unwrap();
+
+        // not due before its backoff
+        assert_eq!(deliver_due_webhooks().await.unwrap(), 0);
+        sqlx::query("UPDATE webhook_deliveries SET next_attempt_at = UTC_TIMESTAMP()")
+            .execute(&*DB_POOL)
+            .await
+            .unwrap();
+        assert_eq!(deliver_due_webhooks().await.unwrap(), 1);
+        let res = admin.get(&deliveries).await;
+        assert_eq!(res.data()[0]["status"], "succeeded");
+        assert_eq!(res.data()[0]["attempts"], 2);
+        assert_eq!(res.body["meta"]["total"], 1);
+
+        {
+            let received = received.lock().unwrap();
+            let last = &received[1];
+            let payload: Value = serde_json::from_str(&last.body).unwrap();
+            assert_eq!(payload["type"], "user.registered");
+            assert_eq!(payload["data"]["email"], email.as_str());
+            assert_eq!(last.header("x-webhook-event"), "user.registered");
+            assert_eq!(
+                last.header("x-webhook-signature"),
+                signature(&secret, last.header("x-webhook-timestamp"), &last.body)
+            );
+            assert_eq!(
+                last.header("x-webhook-id"),
+                received[0].header("x-webhook-id")
+            );
+        }
+
+        let res = admin
+            .post_empty(&format!("{deliveries}/{delivery_id}/replay"))
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        assert_eq!(res.data()["replay_of"], delivery_id);
+        assert_eq!(deliver_due_webhooks().await.unwrap(), 1);
+        {
+            let received = received.lock().unwrap();
+            assert_eq!(received.len(), 3);
+            assert_eq!(
+                received[2].header("x-webhook-id"),
+                received[0].header("x-webhook-id")
+            );
+        }
+
+        // an address change is sent once confirmed
+        let res = admin
+            .post(
+                "/admin/webhooks",
+                json!({ "url": url, "event_types": ["user.email_changed"] }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED);
+        let new_email = unique_email();
+        let res = user
+            .post(
+                "/account/email",
+                json!({ "email": new_email, "password": PASSWORD }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::ACCEPTED);
+        assert_eq!(deliver_due_webhooks().await.unwrap(), 0);
+        let key = last_link_key(&new_email, "/auth/email/confirm").unwrap();
+        user.post("/auth/email/confirm", json!({ "key": key }))
+            .await;
+        assert_eq!(deliver_due_webhooks().await.unwrap(), 1);
+        let received = received.lock().unwrap();
+        let payload: Value = serde_json::from_str(&received[3].body).unwrap();
+        assert_eq!(payload["type"], "user.email_changed");
+        assert_eq!(payload["data"]["email"], new_email.as_str());
+        assert_eq!(payload["data"]["previous_email"], email.as_str());
+    });
+}